@@ -0,0 +1,22 @@
+-- State recorded by the library scanner so that subsequent scans only need to
+-- look at directories and files that have changed.
+
+-- Directories in the library and their modification time when last listed.
+-- A directory's modification time only changes when entries are added, removed,
+-- or renamed, so a directory with an unchanged modification time doesn't need
+-- to be listed again.
+CREATE TABLE scanned_dirs (
+        dir_path_b64    TEXT PRIMARY KEY UNIQUE NOT NULL, -- path relative to library root (base64 encoded)
+        dir_path_lossy  TEXT NOT NULL, -- human readable path for debugging
+        fs_size         INTEGER NOT NULL, -- size in bytes reported by file system
+        fs_modified_ns  INTEGER NOT NULL -- modification time in nanoseconds since UNIX epoch
+);
+
+-- Pictures and videos in the library and their size and modification time
+-- when last scanned.
+CREATE TABLE scanned_files (
+        file_path_b64   TEXT PRIMARY KEY UNIQUE NOT NULL, -- path relative to library root (base64 encoded)
+        file_path_lossy TEXT NOT NULL, -- human readable path for debugging
+        fs_size         INTEGER NOT NULL, -- size in bytes
+        fs_modified_ns  INTEGER NOT NULL -- modification time in nanoseconds since UNIX epoch
+);
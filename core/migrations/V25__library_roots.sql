@@ -0,0 +1,340 @@
+-- Support for more than one library root directory.
+--
+-- Picture and video paths are stored relative to their library root, so
+-- the unique constraints on paths must now include the root ID. SQLite can't
+-- alter a unique constraint, so the pictures and videos tables are recreated.
+--
+-- Foreign key constraints are enabled and can't be disabled inside a migration,
+-- so dropping the old pictures and videos tables will cascade deletes to tables
+-- that reference them. Rows in those tables are copied aside and restored
+-- after the pictures and videos tables have been recreated.
+
+-- Directories containing pictures and videos.
+CREATE TABLE library_roots (
+        root_id          INTEGER PRIMARY KEY UNIQUE NOT NULL, -- unique ID for library root
+        root_path_b64    TEXT UNIQUE NOT NULL, -- sandbox path to root directory (base64 encoded)
+        root_path_lossy  TEXT NOT NULL -- human readable path for debugging
+);
+
+-- Existing pictures and videos are relative to the single library directory
+-- configured in the application settings, which isn't known here. Create a
+-- placeholder root with an empty path that is claimed by the first library
+-- root configured by the application.
+INSERT INTO library_roots (root_id, root_path_b64, root_path_lossy) VALUES (1, '', '');
+
+-- Must drop because views reference pictures and videos, which are about to be
+-- dropped and recreated.
+DROP VIEW visual;
+DROP VIEW pictures_cleanup;
+DROP VIEW videos_cleanup;
+
+CREATE TEMP TABLE motion_photos_copy AS SELECT * FROM motion_photos;
+CREATE TEMP TABLE pictures_geo_copy AS SELECT * FROM pictures_geo;
+CREATE TEMP TABLE pictures_face_scans_copy AS SELECT * FROM pictures_face_scans;
+CREATE TEMP TABLE pictures_faces_copy AS SELECT * FROM pictures_faces;
+CREATE TEMP TABLE migrate_faces_copy AS SELECT * FROM migrate_faces;
+CREATE TEMP TABLE videos_geo_copy AS SELECT * FROM videos_geo;
+
+-- A photo in the library
+CREATE TABLE pictures2 (
+        picture_id         INTEGER PRIMARY KEY UNIQUE NOT NULL, -- unique ID for picture
+        root_id            INTEGER NOT NULL, -- library root that picture path is relative to
+        picture_path_b64   TEXT NOT NULL, -- path to picture (base64 encoded)
+        picture_path_lossy TEXT NOT NULL, --path to picture. Human readable for debugging.
+        thumbnail_path     TEXT UNIQUE, -- path to picture thumbnail. Not b64 as we only build UTF8 paths.
+        exif_created_ts    DATETIME, -- UTC timestamp for EXIF original creation date
+        exif_modified_ts   DATETIME, -- UTC timestamp for EXIF original modification date
+        is_selfie          BOOLEAN CHECK (is_selfie IN (0, 1)), -- front camera?
+        link_path_b64      TEXT NOT NULL, -- picture parent path, for linking picture/photo siblings. Base64 encoded.
+        link_path_lossy    TEXT NOT NULL, --picture parent path. Human readable for debugging.
+        content_id         TEXT,
+        metadata_version   INTEGER NOT NULL DEFAULT 0, -- code version that scanned metadata
+        orientation        INTEGER,
+        is_broken          BOOLEAN CHECK (is_broken IN (0, 1)),
+        fs_created_ts      DATETIME,
+        fs_modified_ts     DATETIME,
+        insert_ts          DATETIME NOT NULL DEFAULT '1970-01-01 00:00:00',
+
+        UNIQUE (root_id, picture_path_b64)
+);
+
+INSERT INTO pictures2 (
+        picture_id,
+        root_id,
+        picture_path_b64,
+        picture_path_lossy,
+        thumbnail_path,
+        exif_created_ts,
+        exif_modified_ts,
+        is_selfie,
+        link_path_b64,
+        link_path_lossy,
+        content_id,
+        metadata_version,
+        orientation,
+        is_broken,
+        fs_created_ts,
+        fs_modified_ts,
+        insert_ts
+)
+SELECT
+        picture_id,
+        1 AS root_id,
+        picture_path_b64,
+        picture_path_lossy,
+        thumbnail_path,
+        exif_created_ts,
+        exif_modified_ts,
+        is_selfie,
+        link_path_b64,
+        link_path_lossy,
+        content_id,
+        metadata_version,
+        orientation,
+        is_broken,
+        fs_created_ts,
+        fs_modified_ts,
+        insert_ts
+FROM pictures;
+
+-- A video in the library
+CREATE TABLE videos2 (
+        video_id          INTEGER PRIMARY KEY UNIQUE NOT NULL, -- unique ID for video
+        root_id           INTEGER NOT NULL, -- library root that video path is relative to
+        video_path_b64    TEXT NOT NULL, -- base64 encoded path to video
+        video_path_lossy  TEXT NOT NULL, -- human readable path to video for debugging
+        link_path_b64     TEXT NOT NULL, -- base64 encoded video path minus suffix for linking with sibling photos
+        link_path_lossy   TEXT NOT NULL, -- human readable link path for debugging
+        thumbnail_path    TEXT UNIQUE, -- path to thumbnail. Not b64 as we only build UTF8 paths.
+        stream_created_ts DATETIME, -- UTC creation timestamp from video stream metadata
+        duration_millis   INTEGER, -- Duration in milliseconds of video
+        video_codec       TEXT, -- Video codec.
+        transcoded_path   TEXT, -- path to transcoded video. Not b64 as we only build UTF8 paths.
+        content_id        TEXT, -- iOS ID for linking with sibling photos
+        metadata_version  INTEGER NOT NULL DEFAULT 0, -- code version that scanned metadata
+        rotation          INTEGER,
+        is_broken         BOOLEAN CHECK (is_broken IN (0, 1)),
+        fs_created_ts     DATETIME,
+        fs_modified_ts    DATETIME,
+        insert_ts         DATETIME NOT NULL DEFAULT '1970-01-01 00:00:00',
+
+        UNIQUE (root_id, video_path_b64)
+);
+
+INSERT INTO videos2 (
+        video_id,
+        root_id,
+        video_path_b64,
+        video_path_lossy,
+        link_path_b64,
+        link_path_lossy,
+        thumbnail_path,
+        stream_created_ts,
+        duration_millis,
+        video_codec,
+        transcoded_path,
+        content_id,
+        metadata_version,
+        rotation,
+        is_broken,
+        fs_created_ts,
+        fs_modified_ts,
+        insert_ts
+)
+SELECT
+        video_id,
+        1 AS root_id,
+        video_path_b64,
+        video_path_lossy,
+        link_path_b64,
+        link_path_lossy,
+        thumbnail_path,
+        stream_created_ts,
+        duration_millis,
+        video_codec,
+        transcoded_path,
+        content_id,
+        metadata_version,
+        rotation,
+        is_broken,
+        fs_created_ts,
+        fs_modified_ts,
+        insert_ts
+FROM videos;
+
+DROP TABLE pictures;
+ALTER TABLE pictures2 RENAME TO pictures;
+
+DROP TABLE videos;
+ALTER TABLE videos2 RENAME TO videos;
+
+CREATE INDEX pic_live_photo_idx ON pictures(root_id, link_path_b64, content_id);
+CREATE INDEX vid_live_photo_idx ON videos(root_id, link_path_b64, content_id);
+
+-- Restore rows deleted by cascading deletes.
+-- Order matters because migrate_faces references pictures_faces.
+INSERT INTO motion_photos SELECT * FROM motion_photos_copy;
+INSERT INTO pictures_geo SELECT * FROM pictures_geo_copy;
+INSERT INTO pictures_face_scans SELECT * FROM pictures_face_scans_copy;
+INSERT INTO pictures_faces SELECT * FROM pictures_faces_copy;
+INSERT INTO migrate_faces SELECT * FROM migrate_faces_copy;
+INSERT INTO videos_geo SELECT * FROM videos_geo_copy;
+
+DROP TABLE motion_photos_copy;
+DROP TABLE pictures_geo_copy;
+DROP TABLE pictures_face_scans_copy;
+DROP TABLE pictures_faces_copy;
+DROP TABLE migrate_faces_copy;
+DROP TABLE videos_geo_copy;
+
+-- Scanner state is recreated with library root IDs. The next scan will
+-- record the state again.
+DROP TABLE scanned_dirs;
+DROP TABLE scanned_files;
+
+CREATE TABLE scanned_dirs (
+        root_id         INTEGER NOT NULL, -- library root that directory path is relative to
+        dir_path_b64    TEXT NOT NULL, -- path relative to library root (base64 encoded)
+        dir_path_lossy  TEXT NOT NULL, -- human readable path for debugging
+        fs_size         INTEGER NOT NULL, -- size in bytes reported by file system
+        fs_modified_ns  INTEGER NOT NULL, -- modification time in nanoseconds since UNIX epoch
+
+        PRIMARY KEY (root_id, dir_path_b64)
+);
+
+CREATE TABLE scanned_files (
+        root_id         INTEGER NOT NULL, -- library root that file path is relative to
+        file_path_b64   TEXT NOT NULL, -- path relative to library root (base64 encoded)
+        file_path_lossy TEXT NOT NULL, -- human readable path for debugging
+        fs_size         INTEGER NOT NULL, -- size in bytes
+        fs_modified_ns  INTEGER NOT NULL, -- modification time in nanoseconds since UNIX epoch
+
+        PRIMARY KEY (root_id, file_path_b64)
+);
+
+CREATE VIEW pictures_cleanup AS
+SELECT
+        picture_id,
+        'cache' AS root_name,
+        'picture thumbnail' AS description,
+        thumbnail_path AS path
+FROM pictures
+
+UNION
+
+SELECT
+        picture_id,
+        'cache' AS root_name,
+        'motion photo video' AS description,
+        video_path AS path
+FROM motion_photos
+WHERE video_path IS NOT NULL
+
+UNION
+
+SELECT
+        picture_id,
+        'cache' AS root_name,
+        'motion photo transcoded video' AS description,
+        transcoded_path AS path
+FROM motion_photos
+WHERE transcoded_path IS NOT NULL
+
+UNION
+
+SELECT
+        picture_id,
+        'data' AS root_name,
+        'face bounds' AS description,
+        bounds_path AS path
+FROM pictures_faces
+
+UNION
+
+SELECT
+        picture_id,
+        'data' AS root_name,
+        'face thumbnail' AS description,
+        thumbnail_path AS path
+FROM pictures_faces
+;
+
+CREATE VIEW videos_cleanup AS
+
+SELECT video_id, 'cache' AS root_name, 'video thumbnail' AS description, thumbnail_path AS path
+FROM videos
+
+UNION
+
+SELECT video_id, 'cache' AS root_name, 'video transcode' AS description, transcoded_path AS path
+FROM videos
+WHERE transcoded_path IS NOT NULL
+;
+
+CREATE VIEW visual AS
+SELECT
+  -- Unique ID
+  COALESCE(pictures.picture_id, 'x') || '_' || COALESCE(videos.video_id, 'x') AS visual_id,
+  COALESCE(pictures.root_id, videos.root_id) AS root_id,
+  COALESCE(pictures.link_path_b64, videos.link_path_b64) AS link_path_b64,
+
+  pictures.picture_id,
+  pictures.picture_path_b64,
+  pictures.picture_path_lossy, -- for debug only. Never read in Fotema.
+  pictures.orientation AS picture_orientation,
+  pictures.is_selfie,
+
+  videos.video_id,
+  videos.video_path_b64,
+  videos.video_path_lossy, -- for debug only. Never read in Fotema.
+
+  COALESCE(videos.video_codec, motion_photos.video_codec) AS video_codec,
+
+  -- GNOME 48 runtime appears to support HEVC videos without transcoding.
+  false AS is_transcode_required,
+
+  COALESCE(videos.transcoded_path, motion_photos.transcoded_path) AS video_transcoded_path,
+
+  COALESCE(videos.rotation, motion_photos.rotation) AS video_rotation,
+
+  -- An iOS live photo is a photo and a video linked with a content ID.
+  -- However, we only really need the video part, and short (<3 seconds)
+  -- videos are possibly live photos that have a missing or misnamed photo.
+  CASE
+        WHEN videos.content_id IS NOT NULL THEN true
+        WHEN videos.duration_millis <= 3000 THEN true
+        WHEN motion_photos.video_path IS NOT NULL THEN true
+        ELSE false
+  END AS is_live_photo,
+
+  COALESCE(videos.duration_millis, motion_photos.duration_millis) as duration_millis,
+
+  motion_photos.video_path AS motion_photo_video_path,
+
+  pictures_geo.longitude AS longitude,
+  pictures_geo.latitude AS latitude,
+
+  -- Timestamp to order visual items by.
+  -- Prefer embedded metadata over file system metadata.
+  COALESCE(
+    pictures.exif_created_ts,
+    videos.stream_created_ts,
+    pictures.exif_modified_ts,
+    pictures.fs_created_ts,
+    videos.fs_created_ts,
+    pictures.fs_modified_ts,
+    videos.fs_modified_ts,
+    pictures.insert_ts,
+    videos.insert_ts,
+    CURRENT_TIMESTAMP
+  ) AS ordering_ts
+FROM
+  pictures
+  -- Pictures and videos are only siblings if they are in the same library root.
+  FULL OUTER JOIN videos USING (root_id, link_path_b64, content_id)
+  FULL OUTER JOIN motion_photos USING (picture_id)
+  FULL OUTER JOIN pictures_geo USING (picture_id)
+WHERE COALESCE(pictures.is_broken, FALSE) IS FALSE
+AND COALESCE(videos.is_broken, FALSE) IS FALSE
+ORDER BY
+  ordering_ts ASC;
@@ -0,0 +1,4 @@
+-- Hash of the start and end of a file. Together with the file size this
+-- identifies a file cheaply, so a file that has been moved or renamed can
+-- keep its picture or video ID.
+ALTER TABLE scanned_files ADD COLUMN partial_hash TEXT;
@@ -0,0 +1,5 @@
+-- Type of picture or video detected from file content, or from the file extension
+-- if the content isn't recognised. Pictures and videos added before file types were
+-- detected have a NULL file type.
+ALTER TABLE pictures ADD COLUMN file_type TEXT;
+ALTER TABLE videos ADD COLUMN file_type TEXT;
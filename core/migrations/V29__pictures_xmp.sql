@@ -0,0 +1,17 @@
+-- Metadata from XMP sidecars and embedded XMP packets, such as ratings and
+-- captions added in photo managers like darktable and digiKam.
+ALTER TABLE pictures ADD COLUMN rating INTEGER; -- 1 to 5 stars. 0 is unrated and -1 is rejected.
+ALTER TABLE pictures ADD COLUMN label TEXT; -- colour label
+ALTER TABLE pictures ADD COLUMN title TEXT;
+ALTER TABLE pictures ADD COLUMN description TEXT;
+
+-- Keywords, or tags, from XMP
+CREATE TABLE pictures_keywords (
+        picture_id  INTEGER NOT NULL, -- unique ID for picture
+        keyword     TEXT NOT NULL,
+
+        PRIMARY KEY (picture_id, keyword),
+        FOREIGN KEY (picture_id) REFERENCES pictures (picture_id) ON DELETE CASCADE
+);
+
+CREATE INDEX pictures_keywords_keyword_idx ON pictures_keywords(keyword);
@@ -0,0 +1,138 @@
+-- Camera and exposure details from EXIF.
+CREATE TABLE pictures_exif (
+        picture_id         INTEGER PRIMARY KEY UNIQUE NOT NULL, -- unique ID for picture
+        camera_make        TEXT,
+        camera_model       TEXT,
+        lens_model         TEXT,
+        focal_length       REAL, -- millimetres
+        focal_length_35mm  INTEGER, -- millimetres for equivalent field of view on 35mm film
+        f_number           REAL, -- aperture
+        exposure_time      REAL, -- shutter speed in seconds
+        iso                INTEGER,
+        is_flash_fired     BOOLEAN CHECK (is_flash_fired IN (0, 1)),
+        white_balance      TEXT, -- 'auto' or 'manual'
+        width              INTEGER, -- pixels
+        height             INTEGER, -- pixels
+        FOREIGN KEY (picture_id) REFERENCES pictures (picture_id) ON DELETE CASCADE
+);
+
+-- For finding pictures taken with a given camera or lens.
+CREATE INDEX pictures_exif_camera_idx ON pictures_exif(camera_make, camera_model);
+CREATE INDEX pictures_exif_lens_idx ON pictures_exif(lens_model, focal_length);
+
+DROP VIEW visual;
+
+CREATE VIEW visual AS
+WITH
+  raw_pictures AS (
+    SELECT picture_id, root_id, link_path_b64, picture_path_b64
+    FROM pictures
+    WHERE file_type IN ('arw', 'cr2', 'cr3', 'dng', 'nef')
+  ),
+
+  -- Pictures that aren't RAW pictures hidden behind a sibling.
+  shown_pictures AS (
+    SELECT *
+    FROM pictures
+    WHERE picture_id NOT IN (
+      SELECT raw_pictures.picture_id
+      FROM raw_pictures
+      JOIN pictures AS siblings USING (root_id, link_path_b64)
+      WHERE COALESCE(siblings.file_type, '') NOT IN ('arw', 'cr2', 'cr3', 'dng', 'nef')
+      AND COALESCE(siblings.is_broken, FALSE) IS FALSE
+    )
+  ),
+
+  -- RAW picture for each shown picture. A RAW picture shown on its own is its own RAW picture.
+  raw_siblings AS (
+    SELECT
+      shown_pictures.picture_id,
+      MIN(raw_pictures.picture_id) AS raw_picture_id
+    FROM shown_pictures
+    JOIN raw_pictures USING (root_id, link_path_b64)
+    GROUP BY shown_pictures.picture_id
+  )
+SELECT
+  -- Unique ID
+  COALESCE(pictures.picture_id, 'x') || '_' || COALESCE(videos.video_id, 'x') AS visual_id,
+  COALESCE(pictures.root_id, videos.root_id) AS root_id,
+  COALESCE(pictures.link_path_b64, videos.link_path_b64) AS link_path_b64,
+
+  pictures.picture_id,
+  pictures.picture_path_b64,
+  pictures.picture_path_lossy, -- for debug only. Never read in Fotema.
+  pictures.orientation AS picture_orientation,
+  pictures.is_selfie,
+
+  raw_pictures.picture_path_b64 AS raw_path_b64,
+
+  pictures_exif.camera_make AS exif_camera_make,
+  pictures_exif.camera_model AS exif_camera_model,
+  pictures_exif.lens_model AS exif_lens_model,
+  pictures_exif.focal_length AS exif_focal_length,
+  pictures_exif.focal_length_35mm AS exif_focal_length_35mm,
+  pictures_exif.f_number AS exif_f_number,
+  pictures_exif.exposure_time AS exif_exposure_time,
+  pictures_exif.iso AS exif_iso,
+  pictures_exif.is_flash_fired AS exif_is_flash_fired,
+  pictures_exif.white_balance AS exif_white_balance,
+  pictures_exif.width AS exif_width,
+  pictures_exif.height AS exif_height,
+
+  videos.video_id,
+  videos.video_path_b64,
+  videos.video_path_lossy, -- for debug only. Never read in Fotema.
+
+  COALESCE(videos.video_codec, motion_photos.video_codec) AS video_codec,
+
+  -- GNOME 48 runtime appears to support HEVC videos without transcoding.
+  false AS is_transcode_required,
+
+  COALESCE(videos.transcoded_path, motion_photos.transcoded_path) AS video_transcoded_path,
+
+  COALESCE(videos.rotation, motion_photos.rotation) AS video_rotation,
+
+  -- An iOS live photo is a photo and a video linked with a content ID.
+  -- However, we only really need the video part, and short (<3 seconds)
+  -- videos are possibly live photos that have a missing or misnamed photo.
+  CASE
+        WHEN videos.content_id IS NOT NULL THEN true
+        WHEN videos.duration_millis <= 3000 THEN true
+        WHEN motion_photos.video_path IS NOT NULL THEN true
+        ELSE false
+  END AS is_live_photo,
+
+  COALESCE(videos.duration_millis, motion_photos.duration_millis) as duration_millis,
+
+  motion_photos.video_path AS motion_photo_video_path,
+
+  pictures_geo.longitude AS longitude,
+  pictures_geo.latitude AS latitude,
+
+  -- Timestamp to order visual items by.
+  -- Prefer embedded metadata over file system metadata.
+  COALESCE(
+    pictures.exif_created_ts,
+    videos.stream_created_ts,
+    pictures.exif_modified_ts,
+    pictures.fs_created_ts,
+    videos.fs_created_ts,
+    pictures.fs_modified_ts,
+    videos.fs_modified_ts,
+    pictures.insert_ts,
+    videos.insert_ts,
+    CURRENT_TIMESTAMP
+  ) AS ordering_ts
+FROM
+  shown_pictures AS pictures
+  -- Pictures and videos are only siblings if they are in the same library root.
+  FULL OUTER JOIN videos USING (root_id, link_path_b64, content_id)
+  FULL OUTER JOIN motion_photos USING (picture_id)
+  FULL OUTER JOIN pictures_geo USING (picture_id)
+  LEFT JOIN raw_siblings ON raw_siblings.picture_id = pictures.picture_id
+  LEFT JOIN raw_pictures ON raw_pictures.picture_id = raw_siblings.raw_picture_id
+  LEFT JOIN pictures_exif ON pictures_exif.picture_id = pictures.picture_id
+WHERE COALESCE(pictures.is_broken, FALSE) IS FALSE
+AND COALESCE(videos.is_broken, FALSE) IS FALSE
+ORDER BY
+  ordering_ts ASC;
@@ -0,0 +1,4 @@
+-- Motion photo format, such as Google Motion Photo or Samsung, and the timestamp
+-- of the video frame that matches the still image.
+ALTER TABLE motion_photos ADD COLUMN format TEXT;
+ALTER TABLE motion_photos ADD COLUMN still_frame_micros INTEGER;
@@ -0,0 +1,3 @@
+-- Why the last attempt to transcode a video failed, so that failures are visible
+-- rather than leaving a video without a transcode and no explanation.
+ALTER TABLE videos ADD COLUMN transcode_error TEXT;
@@ -0,0 +1,3 @@
+-- Version of the video thumbnail generation that produced the thumbnails of a video,
+-- so that thumbnails can be regenerated when the generation improves.
+ALTER TABLE videos ADD COLUMN thumbnail_version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,13 @@
+-- Hashes of pictures for finding duplicates.
+-- The content hash finds byte-identical copies. The perceptual hash finds copies
+-- that have been re-encoded, resized, or had their metadata stripped.
+CREATE TABLE pictures_hashes (
+        picture_id       INTEGER PRIMARY KEY UNIQUE NOT NULL, -- unique ID for picture
+        content_hash     TEXT NOT NULL, -- MD5 of file content
+        perceptual_hash  INTEGER NOT NULL, -- 64-bit difference hash of large thumbnail
+        hash_version     INTEGER NOT NULL, -- version of hashing that produced hashes
+        FOREIGN KEY (picture_id) REFERENCES pictures (picture_id) ON DELETE CASCADE
+);
+
+-- For finding byte-identical copies.
+CREATE INDEX pictures_hashes_content_idx ON pictures_hashes(content_hash);
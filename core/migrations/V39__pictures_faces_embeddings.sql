@@ -0,0 +1,11 @@
+-- Face recognition feature vectors of faces, so that recognition only has to compare
+-- vectors rather than recompute them from face images on every run.
+-- Embeddings from different models, or different versions of a model, can't be compared.
+CREATE TABLE pictures_faces_embeddings (
+        face_id        INTEGER NOT NULL, -- unique ID for face
+        model_name     TEXT NOT NULL, -- face recognition model that computed embedding
+        model_version  INTEGER NOT NULL, -- version of model and of how faces are aligned
+        embedding      BLOB NOT NULL, -- feature vector as little-endian 32-bit floats
+        PRIMARY KEY (face_id, model_name),
+        FOREIGN KEY (face_id) REFERENCES pictures_faces (face_id) ON DELETE CASCADE
+);
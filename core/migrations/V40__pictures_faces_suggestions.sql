@@ -0,0 +1,9 @@
+-- Unknown faces grouped by similarity into people the user might want to name.
+-- Rebuilt from scratch each time faces are clustered.
+CREATE TABLE pictures_faces_suggestions (
+        face_id        INTEGER PRIMARY KEY NOT NULL, -- unique ID for face
+        suggestion_id  INTEGER NOT NULL, -- faces with the same ID look like the same person
+        FOREIGN KEY (face_id) REFERENCES pictures_faces (face_id) ON DELETE CASCADE
+);
+
+CREATE INDEX pictures_faces_suggestions_idx ON pictures_faces_suggestions (suggestion_id);
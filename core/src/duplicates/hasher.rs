@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Computes the hashes used to find duplicate pictures.
+
+use crate::FlatpakPathBuf;
+use crate::duplicates::model::PictureHashes;
+use crate::photo::model::PictureId;
+use crate::thumbnailify;
+use crate::thumbnailify::ThumbnailSize;
+
+use anyhow::*;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage};
+use std::path::{Path, PathBuf};
+use std::result::Result::Ok;
+
+/// Version of hashing. Increment to re-hash every picture when hashing changes.
+pub const VERSION: u32 = 1;
+
+/// Width of the image a difference hash is computed from. One more than the
+/// number of bits in a row, because each bit compares two neighbouring pixels.
+const DHASH_WIDTH: u32 = 9;
+
+/// Height of the image a difference hash is computed from.
+const DHASH_HEIGHT: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub struct PictureHasher {
+    thumbnails_path: PathBuf,
+}
+
+impl PictureHasher {
+    pub fn build(thumbnails_path: &Path) -> Result<Self> {
+        Ok(Self {
+            thumbnails_path: thumbnails_path.into(),
+        })
+    }
+
+    /// Path of the thumbnail the perceptual hash of a picture is computed from.
+    pub fn thumbnail_path(&self, path: &FlatpakPathBuf) -> PathBuf {
+        thumbnailify::get_thumbnail_hash_output(
+            &self.thumbnails_path,
+            &path.thumbnail_hash(),
+            ThumbnailSize::Large,
+        )
+    }
+
+    /// Hashes a picture. The perceptual hash is computed from the large thumbnail
+    /// rather than the picture, because the thumbnail is quick to decode and has
+    /// already had its orientation corrected.
+    pub fn hash(&self, picture_id: PictureId, path: &FlatpakPathBuf) -> Result<PictureHashes> {
+        let content_hash = content_hash(&path.sandbox_path)?;
+
+        let thumbnail = image::open(self.thumbnail_path(path))?;
+        let perceptual_hash = dhash(&thumbnail);
+
+        Ok(PictureHashes {
+            picture_id,
+            content_hash,
+            perceptual_hash,
+        })
+    }
+}
+
+/// Hash of the bytes of a file, for finding identical copies.
+fn content_hash(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    Ok(format!("{:x}", md5::compute(bytes)))
+}
+
+/// Difference hash. Each bit records whether a pixel of a tiny greyscale copy of the
+/// image is darker than its right-hand neighbour, so the hash survives resizing,
+/// re-encoding, and small changes of brightness.
+fn dhash(image: &DynamicImage) -> u64 {
+    let gray: GrayImage = image.to_luma8();
+    let small = imageops::resize(&gray, DHASH_WIDTH, DHASH_HEIGHT, FilterType::Triangle);
+
+    let mut hash = 0u64;
+    for y in 0..DHASH_HEIGHT {
+        for x in 0..DHASH_WIDTH - 1 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left < right);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::model::distance;
+    use image::{Luma, RgbImage};
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        let image = GrayImage::from_fn(width, height, |x, y| {
+            Luma([(x * 200 / width + y * 40 / height) as u8])
+        });
+        DynamicImage::ImageLuma8(image)
+    }
+
+    #[test]
+    fn test_dhash_flat_image() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 64, image::Rgb([90; 3])));
+        assert_eq!(0, dhash(&image));
+    }
+
+    #[test]
+    fn test_dhash_survives_resize() {
+        let image = gradient(256, 192);
+        let smaller = image.resize_exact(128, 96, FilterType::Lanczos3);
+
+        assert_ne!(0, dhash(&image));
+        assert!(distance(dhash(&image), dhash(&smaller)) <= 2);
+    }
+
+    #[test]
+    fn test_dhash_differs_for_different_images() {
+        let image = gradient(256, 192);
+        let flipped = image.fliph();
+
+        assert!(distance(dhash(&image), dhash(&flipped)) > 32);
+    }
+}
@@ -0,0 +1,13 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub mod hasher;
+pub mod model;
+pub mod repo;
+
+pub use hasher::PictureHasher;
+pub use model::DuplicateGroup;
+pub use model::DuplicateKind;
+pub use model::PictureHashes;
+pub use repo::Repository;
@@ -0,0 +1,247 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::photo::model::PictureId;
+use crate::visual::model::Visual;
+
+use std::collections::HashMap;
+use strum::{AsRefStr, EnumString};
+
+/// Most bits that can differ between the perceptual hashes of two pictures for
+/// them to be considered copies of each other. Must be less than the number of
+/// bytes in a hash for the bucketing in `group` to find every match.
+pub const NEAR_DISTANCE: u32 = 6;
+
+/// Hashes of a picture used to find its duplicates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureHashes {
+    pub picture_id: PictureId,
+
+    /// Hash of the bytes of the file.
+    pub content_hash: String,
+
+    /// Difference hash of the large thumbnail.
+    pub perceptual_hash: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum DuplicateKind {
+    /// Every picture in the group is byte-for-byte identical.
+    Exact,
+
+    /// Pictures in the group look the same, but have been re-encoded, resized,
+    /// or had their metadata changed.
+    Near,
+}
+
+/// Pictures that are copies of each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    pub picture_ids: Vec<PictureId>,
+}
+
+/// Number of bits that differ between two perceptual hashes.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Groups pictures that have the same content, or perceptual hashes within
+/// `NEAR_DISTANCE` bits of each other. Pictures without copies aren't included.
+///
+/// Comparing every pair of pictures is too slow for large libraries, so pictures
+/// are bucketed by each byte of their perceptual hash. Hashes within `NEAR_DISTANCE`
+/// bits of each other must share at least one byte, so only pictures sharing a
+/// bucket need to be compared.
+pub fn group(hashes: &[PictureHashes]) -> Vec<DuplicateGroup> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+
+    let mut by_content: HashMap<&str, usize> = HashMap::new();
+    for (index, hash) in hashes.iter().enumerate() {
+        if let Some(other) = by_content.insert(&hash.content_hash, index) {
+            union(&mut parents, index, other);
+        }
+    }
+
+    let mut buckets: HashMap<(usize, u8), Vec<usize>> = HashMap::new();
+    for (index, hash) in hashes.iter().enumerate() {
+        // A hash of zero is a picture without any detail, such as a blank frame or a
+        // photo of the inside of a pocket. Those all look alike but aren't copies.
+        if hash.perceptual_hash == 0 {
+            continue;
+        }
+        for (position, byte) in hash.perceptual_hash.to_le_bytes().into_iter().enumerate() {
+            buckets.entry((position, byte)).or_default().push(index);
+        }
+    }
+
+    for bucket in buckets.values() {
+        for (i, &a) in bucket.iter().enumerate() {
+            for &b in &bucket[i + 1..] {
+                if distance(hashes[a].perceptual_hash, hashes[b].perceptual_hash) <= NEAR_DISTANCE {
+                    union(&mut parents, a, b);
+                }
+            }
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..hashes.len() {
+        let root = find(&mut parents, index);
+        members.entry(root).or_default().push(index);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = members
+        .into_values()
+        .filter(|indices| indices.len() > 1)
+        .map(|indices| {
+            let first = &hashes[indices[0]].content_hash;
+            let kind = if indices.iter().all(|i| hashes[*i].content_hash == *first) {
+                DuplicateKind::Exact
+            } else {
+                DuplicateKind::Near
+            };
+            DuplicateGroup {
+                kind,
+                picture_ids: indices.iter().map(|i| hashes[*i].picture_id).collect(),
+            }
+        })
+        .collect();
+
+    // Stable order so that the duplicates view doesn't shuffle on every refresh.
+    groups.sort_by_key(|group| group.picture_ids.iter().map(|id| id.id()).min());
+    groups
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    // Compress path so later finds are quick.
+    let mut index = index;
+    while parents[index] != root {
+        let next = parents[index];
+        parents[index] = root;
+        index = next;
+    }
+
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let a = find(parents, a);
+    let b = find(parents, b);
+    if a != b {
+        parents[b.max(a)] = a.min(b);
+    }
+}
+
+/// Index of the copy most worth keeping. Prefers the highest resolution, and then
+/// the copy with the most metadata, because messaging apps and some photo exports
+/// shrink pictures and strip their metadata.
+pub fn best_copy(visuals: &[&Visual]) -> Option<usize> {
+    visuals
+        .iter()
+        .enumerate()
+        .max_by_key(|(index, visual)| {
+            // Earliest copy wins a tie, as it is most likely the original.
+            (pixels(visual), metadata_score(visual), usize::MAX - index)
+        })
+        .map(|(index, _)| index)
+}
+
+fn pixels(visual: &Visual) -> u64 {
+    visual
+        .exif
+        .as_ref()
+        .and_then(|exif| Some(u64::from(exif.width?) * u64::from(exif.height?)))
+        .unwrap_or(0)
+}
+
+fn metadata_score(visual: &Visual) -> u32 {
+    let exif = visual.exif.as_ref();
+    [
+        visual.capture_local_ts.is_some(),
+        visual.location.is_some(),
+        exif.is_some_and(|exif| exif.camera_make.is_some() || exif.camera_model.is_some()),
+        exif.is_some_and(|exif| exif.exposure_time.is_some() || exif.f_number.is_some()),
+    ]
+    .into_iter()
+    .filter(|x| *x)
+    .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(id: i64, content_hash: &str, perceptual_hash: u64) -> PictureHashes {
+        PictureHashes {
+            picture_id: PictureId::new(id),
+            content_hash: content_hash.into(),
+            perceptual_hash,
+        }
+    }
+
+    #[test]
+    fn test_group_exact_and_near() {
+        let hashes = vec![
+            hashes(1, "a", 0xF0F0_F0F0_F0F0_F0F0),
+            hashes(2, "a", 0xF0F0_F0F0_F0F0_F0F0),
+            hashes(3, "b", 0x0F0F_0F0F_0F0F_0F0F),
+            // Re-encoded copy of 3 with a few bits different.
+            hashes(4, "c", 0x0F0F_0F0F_0F0F_0F08),
+            // Unrelated.
+            hashes(5, "d", 0xFFFF_0000_FFFF_0000),
+        ];
+
+        let groups = group(&hashes);
+
+        assert_eq!(
+            vec![
+                DuplicateGroup {
+                    kind: DuplicateKind::Exact,
+                    picture_ids: vec![PictureId::new(1), PictureId::new(2)],
+                },
+                DuplicateGroup {
+                    kind: DuplicateKind::Near,
+                    picture_ids: vec![PictureId::new(3), PictureId::new(4)],
+                },
+            ],
+            groups
+        );
+    }
+
+    #[test]
+    fn test_group_is_transitive() {
+        // 1 and 3 are too far apart, but are both near 2.
+        let hashes = vec![
+            hashes(1, "a", 0xFFFF_FFFF_FFFF_FFFF),
+            hashes(2, "b", 0xFFFF_FFFF_FFFF_FFC0),
+            hashes(3, "c", 0xFFFF_FFFF_FFFF_F000),
+        ];
+
+        let groups = group(&hashes);
+
+        assert_eq!(1, groups.len());
+        assert_eq!(3, groups[0].picture_ids.len());
+        assert_eq!(DuplicateKind::Near, groups[0].kind);
+    }
+
+    #[test]
+    fn test_group_ignores_blank_pictures() {
+        let hashes = vec![hashes(1, "a", 0), hashes(2, "b", 0)];
+        assert!(group(&hashes).is_empty());
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(0, distance(0xABCD, 0xABCD));
+        assert_eq!(64, distance(0, u64::MAX));
+        assert_eq!(3, distance(0b1011, 0b0000));
+    }
+}
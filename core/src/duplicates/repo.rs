@@ -0,0 +1,111 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::duplicates::hasher;
+use crate::duplicates::model::{self, DuplicateGroup, PictureHashes};
+use crate::photo::model::PictureId;
+
+use anyhow::*;
+use rusqlite;
+use rusqlite::Row;
+use rusqlite::params;
+use std::collections::HashSet;
+use std::result::Result::Ok;
+use std::sync::{Arc, Mutex};
+
+/// Repository of picture hashes for finding duplicates.
+/// Repository is backed by a Sqlite database.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    /// Connection to backing Sqlite database.
+    con: Arc<Mutex<rusqlite::Connection>>,
+}
+
+impl Repository {
+    pub fn open(con: Arc<Mutex<rusqlite::Connection>>) -> Result<Repository> {
+        Ok(Repository { con })
+    }
+
+    /// Pictures that have been hashed with the current version of hashing.
+    pub fn find_hashed(&self) -> Result<HashSet<i64>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT picture_id
+            FROM pictures_hashes
+            WHERE hash_version >= ?1",
+        )?;
+
+        let result = stmt
+            .query_map([hasher::VERSION], |row| row.get(0))?
+            .flatten()
+            .collect();
+
+        Ok(result)
+    }
+
+    pub fn add_hashes(&mut self, hashes: &[PictureHashes]) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO pictures_hashes (
+                    picture_id,
+                    content_hash,
+                    perceptual_hash,
+                    hash_version
+                ) VALUES (
+                    ?1, ?2, ?3, ?4
+                )",
+            )?;
+
+            for hash in hashes {
+                stmt.execute(params![
+                    hash.picture_id.id(),
+                    hash.content_hash,
+                    // SQLite integers are signed, so store the bits of the hash as-is.
+                    hash.perceptual_hash as i64,
+                    hasher::VERSION,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Groups of pictures that are copies of each other.
+    pub fn find_duplicates(&self) -> Result<Vec<DuplicateGroup>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                pictures_hashes.picture_id,
+                pictures_hashes.content_hash,
+                pictures_hashes.perceptual_hash
+            FROM pictures_hashes
+            JOIN pictures USING (picture_id)
+            WHERE COALESCE(pictures.is_broken, FALSE) IS FALSE
+            ORDER BY pictures_hashes.picture_id ASC",
+        )?;
+
+        let hashes: Vec<PictureHashes> = stmt
+            .query_map([], |row| self.to_hashes(row))?
+            .flatten()
+            .collect();
+
+        Ok(model::group(&hashes))
+    }
+
+    fn to_hashes(&self, row: &Row<'_>) -> rusqlite::Result<PictureHashes> {
+        let picture_id = row.get("picture_id").map(PictureId::new)?;
+        let content_hash = row.get("content_hash")?;
+        let perceptual_hash = row.get("perceptual_hash").map(|x: i64| x as u64)?;
+
+        Ok(PictureHashes {
+            picture_id,
+            content_hash,
+            perceptual_hash,
+        })
+    }
+}
@@ -2,38 +2,325 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
+use strum::{AsRefStr, EnumString};
 
-const PICTURES_SUFFIXES: [&str; 11] = [
-    "avif", "exr", "heic", "jpeg", "jpg", "jxl", "png", "qoi", "tiff", "webp", "gif",
-];
+/// Number of bytes read from the start of a file to detect its type.
+/// MPEG transport streams need enough bytes to see the sync byte of a second packet.
+const SNIFF_LEN: u64 = 512;
 
-const VIDEO_SUFFIXES: [&str; 5] = ["m4v", "mov", "mp4", "avi", "mkv"];
+/// Kind of picture or video, by container format.
+/// Stored in the database by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum FileType {
+    Avif,
+    Bmp,
+    Exr,
+    Gif,
+    /// HEIF, including HEIC.
+    Heif,
+    /// JPEG, including JFIF.
+    Jpeg,
+    Jxl,
+    Png,
+    Qoi,
+    Tiff,
+    Webp,
 
-pub fn is_supported_picture(path: &Path) -> bool {
-    let Some(path_ext) = path.extension() else {
-        return false;
-    };
+    /// Sony camera RAW.
+    Arw,
+    /// Canon camera RAW, version 2. A TIFF file.
+    Cr2,
+    /// Canon camera RAW, version 3. An ISO base media file.
+    Cr3,
+    /// Adobe digital negative camera RAW.
+    Dng,
+    /// Nikon camera RAW.
+    Nef,
 
-    for pic_ext in PICTURES_SUFFIXES {
-        if path_ext.eq_ignore_ascii_case(pic_ext) {
-            return true;
+    /// 3GPP mobile phone video.
+    ThreeGp,
+    Avi,
+    Matroska,
+    /// MPEG-4, including M4V.
+    Mp4,
+    /// MPEG transport stream, including AVCHD `.mts` files.
+    MpegTs,
+    QuickTime,
+    WebM,
+}
+
+impl FileType {
+    pub fn is_picture(&self) -> bool {
+        !self.is_video()
+    }
+
+    /// Is this a camera RAW format?
+    pub fn is_raw(&self) -> bool {
+        matches!(
+            self,
+            FileType::Arw | FileType::Cr2 | FileType::Cr3 | FileType::Dng | FileType::Nef
+        )
+    }
+
+    pub fn is_video(&self) -> bool {
+        matches!(
+            self,
+            FileType::ThreeGp
+                | FileType::Avi
+                | FileType::Matroska
+                | FileType::Mp4
+                | FileType::MpegTs
+                | FileType::QuickTime
+                | FileType::WebM
+        )
+    }
+
+    /// Detects the type of a file from its content, falling back to its extension
+    /// if the content isn't recognised.
+    pub fn detect(path: &Path) -> Option<FileType> {
+        let mut header = Vec::with_capacity(SNIFF_LEN as usize);
+        let sniffed = File::open(path)
+            .and_then(|file| file.take(SNIFF_LEN).read_to_end(&mut header))
+            .ok()
+            .and_then(|_| Self::sniff(&header));
+
+        sniffed.or_else(|| Self::from_extension(path))
+    }
+
+    /// Detects the type of a file from its extension.
+    pub fn from_extension(path: &Path) -> Option<FileType> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        let file_type = match ext.as_str() {
+            "avif" => FileType::Avif,
+            "bmp" => FileType::Bmp,
+            "exr" => FileType::Exr,
+            "gif" => FileType::Gif,
+            "heic" | "heif" => FileType::Heif,
+            "jfif" | "jpeg" | "jpg" => FileType::Jpeg,
+            "jxl" => FileType::Jxl,
+            "png" => FileType::Png,
+            "qoi" => FileType::Qoi,
+            "tif" | "tiff" => FileType::Tiff,
+            "webp" => FileType::Webp,
+            "arw" => FileType::Arw,
+            "cr2" => FileType::Cr2,
+            "cr3" => FileType::Cr3,
+            "dng" => FileType::Dng,
+            "nef" => FileType::Nef,
+            "3gp" => FileType::ThreeGp,
+            "avi" => FileType::Avi,
+            "mkv" => FileType::Matroska,
+            "m4v" | "mp4" => FileType::Mp4,
+            "m2ts" | "mts" | "ts" => FileType::MpegTs,
+            "mov" => FileType::QuickTime,
+            "webm" => FileType::WebM,
+            _ => return None,
+        };
+        Some(file_type)
+    }
+
+    /// Detects the type of a file from the magic bytes at the start of the file.
+    ///
+    /// TIFF isn't detected because many camera RAW formats are TIFF files, so
+    /// TIFF, and RAW formats other than Canon's, are only recognised by extension.
+    pub fn sniff(header: &[u8]) -> Option<FileType> {
+        if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(FileType::Jpeg)
+        } else if header.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some(FileType::Png)
+        } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+            Some(FileType::Gif)
+        } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP".as_slice()) {
+            Some(FileType::Webp)
+        } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"AVI ".as_slice()) {
+            Some(FileType::Avi)
+        } else if header.starts_with(&[0xFF, 0x0A])
+            || header.starts_with(&[
+                0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+            ])
+        {
+            Some(FileType::Jxl)
+        } else if header.starts_with(&[0x76, 0x2F, 0x31, 0x01]) {
+            Some(FileType::Exr)
+        } else if header.starts_with(b"II*\0") && header.get(8..10) == Some(b"CR".as_slice()) {
+            Some(FileType::Cr2)
+        } else if header.starts_with(b"qoif") {
+            Some(FileType::Qoi)
+        } else if Self::is_bmp(header) {
+            Some(FileType::Bmp)
+        } else if header.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+            // EBML document type says if Matroska is WebM.
+            if header.windows(4).any(|w| w == b"webm") {
+                Some(FileType::WebM)
+            } else {
+                Some(FileType::Matroska)
+            }
+        } else if Self::is_mpeg_ts(header) {
+            Some(FileType::MpegTs)
+        } else {
+            Self::sniff_iso_bmff(header)
         }
     }
 
-    return false;
+    /// BMP only has a two byte signature, so also check the size of the DIB header.
+    fn is_bmp(header: &[u8]) -> bool {
+        header.starts_with(b"BM")
+            && header
+                .get(14..18)
+                .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]))
+                .is_some_and(|size| [12, 40, 52, 56, 64, 108, 124].contains(&size))
+    }
+
+    /// MPEG transport streams are a sequence of packets starting with a 0x47 sync byte.
+    /// Packets are 188 bytes, or 192 bytes with a four byte timecode prefix for AVCHD.
+    fn is_mpeg_ts(header: &[u8]) -> bool {
+        let is_sync = |offset: usize| header.get(offset) == Some(&0x47);
+        (is_sync(0) && is_sync(188) && is_sync(376)) || (is_sync(4) && is_sync(196) && is_sync(388))
+    }
+
+    /// HEIF, AVIF, MP4, QuickTime, and 3GPP files are all ISO base media files that
+    /// start with an `ftyp` box naming the major brand.
+    fn sniff_iso_bmff(header: &[u8]) -> Option<FileType> {
+        let box_type = header.get(4..8)?;
+
+        // Older QuickTime files don't have an ftyp box.
+        if box_type != b"ftyp" {
+            return match box_type {
+                b"moov" | b"mdat" | b"wide" | b"free" | b"skip" => Some(FileType::QuickTime),
+                _ => None,
+            };
+        }
+
+        let box_size = u32::from_be_bytes(header.get(0..4)?.try_into().ok()?) as usize;
+        let major_brand = header.get(8..12)?;
+
+        // Compatible brands follow the major brand and minor version.
+        let compatible_brands = header
+            .get(16..box_size.min(header.len()))
+            .unwrap_or_default();
+        let is_compatible = |brand: &[u8]| compatible_brands.chunks(4).any(|b| b == brand);
+
+        match major_brand {
+            b"avif" | b"avis" => Some(FileType::Avif),
+            b"mif1" | b"msf1" if is_compatible(b"avif") => Some(FileType::Avif),
+            b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" | b"mif1" | b"msf1" => {
+                Some(FileType::Heif)
+            }
+            b"qt  " => Some(FileType::QuickTime),
+            b"crx " => Some(FileType::Cr3),
+            brand if brand.starts_with(b"3g") => Some(FileType::ThreeGp),
+            b"isom" | b"iso2" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1"
+            | b"dash" | b"M4V " | b"M4VH" | b"M4VP" | b"MSNV" | b"XAVC" | b"f4v " => {
+                Some(FileType::Mp4)
+            }
+            _ => None,
+        }
+    }
 }
 
-pub fn is_supported_video(path: &Path) -> bool {
-    let Some(path_ext) = path.extension() else {
-        return false;
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ftyp(major_brand: &[u8; 4], compatible_brands: &[&[u8; 4]]) -> Vec<u8> {
+        let size = 16 + 4 * compatible_brands.len() as u32;
+        let mut header = size.to_be_bytes().to_vec();
+        header.extend_from_slice(b"ftyp");
+        header.extend_from_slice(major_brand);
+        header.extend_from_slice(&[0, 0, 0, 0]);
+        for brand in compatible_brands {
+            header.extend_from_slice(*brand);
+        }
+        header
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(
+            Some(FileType::Jpeg),
+            FileType::sniff(&[0xFF, 0xD8, 0xFF, 0xE0])
+        );
+        assert_eq!(Some(FileType::Gif), FileType::sniff(b"GIF89a"));
+        assert_eq!(
+            Some(FileType::Webp),
+            FileType::sniff(b"RIFF\0\0\0\0WEBPVP8 ")
+        );
+        assert_eq!(
+            Some(FileType::Heif),
+            FileType::sniff(&ftyp(b"heic", &[b"mif1", b"heic"]))
+        );
+        assert_eq!(
+            Some(FileType::Avif),
+            FileType::sniff(&ftyp(b"mif1", &[b"avif", b"mif1"]))
+        );
+        assert_eq!(
+            Some(FileType::QuickTime),
+            FileType::sniff(&ftyp(b"qt  ", &[b"qt  "]))
+        );
+        assert_eq!(
+            Some(FileType::ThreeGp),
+            FileType::sniff(&ftyp(b"3gp5", &[b"isom"]))
+        );
+        assert_eq!(
+            Some(FileType::Mp4),
+            FileType::sniff(&ftyp(b"isom", &[b"isom", b"avc1"]))
+        );
+        assert_eq!(
+            Some(FileType::Cr3),
+            FileType::sniff(&ftyp(b"crx ", &[b"crx ", b"isom"]))
+        );
+        assert_eq!(None, FileType::sniff(&ftyp(b"abcd", &[b"abcd"])));
+        assert_eq!(
+            Some(FileType::Cr2),
+            FileType::sniff(b"II*\0\x10\0\0\0CR\x02\0")
+        );
+
+        let mut webm = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x84];
+        webm.extend_from_slice(b"webm");
+        assert_eq!(Some(FileType::WebM), FileType::sniff(&webm));
 
-    for pic_ext in VIDEO_SUFFIXES {
-        if path_ext.eq_ignore_ascii_case(pic_ext) {
-            return true;
+        let mut mts = vec![0; 512];
+        for offset in [4, 196, 388] {
+            mts[offset] = 0x47;
         }
+        assert_eq!(Some(FileType::MpegTs), FileType::sniff(&mts));
+
+        // TIFF is only detected by extension
+        assert_eq!(None, FileType::sniff(b"II*\0"));
+        assert_eq!(None, FileType::sniff(b"not a picture"));
     }
 
-    return false;
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Content wins over a misleading extension.
+        let mislabelled = dir.path().join("picture.mp4");
+        std::fs::write(&mislabelled, [0xFF, 0xD8, 0xFF, 0xE1]).unwrap();
+        assert_eq!(Some(FileType::Jpeg), FileType::detect(&mislabelled));
+
+        // No extension
+        let no_ext = dir.path().join("picture");
+        std::fs::write(&no_ext, b"GIF87a").unwrap();
+        assert_eq!(Some(FileType::Gif), FileType::detect(&no_ext));
+
+        // Fall back to extension
+        let tiff = dir.path().join("scan.TIF");
+        std::fs::write(&tiff, b"II*\0").unwrap();
+        assert_eq!(Some(FileType::Tiff), FileType::detect(&tiff));
+
+        // TIFF based RAW files
+        let nef = dir.path().join("DSC_0001.NEF");
+        std::fs::write(&nef, b"MM\0*").unwrap();
+        assert_eq!(Some(FileType::Nef), FileType::detect(&nef));
+        assert!(FileType::Nef.is_raw() && FileType::Nef.is_picture());
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, b"not a picture").unwrap();
+        assert_eq!(None, FileType::detect(&text));
+    }
 }
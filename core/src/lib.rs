@@ -3,8 +3,10 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 pub mod database;
+pub mod duplicates;
 pub mod file_types;
 pub mod flatpak_path;
+pub mod library_root;
 pub mod machine_learning;
 pub mod path_encoding;
 pub mod people;
@@ -16,6 +18,7 @@ pub mod video;
 pub mod visual;
 
 pub use flatpak_path::FlatpakPathBuf;
+pub use library_root::LibraryRootId;
 pub use people::model::FaceId;
 pub use people::model::PersonId;
 pub use photo::model::PictureId;
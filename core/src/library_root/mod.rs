@@ -0,0 +1,11 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub mod model;
+pub mod repo;
+
+pub use model::LibraryRoot;
+pub use model::LibraryRootId;
+pub use model::LibraryRoots;
+pub use repo::Repository;
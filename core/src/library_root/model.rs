@@ -0,0 +1,186 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::FlatpakPathBuf;
+use crate::path_encoding;
+
+use anyhow::{Result, anyhow};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Database ID of a library root directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LibraryRootId(i64);
+
+impl LibraryRootId {
+    pub fn new(id: i64) -> Self {
+        Self(id)
+    }
+
+    /// FIXME replace this with a To/From SQL implementation.
+    pub fn id(&self) -> i64 {
+        self.0
+    }
+}
+
+impl Display for LibraryRootId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A directory tree of pictures and videos.
+/// Paths of pictures and videos are stored relative to their library root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryRoot {
+    /// Database primary key for library root
+    pub root_id: LibraryRootId,
+
+    /// Path to root directory
+    pub path: FlatpakPathBuf,
+}
+
+/// All library roots that make up the library.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryRoots(Vec<LibraryRoot>);
+
+impl LibraryRoots {
+    pub fn new(roots: Vec<LibraryRoot>) -> Self {
+        Self(roots)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LibraryRoot> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, root_id: LibraryRootId) -> Option<&LibraryRoot> {
+        self.0.iter().find(|root| root.root_id == root_id)
+    }
+
+    /// Sandbox paths of all library roots.
+    pub fn sandbox_paths(&self) -> Vec<PathBuf> {
+        self.0
+            .iter()
+            .map(|root| root.path.sandbox_path.clone())
+            .collect()
+    }
+
+    /// Finds the library root containing a sandbox path, and the path relative to that root.
+    /// If roots are nested, then the innermost root is chosen.
+    pub fn relativize<'a>(&self, sandbox_path: &'a Path) -> Option<(&LibraryRoot, &'a Path)> {
+        self.0
+            .iter()
+            .filter_map(|root| {
+                sandbox_path
+                    .strip_prefix(&root.path.sandbox_path)
+                    .ok()
+                    .map(|relative_path| (root, relative_path))
+            })
+            .max_by_key(|(root, _)| root.path.sandbox_path.components().count())
+    }
+
+    /// Builds the full path of a file from its library root and relative path.
+    pub fn resolve(&self, root_id: LibraryRootId, relative_path: &Path) -> Option<FlatpakPathBuf> {
+        self.get(root_id).map(|root| {
+            FlatpakPathBuf::build(
+                root.path.host_path.join(relative_path),
+                root.path.sandbox_path.join(relative_path),
+            )
+        })
+    }
+
+    /// Library root ID and relative path, as stored in the database, for a sandbox path.
+    pub fn to_relative_path<'a>(
+        &self,
+        sandbox_path: &'a Path,
+    ) -> Result<(LibraryRootId, &'a Path)> {
+        self.relativize(sandbox_path)
+            .map(|(root, relative_path)| (root.root_id, relative_path))
+            .ok_or_else(|| anyhow!("{:?} is not in a library root directory", sandbox_path))
+    }
+
+    /// Builds the full path of a file from its library root and base64 encoded relative path,
+    /// as stored in the database. Paths in library roots that have since been removed are `None`.
+    pub fn resolve_base64(
+        &self,
+        root_id: LibraryRootId,
+        relative_path_b64: &str,
+    ) -> Result<Option<FlatpakPathBuf>> {
+        let relative_path = path_encoding::from_base64(relative_path_b64)?;
+        Ok(self.resolve(root_id, &relative_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots() -> LibraryRoots {
+        LibraryRoots::new(vec![
+            LibraryRoot {
+                root_id: LibraryRootId::new(1),
+                path: FlatpakPathBuf::build("/home/user/Pictures", "/run/docs/1/Pictures"),
+            },
+            LibraryRoot {
+                root_id: LibraryRootId::new(2),
+                path: FlatpakPathBuf::build("/media/archive", "/run/docs/2/archive"),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_relativize() {
+        let roots = roots();
+        let (root, relative_path) = roots
+            .relativize(Path::new("/run/docs/2/archive/1999/scan.jpg"))
+            .unwrap();
+        assert_eq!(LibraryRootId::new(2), root.root_id);
+        assert_eq!(Path::new("1999/scan.jpg"), relative_path);
+
+        assert!(roots.relativize(Path::new("/tmp/other.jpg")).is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let roots = roots();
+        let path = roots
+            .resolve(LibraryRootId::new(1), Path::new("phone/a.jpg"))
+            .unwrap();
+        assert_eq!(PathBuf::from("/home/user/Pictures/phone/a.jpg"), path.host_path);
+        assert_eq!(PathBuf::from("/run/docs/1/Pictures/phone/a.jpg"), path.sandbox_path);
+
+        assert!(roots.resolve(LibraryRootId::new(3), Path::new("a.jpg")).is_none());
+    }
+
+    #[test]
+    fn test_relative_path_round_trip() {
+        let roots = roots();
+        let (root_id, relative_path) = roots
+            .to_relative_path(Path::new("/run/docs/1/Pictures/phone/a.jpg"))
+            .unwrap();
+        assert_eq!(LibraryRootId::new(1), root_id);
+
+        let relative_path_b64 = path_encoding::to_base64(relative_path);
+        let path = roots
+            .resolve_base64(root_id, &relative_path_b64)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            PathBuf::from("/run/docs/1/Pictures/phone/a.jpg"),
+            path.sandbox_path
+        );
+
+        assert!(roots.to_relative_path(Path::new("/tmp/other.jpg")).is_err());
+        assert!(
+            roots
+                .resolve_base64(LibraryRootId::new(3), &relative_path_b64)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
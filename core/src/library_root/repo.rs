@@ -0,0 +1,162 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::model::{LibraryRoot, LibraryRootId, LibraryRoots};
+use crate::FlatpakPathBuf;
+use crate::path_encoding;
+
+use anyhow::*;
+use rusqlite;
+use rusqlite::{OptionalExtension, params};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Repository of library root directories.
+/// Repository is backed by a Sqlite database.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    /// Connection to backing Sqlite database.
+    con: Arc<Mutex<rusqlite::Connection>>,
+}
+
+impl Repository {
+    pub fn open(con: Arc<Mutex<rusqlite::Connection>>) -> Result<Self> {
+        Ok(Repository { con })
+    }
+
+    /// Gets the library roots for the given directories, creating any that don't exist yet.
+    ///
+    /// Library roots for directories that are not given are removed, along with the scanner
+    /// state for those roots. Pictures and videos in removed roots are left for the
+    /// clean tasks to remove.
+    pub fn sync(&mut self, paths: &[FlatpakPathBuf]) -> Result<LibraryRoots> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let mut roots = Vec::new();
+
+        {
+            let mut find_stmt =
+                tx.prepare_cached("SELECT root_id FROM library_roots WHERE root_path_b64 = ?1")?;
+
+            // Pictures and videos from before library roots existed belong to a placeholder
+            // root with an empty path, which is claimed by the first root configured.
+            let mut claim_stmt = tx.prepare_cached(
+                "UPDATE library_roots
+                SET
+                    root_path_b64 = ?1,
+                    root_path_lossy = ?2
+                WHERE root_path_b64 = ''
+                RETURNING root_id",
+            )?;
+
+            let mut insert_stmt = tx.prepare_cached(
+                "INSERT INTO library_roots (
+                    root_path_b64,
+                    root_path_lossy
+                ) VALUES (
+                    ?1, ?2
+                ) RETURNING root_id",
+            )?;
+
+            for path in paths {
+                let root_path_b64 = path_encoding::to_base64(&path.sandbox_path);
+                let root_path_lossy = path.sandbox_path.to_string_lossy();
+
+                let root_id: Option<i64> = find_stmt
+                    .query_row([&root_path_b64], |row| row.get(0))
+                    .optional()?;
+
+                let root_id = match root_id {
+                    Some(root_id) => root_id,
+                    None => {
+                        let claimed: Option<i64> = claim_stmt
+                            .query_row(params![root_path_b64, root_path_lossy], |row| row.get(0))
+                            .optional()?;
+
+                        match claimed {
+                            Some(root_id) => root_id,
+                            None => insert_stmt
+                                .query_row(params![root_path_b64, root_path_lossy], |row| {
+                                    row.get(0)
+                                })?,
+                        }
+                    }
+                };
+
+                roots.push(LibraryRoot {
+                    root_id: LibraryRootId::new(root_id),
+                    path: path.clone(),
+                });
+            }
+        }
+
+        {
+            let root_ids: Vec<i64> = roots.iter().map(|root| root.root_id.id()).collect();
+
+            let mut stmt = tx.prepare_cached("SELECT root_id, root_path_lossy FROM library_roots")?;
+            let removed: Vec<(i64, String)> = stmt
+                .query_map([], |row| {
+                    std::result::Result::Ok((row.get("root_id")?, row.get("root_path_lossy")?))
+                })?
+                .flatten()
+                .filter(|(root_id, path)| !root_ids.contains(root_id) && !path.is_empty())
+                .collect();
+
+            let mut delete_dirs = tx.prepare_cached("DELETE FROM scanned_dirs WHERE root_id = ?1")?;
+            let mut delete_files =
+                tx.prepare_cached("DELETE FROM scanned_files WHERE root_id = ?1")?;
+            let mut delete_root =
+                tx.prepare_cached("DELETE FROM library_roots WHERE root_id = ?1")?;
+
+            for (root_id, path) in removed {
+                info!("Removing library root {}: {}", root_id, path);
+                delete_dirs.execute([root_id])?;
+                delete_files.execute([root_id])?;
+                delete_root.execute([root_id])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(LibraryRoots::new(roots))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+
+    fn repo() -> Repository {
+        let con = database::setup_in_memory().unwrap();
+        Repository::open(Arc::new(Mutex::new(con))).unwrap()
+    }
+
+    #[test]
+    fn test_sync() {
+        let mut repo = repo();
+
+        let phone = FlatpakPathBuf::build("/phone", "/phone");
+        let dslr = FlatpakPathBuf::build("/dslr", "/dslr");
+
+        // First root claims the placeholder root for pictures and videos
+        // that pre-date library roots.
+        let roots = repo.sync(&[phone.clone(), dslr.clone()]).unwrap();
+        let ids: Vec<LibraryRootId> = roots.iter().map(|r| r.root_id).collect();
+        assert_eq!(LibraryRootId::new(1), ids[0]);
+        assert_ne!(ids[0], ids[1]);
+
+        // IDs are stable
+        let roots = repo.sync(&[dslr.clone(), phone.clone()]).unwrap();
+        let dslr_id = roots.iter().next().unwrap().root_id;
+        assert_eq!(ids[1], dslr_id);
+
+        // Removed roots are forgotten
+        let roots = repo.sync(&[dslr.clone()]).unwrap();
+        assert_eq!(dslr_id, roots.iter().next().unwrap().root_id);
+        let roots = repo.sync(&[phone.clone(), dslr.clone()]).unwrap();
+        assert_ne!(ids[0], roots.iter().next().unwrap().root_id);
+    }
+}
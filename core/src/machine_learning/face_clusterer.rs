@@ -0,0 +1,187 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+
+use crate::people::model::{Embedding, FaceId};
+
+/// Groups unknown faces that look like the same person, so they can be suggested as
+/// new people. Uses DBSCAN, which doesn't need to know how many people there are and
+/// leaves faces that don't look like anyone else out of every group.
+#[derive(Debug, Clone)]
+pub struct FaceClusterer {
+    /// Faces within this L2 distance of each other are neighbours.
+    /// Stricter than the recognition threshold because there is no confirmed face
+    /// to anchor a group, and chains of neighbours can drift between people.
+    max_distance: f32,
+
+    /// Minimum number of faces, including itself, in a face's neighbourhood for
+    /// the face to start or grow a group.
+    min_faces: usize,
+}
+
+impl Default for FaceClusterer {
+    fn default() -> Self {
+        Self {
+            max_distance: 0.95,
+            min_faces: 3,
+        }
+    }
+}
+
+impl FaceClusterer {
+    /// Set how many similar faces are needed to start a group.
+    /// With one, every face ends up in a group, even if only by itself.
+    pub fn with_min_faces(mut self, min_faces: usize) -> Self {
+        self.min_faces = min_faces.max(1);
+        self
+    }
+
+    /// Groups of faces, largest first.
+    /// Every face is compared with every other face, which is slow for large libraries,
+    /// so None if `stop` is set before grouping finishes.
+    pub fn cluster(
+        &self,
+        faces: &[(FaceId, Embedding)],
+        stop: &AtomicBool,
+    ) -> Option<Vec<Vec<FaceId>>> {
+        let neighbours: Vec<Vec<usize>> = faces
+            .par_iter()
+            .map(|(_, embedding)| {
+                if stop.load(Ordering::Relaxed) {
+                    return None;
+                }
+
+                let neighbours = faces
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, other))| embedding.distance(other) <= self.max_distance)
+                    .map(|(index, _)| index)
+                    .collect();
+                Some(neighbours)
+            })
+            .collect::<Option<_>>()?;
+
+        let is_core = |index: usize| neighbours[index].len() >= self.min_faces;
+
+        let mut labels: Vec<Option<usize>> = vec![None; faces.len()];
+        let mut clusters: Vec<Vec<FaceId>> = vec![];
+
+        for start in 0..faces.len() {
+            if labels[start].is_some() || !is_core(start) {
+                continue;
+            }
+
+            let label = clusters.len();
+            let mut members = vec![];
+            let mut queue = VecDeque::from([start]);
+            labels[start] = Some(label);
+
+            while let Some(index) = queue.pop_front() {
+                members.push(faces[index].0);
+
+                // Border faces join a group but don't grow it.
+                if !is_core(index) {
+                    continue;
+                }
+
+                for &neighbour in &neighbours[index] {
+                    if labels[neighbour].is_none() {
+                        labels[neighbour] = Some(label);
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+
+            clusters.push(members);
+        }
+
+        clusters.sort_by_key(|members| std::cmp::Reverse(members.len()));
+        Some(clusters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn faces(embeddings: &[(i64, [f32; 3])]) -> Vec<(FaceId, Embedding)> {
+        embeddings
+            .iter()
+            .map(|(face_id, features)| (FaceId::new(*face_id), Embedding::new(features.to_vec())))
+            .collect()
+    }
+
+    #[test]
+    fn test_cluster_groups_similar_faces() {
+        let faces = faces(&[
+            (1, [1.0, 0.0, 0.0]),
+            (2, [0.0, 1.0, 0.0]),
+            (3, [1.0, 0.1, 0.0]),
+            (4, [0.0, 1.0, 0.1]),
+            (5, [1.0, 0.0, 0.1]),
+            (6, [0.0, 1.0, 0.2]),
+            (7, [0.0, 1.0, -0.1]),
+            // Doesn't look like anyone else
+            (8, [0.0, 0.0, 1.0]),
+        ]);
+
+        let clusters = FaceClusterer::default()
+            .cluster(&faces, &AtomicBool::new(false))
+            .unwrap();
+
+        assert_eq!(2, clusters.len());
+        assert_eq!(
+            vec![2, 4, 6, 7],
+            clusters[0].iter().map(|id| id.id()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![1, 3, 5],
+            clusters[1].iter().map(|id| id.id()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_cluster_needs_enough_faces() {
+        let faces = faces(&[(1, [1.0, 0.0, 0.0]), (2, [1.0, 0.1, 0.0])]);
+        assert!(
+            FaceClusterer::default()
+                .cluster(&faces, &AtomicBool::new(false))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_cluster_every_face() {
+        let faces = faces(&[
+            (1, [1.0, 0.0, 0.0]),
+            (2, [1.0, 0.1, 0.0]),
+            (3, [0.0, 0.0, 1.0]),
+        ]);
+
+        let clusters = FaceClusterer::default()
+            .with_min_faces(1)
+            .cluster(&faces, &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(2, clusters.len());
+        assert_eq!(2, clusters[0].len());
+        assert_eq!(vec![FaceId::new(3)], clusters[1]);
+    }
+
+    #[test]
+    fn test_cluster_stopped() {
+        let faces = faces(&[
+            (1, [1.0, 0.0, 0.0]),
+            (2, [1.0, 0.1, 0.0]),
+            (3, [1.0, 0.0, 0.1]),
+        ]);
+
+        let stop = AtomicBool::new(true);
+        assert!(FaceClusterer::default().cluster(&faces, &stop).is_none());
+    }
+}
@@ -7,6 +7,7 @@ use crate::thumbnailify::{ThumbnailSize, Thumbnailer};
 
 use anyhow::*;
 
+use super::model_registry::{self, ModelRegistry};
 use super::nms::Nms;
 use image::ImageReader;
 use std::io::Cursor;
@@ -92,7 +93,11 @@ pub struct FaceExtractor {
 }
 
 impl FaceExtractor {
-    pub fn build(base_path: &Path, thumbnailer: Thumbnailer) -> Result<FaceExtractor> {
+    pub fn build(
+        base_path: &Path,
+        thumbnailer: Thumbnailer,
+        models: &ModelRegistry,
+    ) -> Result<FaceExtractor> {
         let faces_base_path = PathBuf::from(base_path).join("faces");
         let _ = std::fs::create_dir_all(&faces_base_path)?;
 
@@ -105,20 +110,17 @@ impl FaceExtractor {
 
         let bz_params_default = BlazeFaceParams::default();
 
+        let blaze_face_path = models.resolve(&model_registry::BLAZE_FACE_640)?;
+
         let blaze_face_default =
             FaceDetectorBuilder::new(FaceDetection::BlazeFace640(bz_params_default.clone()))
-                .download()
+                .from_file(blaze_face_path.to_string_lossy().into())
                 .build()?;
 
         detectors.push((blaze_face_default, "blaze_face_640_default".into()));
 
-        let mtcnn_params = rust_faces::MtCnnParams::default();
-
-        let mtcnn = FaceDetectorBuilder::new(FaceDetection::MtCnn(mtcnn_params))
-            .download()
-            .build()?;
-
-        detectors.push((mtcnn, "mtcnn".into()));
+        // MTCNN isn't used because rust-faces can only open it by downloading it
+        // itself, which bypasses the model registry's verification and offline sources.
 
         Ok(FaceExtractor {
             faces_base_path,
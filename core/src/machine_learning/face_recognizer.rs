@@ -2,153 +2,182 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
 use std::path::PathBuf;
 
-use anyhow::{Result, anyhow};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 
 use opencv::core::Mat;
 use opencv::imgcodecs;
-use opencv::objdetect::{FaceRecognizerSF, FaceRecognizerSF_DisType};
+use opencv::objdetect::FaceRecognizerSF;
 use opencv::prelude::*;
 
-use reqwest::header::{ACCEPT, HeaderMap, HeaderValue};
-
-use tracing::info;
-
-use crate::people::model::{DetectedFace, PersonForRecognition, PersonId};
-
-pub struct FaceRecognizer {
-    /// Person recognition data and a opencv matrix of aligned face features.
-    people: Vec<(PersonForRecognition, Mat)>,
+use super::model_registry::{self, ModelRegistry};
+use crate::people::model::{
+    DetectedFace, Embedding, EmbeddingModel, PersonForRecognition, PersonId,
+};
 
+/// Computes face embeddings with the SFace face recognition model.
+pub struct FaceEmbedder {
     /// Path to OpenCV face recognition model
     model_path: PathBuf,
 }
 
-impl FaceRecognizer {
-    //const COSINE_SIMILAR_THRESH: f64 = 0.363;
-    const L2NORM_SIMILAR_THRESH: f64 = 1.128;
+impl FaceEmbedder {
+    pub const MODEL: EmbeddingModel = EmbeddingModel {
+        name: "sface_2021dec",
+        version: 1,
+    };
 
-    const MODEL_URL: &'static str = "https://github.com/blissd/fotema-opencv_zoo/raw/fotema-1.0/models/face_recognition_sface/face_recognition_sface_2021dec.onnx";
+    pub fn build(models: &ModelRegistry) -> Result<Self> {
+        let model_path = models.resolve(&model_registry::SFACE)?;
+        Ok(Self { model_path })
+    }
 
-    pub fn build(cache_dir: &Path, people: Vec<PersonForRecognition>) -> Result<Self> {
-        let model_path = {
-            let base_path = cache_dir.join("opencv_models");
-            std::fs::create_dir_all(&base_path)?;
-            base_path.join("face_recognition_sface_2021dec.onnx")
-        };
+    pub fn embed(&self, face: &DetectedFace) -> Result<Embedding> {
+        // WARNING cannot re-use recognizer. MUST use a separate one for each face.
+        let mut face_recognizer =
+            FaceRecognizerSF::create_def(&self.model_path.to_string_lossy(), "")?;
 
-        Self::download_model(Self::MODEL_URL, &model_path)?;
+        let face_img = imgcodecs::imread_def(&face.face_path.to_string_lossy())?;
 
-        let mut recognizer = Self {
-            people: vec![],
-            model_path,
-        };
+        let face_landmarks = face.landmarks_as_mat();
 
-        for person in people {
-            // WARNING cannot re-use recognizer. MUST use a separate one for each person.
-            let mut opencv_face_recognizer =
-                FaceRecognizerSF::create_def(&recognizer.model_path.to_string_lossy(), "")?;
+        let mut aligned_face = Mat::default();
+        face_recognizer.align_crop(&face_img, &face_landmarks, &mut aligned_face)?;
 
-            let face_img = imgcodecs::imread_def(&person.face.face_path.to_string_lossy())?;
+        // Run feature extraction with given aligned_face
+        let mut face_features = Mat::default();
+        face_recognizer.feature(&aligned_face, &mut face_features)?;
 
-            let face_landarks = person.face.landmarks_as_mat();
+        let features = face_features.data_typed::<f32>()?.to_vec();
+        Ok(Embedding::new(features))
+    }
+}
 
-            let mut aligned_face = Mat::default();
-            opencv_face_recognizer.align_crop(&face_img, &face_landarks, &mut aligned_face)?;
+/// Embeddings of a diverse selection of a person's confirmed faces, so that a person
+/// can be recognized at different ages, angles, with or without glasses, etc.
+#[derive(Debug, Clone)]
+pub struct PersonModel {
+    pub person_id: PersonId,
 
-            // Run feature extraction with given aligned_face
-            let mut face_features = Mat::default();
-            opencv_face_recognizer.feature(&aligned_face, &mut face_features)?;
+    /// Time of last recognition
+    pub recognized_at: DateTime<Utc>,
 
-            recognizer.people.push((person, face_features));
+    exemplars: Vec<Embedding>,
+}
+
+impl PersonModel {
+    /// Upper bound on comparisons per person for each unknown face.
+    pub const MAX_EXEMPLARS: usize = 24;
+
+    /// Build model from embeddings of a person's confirmed faces.
+    /// Returns None if there are no embeddings.
+    pub fn build(person: &PersonForRecognition, embeddings: Vec<Embedding>) -> Option<Self> {
+        let exemplars = Self::select_exemplars(embeddings, Self::MAX_EXEMPLARS);
+        if exemplars.is_empty() {
+            return None;
         }
 
-        Ok(recognizer)
+        Some(Self {
+            person_id: person.person_id,
+            recognized_at: person.recognized_at,
+            exemplars,
+        })
     }
 
-    pub fn recognize(&self, unknown_face: &DetectedFace) -> Result<Option<PersonId>> {
-        let mut face_recognizer =
-            FaceRecognizerSF::create_def(&self.model_path.to_string_lossy(), "")?;
+    /// Distance to the nearest exemplar.
+    pub fn distance(&self, embedding: &Embedding) -> f32 {
+        self.exemplars
+            .iter()
+            .map(|exemplar| exemplar.distance(embedding))
+            .filter(|distance| !distance.is_nan())
+            .min_by(|a, b| a.total_cmp(b))
+            .unwrap_or(f32::NAN)
+    }
 
-        let face_img = imgcodecs::imread_def(&unknown_face.face_path.to_string_lossy())?;
+    /// Pick at most `max` embeddings that cover the spread of a person's faces.
+    /// Starts with the most typical face, the one nearest the centroid, then repeatedly
+    /// adds the face furthest from those already picked (farthest-point sampling).
+    fn select_exemplars(mut embeddings: Vec<Embedding>, max: usize) -> Vec<Embedding> {
+        if embeddings.len() <= max {
+            return embeddings;
+        }
 
-        let face_landmarks = unknown_face.landmarks_as_mat();
+        let dimensions = embeddings[0].features().len();
+        let mut sum = vec![0.0; dimensions];
+        for embedding in &embeddings {
+            for (total, x) in sum.iter_mut().zip(embedding.features()) {
+                *total += x;
+            }
+        }
+        let centroid = Embedding::new(sum);
 
-        let mut aligned_face = Mat::default();
-        face_recognizer.align_crop(&face_img, &face_landmarks, &mut aligned_face)?;
+        let first = embeddings
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.distance(&centroid).total_cmp(&b.distance(&centroid)))
+            .map(|(index, _)| index)
+            .unwrap_or(0);
 
-        let mut face_features = Mat::default();
-        face_recognizer.feature(&aligned_face, &mut face_features)?;
+        let mut exemplars = vec![embeddings.swap_remove(first)];
 
-        let best_person_and_score = self
-            .people
+        // Distance of each remaining embedding to its nearest exemplar.
+        let mut nearest: Vec<f32> = embeddings
             .iter()
-            .filter(|(p, _)| p.recognized_at <= unknown_face.detected_at)
-            .map(|(person, person_face_features)| {
-                let l2_score = face_recognizer.match_(
-                    &person_face_features,
-                    &face_features,
-                    FaceRecognizerSF_DisType::FR_NORM_L2.into(),
-                );
-                (
-                    person,
-                    l2_score.unwrap_or(Self::L2NORM_SIMILAR_THRESH + 100.0),
-                )
-            })
-            // FIXME do we need to filter out NaNs?
-            .min_by_key(|x| (x.1 * 10000.0) as i32); // f64 doesn't implement Ord.
-
-        if let Some((person, l2_score)) = best_person_and_score {
-            // The internet said the l2norm should give better results than the cosine.
-            if l2_score <= Self::L2NORM_SIMILAR_THRESH {
-                return Ok(Some(person.person_id));
+            .map(|embedding| embedding.distance(&exemplars[0]))
+            .collect();
+
+        while exemplars.len() < max && !embeddings.is_empty() {
+            let furthest = nearest
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+
+            nearest.swap_remove(furthest);
+            let exemplar = embeddings.swap_remove(furthest);
+
+            for (distance, embedding) in nearest.iter_mut().zip(&embeddings) {
+                *distance = distance.min(embedding.distance(&exemplar));
             }
+
+            exemplars.push(exemplar);
         }
 
-        Ok(None)
+        exemplars
     }
+}
 
-    fn download_model(url: &str, destination: &Path) -> Result<()> {
-        if destination.exists() {
-            info!("Face recognition model already downloaded.");
-            return Ok(());
-        }
+/// Matches faces to people by comparing embeddings.
+pub struct FaceRecognizer {
+    people: Vec<PersonModel>,
+}
 
-        info!("Downloading face recognition model from {}", url);
-        info!("Model is approximately 40MB.");
+impl FaceRecognizer {
+    //const COSINE_SIMILAR_THRESH: f64 = 0.363;
+    const L2NORM_SIMILAR_THRESH: f32 = 1.128;
 
-        let headers = {
-            let mut headers = HeaderMap::new();
-            headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
-            headers
-        };
+    pub fn build(people: Vec<PersonModel>) -> Self {
+        Self { people }
+    }
 
-        let client = reqwest::blocking::Client::new();
-        let mut response = client.get(url).headers(headers).send()?;
-
-        if response.status().is_success() {
-            let tmp_path = destination.with_extension("tmp");
-            let tmp_file = File::create(&tmp_path)?;
-            let mut writer = BufWriter::new(tmp_file);
-            while let Ok(bytes_read) = response.copy_to(&mut writer) {
-                if bytes_read == 0 {
-                    break;
-                }
-            }
-            info!("Face recognition model successfully downloaded.");
-            std::fs::rename(tmp_path, destination)?;
-
-            Ok(())
-        } else {
-            Err(anyhow!(
-                "Failed to download face recognition model: {}",
-                response.status()
-            ))
-        }
+    pub fn recognize(
+        &self,
+        unknown_face: &DetectedFace,
+        embedding: &Embedding,
+    ) -> Option<PersonId> {
+        let (person, l2_score) = self
+            .people
+            .iter()
+            .filter(|p| p.recognized_at <= unknown_face.detected_at)
+            .map(|person| (person, person.distance(embedding)))
+            .filter(|(_, l2_score)| !l2_score.is_nan())
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        // The internet said the l2norm should give better results than the cosine.
+        (l2_score <= Self::L2NORM_SIMILAR_THRESH).then_some(person.person_id)
     }
 }
 
@@ -156,31 +185,116 @@ impl FaceRecognizer {
 mod tests {
     use super::*;
     use crate::people::model::{FaceId, Rect};
-    use std::path::PathBuf;
-
-    #[test]
-    fn test_recognize() {
-        let person_face = DetectedFace {
-            face_id: FaceId::new(1),
-            face_path: PathBuf::from(
-                "/var/home/david/.var/app/app.fotema.Fotema.Devel/cache/app.fotema.Fotema.Devel/photo_faces/0003/3027/0_blaze_face_640_original.png",
-            ),
+    use chrono::TimeDelta;
+
+    fn face(face_id: i64, detected_at: DateTime<Utc>) -> DetectedFace {
+        DetectedFace {
+            face_id: FaceId::new(face_id),
+            face_path: PathBuf::from("face.png"),
+            small_thumbnail_path: PathBuf::from("face_small.png"),
+            detected_at,
             bounds: Rect {
                 x: 0.,
                 y: 0.,
                 width: 100.,
                 height: 100.,
             },
-
+            is_source_original: false,
             right_eye: (20., 10.),
             left_eye: (10., 10.),
             nose: (15., 15.),
             right_mouth_corner: (20., 20.),
             left_mouth_corner: (10., 20.),
-
             confidence: 0.98,
+        }
+    }
+
+    fn person(
+        person_id: i64,
+        recognized_at: DateTime<Utc>,
+        embeddings: Vec<Vec<f32>>,
+    ) -> PersonModel {
+        let person = PersonForRecognition {
+            person_id: PersonId::new(person_id),
+            recognized_at,
+            faces: vec![face(person_id, recognized_at)],
         };
+        let embeddings = embeddings.into_iter().map(Embedding::new).collect();
+        PersonModel::build(&person, embeddings).unwrap()
+    }
+
+    #[test]
+    fn test_recognize_nearest_person() {
+        let now = Utc::now();
+        let recognizer = FaceRecognizer::build(vec![
+            person(1, now, vec![vec![1.0, 0.0, 0.0]]),
+            person(2, now, vec![vec![0.0, 1.0, 0.0]]),
+        ]);
+
+        let unknown = face(10, now + TimeDelta::seconds(1));
+
+        let embedding = Embedding::new(vec![0.2, 1.0, 0.0]);
+        assert_eq!(
+            Some(PersonId::new(2)),
+            recognizer.recognize(&unknown, &embedding)
+        );
+
+        // Too far from everyone.
+        let embedding = Embedding::new(vec![0.0, 0.0, 1.0]);
+        assert_eq!(None, recognizer.recognize(&unknown, &embedding));
+    }
 
-        let _ = FaceRecognizer::build(&person_face).unwrap();
+    #[test]
+    fn test_recognize_by_nearest_exemplar() {
+        let now = Utc::now();
+
+        // Person 1 has been seen from two very different angles.
+        let recognizer = FaceRecognizer::build(vec![
+            person(1, now, vec![vec![1.0, 0.0, 0.0], vec![0.0, 0.0, 1.0]]),
+            person(2, now, vec![vec![0.0, 1.0, 0.0]]),
+        ]);
+
+        let unknown = face(10, now + TimeDelta::seconds(1));
+        let embedding = Embedding::new(vec![0.0, 0.2, 1.0]);
+        assert_eq!(
+            Some(PersonId::new(1)),
+            recognizer.recognize(&unknown, &embedding)
+        );
+    }
+
+    #[test]
+    fn test_recognize_skips_people_recognized_after_face_detected() {
+        let now = Utc::now();
+        let recognizer = FaceRecognizer::build(vec![person(1, now, vec![vec![1.0, 0.0]])]);
+
+        let unknown = face(10, now - TimeDelta::seconds(1));
+        let embedding = Embedding::new(vec![1.0, 0.0]);
+        assert_eq!(None, recognizer.recognize(&unknown, &embedding));
+    }
+
+    #[test]
+    fn test_select_exemplars_covers_spread() {
+        // Many near-identical faces and two outliers.
+        let mut embeddings: Vec<Embedding> = (0..10)
+            .map(|i| Embedding::new(vec![1.0, 0.01 * i as f32, 0.0]))
+            .collect();
+        embeddings.push(Embedding::new(vec![0.0, 1.0, 0.0]));
+        embeddings.push(Embedding::new(vec![0.0, 0.0, 1.0]));
+
+        let exemplars = PersonModel::select_exemplars(embeddings, 3);
+        assert_eq!(3, exemplars.len());
+        assert!(exemplars[0].features()[0] > 0.9);
+        assert!(exemplars.contains(&Embedding::new(vec![0.0, 1.0, 0.0])));
+        assert!(exemplars.contains(&Embedding::new(vec![0.0, 0.0, 1.0])));
+    }
+
+    #[test]
+    fn test_person_model_needs_embeddings() {
+        let person = PersonForRecognition {
+            person_id: PersonId::new(1),
+            recognized_at: Utc::now(),
+            faces: vec![],
+        };
+        assert!(PersonModel::build(&person, vec![]).is_none());
     }
 }
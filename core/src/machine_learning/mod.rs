@@ -3,7 +3,9 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 //pub mod blaze_face;
+pub mod face_clusterer;
 pub mod face_extractor;
 pub mod face_recognizer;
+pub mod model_registry;
 pub mod nms;
 //pub mod yolov8;
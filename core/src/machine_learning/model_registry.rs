@@ -0,0 +1,470 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Finds the machine learning models used for face detection and recognition.
+//!
+//! Models are looked for, in order, in the system data directories, a directory chosen
+//! by the user, and the cache directory. Models that can't be found are downloaded to
+//! the cache directory. Computers without internet access can have the models installed
+//! in a system data directory, use a directory of models, or download from a local mirror.
+
+use anyhow::*;
+use gio::glib;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result::Ok;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use strum::AsRefStr;
+use tracing::{info, warn};
+use url::Url;
+
+/// A machine learning model file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelSpec {
+    /// Human readable name of model.
+    pub name: &'static str,
+
+    /// Name of model file in a models directory.
+    pub file_name: &'static str,
+
+    /// Where to download the model from when no mirror is configured.
+    pub url: &'static str,
+
+    /// SHA-256 digest of the model file as lowercase hex.
+    /// Model files that don't match are ignored. A model without a digest can't be
+    /// verified, so is never used.
+    pub sha256: Option<&'static str>,
+}
+
+/// Face detection model.
+pub const BLAZE_FACE_640: ModelSpec = ModelSpec {
+    name: "BlazeFace",
+    file_name: "blazefaces-640.onnx",
+    url: "https://github.com/rustybuilder/model-zoo/raw/main/face-detection/blazefaces-640.onnx",
+    sha256: None,
+};
+
+/// Face recognition model.
+pub const SFACE: ModelSpec = ModelSpec {
+    name: "SFace",
+    file_name: "face_recognition_sface_2021dec.onnx",
+    url: "https://github.com/blissd/fotema-opencv_zoo/raw/fotema-1.0/models/face_recognition_sface/face_recognition_sface_2021dec.onnx",
+    sha256: None,
+};
+
+/// Every model used by Fotema.
+pub const MODELS: [ModelSpec; 2] = [BLAZE_FACE_640, SFACE];
+
+/// Where a model was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ModelLocation {
+    /// System data directory, such as `/usr/share/fotema/models`.
+    System,
+
+    /// Directory chosen by the user.
+    User,
+
+    /// Downloaded to the cache directory.
+    Cache,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelStatus {
+    /// Model file found and verified.
+    Available(ModelLocation, PathBuf),
+
+    /// Model has no pinned digest, so can't be verified and won't be used.
+    Unpinned,
+
+    /// Model file not found. Will be downloaded when needed.
+    Missing,
+
+    /// Model file found, but doesn't match the pinned digest.
+    Mismatch(PathBuf),
+}
+
+/// Fetches a model file. Implement to fetch models from somewhere other than
+/// an HTTP or file URL.
+pub trait Downloader: std::fmt::Debug + Send + Sync {
+    fn download(&self, url: &Url, destination: &Path) -> Result<()>;
+}
+
+/// Downloads HTTP and HTTPS URLs and copies file URLs.
+#[derive(Debug, Default)]
+pub struct UrlDownloader;
+
+impl Downloader for UrlDownloader {
+    fn download(&self, url: &Url, destination: &Path) -> Result<()> {
+        match url.scheme() {
+            "file" => {
+                let source = url
+                    .to_file_path()
+                    .map_err(|_| anyhow!("Invalid file URL: {}", url))?;
+                std::fs::copy(source, destination)?;
+                Ok(())
+            }
+            "http" | "https" => {
+                let client = reqwest::blocking::Client::new();
+                let mut response = client
+                    .get(url.as_str())
+                    .header(reqwest::header::ACCEPT, "*/*")
+                    .send()?;
+
+                if !response.status().is_success() {
+                    bail!("Failed to download {}: {}", url, response.status());
+                }
+
+                let mut writer = BufWriter::new(File::create(destination)?);
+                response.copy_to(&mut writer)?;
+                writer.flush()?;
+                Ok(())
+            }
+            scheme => Err(anyhow!("Unsupported scheme for model URL: {}", scheme)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    /// Directories of models installed with the system.
+    system_dirs: Vec<PathBuf>,
+
+    /// Directory of models chosen by the user.
+    user_dir: Option<PathBuf>,
+
+    /// Directory models are downloaded to.
+    cache_dir: PathBuf,
+
+    /// Base URL to download models from instead of their usual URLs.
+    mirror: Option<String>,
+
+    downloader: Arc<dyn Downloader>,
+
+    /// Model files already verified, with their modification time, so that large
+    /// models aren't hashed every time they are opened.
+    verified: Arc<Mutex<HashSet<(PathBuf, SystemTime)>>>,
+}
+
+impl ModelRegistry {
+    pub fn build(cache_dir: &Path) -> Result<Self> {
+        let system_dirs = glib::system_data_dirs()
+            .into_iter()
+            .map(|dir| dir.join("fotema").join("models"))
+            .collect();
+
+        let models_dir = cache_dir.join("models");
+        std::fs::create_dir_all(&models_dir)?;
+
+        // The face recognition model used to be downloaded to a different directory.
+        let old_sface = cache_dir.join("opencv_models").join(SFACE.file_name);
+        if old_sface.exists() {
+            let _ = std::fs::rename(&old_sface, models_dir.join(SFACE.file_name));
+        }
+
+        Ok(Self::new(system_dirs, models_dir))
+    }
+
+    fn new(system_dirs: Vec<PathBuf>, cache_dir: PathBuf) -> Self {
+        Self {
+            system_dirs,
+            user_dir: None,
+            cache_dir,
+            mirror: None,
+            downloader: Arc::new(UrlDownloader),
+            verified: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn with_user_dir(mut self, user_dir: Option<PathBuf>) -> Self {
+        self.user_dir = user_dir;
+        self
+    }
+
+    /// Download models from a mirror. Model files are expected directly under the
+    /// mirror's base URL, which can be a `file://` URL.
+    pub fn with_mirror(mut self, mirror: Option<String>) -> Self {
+        self.mirror = mirror.filter(|m| !m.trim().is_empty());
+        self
+    }
+
+    pub fn with_downloader(mut self, downloader: Arc<dyn Downloader>) -> Self {
+        self.downloader = downloader;
+        self
+    }
+
+    /// Model files to check, in order of preference.
+    fn candidates(&self, spec: &ModelSpec) -> Vec<(ModelLocation, PathBuf)> {
+        let system = self
+            .system_dirs
+            .iter()
+            .map(|dir| (ModelLocation::System, dir.join(spec.file_name)));
+        let user = self
+            .user_dir
+            .iter()
+            .map(|dir| (ModelLocation::User, dir.join(spec.file_name)));
+        let cache = std::iter::once((ModelLocation::Cache, self.cache_dir.join(spec.file_name)));
+
+        system.chain(user).chain(cache).collect()
+    }
+
+    /// Finds a model without downloading it.
+    pub fn status(&self, spec: &ModelSpec) -> ModelStatus {
+        if spec.sha256.is_none() {
+            return ModelStatus::Unpinned;
+        }
+
+        let mut mismatch = None;
+
+        for (location, path) in self.candidates(spec) {
+            if !path.is_file() {
+                continue;
+            }
+
+            match self.verify(spec, &path) {
+                Ok(true) => return ModelStatus::Available(location, path),
+                Ok(false) => {
+                    warn!("Model {} at {:?} doesn't match its digest", spec.name, path);
+                    mismatch.get_or_insert(path);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to verify model {} at {:?}: {:?}",
+                        spec.name, path, e
+                    );
+                }
+            }
+        }
+
+        mismatch.map_or(ModelStatus::Missing, ModelStatus::Mismatch)
+    }
+
+    /// Path to a verified model file, downloading the model if needed.
+    pub fn resolve(&self, spec: &ModelSpec) -> Result<PathBuf> {
+        match self.status(spec) {
+            ModelStatus::Available(location, path) => {
+                info!(
+                    "Using {} model from {} directory",
+                    spec.name,
+                    location.as_ref()
+                );
+                return Ok(path);
+            }
+            ModelStatus::Unpinned => {
+                bail!(
+                    "No digest is pinned for {} model, so it can't be verified",
+                    spec.name
+                );
+            }
+            _ => {}
+        }
+
+        let url = self.url(spec)?;
+        info!("Downloading {} model from {}", spec.name, url);
+
+        let destination = self.cache_dir.join(spec.file_name);
+        let tmp_path = destination.with_extension("tmp");
+
+        let result = self
+            .downloader
+            .download(&url, &tmp_path)
+            .and_then(|_| self.verify(spec, &tmp_path));
+
+        match result {
+            Ok(true) => {
+                std::fs::rename(&tmp_path, &destination)?;
+                info!("Downloaded {} model to {:?}", spec.name, destination);
+                Ok(destination)
+            }
+            Ok(false) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                Err(anyhow!(
+                    "Downloaded {} model from {} doesn't match its digest",
+                    spec.name,
+                    url
+                ))
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                Err(e.context(format!("Failed to download {} model", spec.name)))
+            }
+        }
+    }
+
+    /// URL to download a model from.
+    pub fn url(&self, spec: &ModelSpec) -> Result<Url> {
+        if let Some(ref mirror) = self.mirror {
+            let mut base = Url::parse(mirror.trim())?;
+
+            // Without a trailing slash, joining replaces the last path segment.
+            if !base.path().ends_with('/') {
+                base.set_path(&format!("{}/", base.path()));
+            }
+
+            Ok(base.join(spec.file_name)?)
+        } else {
+            Ok(Url::parse(spec.url)?)
+        }
+    }
+
+    /// Checks a model file against its pinned digest.
+    fn verify(&self, spec: &ModelSpec, path: &Path) -> Result<bool> {
+        let Some(expected) = spec.sha256 else {
+            bail!("No digest is pinned for {} model", spec.name);
+        };
+
+        let key = (path.to_path_buf(), std::fs::metadata(path)?.modified()?);
+        if self.verified.lock().unwrap().contains(&key) {
+            return Ok(true);
+        }
+
+        let is_match = sha256(path)? == expected;
+        if is_match {
+            self.verified.lock().unwrap().insert(key);
+        }
+
+        Ok(is_match)
+    }
+}
+
+/// SHA-256 digest of a file as lowercase hex.
+pub fn sha256(path: &Path) -> Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 digest of "abc".
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    const ABC: ModelSpec = ModelSpec {
+        name: "ABC",
+        file_name: "abc.onnx",
+        url: "https://example.com/abc.onnx",
+        sha256: Some(ABC_SHA256),
+    };
+
+    #[test]
+    fn test_sha256() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc");
+        std::fs::write(&path, "abc").unwrap();
+
+        assert_eq!(ABC_SHA256, sha256(&path).unwrap());
+    }
+
+    #[test]
+    fn test_status_prefers_user_dir_to_cache() {
+        let user_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::write(user_dir.path().join("abc.onnx"), "abc").unwrap();
+        std::fs::write(cache_dir.path().join("abc.onnx"), "abc").unwrap();
+
+        let registry = ModelRegistry::new(vec![], cache_dir.path().into())
+            .with_user_dir(Some(user_dir.path().into()));
+
+        assert_eq!(
+            ModelStatus::Available(ModelLocation::User, user_dir.path().join("abc.onnx")),
+            registry.status(&ABC)
+        );
+    }
+
+    #[test]
+    fn test_status_unpinned_without_digest() {
+        let mirror_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::write(mirror_dir.path().join("abc.onnx"), "abc").unwrap();
+        std::fs::write(cache_dir.path().join("abc.onnx"), "abc").unwrap();
+
+        let unpinned = ModelSpec {
+            sha256: None,
+            ..ABC
+        };
+        let mirror = Url::from_directory_path(mirror_dir.path()).unwrap();
+        let registry = ModelRegistry::new(vec![], cache_dir.path().into())
+            .with_mirror(Some(mirror.to_string()));
+
+        // Neither the file that is present nor a download is accepted.
+        assert_eq!(ModelStatus::Unpinned, registry.status(&unpinned));
+        assert!(registry.resolve(&unpinned).is_err());
+        assert!(!cache_dir.path().join("abc.tmp").exists());
+    }
+
+    #[test]
+    fn test_status_skips_mismatched_file() {
+        let system_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::write(system_dir.path().join("abc.onnx"), "not abc").unwrap();
+
+        let registry = ModelRegistry::new(vec![system_dir.path().into()], cache_dir.path().into());
+        assert_eq!(
+            ModelStatus::Mismatch(system_dir.path().join("abc.onnx")),
+            registry.status(&ABC)
+        );
+
+        std::fs::write(cache_dir.path().join("abc.onnx"), "abc").unwrap();
+        assert_eq!(
+            ModelStatus::Available(ModelLocation::Cache, cache_dir.path().join("abc.onnx")),
+            registry.status(&ABC)
+        );
+    }
+
+    #[test]
+    fn test_resolve_downloads_from_file_mirror() {
+        let mirror_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::write(mirror_dir.path().join("abc.onnx"), "abc").unwrap();
+
+        let mirror = Url::from_directory_path(mirror_dir.path()).unwrap();
+        let registry = ModelRegistry::new(vec![], cache_dir.path().into())
+            .with_mirror(Some(mirror.to_string()));
+
+        assert_eq!(ModelStatus::Missing, registry.status(&ABC));
+
+        let path = registry.resolve(&ABC).unwrap();
+        assert_eq!(cache_dir.path().join("abc.onnx"), path);
+        assert_eq!("abc", std::fs::read_to_string(path).unwrap());
+    }
+
+    #[test]
+    fn test_resolve_rejects_mismatched_download() {
+        let mirror_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::write(mirror_dir.path().join("abc.onnx"), "not abc").unwrap();
+
+        let mirror = Url::from_directory_path(mirror_dir.path()).unwrap();
+        let registry = ModelRegistry::new(vec![], cache_dir.path().into())
+            .with_mirror(Some(mirror.to_string()));
+
+        assert!(registry.resolve(&ABC).is_err());
+        assert!(!cache_dir.path().join("abc.onnx").exists());
+        assert!(!cache_dir.path().join("abc.tmp").exists());
+    }
+
+    #[test]
+    fn test_url_with_mirror() {
+        let registry = ModelRegistry::new(vec![], PathBuf::from("/tmp"))
+            .with_mirror(Some("https://mirror.example.com/models".into()));
+
+        assert_eq!(
+            "https://mirror.example.com/models/abc.onnx",
+            registry.url(&ABC).unwrap().as_str()
+        );
+
+        let registry = ModelRegistry::new(vec![], PathBuf::from("/tmp"));
+        assert_eq!(
+            "https://example.com/abc.onnx",
+            registry.url(&ABC).unwrap().as_str()
+        );
+    }
+}
@@ -28,7 +28,7 @@ pub fn to_base64(p: &Path) -> String {
     BASE64_STANDARD.encode(p.as_os_str().as_bytes())
 }
 
-pub fn from_base64(s: &String) -> Result<PathBuf> {
+pub fn from_base64(s: &str) -> Result<PathBuf> {
     Ok(BASE64_STANDARD
         .decode(s)
         .map(OsString::from_vec)
@@ -7,7 +7,7 @@ use anyhow::*;
 use tracing::{error, info};
 
 use super::model::MigratedFace;
-use crate::FlatpakPathBuf;
+use crate::library_root::LibraryRoots;
 use crate::thumbnailify;
 
 use std::path::{Path, PathBuf};
@@ -18,7 +18,7 @@ use std::path::{Path, PathBuf};
 #[derive(Debug, Clone)]
 pub struct Migrate {
     data_dir_base_path: PathBuf,
-    library_dir_base_path: FlatpakPathBuf,
+    library_roots: LibraryRoots,
     repo: Repository,
 }
 
@@ -26,12 +26,12 @@ impl Migrate {
     pub fn build(
         repo: Repository,
         data_dir_base_path: &Path,
-        library_dir_base_path: FlatpakPathBuf,
+        library_roots: LibraryRoots,
     ) -> Migrate {
         Migrate {
             repo,
             data_dir_base_path: data_dir_base_path.into(),
-            library_dir_base_path,
+            library_roots,
         }
     }
 
@@ -58,10 +58,15 @@ impl Migrate {
         info!("Migrating {} faces", faces_to_migrate.len());
 
         faces_to_migrate.into_iter().for_each(|f| {
-            let picture_path = self
-                .library_dir_base_path
-                .host_path
-                .join(f.picture_relative_path);
+            let Some(picture_path) = self
+                .library_roots
+                .resolve(f.root_id, &f.picture_relative_path)
+                .map(|path| path.host_path)
+            else {
+                error!("No library root for face {:?}", f.face_id);
+                return;
+            };
+
             info!(
                 "Migrating face detection and recognition files for {:?}",
                 picture_path
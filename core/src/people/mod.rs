@@ -7,6 +7,8 @@ pub mod model;
 pub mod repo;
 pub mod thumbnailer;
 
+pub use model::Embedding;
+pub use model::EmbeddingModel;
 pub use model::Face;
 pub use model::FaceDetectionCandidate;
 pub use model::FaceId;
@@ -14,5 +16,6 @@ pub use model::FaceToMigrate;
 pub use model::MigratedFace;
 pub use model::Person;
 pub use model::PersonId;
+pub use model::SuggestedPerson;
 pub use repo::Repository;
 pub use thumbnailer::PersonThumbnailer;
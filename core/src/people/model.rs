@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use crate::FlatpakPathBuf;
+use crate::library_root::LibraryRootId;
 use crate::photo::model::PictureId;
 use crate::thumbnailify;
 use chrono::{DateTime, Utc};
@@ -23,7 +24,7 @@ impl FaceDetectionCandidate {
 }
 
 /// Database ID
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct FaceId(i64);
 
 impl FaceId {
@@ -66,7 +67,7 @@ impl Person {
 }
 
 /// Database ID
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct PersonId(i64);
 
 impl PersonId {
@@ -114,6 +115,13 @@ pub struct Face {
     pub thumbnail_path: PathBuf,
 }
 
+/// Unknown faces that look like the same person, for the user to name.
+#[derive(Debug, Clone)]
+pub struct SuggestedPerson {
+    /// Faces in group, most confident first.
+    pub faces: Vec<Face>,
+}
+
 /// A face hat has been detected, containing the appropriate landmarks to perform
 /// a recognition upon the face.
 #[derive(Debug, Clone)]
@@ -203,6 +211,60 @@ impl DetectedFace {
     }
 }
 
+/// Face recognition model that computed an embedding.
+/// Embeddings from different models, or different versions of a model, can't be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddingModel {
+    pub name: &'static str,
+
+    /// Increment when the model, or how faces are aligned for it, changes.
+    pub version: u32,
+}
+
+/// Face recognition feature vector of a face.
+/// Normalized to unit length, so that the distance between two embeddings is
+/// the same as OpenCV's `FR_NORM_L2` distance between the features they came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embedding(Vec<f32>);
+
+impl Embedding {
+    pub fn new(features: Vec<f32>) -> Self {
+        let norm = features.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            Self(features.into_iter().map(|x| x / norm).collect())
+        } else {
+            Self(features)
+        }
+    }
+
+    pub fn features(&self) -> &[f32] {
+        &self.0
+    }
+
+    /// Euclidean distance to another embedding.
+    pub fn distance(&self, other: &Embedding) -> f32 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    /// Features as little-endian bytes, for storing in the database.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let features = bytes
+            .chunks_exact(4)
+            .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+            .collect();
+        Self(features)
+    }
+}
+
 /// A person to perform face recognition for
 #[derive(Debug, Clone)]
 pub struct PersonForRecognition {
@@ -212,8 +274,8 @@ pub struct PersonForRecognition {
     /// Time of last recognition
     pub recognized_at: DateTime<Utc>,
 
-    /// "Best" confirmed face for person.
-    pub face: DetectedFace,
+    /// All confirmed faces for person, most confident first.
+    pub faces: Vec<DetectedFace>,
 }
 
 /// A face to migrated from Fotema 1.x to Fotema 2.0
@@ -222,6 +284,9 @@ pub struct FaceToMigrate {
     pub face_id: FaceId,
     pub face_index: u32,
 
+    /// Library root that picture path is relative to.
+    pub root_id: LibraryRootId,
+
     /// Path to picture in library.
     /// Relative because people repository cannot have a library base path.
     pub picture_relative_path: PathBuf,
@@ -236,3 +301,28 @@ pub struct MigratedFace {
     pub bounds_path: PathBuf,
     pub thumbnail_path: PathBuf,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_is_normalized() {
+        let embedding = Embedding::new(vec![3.0, 4.0]);
+        assert_eq!(&[0.6, 0.8], embedding.features());
+    }
+
+    #[test]
+    fn test_embedding_bytes_round_trip() {
+        let embedding = Embedding::new(vec![1.0, -2.0, 0.5, 8.0]);
+        assert_eq!(embedding, Embedding::from_bytes(&embedding.to_bytes()));
+    }
+
+    #[test]
+    fn test_embedding_distance() {
+        let a = Embedding::new(vec![1.0, 0.0]);
+        let b = Embedding::new(vec![0.0, 5.0]);
+        assert_eq!(0.0, a.distance(&a));
+        assert!((a.distance(&b) - 2.0_f32.sqrt()).abs() < 1e-6);
+    }
+}
@@ -2,22 +2,27 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use crate::library_root::LibraryRootId;
 use crate::photo::model::PictureId;
 
 use crate::machine_learning::face_extractor;
 use crate::path_encoding;
+use crate::people::EmbeddingModel;
 use crate::people::FaceId;
 use crate::people::FaceToMigrate;
 use crate::people::MigratedFace;
 use crate::people::PersonId;
 use crate::people::model;
+use crate::people::model::Embedding;
 use crate::people::model::PersonForRecognition;
 use crate::people::model::Rect;
 
 use anyhow::*;
 use rusqlite;
+use rusqlite::OptionalExtension;
 use rusqlite::Row;
 use rusqlite::params;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::result::Result::Ok;
 use std::sync::{Arc, Mutex};
@@ -61,6 +66,24 @@ impl Repository {
     /// violation on the bounds_path.
     pub fn delete_faces(&self, picture_id: PictureId) -> Result<()> {
         let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "DELETE FROM pictures_faces_embeddings
+            WHERE face_id IN (
+                SELECT face_id FROM pictures_faces WHERE picture_id = ?1
+            )",
+        )?;
+
+        stmt.execute([picture_id.id()])?;
+
+        let mut stmt = con.prepare(
+            "DELETE FROM pictures_faces_suggestions
+            WHERE face_id IN (
+                SELECT face_id FROM pictures_faces WHERE picture_id = ?1
+            )",
+        )?;
+
+        stmt.execute([picture_id.id()])?;
+
         let mut stmt = con.prepare(
             "DELETE FROM pictures_faces
             WHERE pictures_faces.picture_id = ?1",
@@ -198,6 +221,162 @@ impl Repository {
         Ok(())
     }
 
+    /// Merge one person into another. All faces of the merged person are re-pointed,
+    /// and the person kept keeps their thumbnail.
+    pub fn merge_people(&mut self, from: PersonId, into: PersonId) -> Result<()> {
+        if from == into {
+            return Ok(());
+        }
+
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            // Unconfirmed faces stay unconfirmed, unlike faces the user moves by hand.
+            let mut stmt = tx.prepare_cached(
+                "UPDATE pictures_faces
+                SET
+                    person_id = ?2,
+                    is_thumbnail = FALSE
+                WHERE person_id = ?1",
+            )?;
+            stmt.execute(params![from.id(), into.id()])?;
+
+            let mut stmt = tx.prepare_cached("DELETE FROM people WHERE person_id = ?1")?;
+            stmt.execute(params![from.id(),])?;
+
+            Self::ensure_thumbnail(&tx, into)?;
+            Self::reset_recognized_at(&tx, into)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move faces to an existing person, confirming them.
+    /// People who lose their thumbnail face get a new one.
+    pub fn move_faces_to_person(&mut self, face_ids: &[FaceId], person_id: PersonId) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+        Self::move_faces(&tx, face_ids, person_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move faces to a new person, confirming them.
+    pub fn move_faces_to_new_person(
+        &mut self,
+        face_ids: &[FaceId],
+        name: &str,
+    ) -> Result<PersonId> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let person_id = {
+            let mut stmt = tx.prepare_cached("INSERT INTO people (name) VALUES (?1)")?;
+            stmt.execute(params![name])?;
+            PersonId::new(tx.last_insert_rowid())
+        };
+
+        Self::move_faces(&tx, face_ids, person_id)?;
+
+        tx.commit()?;
+        Ok(person_id)
+    }
+
+    fn move_faces(
+        tx: &rusqlite::Transaction<'_>,
+        face_ids: &[FaceId],
+        person_id: PersonId,
+    ) -> Result<()> {
+        let mut previous_people = HashSet::new();
+
+        {
+            let mut find_person = tx.prepare_cached(
+                "SELECT person_id FROM pictures_faces
+                WHERE face_id = ?1 AND person_id IS NOT NULL",
+            )?;
+
+            let mut update_face = tx.prepare_cached(
+                "UPDATE pictures_faces
+                SET
+                    person_id = ?2,
+                    is_confirmed = TRUE,
+                    is_thumbnail = FALSE
+                WHERE face_id = ?1",
+            )?;
+
+            for face_id in face_ids {
+                let previous: Option<i64> = find_person
+                    .query_row(params![face_id.id()], |row| row.get(0))
+                    .optional()?;
+                previous_people.extend(previous.map(PersonId::new));
+
+                update_face.execute(params![face_id.id(), person_id.id()])?;
+            }
+        }
+
+        previous_people.insert(person_id);
+        for affected_person_id in previous_people {
+            Self::ensure_thumbnail(tx, affected_person_id)?;
+        }
+
+        Self::reset_recognized_at(tx, person_id)?;
+        Ok(())
+    }
+
+    /// Use a person's most confident face as their thumbnail if they don't have one.
+    fn ensure_thumbnail(tx: &rusqlite::Transaction<'_>, person_id: PersonId) -> Result<()> {
+        let mut stmt = tx.prepare_cached(
+            "UPDATE pictures_faces
+            SET
+                is_thumbnail = TRUE
+            WHERE face_id = (
+                SELECT face_id FROM pictures_faces
+                WHERE person_id = ?1
+                ORDER BY is_confirmed DESC, confidence DESC
+                LIMIT 1
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM pictures_faces
+                WHERE person_id = ?1 AND is_thumbnail = TRUE
+            )",
+        )?;
+
+        stmt.execute(params![person_id.id(),])?;
+        Ok(())
+    }
+
+    /// All faces of a person, confirmed or not, most confident first.
+    pub fn find_faces_for_person(&self, person_id: PersonId) -> Result<Vec<model::Face>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                face_id,
+                thumbnail_path
+            FROM pictures_faces
+            WHERE person_id = ?1
+            AND is_ignored = FALSE
+            ORDER BY confidence DESC",
+        )?;
+
+        let result = stmt
+            .query_map(params![person_id.id()], |row| {
+                let face_id = row.get("face_id").map(FaceId::new)?;
+                let thumbnail_path = row
+                    .get("thumbnail_path")
+                    .map(|p: String| self.data_dir_base_path.join(p))?;
+                std::result::Result::Ok(model::Face {
+                    face_id,
+                    thumbnail_path,
+                })
+            })?
+            .flatten()
+            .collect();
+
+        Ok(result)
+    }
+
     pub fn all_people(&self) -> Result<Vec<model::Person>> {
         let con = self.con.lock().unwrap();
         let mut stmt = con.prepare(
@@ -219,13 +398,11 @@ impl Repository {
         Ok(result)
     }
 
-    /// All known people that must have a face recognition performed.
-    /// Select the best face for recognition, where "best" is the face with
-    /// the highest confidence for a face that the user has confirmed is a particular person.
+    /// All known people that must have a face recognition performed, along with every
+    /// face that the user has confirmed is that person, most confident face first.
     pub fn find_people_for_recognition(&self) -> Result<Vec<model::PersonForRecognition>> {
         let con = self.con.lock().unwrap();
 
-        // NOTE: this is non-standard SQL that might not work in DBs that aren't SQLite.
         let mut stmt = con.prepare(
             "SELECT
                 person_id,
@@ -259,17 +436,28 @@ impl Repository {
                 left_mouth_corner_x,
                 left_mouth_corner_y,
 
-                max(confidence) AS confidence
+                confidence
             FROM  pictures_faces AS faces
             INNER JOIN people USING (person_id)
             WHERE faces.is_confirmed = TRUE
-            GROUP BY faces.person_id",
+            AND faces.is_ignored = FALSE
+            ORDER BY faces.person_id, faces.confidence DESC",
         )?;
 
-        let result: Vec<model::PersonForRecognition> = stmt
+        let rows = stmt
             .query_map([], |row| self.to_person_for_recognition(row))?
-            .flatten()
-            .collect();
+            .flatten();
+
+        // Rows are ordered by person, so consecutive rows belong to the same person.
+        let mut result: Vec<model::PersonForRecognition> = vec![];
+        for row in rows {
+            match result.last_mut() {
+                Some(person) if person.person_id == row.person_id => {
+                    person.faces.extend(row.faces);
+                }
+                _ => result.push(row),
+            }
+        }
 
         Ok(result)
     }
@@ -324,6 +512,200 @@ impl Repository {
         Ok(result)
     }
 
+    /// Embeddings of faces that haven't been ignored, computed by the given model.
+    pub fn find_embeddings(&self, model: &EmbeddingModel) -> Result<HashMap<FaceId, Embedding>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                embeddings.face_id,
+                embeddings.embedding
+            FROM pictures_faces_embeddings AS embeddings
+            INNER JOIN pictures_faces AS faces USING (face_id)
+            WHERE embeddings.model_name = ?1
+            AND embeddings.model_version = ?2
+            AND faces.is_ignored = FALSE",
+        )?;
+
+        let result = stmt
+            .query_map(params![model.name, model.version], |row| {
+                let face_id = row.get("face_id").map(FaceId::new)?;
+                let embedding = row
+                    .get("embedding")
+                    .map(|x: Vec<u8>| Embedding::from_bytes(&x))?;
+                std::result::Result::Ok((face_id, embedding))
+            })?
+            .flatten()
+            .collect();
+
+        Ok(result)
+    }
+
+    pub fn add_embeddings(
+        &mut self,
+        model: &EmbeddingModel,
+        embeddings: &[(FaceId, Embedding)],
+    ) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO pictures_faces_embeddings (
+                    face_id,
+                    model_name,
+                    model_version,
+                    embedding
+                ) VALUES (
+                    ?1, ?2, ?3, ?4
+                )",
+            )?;
+
+            for (face_id, embedding) in embeddings {
+                stmt.execute(params![
+                    face_id.id(),
+                    model.name,
+                    model.version,
+                    embedding.to_bytes(),
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace all suggested people with new groups of faces.
+    pub fn replace_suggestions(&mut self, suggestions: &[Vec<FaceId>]) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            tx.execute("DELETE FROM pictures_faces_suggestions", [])?;
+
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO pictures_faces_suggestions (
+                    face_id,
+                    suggestion_id
+                ) VALUES (
+                    ?1, ?2
+                )",
+            )?;
+
+            for (suggestion_id, face_ids) in suggestions.iter().enumerate() {
+                for face_id in face_ids {
+                    stmt.execute(params![face_id.id(), suggestion_id as i64])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Suggested people, largest group first.
+    /// Faces that have since been named or ignored are left out, as are groups
+    /// that no longer have more than one face.
+    pub fn find_suggested_people(&self) -> Result<Vec<model::SuggestedPerson>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                suggestions.suggestion_id AS suggestion_id,
+                faces.face_id AS face_id,
+                faces.thumbnail_path AS face_thumbnail_path
+            FROM pictures_faces_suggestions AS suggestions
+            INNER JOIN pictures_faces AS faces USING (face_id)
+            WHERE faces.person_id IS NULL
+            AND faces.is_ignored = FALSE
+            ORDER BY suggestions.suggestion_id, faces.confidence DESC",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let suggestion_id: i64 = row.get("suggestion_id")?;
+                let face_id = row.get("face_id").map(FaceId::new)?;
+                let thumbnail_path = row
+                    .get("face_thumbnail_path")
+                    .map(|p: String| self.data_dir_base_path.join(p))?;
+                let face = model::Face {
+                    face_id,
+                    thumbnail_path,
+                };
+                std::result::Result::Ok((suggestion_id, face))
+            })?
+            .flatten();
+
+        let mut groups: Vec<(i64, model::SuggestedPerson)> = vec![];
+        for (suggestion_id, face) in rows {
+            match groups.last_mut() {
+                Some((id, suggestion)) if *id == suggestion_id => suggestion.faces.push(face),
+                _ => groups.push((suggestion_id, model::SuggestedPerson { faces: vec![face] })),
+            }
+        }
+
+        let mut result: Vec<model::SuggestedPerson> = groups
+            .into_iter()
+            .map(|(_, suggestion)| suggestion)
+            .filter(|suggestion| suggestion.faces.len() > 1)
+            .collect();
+
+        result.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.faces.len()));
+
+        Ok(result)
+    }
+
+    /// Name a group of faces as a new person, confirming every face.
+    /// The first face becomes the person's thumbnail.
+    pub fn add_person_with_faces(&mut self, face_ids: &[FaceId], name: &str) -> Result<()> {
+        let Some(first_face_id) = face_ids.first() else {
+            return Ok(());
+        };
+
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            // As for add_person, guard against the name entry being activated twice.
+            let mut insert_person = tx.prepare_cached(
+                "
+                INSERT INTO people (name)
+                SELECT ?1 AS name
+                FROM pictures_faces
+                WHERE face_id = ?2 AND person_id IS NULL
+                ",
+            )?;
+
+            if insert_person.execute(params![name, first_face_id.id(),])? == 0 {
+                warn!("Detected double insert of person. Skipping.");
+                return Ok(());
+            }
+
+            let person_id = tx.last_insert_rowid();
+
+            // Faces named in the meantime keep their person.
+            let mut update_face = tx.prepare_cached(
+                "UPDATE pictures_faces
+                SET
+                    person_id = ?2,
+                    is_confirmed = TRUE,
+                    is_thumbnail = ?3
+                WHERE face_id = ?1 AND person_id IS NULL",
+            )?;
+
+            let mut delete_suggestion = tx.prepare_cached(
+                "DELETE FROM pictures_faces_suggestions
+                WHERE face_id = ?1",
+            )?;
+
+            for face_id in face_ids {
+                update_face.execute(params![face_id.id(), person_id, face_id == first_face_id,])?;
+                delete_suggestion.execute(params![face_id.id()])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
     /// Finds all pictures that feature a known person.
     pub fn find_pictures_for_person(&self, person_id: PersonId) -> Result<Vec<PictureId>> {
         let con = self.con.lock().unwrap();
@@ -557,12 +939,28 @@ impl Repository {
             )?;
 
             stmt.execute(params![face_id.id(), person_id.id(),])?;
+
+            Self::reset_recognized_at(&tx, person_id)?;
         }
 
         tx.commit()?;
         Ok(())
     }
 
+    /// A newly confirmed face changes how a person is recognized, so faces previously
+    /// compared against the person must be compared again.
+    fn reset_recognized_at(tx: &rusqlite::Transaction<'_>, person_id: PersonId) -> Result<()> {
+        let mut stmt = tx.prepare_cached(
+            "UPDATE people
+            SET
+                recognized_at = '1970-01-01 00:00:00'
+            WHERE person_id = ?1",
+        )?;
+
+        stmt.execute(params![person_id.id(),])?;
+        Ok(())
+    }
+
     /// Face recognition is automatically marking a face as a person
     pub fn mark_as_person_unconfirmed(
         &mut self,
@@ -654,6 +1052,8 @@ impl Repository {
             )?;
 
             stmt.execute(params![face_id.id(),])?;
+
+            Self::reset_recognized_at(&tx, person_id)?;
         }
 
         tx.commit()?;
@@ -816,7 +1216,7 @@ impl Repository {
         let person = PersonForRecognition {
             person_id,
             recognized_at,
-            face,
+            faces: vec![face],
         };
 
         std::result::Result::Ok(person)
@@ -828,6 +1228,7 @@ impl Repository {
             "SELECT
                 migrate_faces.face_id AS face_id,
                 migrate_faces.face_index AS face_index,
+                pictures.root_id AS root_id,
                 pictures.picture_path_b64 AS picture_path_b64,
                 pictures_faces.bounds_path AS bounds_path,
                 pictures_faces.thumbnail_path AS thumbnail_path
@@ -881,6 +1282,7 @@ impl Repository {
     fn to_face_to_migrate(&self, row: &Row<'_>) -> rusqlite::Result<FaceToMigrate> {
         let face_id = row.get("face_id").map(FaceId::new)?;
         let face_index: u32 = row.get("face_index")?;
+        let root_id = row.get("root_id").map(LibraryRootId::new)?;
 
         let picture_relative_path = row
             .get("picture_path_b64")
@@ -899,6 +1301,7 @@ impl Repository {
         let face = model::FaceToMigrate {
             face_id,
             face_index,
+            root_id,
             picture_relative_path,
             bounds_path,
             thumbnail_path,
@@ -907,3 +1310,238 @@ impl Repository {
         std::result::Result::Ok(face)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use chrono::DateTime;
+
+    const MODEL: EmbeddingModel = EmbeddingModel {
+        name: "test",
+        version: 2,
+    };
+
+    fn repo() -> Repository {
+        let con = Arc::new(Mutex::new(database::setup_in_memory().unwrap()));
+        Repository::open(Path::new("/cache"), Path::new("/data"), con).unwrap()
+    }
+
+    fn insert_face(repo: &Repository, face_id: i64, picture_id: i64) {
+        let con = repo.con.lock().unwrap();
+        con.execute(
+            "INSERT INTO pictures_faces (
+                face_id, model_name, picture_id, thumbnail_path, bounds_path,
+                bounds_x, bounds_y, bounds_width, bounds_height,
+                right_eye_x, right_eye_y, left_eye_x, left_eye_y, nose_x, nose_y,
+                right_mouth_corner_x, right_mouth_corner_y,
+                left_mouth_corner_x, left_mouth_corner_y,
+                confidence
+            ) VALUES (
+                ?1, 'test', ?2, ?3, ?4, 0, 0, 10, 10, 7, 3, 3, 3, 5, 5, 7, 7, 3, 7, 0.9
+            )",
+            params![
+                face_id,
+                picture_id,
+                format!("thumbnail_{face_id}.png"),
+                format!("bounds_{face_id}.png"),
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_embeddings() {
+        let mut repo = repo();
+        insert_face(&repo, 1, 10);
+        insert_face(&repo, 2, 20);
+
+        let embedding = Embedding::new(vec![1.0, 2.0, 3.0]);
+        repo.add_embeddings(&MODEL, &[(FaceId::new(1), embedding.clone())])
+            .unwrap();
+
+        let embeddings = repo.find_embeddings(&MODEL).unwrap();
+        assert_eq!(1, embeddings.len());
+        assert_eq!(Some(&embedding), embeddings.get(&FaceId::new(1)));
+
+        // Embeddings from another version of the model can't be compared.
+        let other = EmbeddingModel {
+            name: "test",
+            version: 3,
+        };
+        assert!(repo.find_embeddings(&other).unwrap().is_empty());
+
+        // Deleting faces deletes their embeddings.
+        repo.delete_faces(PictureId::new(10)).unwrap();
+        let count: i64 = repo
+            .con
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM pictures_faces_embeddings",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(0, count);
+    }
+
+    #[test]
+    fn test_find_people_for_recognition_has_all_confirmed_faces() {
+        let mut repo = repo();
+        insert_face(&repo, 1, 10);
+        insert_face(&repo, 2, 20);
+        insert_face(&repo, 3, 30);
+        insert_face(&repo, 4, 40);
+
+        repo.add_person(FaceId::new(1), "Alice").unwrap();
+        repo.add_person(FaceId::new(3), "Bob").unwrap();
+
+        let people = repo.find_people_for_recognition().unwrap();
+        assert_eq!(2, people.len());
+        let alice = people[0].person_id;
+
+        // Unconfirmed faces don't describe a person.
+        repo.mark_as_person_unconfirmed(FaceId::new(4), alice)
+            .unwrap();
+        repo.mark_face_recognition_complete(alice).unwrap();
+
+        let people = repo.find_people_for_recognition().unwrap();
+        assert_eq!(1, people[0].faces.len());
+        assert!(people[0].recognized_at > DateTime::UNIX_EPOCH);
+
+        // Confirming a face adds it to the person and forces recognition to run again.
+        repo.mark_as_person(FaceId::new(2), alice).unwrap();
+
+        let people = repo.find_people_for_recognition().unwrap();
+        assert_eq!(2, people.len());
+        assert_eq!(alice, people[0].person_id);
+        assert_eq!(2, people[0].faces.len());
+        assert_eq!(DateTime::UNIX_EPOCH, people[0].recognized_at);
+        assert_eq!(1, people[1].faces.len());
+    }
+
+    #[test]
+    fn test_name_suggested_person() {
+        let mut repo = repo();
+        for face_id in 1..=5 {
+            insert_face(&repo, face_id, face_id * 10);
+        }
+
+        repo.replace_suggestions(&[
+            vec![FaceId::new(1), FaceId::new(2), FaceId::new(3)],
+            vec![FaceId::new(4), FaceId::new(5)],
+        ])
+        .unwrap();
+
+        let suggestions = repo.find_suggested_people().unwrap();
+        assert_eq!(2, suggestions.len());
+        assert_eq!(3, suggestions[0].faces.len());
+
+        // A face named individually leaves a group too small to suggest.
+        repo.add_person(FaceId::new(4), "Bob").unwrap();
+
+        let suggestions = repo.find_suggested_people().unwrap();
+        assert_eq!(1, suggestions.len());
+
+        let face_ids: Vec<FaceId> = suggestions[0].faces.iter().map(|f| f.face_id).collect();
+        repo.add_person_with_faces(&face_ids, "Alice").unwrap();
+
+        // Naming twice must not create a second person.
+        repo.add_person_with_faces(&face_ids, "Alice").unwrap();
+
+        assert!(repo.find_suggested_people().unwrap().is_empty());
+
+        let people = repo.all_people().unwrap();
+        assert_eq!(2, people.len());
+        assert_eq!("Alice", people[0].name);
+        assert!(people[0].small_thumbnail_path.is_some());
+
+        let people = repo.find_people_for_recognition().unwrap();
+        let alice = people
+            .iter()
+            .find(|p| p.faces.len() == 3)
+            .expect("Alice has all faces");
+        assert!(
+            alice
+                .faces
+                .iter()
+                .all(|face| face_ids.contains(&face.face_id))
+        );
+    }
+
+    fn thumbnail_face(repo: &Repository, person_id: PersonId) -> Option<i64> {
+        repo.con
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT face_id FROM pictures_faces WHERE person_id = ?1 AND is_thumbnail = TRUE",
+                [person_id.id()],
+                |row| row.get(0),
+            )
+            .ok()
+    }
+
+    #[test]
+    fn test_merge_people() {
+        let mut repo = repo();
+        for face_id in 1..=4 {
+            insert_face(&repo, face_id, face_id * 10);
+        }
+
+        repo.add_person(FaceId::new(1), "Mum").unwrap();
+        repo.add_person(FaceId::new(2), "Mom").unwrap();
+
+        let people = repo.all_people().unwrap();
+        let mom = people[0].person_id;
+        let mum = people[1].person_id;
+
+        repo.mark_as_person(FaceId::new(3), mom).unwrap();
+        repo.mark_as_person_unconfirmed(FaceId::new(4), mom)
+            .unwrap();
+
+        repo.merge_people(mom, mum).unwrap();
+
+        let people = repo.all_people().unwrap();
+        assert_eq!(1, people.len());
+        assert_eq!("Mum", people[0].name);
+
+        assert_eq!(4, repo.find_faces_for_person(mum).unwrap().len());
+        assert!(repo.find_faces_for_person(mom).unwrap().is_empty());
+
+        // Only one thumbnail is kept.
+        assert_eq!(Some(1), thumbnail_face(&repo, mum));
+
+        let people = repo.find_people_for_recognition().unwrap();
+        assert_eq!(3, people[0].faces.len());
+    }
+
+    #[test]
+    fn test_move_faces() {
+        let mut repo = repo();
+        for face_id in 1..=4 {
+            insert_face(&repo, face_id, face_id * 10);
+        }
+
+        repo.add_person(FaceId::new(1), "Siblings").unwrap();
+        let siblings = repo.all_people().unwrap()[0].person_id;
+        for face_id in 2..=4 {
+            repo.mark_as_person(FaceId::new(face_id), siblings).unwrap();
+        }
+
+        // Moving the thumbnail face away gives the person a new thumbnail.
+        let brother = repo
+            .move_faces_to_new_person(&[FaceId::new(1), FaceId::new(2)], "Brother")
+            .unwrap();
+
+        assert_eq!(2, repo.find_faces_for_person(brother).unwrap().len());
+        assert_eq!(2, repo.find_faces_for_person(siblings).unwrap().len());
+        assert!(thumbnail_face(&repo, brother).is_some());
+        assert!(thumbnail_face(&repo, siblings).is_some());
+
+        repo.move_faces_to_person(&[FaceId::new(3)], brother)
+            .unwrap();
+        assert_eq!(3, repo.find_faces_for_person(brother).unwrap().len());
+        assert_eq!(1, repo.find_faces_for_person(siblings).unwrap().len());
+    }
+}
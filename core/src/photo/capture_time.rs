@@ -0,0 +1,243 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Resolves the time zone of a capture time.
+//!
+//! Cameras record `DateTimeOriginal` in local wall-clock time. Newer cameras
+//! also record the UTC offset in `OffsetTimeOriginal`, but older cameras don't,
+//! so the offset must be derived from whatever else the photo tells us.
+
+use super::gps::GPSLocation;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Offset, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use std::sync::LazyLock;
+use strum::{AsRefStr, EnumString};
+use tzf_rs::DefaultFinder;
+
+/// Granularity of UTC offsets. Every time zone in use is a multiple of 15 minutes.
+const OFFSET_GRANULARITY_SECS: i64 = 15 * 60;
+
+/// Most negative UTC offset in use (Baker Island, UTC-12).
+const MIN_OFFSET_SECS: i64 = -12 * 60 * 60;
+
+/// Most positive UTC offset in use (Line Islands, UTC+14).
+const MAX_OFFSET_SECS: i64 = 14 * 60 * 60;
+
+/// Time zone boundaries, which are bundled so that no network access is needed.
+/// Loading them takes a moment, so they are only loaded when first needed.
+static TIME_ZONE_FINDER: LazyLock<DefaultFinder> = LazyLock::new(DefaultFinder::new);
+
+/// Where the UTC offset of a capture time came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum OffsetSource {
+    /// Offset recorded in the metadata, such as `OffsetTimeOriginal`.
+    Exif,
+
+    /// Offset derived from the difference between the local time and the GPS time.
+    GpsTime,
+
+    /// Offset of the time zone at the GPS coordinates.
+    Location,
+}
+
+/// Local wall-clock time a photo was taken, with the UTC offset if it could be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureTime {
+    /// Wall-clock time where the photo was taken.
+    pub local: NaiveDateTime,
+
+    /// UTC offset of the local time.
+    pub offset: Option<FixedOffset>,
+
+    /// Where the UTC offset came from.
+    pub offset_source: Option<OffsetSource>,
+}
+
+impl CaptureTime {
+    /// Resolves the UTC offset of a local capture time. In order of preference the
+    /// offset is the one recorded in the metadata, the one implied by the GPS time, or
+    /// the one of the time zone at the GPS location.
+    pub fn resolve(
+        local: NaiveDateTime,
+        exif_offset: Option<FixedOffset>,
+        gps_time: Option<NaiveDateTime>,
+        location: Option<&GPSLocation>,
+    ) -> Self {
+        let resolved = exif_offset
+            .map(|offset| (offset, OffsetSource::Exif))
+            .or_else(|| {
+                gps_time
+                    .and_then(|gps_time| offset_from_gps_time(local, gps_time))
+                    .map(|offset| (offset, OffsetSource::GpsTime))
+            })
+            .or_else(|| {
+                location
+                    .and_then(|location| offset_from_location(local, location))
+                    .map(|offset| (offset, OffsetSource::Location))
+            });
+
+        Self {
+            local,
+            offset: resolved.map(|x| x.0),
+            offset_source: resolved.map(|x| x.1),
+        }
+    }
+
+    /// Capture time with its UTC offset. If the offset couldn't be resolved, then
+    /// the local time is assumed to be UTC.
+    pub fn to_date_time(&self) -> Option<DateTime<FixedOffset>> {
+        let offset = self.offset.or_else(|| FixedOffset::east_opt(0))?;
+        offset.from_local_datetime(&self.local).single()
+    }
+}
+
+/// GPS time is UTC, so the difference between the local time and the GPS time is
+/// the UTC offset. GPS receivers don't record the time at exactly the same moment as
+/// the shutter, so the difference is rounded to the nearest valid offset.
+fn offset_from_gps_time(local: NaiveDateTime, gps_time: NaiveDateTime) -> Option<FixedOffset> {
+    let difference = local.signed_duration_since(gps_time).num_seconds();
+    let offset = (difference as f64 / OFFSET_GRANULARITY_SECS as f64).round() as i64
+        * OFFSET_GRANULARITY_SECS;
+
+    // A GPS time more than a few minutes from a valid offset is stale, or the camera
+    // clock is wrong.
+    let drift = TimeDelta::seconds(difference - offset).abs();
+    if drift > TimeDelta::minutes(5) || !(MIN_OFFSET_SECS..=MAX_OFFSET_SECS).contains(&offset) {
+        return None;
+    }
+
+    FixedOffset::east_opt(i32::try_from(offset).ok()?)
+}
+
+/// Finds the time zone at a location from the time zone boundaries, then the UTC offset
+/// of the local time in that time zone, so daylight saving time is respected.
+/// A local time that occurs twice when the clocks go back takes the earlier offset.
+fn offset_from_location(local: NaiveDateTime, location: &GPSLocation) -> Option<FixedOffset> {
+    let latitude = location.latitude.to_f64_safe()?;
+    let longitude = location.longitude.to_f64_safe()?;
+
+    // Zero coordinates are much more likely to be a missing fix than a photo
+    // taken in the Gulf of Guinea.
+    if (latitude == 0.0 && longitude == 0.0)
+        || !(-90.0..=90.0).contains(&latitude)
+        || !(-180.0..=180.0).contains(&longitude)
+    {
+        return None;
+    }
+
+    let tz: Tz = TIME_ZONE_FINDER
+        .get_tz_name(longitude, latitude)
+        .parse()
+        .ok()?;
+
+    tz.offset_from_local_datetime(&local)
+        .earliest()
+        .map(|offset| offset.fix())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date_time(h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    fn hours(h: i32) -> FixedOffset {
+        FixedOffset::east_opt(h * 60 * 60).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_prefers_exif_offset() {
+        let location = GPSLocation::for_iso6709("+35.6895+139.6917/");
+        let capture = CaptureTime::resolve(
+            date_time(12, 0, 0),
+            Some(hours(2)),
+            Some(date_time(3, 0, 0)),
+            location.as_ref(),
+        );
+
+        assert_eq!(Some(hours(2)), capture.offset);
+        assert_eq!(Some(OffsetSource::Exif), capture.offset_source);
+        assert_eq!(
+            date_time(10, 0, 0),
+            capture.to_date_time().unwrap().naive_utc()
+        );
+    }
+
+    #[test]
+    fn test_resolve_gps_time() {
+        // GPS time a few seconds before the shutter, in UTC+09:30.
+        // GPS time is preferred to the location, which is in Tokyo.
+        let location = GPSLocation::for_iso6709("+35.6895+139.6917/");
+        let capture = CaptureTime::resolve(
+            date_time(12, 0, 0),
+            None,
+            Some(date_time(2, 29, 40)),
+            location.as_ref(),
+        );
+
+        assert_eq!(FixedOffset::east_opt(9 * 60 * 60 + 30 * 60), capture.offset);
+        assert_eq!(Some(OffsetSource::GpsTime), capture.offset_source);
+
+        // Stale GPS time.
+        let capture =
+            CaptureTime::resolve(date_time(12, 0, 0), None, Some(date_time(2, 22, 30)), None);
+        assert_eq!(None, capture.offset);
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        // New York is on daylight saving time in June...
+        let location = GPSLocation::for_iso6709("+40.7128-074.0060/");
+        let capture = CaptureTime::resolve(date_time(12, 0, 0), None, None, location.as_ref());
+
+        assert_eq!(Some(hours(-4)), capture.offset);
+        assert_eq!(Some(OffsetSource::Location), capture.offset_source);
+
+        // ...but not in January.
+        let january = NaiveDate::from_ymd_opt(2024, 1, 15)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap();
+        let capture = CaptureTime::resolve(january, None, None, location.as_ref());
+        assert_eq!(Some(hours(-5)), capture.offset);
+
+        // Time zones follow political boundaries, not longitude.
+        let location = GPSLocation::for_iso6709("+42.8782-008.5448/");
+        let capture = CaptureTime::resolve(january, None, None, location.as_ref());
+        assert_eq!(Some(hours(1)), capture.offset);
+
+        // Stale GPS time falls back to the location.
+        let location = GPSLocation::for_iso6709("+35.6895+139.6917/");
+        let capture = CaptureTime::resolve(
+            date_time(12, 0, 0),
+            None,
+            Some(date_time(2, 22, 30)),
+            location.as_ref(),
+        );
+        assert_eq!(Some(hours(9)), capture.offset);
+        assert_eq!(Some(OffsetSource::Location), capture.offset_source);
+
+        // Zero coordinates are a missing GPS fix.
+        let location = GPSLocation::for_iso6709("+00.0000+000.0000/");
+        let capture = CaptureTime::resolve(date_time(12, 0, 0), None, None, location.as_ref());
+        assert_eq!(None, capture.offset);
+    }
+
+    #[test]
+    fn test_resolve_unknown() {
+        let capture = CaptureTime::resolve(date_time(12, 0, 0), None, None, None);
+
+        assert_eq!(None, capture.offset);
+        assert_eq!(
+            date_time(12, 0, 0),
+            capture.to_date_time().unwrap().naive_local()
+        );
+    }
+}
@@ -87,6 +87,48 @@ impl GPSCoord {
     }
 }
 
+impl GPSCoord {
+    /// Parses a signed ISO 6709 coordinate. The number of digits before the decimal
+    /// point says whether the coordinate is in degrees (±DD.D), degrees and minutes
+    /// (±DDMM.M), or degrees, minutes, and seconds (±DDMMSS.S). Longitude has one more
+    /// degree digit than latitude.
+    fn position_iso6709(value: &str, degree_digits: usize) -> Option<Self> {
+        let (sign, number) = value.split_at_checked(1)?;
+        let sing = match sign {
+            "+" => true,
+            "-" => false,
+            _ => None?,
+        };
+
+        let int_len = number.find('.').unwrap_or(number.len());
+        if int_len == 0 || !number[..int_len].bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let parse = |x: &str| x.parse::<f64>().ok();
+
+        let (deg, min, sec) = if int_len == degree_digits {
+            (parse(number)?, None, None)
+        } else if int_len == degree_digits + 2 {
+            let (deg, min) = number.split_at(degree_digits);
+            (parse(deg)?, Some(parse(min)?), None)
+        } else if int_len == degree_digits + 4 {
+            let (deg, rest) = number.split_at(degree_digits);
+            let (min, sec) = rest.split_at(2);
+            (parse(deg)?, Some(parse(min)?), Some(parse(sec)?))
+        } else {
+            return None;
+        };
+
+        Some(GPSCoord {
+            sing,
+            deg,
+            min,
+            sec,
+        })
+    }
+}
+
 impl GPSLocation {
     pub fn for_exif(
         latitude: &[exif::Rational],
@@ -136,8 +178,92 @@ impl GPSLocation {
         })
     }
 
+    /// Parses an ISO 6709 location, such as `+37.3349-122.0090+072.000/`, as written
+    /// to the `com.apple.quicktime.location.ISO6709` and `location` tags and the `©xyz`
+    /// atom of QuickTime and MP4 videos. Latitude and longitude can be decimal degrees,
+    /// degrees and decimal minutes, or degrees, minutes, and decimal seconds. Altitude
+    /// and the coordinate reference system are ignored.
+    pub fn for_iso6709(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        // Split into signed components, ignoring altitude and anything after the '/'.
+        let value = value.split('/').next()?;
+        let starts: Vec<usize> = value
+            .char_indices()
+            .filter(|(_, c)| *c == '+' || *c == '-')
+            .map(|(i, _)| i)
+            .collect();
+
+        if starts.len() < 2 || starts[0] != 0 {
+            return None;
+        }
+
+        let end = starts.get(2).copied().unwrap_or(value.len());
+        let latitude = GPSCoord::position_iso6709(&value[starts[0]..starts[1]], 2)?;
+        let longitude = GPSCoord::position_iso6709(&value[starts[1]..end], 3)?;
+
+        if latitude.to_f64_safe().is_none_or(|x| x.abs() > 90.0)
+            || longitude.to_f64_safe().is_none_or(|x| x.abs() > 180.0)
+        {
+            debug!(
+                "ISO 6709 location {:?} is out of range, so skipping.",
+                value
+            );
+            return None;
+        }
+
+        Some(Self {
+            latitude,
+            longitude,
+        })
+    }
+
     pub fn to_cell_index(&self, resolution: Resolution) -> Result<CellIndex> {
         let ll = LatLng::new(self.latitude.to_f64(), self.longitude.to_f64())?;
         Ok(ll.to_cell(resolution))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(location: Option<GPSLocation>) -> Option<(f64, f64)> {
+        location.map(|x| {
+            let round = |x: f64| (x * 10_000.0).round() / 10_000.0;
+            (round(x.latitude.to_f64()), round(x.longitude.to_f64()))
+        })
+    }
+
+    #[test]
+    fn test_for_iso6709() {
+        // iPhone, with altitude
+        assert_eq!(
+            Some((37.3349, -122.009)),
+            decimal(GPSLocation::for_iso6709("+37.3349-122.0090+072.000/"))
+        );
+
+        // Android, without altitude
+        assert_eq!(
+            Some((-33.8568, 151.2153)),
+            decimal(GPSLocation::for_iso6709("-33.8568+151.2153/"))
+        );
+
+        // Degrees and minutes
+        assert_eq!(
+            Some((40.5697, -73.9958)),
+            decimal(GPSLocation::for_iso6709("+4034.18-07359.75/"))
+        );
+
+        // Degrees, minutes, and seconds
+        assert_eq!(
+            Some((40.5, -73.5)),
+            decimal(GPSLocation::for_iso6709("+403000-0733000/"))
+        );
+
+        assert!(GPSLocation::for_iso6709("").is_none());
+        assert!(GPSLocation::for_iso6709("+37.3349/").is_none());
+        assert!(GPSLocation::for_iso6709("+97.0000+010.0000/").is_none());
+        assert!(GPSLocation::for_iso6709("somewhere").is_none());
+    }
+}
@@ -3,8 +3,12 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use super::Metadata;
+use super::capture_time::CaptureTime;
 use super::gps::GPSLocation;
-use super::model::Orientation;
+use super::model::{Orientation, PictureExif, WhiteBalance};
+use super::raw::Cr3Exif;
+use super::xmp::Xmp;
+use crate::file_types::FileType;
 use anyhow::*;
 use chrono::prelude::*;
 use chrono::{DateTime, FixedOffset};
@@ -14,6 +18,7 @@ use std::fs;
 use std::io::BufReader;
 use std::path::Path;
 use std::result::Result::Ok;
+use tracing::{debug, warn};
 
 /// This version number should be incremented each time metadata scanning has
 /// a bug fix or feature addition that changes the metadata produced.
@@ -25,18 +30,45 @@ use std::result::Result::Ok;
 /// 1. Orientation.
 /// 2. Motion photos.
 /// 3. GPS coordinates.
-pub const VERSION: u32 = 3;
-
-/// Extract EXIF metadata from file
+/// 4. XMP sidecars and embedded XMP.
+/// 5. Camera and exposure details.
+/// 6. Time zone of capture time from EXIF offset, GPS time, or GPS location.
+/// 7. Time zone of XMP capture time resolved like EXIF, and not from GPS location.
+/// 8. Time zone of capture time from the time zone boundaries at the GPS location.
+pub const VERSION: u32 = 8;
+
+/// Extract EXIF and XMP metadata from file.
+/// XMP embedded in the file fills in gaps in the EXIF metadata, and an XMP sidecar
+/// file, which holds edits made in photo managers such as darktable, overrides both.
 pub fn from_path(path: &Path) -> Result<Metadata> {
-    let file = fs::File::open(path)?;
-    let file = &mut BufReader::new(file);
+    let mut metadata = if FileType::detect(path) == Some(FileType::Cr3) {
+        // CR3 isn't a container format that kamadak-exif understands.
+        match Cr3Exif::read(path) {
+            Ok(cr3_exif) => from_fields(|tag| cr3_exif.get_field(tag))?,
+            Err(_) => Metadata::default(),
+        }
+    } else {
+        let file = fs::File::open(path)?;
+        let file = &mut BufReader::new(file);
 
-    let mut metadata = match exif::Reader::new().read_from_container(file) {
-        Ok(exif_data) => from_exif(exif_data)?,
-        Err(_) => Metadata::default(),
+        match exif::Reader::new().read_from_container(file) {
+            Ok(exif_data) => from_exif(exif_data)?,
+            Err(_) => Metadata::default(),
+        }
     };
 
+    match Xmp::from_embedded(path) {
+        Ok(Some(xmp)) => merge_xmp(&mut metadata, xmp, false),
+        Ok(None) => {}
+        Err(e) => debug!("Failed reading embedded XMP from {:?}: {}", path, e),
+    }
+
+    match Xmp::from_sidecar(path) {
+        Ok(Some(xmp)) => merge_xmp(&mut metadata, xmp, true),
+        Ok(None) => {}
+        Err(e) => warn!("Failed reading XMP sidecar for {:?}: {}", path, e),
+    }
+
     let fs_metadata = fs::metadata(path)?;
 
     metadata.fs_created_at = fs_metadata.created().map(Into::<DateTime<Utc>>::into).ok();
@@ -81,11 +113,58 @@ pub fn from_raw(data: Vec<u8>) -> Result<Metadata> {
     from_exif(exif_data)
 }
 
+/// Merges XMP metadata. Values from an overriding XMP replace existing values,
+/// otherwise XMP values only fill in gaps.
+fn merge_xmp(metadata: &mut Metadata, xmp: Xmp, is_override: bool) {
+    fn merge<T>(value: &mut Option<T>, xmp_value: Option<T>, is_override: bool) {
+        if xmp_value.is_some() && (is_override || value.is_none()) {
+            *value = xmp_value;
+        }
+    }
+
+    // XMP dates are resolved the same way as EXIF dates, so a date without a time zone
+    // can still take its offset from the GPS time or location.
+    if let Some(created_at) = xmp.created_at
+        && (is_override || metadata.exif_created_at.is_none())
+    {
+        let capture_time = CaptureTime::resolve(
+            created_at.local,
+            created_at.offset,
+            metadata.gps_time,
+            metadata.location.as_ref(),
+        );
+        metadata.exif_created_at = capture_time.to_date_time();
+        metadata.capture_time = Some(capture_time);
+    }
+
+    if let Some(modified_at) = xmp.modified_at
+        && (is_override || metadata.exif_modified_at.is_none())
+    {
+        metadata.exif_modified_at =
+            modified_date_time(modified_at.local, modified_at.offset, metadata.capture_time);
+    }
+
+    merge(&mut metadata.rating, xmp.rating, is_override);
+    merge(&mut metadata.label, xmp.label, is_override);
+    merge(&mut metadata.title, xmp.title, is_override);
+    merge(&mut metadata.description, xmp.description, is_override);
+
+    if !xmp.keywords.is_empty() && (is_override || metadata.keywords.is_empty()) {
+        metadata.keywords = xmp.keywords;
+    }
+}
+
 fn from_exif(exif_data: Exif) -> Result<Metadata> {
+    from_fields(|tag| exif_data.get_field(tag, exif::In::PRIMARY))
+}
+
+/// Extract metadata from EXIF fields in the primary image.
+fn from_fields<'a>(get_field: impl Fn(exif::Tag) -> Option<&'a exif::Field>) -> Result<Metadata> {
+    /// Parses a local date time and, if the offset field is present, its UTC offset.
     fn parse_date_time(
         date_time_field: Option<&exif::Field>,
         time_offset_field: Option<&exif::Field>,
-    ) -> Option<DateTime<FixedOffset>> {
+    ) -> Option<(NaiveDateTime, Option<FixedOffset>)> {
         let date_time_field = date_time_field?;
 
         let mut date_time = match date_time_field.value {
@@ -104,8 +183,10 @@ fn from_exif(exif_data: Exif) -> Result<Metadata> {
             };
         }
 
-        let offset = date_time.offset.unwrap_or(0); // offset in minutes
-        let offset = FixedOffset::east_opt((offset as i32) * 60)?;
+        // offset in minutes
+        let offset = date_time
+            .offset
+            .and_then(|offset| FixedOffset::east_opt((offset as i32) * 60));
 
         let date = NaiveDate::from_ymd_opt(
             date_time.year.into(),
@@ -119,59 +200,132 @@ fn from_exif(exif_data: Exif) -> Result<Metadata> {
             date_time.second.into(),
         )?;
 
-        let naive_date_time = date.and_time(time);
-        Some(offset.from_utc_datetime(&naive_date_time))
+        Some((date.and_time(time), offset))
     }
 
-    let exif_created_at = parse_date_time(
-        exif_data.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY),
-        exif_data.get_field(exif::Tag::OffsetTimeOriginal, exif::In::PRIMARY),
-    );
+    let location = gps_location(&get_field);
+
+    let gps_time = gps_time(&get_field);
+
+    let capture_time = parse_date_time(
+        get_field(exif::Tag::DateTimeOriginal),
+        get_field(exif::Tag::OffsetTimeOriginal),
+    )
+    .map(|(local, offset)| CaptureTime::resolve(local, offset, gps_time, location.as_ref()));
+
+    let exif_created_at = capture_time.and_then(|x| x.to_date_time());
 
     let exif_modified_at = parse_date_time(
-        exif_data.get_field(exif::Tag::DateTime, exif::In::PRIMARY),
-        exif_data.get_field(exif::Tag::OffsetTime, exif::In::PRIMARY),
-    );
+        get_field(exif::Tag::DateTime),
+        get_field(exif::Tag::OffsetTime),
+    )
+    .and_then(|(local, offset)| modified_date_time(local, offset, capture_time));
 
-    let lens_model = exif_data
-        .get_field(exif::Tag::LensModel, exif::In::PRIMARY)
-        .map(|e| e.display_value().to_string());
+    let lens_model = get_field(exif::Tag::LensModel).map(|e| e.display_value().to_string());
 
     // How to orient and flip the image.
     // Note that libheif will automatically apply the transformations when loading the image
     // so must be aware of file format before transforming to avoid a double transformation.
-    let orientation = exif_data
-        .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+    let orientation = get_field(exif::Tag::Orientation)
         .and_then(|e| e.value.get_uint(0))
         .map(Orientation::from);
 
-    let content_id = ios_content_id(&exif_data);
+    let content_id = get_field(exif::Tag::MakerNote).and_then(ios_content_id);
 
-    let location = gps_location(&exif_data);
+    let exif = picture_exif(&get_field);
 
     let metadata = Metadata {
         fs_created_at: None,
         fs_modified_at: None,
         exif_created_at,
         exif_modified_at,
+        capture_time,
         lens_model,
         orientation,
         content_id,
         location,
+        gps_time,
+        exif,
+        ..Metadata::default()
     };
 
     Ok(metadata)
 }
 
+/// A modification time is from the same camera clock as the capture time,
+/// so shares its offset if it doesn't have its own. Otherwise it is assumed to be UTC.
+fn modified_date_time(
+    local: NaiveDateTime,
+    offset: Option<FixedOffset>,
+    capture_time: Option<CaptureTime>,
+) -> Option<DateTime<FixedOffset>> {
+    let offset = offset
+        .or_else(|| capture_time.and_then(|x| x.offset))
+        .or_else(|| FixedOffset::east_opt(0))?;
+    offset.from_local_datetime(&local).single()
+}
+
+/// Camera and exposure details.
+fn picture_exif<'a>(get_field: impl Fn(exif::Tag) -> Option<&'a exif::Field>) -> PictureExif {
+    let ascii = |tag| {
+        let field = get_field(tag)?;
+        let exif::Value::Ascii(ref vec) = field.value else {
+            return None;
+        };
+        let value = String::from_utf8_lossy(vec.first()?);
+        let value = value.trim_matches(char::from(0)).trim();
+        (!value.is_empty()).then(|| value.to_string())
+    };
+
+    let rational = |tag| {
+        match get_field(tag)?.value {
+            exif::Value::Rational(ref vec) => vec.first().map(|x| x.to_f64()),
+            exif::Value::SRational(ref vec) => vec.first().map(|x| x.to_f64()),
+            _ => None,
+        }
+        .filter(|x| x.is_finite() && *x > 0.0)
+    };
+
+    let uint = |tag| get_field(tag)?.value.get_uint(0).filter(|x| *x > 0);
+
+    // Bit 0 of the flash field is set if the flash fired.
+    let is_flash_fired = get_field(exif::Tag::Flash)
+        .and_then(|field| field.value.get_uint(0))
+        .map(|x| x & 1 == 1);
+
+    let white_balance = match get_field(exif::Tag::WhiteBalance).and_then(|x| x.value.get_uint(0)) {
+        Some(0) => Some(WhiteBalance::Auto),
+        Some(1) => Some(WhiteBalance::Manual),
+        _ => None,
+    };
+
+    PictureExif {
+        camera_make: ascii(exif::Tag::Make),
+        camera_model: ascii(exif::Tag::Model),
+        lens_model: ascii(exif::Tag::LensModel),
+        focal_length: rational(exif::Tag::FocalLength),
+        focal_length_35mm: uint(exif::Tag::FocalLengthIn35mmFilm),
+        f_number: rational(exif::Tag::FNumber),
+        exposure_time: rational(exif::Tag::ExposureTime),
+        iso: uint(exif::Tag::PhotographicSensitivity),
+        is_flash_fired,
+        white_balance,
+        width: uint(exif::Tag::PixelXDimension).or_else(|| uint(exif::Tag::ImageWidth)),
+        height: uint(exif::Tag::PixelYDimension).or_else(|| uint(exif::Tag::ImageLength)),
+    }
+}
+
 /// Parse GPS latitude and longitude from EXIF data
 /// Mostly borrowed from Loupe.
 /// See https://gitlab.gnome.org/GNOME/loupe/-/blob/main/src/metadata.rs
-fn gps_location(exif: &Exif) -> Option<GPSLocation> {
+fn gps_location<'a>(
+    get_field: impl Fn(exif::Tag) -> Option<&'a exif::Field>,
+) -> Option<GPSLocation> {
     if let (Some(latitude), Some(latitude_ref), Some(longitude), Some(longitude_ref)) = (
-        exif.get_field(exif::Tag::GPSLatitude, exif::In::PRIMARY),
-        exif.get_field(exif::Tag::GPSLatitudeRef, exif::In::PRIMARY),
-        exif.get_field(exif::Tag::GPSLongitude, exif::In::PRIMARY),
-        exif.get_field(exif::Tag::GPSLongitudeRef, exif::In::PRIMARY),
+        get_field(exif::Tag::GPSLatitude),
+        get_field(exif::Tag::GPSLatitudeRef),
+        get_field(exif::Tag::GPSLongitude),
+        get_field(exif::Tag::GPSLongitudeRef),
     ) {
         if let (
             exif::Value::Rational(latitude),
@@ -191,9 +345,32 @@ fn gps_location(exif: &Exif) -> Option<GPSLocation> {
     None
 }
 
+/// GPS date and time, which is always UTC.
+fn gps_time<'a>(get_field: impl Fn(exif::Tag) -> Option<&'a exif::Field>) -> Option<NaiveDateTime> {
+    let exif::Value::Ascii(ref date) = get_field(exif::Tag::GPSDateStamp)?.value else {
+        return None;
+    };
+    let date = String::from_utf8_lossy(date.first()?);
+    let date =
+        NaiveDate::parse_from_str(date.trim_matches(char::from(0)).trim(), "%Y:%m:%d").ok()?;
+
+    let exif::Value::Rational(ref time) = get_field(exif::Tag::GPSTimeStamp)?.value else {
+        return None;
+    };
+    let [hour, minute, second] = time.as_slice() else {
+        return None;
+    };
+    let seconds = hour.to_f64() * 3600.0 + minute.to_f64() * 60.0 + second.to_f64();
+    if !seconds.is_finite() || !(0.0..86_400.0).contains(&seconds) {
+        return None;
+    }
+
+    let time = NaiveTime::from_num_seconds_from_midnight_opt(seconds as u32, 0)?;
+    Some(date.and_time(time))
+}
+
 /// Parse content ID from the Apple maker note
-fn ios_content_id(exif_data: &Exif) -> Option<String> {
-    let maker_note = exif_data.get_field(exif::Tag::MakerNote, exif::In::PRIMARY)?;
+fn ios_content_id(maker_note: &exif::Field) -> Option<String> {
     let exif::Value::Undefined(ref raw, _offset) = maker_note.value else {
         return None;
     };
@@ -243,6 +420,7 @@ fn ios_content_id(exif_data: &Exif) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::photo::capture_time::OffsetSource;
 
     #[test]
     fn test_ios_content_id() {
@@ -252,11 +430,91 @@ mod tests {
         let file = &mut BufReader::new(file);
 
         let exif_data = exif::Reader::new().read_from_container(file).ok().unwrap();
-        let content_id = ios_content_id(&exif_data);
+        let maker_note = exif_data
+            .get_field(exif::Tag::MakerNote, exif::In::PRIMARY)
+            .unwrap();
+        let content_id = ios_content_id(maker_note);
 
         assert_eq!(
             Some("5D3FF377-55D1-4BFF-A4FF-56B2298FC6C2".to_string()),
             content_id
         );
     }
+
+    #[test]
+    fn test_picture_exif() {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let file = Path::new(dir).join("resources/test/Dandelion.jpg");
+        let metadata = from_path(&file).unwrap();
+
+        assert_eq!(Some("Apple".to_string()), metadata.exif.camera_make);
+        assert!(metadata.exif.camera_model.is_some());
+        assert!(metadata.exif.focal_length.is_some());
+        assert!(metadata.exif.f_number.is_some());
+        assert!(metadata.exif.exposure_time.is_some());
+        assert!(metadata.exif.iso.is_some());
+    }
+
+    #[test]
+    fn test_xmp_sidecar_overrides_exif() {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("Dandelion.jpg");
+        fs::copy(Path::new(dir).join("resources/test/Dandelion.jpg"), &file).unwrap();
+
+        let exif_only = from_path(&file).unwrap();
+        assert!(exif_only.exif_created_at.is_some());
+        assert_eq!(None, exif_only.rating);
+
+        fs::write(
+            tmp.path().join("Dandelion.jpg.xmp"),
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+   exif:DateTimeOriginal="2001-02-03T04:05:06+01:00"
+   xmp:Rating="3"/>
+ </rdf:RDF>
+</x:xmpmeta>"#,
+        )
+        .unwrap();
+
+        let with_sidecar = from_path(&file).unwrap();
+        assert_eq!(
+            DateTime::parse_from_rfc3339("2001-02-03T04:05:06+01:00").ok(),
+            with_sidecar.exif_created_at
+        );
+        assert_eq!(
+            Some(OffsetSource::Exif),
+            with_sidecar.capture_time.and_then(|x| x.offset_source)
+        );
+        assert_eq!(exif_only.exif_modified_at, with_sidecar.exif_modified_at);
+        assert_eq!(Some(3), with_sidecar.rating);
+
+        // An XMP date without a time zone doesn't have a resolved offset.
+        fs::write(
+            tmp.path().join("Dandelion.jpg.xmp"),
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+   exif:DateTimeOriginal="2001-02-03T04:05:06"/>
+ </rdf:RDF>
+</x:xmpmeta>"#,
+        )
+        .unwrap();
+
+        let without_zone = from_path(&file).unwrap();
+        let capture_time = without_zone.capture_time.unwrap();
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2001, 2, 3).and_then(|date| date.and_hms_opt(4, 5, 6)),
+            Some(capture_time.local)
+        );
+        assert_eq!(
+            without_zone.gps_time.is_some() || without_zone.location.is_some(),
+            capture_time.offset.is_some()
+        );
+        assert_ne!(Some(OffsetSource::Exif), capture_time.offset_source);
+    }
 }
@@ -2,12 +2,15 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod capture_time;
 pub mod gps;
 pub mod metadata;
 pub mod model;
 pub mod motion_photo;
+pub mod raw;
 pub mod repo;
 pub mod thumbnailer;
+pub mod xmp;
 
 pub use model::PictureId;
 
@@ -2,13 +2,16 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use super::capture_time::CaptureTime;
 use super::gps::GPSLocation;
 use crate::FlatpakPathBuf;
+use crate::file_types::FileType;
 
-use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta, Utc};
 use std::fmt::Display;
+use std::ops::Range;
 use std::path::PathBuf;
-use strum::{AsRefStr, EnumIter};
+use strum::{AsRefStr, EnumIter, EnumString};
 
 /// Database ID of picture
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,6 +48,9 @@ pub struct Picture {
 
     /// Was picture taken with front camera?
     pub is_selfie: Option<bool>,
+
+    /// Type of picture. Not known for pictures added before file types were detected.
+    pub file_type: Option<FileType>,
 }
 
 impl Picture {
@@ -148,6 +154,9 @@ pub struct Metadata {
 
     pub exif_modified_at: Option<DateTime<FixedOffset>>,
 
+    // Local wall-clock time the picture was taken, and how its UTC offset was resolved.
+    pub capture_time: Option<CaptureTime>,
+
     /// On iPhone the lens model tells you if it was the front or back camera.
     pub lens_model: Option<String>,
 
@@ -161,6 +170,27 @@ pub struct Metadata {
 
     // GPS location
     pub location: Option<GPSLocation>,
+
+    // GPS date and time, which is always UTC. Used to resolve the UTC offset of capture times.
+    pub gps_time: Option<NaiveDateTime>,
+
+    // Star rating from XMP. 1 to 5, with 0 for unrated and -1 for rejected.
+    pub rating: Option<i32>,
+
+    // Colour label from XMP.
+    pub label: Option<String>,
+
+    // Keywords from XMP.
+    pub keywords: Vec<String>,
+
+    // Title from XMP.
+    pub title: Option<String>,
+
+    // Description from XMP.
+    pub description: Option<String>,
+
+    // Camera and exposure details.
+    pub exif: PictureExif,
 }
 
 impl Metadata {
@@ -171,10 +201,134 @@ impl Metadata {
     }
 }
 
-/// A video extracted from a motion photo
+/// White balance mode from EXIF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum WhiteBalance {
+    Auto,
+    Manual,
+}
+
+/// Camera and exposure details from EXIF.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PictureExif {
+    pub camera_make: Option<String>,
+
+    pub camera_model: Option<String>,
+
+    pub lens_model: Option<String>,
+
+    /// Focal length in millimetres.
+    pub focal_length: Option<f64>,
+
+    /// Focal length in millimetres for a 35mm film camera with the same field of view.
+    pub focal_length_35mm: Option<u32>,
+
+    /// Aperture as an f-number.
+    pub f_number: Option<f64>,
+
+    /// Shutter speed in seconds.
+    pub exposure_time: Option<f64>,
+
+    pub iso: Option<u32>,
+
+    pub is_flash_fired: Option<bool>,
+
+    pub white_balance: Option<WhiteBalance>,
+
+    /// Width in pixels.
+    pub width: Option<u32>,
+
+    /// Height in pixels.
+    pub height: Option<u32>,
+}
+
+impl PictureExif {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Camera make and model. Many cameras include the make in the model name,
+    /// such as "Canon" and "Canon EOS R5", so the make isn't repeated.
+    pub fn camera(&self) -> Option<String> {
+        match (&self.camera_make, &self.camera_model) {
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => {
+                Some(model.clone())
+            }
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.clone().or_else(|| model.clone()),
+        }
+    }
+
+    /// Focal length, such as "50 mm", or "4.2 mm (26 mm)" with the 35mm equivalent.
+    pub fn focal_length_label(&self) -> Option<String> {
+        let focal_length = self.focal_length.map(format_decimal)?;
+        match self.focal_length_35mm {
+            Some(equivalent) if focal_length != equivalent.to_string() => {
+                Some(format!("{} mm ({} mm)", focal_length, equivalent))
+            }
+            _ => Some(format!("{} mm", focal_length)),
+        }
+    }
+
+    /// Aperture, such as "ƒ/1.8".
+    pub fn aperture_label(&self) -> Option<String> {
+        self.f_number.map(|x| format!("ƒ/{}", format_decimal(x)))
+    }
+
+    /// Shutter speed, such as "1/250 s" or "2.5 s".
+    pub fn exposure_time_label(&self) -> Option<String> {
+        let seconds = self.exposure_time.filter(|x| *x > 0.0)?;
+        if seconds < 1.0 {
+            Some(format!("1/{} s", (1.0 / seconds).round()))
+        } else {
+            Some(format!("{} s", format_decimal(seconds)))
+        }
+    }
+
+    /// Width and height, such as "4000 ⨉ 3000".
+    pub fn dimensions_label(&self) -> Option<String> {
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => Some(format!("{} ⨉ {}", width, height)),
+            _ => None,
+        }
+    }
+}
+
+/// Formats a number with at most one decimal place and without a trailing ".0".
+fn format_decimal(x: f64) -> String {
+    let x = format!("{:.1}", x);
+    x.strip_suffix(".0").map(String::from).unwrap_or(x)
+}
+
+/// How a motion photo embeds its video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum MotionPhotoFormat {
+    /// Google Motion Photo v2, with an XMP container directory.
+    GoogleMotionPhoto,
+
+    /// Older Google MicroVideo, with an XMP offset from the end of the file.
+    GoogleMicroVideo,
+
+    /// Samsung motion photo trailer.
+    Samsung,
+}
+
+/// A video embedded in a motion photo
 #[derive(Debug, Clone)]
 pub struct MotionPhotoVideo {
-    pub path: PathBuf,
+    // Byte range of the video in the motion photo file, if the video can be read in place.
+    pub range: Option<Range<u64>>,
+
+    // Copy of the video in the cache, if the video can't be read in place.
+    pub path: Option<PathBuf>,
+
+    pub format: MotionPhotoFormat,
+
+    // Timestamp of the video frame that matches the still image.
+    pub still_frame_ts: Option<TimeDelta>,
+
     pub duration: Option<TimeDelta>,
     pub video_codec: Option<String>,
     pub transcoded_path: Option<PathBuf>,
@@ -183,3 +337,42 @@ pub struct MotionPhotoVideo {
     // Should be 90, 180, 270, or the negative of those.
     pub rotation: Option<i32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picture_exif_labels() {
+        let exif = PictureExif {
+            camera_make: Some("Canon".into()),
+            camera_model: Some("Canon EOS R5".into()),
+            focal_length: Some(4.2),
+            focal_length_35mm: Some(26),
+            f_number: Some(8.0),
+            exposure_time: Some(0.004),
+            ..PictureExif::default()
+        };
+
+        assert_eq!(Some("Canon EOS R5".into()), exif.camera());
+        assert_eq!(Some("4.2 mm (26 mm)".into()), exif.focal_length_label());
+        assert_eq!(Some("ƒ/8".into()), exif.aperture_label());
+        assert_eq!(Some("1/250 s".into()), exif.exposure_time_label());
+        assert_eq!(None, exif.dimensions_label());
+        assert!(!exif.is_empty());
+
+        let exif = PictureExif {
+            camera_make: Some("Apple".into()),
+            camera_model: Some("iPhone 15".into()),
+            focal_length: Some(50.0),
+            focal_length_35mm: Some(50),
+            exposure_time: Some(2.5),
+            ..PictureExif::default()
+        };
+
+        assert_eq!(Some("Apple iPhone 15".into()), exif.camera());
+        assert_eq!(Some("50 mm".into()), exif.focal_length_label());
+        assert_eq!(Some("2.5 s".into()), exif.exposure_time_label());
+        assert!(PictureExif::default().is_empty());
+    }
+}
@@ -4,24 +4,61 @@
 
 use crate::photo::model::PictureId;
 use anyhow::*;
+use chrono::TimeDelta;
 
-use super::model::MotionPhotoVideo;
+use super::model::{MotionPhotoFormat, MotionPhotoVideo};
+use super::xmp::{GoogleMotionPhoto, Xmp};
 use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::result::Result::Ok;
+use std::sync::atomic::AtomicBool;
 use tracing::debug;
 
 use sm_motion_photo::SmMotion;
 
 use crate::video::metadata as video_metadata;
-use crate::video::transcode;
+use crate::video::transcode::{self, TranscodeProfile};
 
 /// This version number should be incremented each motion photo extraction has
 /// a bug fix or feature addition that changes the motion photo data produced.
 /// Each photo will be saved with a motion photo extraction version which will allow for
 /// easy selection of photos when their motion photo can be updated.
+///
+/// History:
+/// 1. Samsung motion photos.
+/// 2. Google Motion Photo v2 and MicroVideo, and still frame timestamp.
+/// 3. Read videos in place instead of copying them to the cache.
+pub const VERSION: u32 = 3;
 
-pub const VERSION: u32 = 1;
+/// Directory under the cache directory for motion photo videos.
+const CACHE_DIR_NAME: &str = "motion_photos";
+
+/// File name prefix of copies of videos made for playback.
+const COPY_PREFIX: &str = "playback_";
+
+/// A backend finds the video embedded by one motion photo format.
+type Backend = fn(&Path, Option<&GoogleMotionPhoto>) -> Result<Option<EmbeddedVideo>>;
+
+/// Backends in order of preference. Newer Samsung phones write the Google format
+/// as well as their own, so the Google formats are tried first.
+const BACKENDS: [Backend; 3] = [google_motion_photo, google_micro_video, samsung];
+
+/// Video embedded in a motion photo.
+struct EmbeddedVideo {
+    format: MotionPhotoFormat,
+    source: VideoSource,
+    still_frame_ts: Option<TimeDelta>,
+}
+
+enum VideoSource {
+    /// Byte range of the motion photo file.
+    Range { offset: u64, length: u64 },
+
+    /// Samsung trailer, which sm_motion_photo knows how to extract.
+    Samsung(SmMotion),
+}
 
 /// Motion photos are an image followed by an embedded MP4 video.
 #[derive(Debug, Clone)]
@@ -31,28 +68,31 @@ pub struct MotionPhotoExtractor {
 
 impl MotionPhotoExtractor {
     pub fn build(base_path: &Path) -> Result<MotionPhotoExtractor> {
-        let base_path = PathBuf::from(base_path).join("motion_photos");
+        let base_path = PathBuf::from(base_path).join(CACHE_DIR_NAME);
         std::fs::create_dir_all(&base_path)?;
 
         Ok(MotionPhotoExtractor { base_path })
     }
 
-    /// Extract motion photo video if it exists.
+    /// Finds the motion photo video if it exists. Videos are read in place from the
+    /// motion photo where possible, so are only copied to the cache when they can't
+    /// be read in place or must be transcoded.
+    /// Setting `stop` cancels transcoding with a `TranscodeError`.
     pub fn extract(
         &self,
         picture_id: &PictureId,
         picture_path: &Path,
+        stop: &AtomicBool,
     ) -> Result<Option<MotionPhotoVideo>> {
-        let photo_file = File::open(picture_path)?;
-        let Some(sm) = SmMotion::with(&photo_file) else {
-            return Ok(None); // would be nice if API returned a result instead of an option.
-        };
-
-        if !sm.has_video() {
+        let Some(embedded) = find_video(picture_path)? else {
             return Ok(None);
-        }
+        };
 
-        debug!("Photo {:?} has an embedded motion video.", picture_path);
+        debug!(
+            "Photo {:?} has an embedded {} motion video.",
+            picture_path,
+            embedded.format.as_ref()
+        );
 
         let video_path = {
             // Create a directory per 1000 motion photos
@@ -62,31 +102,48 @@ impl MotionPhotoExtractor {
             self.base_path.join(partition).join(file_name)
         };
 
-        if !video_path.exists() {
-            if let Some(p) = video_path.parent() {
-                let _ = std::fs::create_dir_all(p);
+        // Where ffmpeg reads the video from.
+        let (range, path, input) = match embedded.source {
+            VideoSource::Range { offset, length } => {
+                // Earlier versions copied every video to the cache, which is now wasted space.
+                if video_path.exists() {
+                    let _ = std::fs::remove_file(&video_path);
+                }
+
+                let input = subfile(picture_path, offset, length);
+                (Some(offset..offset + length), None, input)
             }
+            VideoSource::Samsung(sm) => {
+                if !video_path.exists() {
+                    if let Some(p) = video_path.parent() {
+                        let _ = std::fs::create_dir_all(p);
+                    }
 
-            let mut video_file = File::create(&video_path).unwrap();
-            sm.dump_video_file(&mut video_file).unwrap();
-        }
+                    let mut video_file = File::create(&video_path)?;
+                    sm.dump_video_file(&mut video_file).unwrap();
+                }
+                (None, Some(video_path.clone()), video_path)
+            }
+        };
 
         let mut mpv = MotionPhotoVideo {
-            path: video_path.clone(),
+            range,
+            path,
+            format: embedded.format,
+            still_frame_ts: embedded.still_frame_ts,
             duration: None,
             video_codec: None,
             rotation: None,
             transcoded_path: None,
         };
 
-        if let Ok(meta) = video_metadata::from_path(&video_path) {
+        if let Ok(meta) = video_metadata::from_path(&input) {
             mpv.video_codec = meta.video_codec;
             mpv.rotation = meta.rotation;
             mpv.duration = meta.duration;
         } else {
-            // If we have extracted the video but can't get any metadata, then the motion photo
-            // format for this file probably isn't supported by the sm_motion_photo library and
-            // we have duff data.
+            // If we have found the video but can't get any metadata, then the motion photo
+            // format for this file probably isn't supported and we have duff data.
             return Ok(None);
         }
 
@@ -103,7 +160,13 @@ impl MotionPhotoExtractor {
                 self.base_path.join(partition).join(file_name)
             };
 
-            transcode::transcode(&video_path, &transcoded_path)?;
+            transcode::transcode(
+                &input,
+                &transcoded_path,
+                &TranscodeProfile::default(),
+                stop,
+                &mut |_| {},
+            )?;
 
             mpv.transcoded_path = Some(transcoded_path);
         }
@@ -112,6 +175,286 @@ impl MotionPhotoExtractor {
     }
 }
 
+/// Finds the video embedded in a motion photo by trying each backend in turn.
+fn find_video(picture_path: &Path) -> Result<Option<EmbeddedVideo>> {
+    let xmp = Xmp::from_embedded(picture_path)
+        .inspect_err(|e| debug!("Failed reading XMP from {:?}: {}", picture_path, e))
+        .ok()
+        .flatten();
+
+    let google = xmp.as_ref().and_then(|xmp| xmp.motion_photo.as_ref());
+
+    for backend in BACKENDS {
+        if let Some(embedded) = backend(picture_path, google)? {
+            return Ok(Some(embedded));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Google Motion Photo v2. The XMP container directory lists the media items
+/// appended to the primary image, so the video is found by working back from
+/// the end of the file.
+fn google_motion_photo(
+    picture_path: &Path,
+    google: Option<&GoogleMotionPhoto>,
+) -> Result<Option<EmbeddedVideo>> {
+    let Some(google) = google else {
+        return Ok(None);
+    };
+
+    let index = google.items.iter().position(|item| {
+        item.semantic.as_deref() == Some("MotionPhoto")
+            || item
+                .mime
+                .as_deref()
+                .is_some_and(|x| x.starts_with("video/"))
+    });
+
+    // The primary image is always first, and never the video.
+    let Some(index) = index.filter(|index| *index > 0) else {
+        return Ok(None);
+    };
+
+    let length = google.items[index].length;
+    let tail_length: u64 = google.items[index..]
+        .iter()
+        .map(|item| item.length + item.padding)
+        .sum();
+
+    let file_length = picture_path.metadata()?.len();
+    let Some(offset) = file_length.checked_sub(tail_length) else {
+        return Ok(None);
+    };
+
+    let source = VideoSource::Range { offset, length };
+    let embedded = EmbeddedVideo {
+        format: MotionPhotoFormat::GoogleMotionPhoto,
+        source,
+        still_frame_ts: still_frame_ts(google),
+    };
+
+    Ok(is_mp4(picture_path, offset, length)?.then_some(embedded))
+}
+
+/// Google MicroVideo, the predecessor to Motion Photo v2. The video is at the end
+/// of the file, at an offset from the end given in the XMP.
+fn google_micro_video(
+    picture_path: &Path,
+    google: Option<&GoogleMotionPhoto>,
+) -> Result<Option<EmbeddedVideo>> {
+    let Some(length) = google.and_then(|google| google.micro_video_offset) else {
+        return Ok(None);
+    };
+
+    let file_length = picture_path.metadata()?.len();
+    let Some(offset) = file_length.checked_sub(length) else {
+        return Ok(None);
+    };
+
+    let source = VideoSource::Range { offset, length };
+    let embedded = EmbeddedVideo {
+        format: MotionPhotoFormat::GoogleMicroVideo,
+        source,
+        still_frame_ts: google.and_then(still_frame_ts),
+    };
+
+    Ok(is_mp4(picture_path, offset, length)?.then_some(embedded))
+}
+
+/// Samsung motion photo trailer.
+fn samsung(
+    picture_path: &Path,
+    _google: Option<&GoogleMotionPhoto>,
+) -> Result<Option<EmbeddedVideo>> {
+    let photo_file = File::open(picture_path)?;
+    let Some(sm) = SmMotion::with(&photo_file) else {
+        return Ok(None); // would be nice if API returned a result instead of an option.
+    };
+
+    if !sm.has_video() {
+        return Ok(None);
+    }
+
+    // Prefer reading the video in place, but fall back to sm_motion_photo
+    // extracting a copy if the trailer isn't laid out as expected.
+    let source = match samsung_range(picture_path)? {
+        Some((offset, length)) => VideoSource::Range { offset, length },
+        None => VideoSource::Samsung(sm),
+    };
+
+    Ok(Some(EmbeddedVideo {
+        format: MotionPhotoFormat::Samsung,
+        source,
+        still_frame_ts: None,
+    }))
+}
+
+/// Byte range of the video in a Samsung motion photo. Samsung phones append an SEF
+/// trailer, ending in `SEFT`, with a directory of the data blocks before it. The video
+/// is the `MotionPhoto_Data` block, so only the trailer and block headers are read.
+fn samsung_range(picture_path: &Path) -> Result<Option<(u64, u64)>> {
+    const MARKER: &[u8] = b"MotionPhoto_Data";
+
+    // Trailers are a few hundred bytes, so anything much larger isn't a trailer.
+    const MAX_TRAILER_LENGTH: u64 = 64 * 1024;
+
+    let mut file = File::open(picture_path)?;
+    let file_length = file.metadata()?.len();
+    if file_length < 8 {
+        return Ok(None);
+    }
+
+    // The trailer ends with its length and a `SEFT` signature.
+    let mut footer = [0; 8];
+    file.seek(SeekFrom::End(-8))?;
+    file.read_exact(&mut footer)?;
+    if &footer[4..8] != b"SEFT" {
+        return Ok(None);
+    }
+
+    let trailer_length = u64::from(le_u32(&footer[0..4]));
+    if !(12..=MAX_TRAILER_LENGTH).contains(&trailer_length) {
+        return Ok(None);
+    }
+
+    let Some(directory_pos) = file_length.checked_sub(8 + trailer_length) else {
+        return Ok(None);
+    };
+
+    let mut directory = vec![0; trailer_length as usize];
+    file.seek(SeekFrom::Start(directory_pos))?;
+    file.read_exact(&mut directory)?;
+    if !directory.starts_with(b"SEFH") {
+        return Ok(None);
+    }
+
+    // Each directory entry is a type, the offset of the block back from the start of
+    // the directory, and the length of the block.
+    let count = le_u32(&directory[8..12]) as usize;
+    for entry in directory[12..].chunks_exact(12).take(count) {
+        let block_offset = u64::from(le_u32(&entry[4..8]));
+        let block_length = u64::from(le_u32(&entry[8..12]));
+
+        let Some(block_pos) = directory_pos
+            .checked_sub(block_offset)
+            .filter(|pos| pos + block_length <= directory_pos)
+        else {
+            continue;
+        };
+
+        // A block starts with its type, then the length of its name and the name.
+        let header_length = 8 + MARKER.len() as u64;
+        if block_length < header_length {
+            continue;
+        }
+
+        let mut header = [0; 8 + MARKER.len()];
+        file.seek(SeekFrom::Start(block_pos))?;
+        file.read_exact(&mut header)?;
+        if le_u32(&header[4..8]) as usize != MARKER.len() || &header[8..] != MARKER {
+            continue;
+        }
+
+        let (offset, length) = (block_pos + header_length, block_length - header_length);
+        return Ok(is_mp4(picture_path, offset, length)?.then_some((offset, length)));
+    }
+
+    Ok(None)
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Copy of the video embedded in a motion photo. The copy is deleted when dropped.
+#[derive(Debug)]
+pub struct VideoCopy {
+    path: tempfile::TempPath,
+}
+
+impl VideoCopy {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Copies the video embedded in a motion photo to the motion photo directory of
+/// `cache_dir`, so that it can be played by media frameworks that only read whole files.
+pub fn copy_video(picture_path: &Path, range: Range<u64>, cache_dir: &Path) -> Result<VideoCopy> {
+    let dir = cache_dir.join(CACHE_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+
+    let mut file = File::open(picture_path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+
+    let mut copy = tempfile::Builder::new()
+        .prefix(COPY_PREFIX)
+        .suffix(".mp4")
+        .tempfile_in(&dir)?;
+
+    let length = range.end.saturating_sub(range.start);
+    let copied = std::io::copy(&mut file.take(length), &mut copy)?;
+    if copied != length {
+        bail!("Video truncated at {} of {} bytes", copied, length);
+    }
+
+    Ok(VideoCopy {
+        path: copy.into_temp_path(),
+    })
+}
+
+/// Deletes copies of videos left behind if the application didn't exit cleanly.
+pub fn remove_video_copies(cache_dir: &Path) -> Result<()> {
+    let Ok(entries) = std::fs::read_dir(cache_dir.join(CACHE_DIR_NAME)) else {
+        return Ok(());
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(COPY_PREFIX) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// FFmpeg input for a byte range of a file, using the subfile protocol,
+/// so that a video can be read without copying it out of a motion photo.
+pub(crate) fn subfile(path: &Path, offset: u64, length: u64) -> PathBuf {
+    let mut input = std::ffi::OsString::from(format!(
+        "subfile,,start,{},end,{},,:",
+        offset,
+        offset + length
+    ));
+    input.push(path.as_os_str());
+    PathBuf::from(input)
+}
+
+/// Presentation timestamp of the still frame. -1 means unspecified.
+fn still_frame_ts(google: &GoogleMotionPhoto) -> Option<TimeDelta> {
+    google
+        .presentation_timestamp_us
+        .filter(|us| *us >= 0)
+        .map(TimeDelta::microseconds)
+}
+
+/// Whether a byte range of a file looks like an MP4 or QuickTime file, which
+/// starts with an `ftyp` box.
+fn is_mp4(path: &Path, offset: u64, length: u64) -> Result<bool> {
+    if length < 8 {
+        return Ok(false);
+    }
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut header = [0; 8];
+    file.read_exact(&mut header)?;
+    Ok(&header[4..8] == b"ftyp")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,6 +466,139 @@ mod tests {
         let path = Path::new("/var/home/david/Pictures/Test/Motion Photos/photo.jpg");
 
         let mp = MotionPhotoExtractor::build(&Path::new(".")).unwrap();
-        mp.extract(&PictureId::new(123), &path);
+        mp.extract(&PictureId::new(123), &path, &AtomicBool::new(false));
+    }
+
+    /// A JPEG with a Motion Photo v2 XMP packet, followed by a fake MP4 video.
+    fn google_motion_photo_file(dir: &Path, padding: &[u8]) -> (PathBuf, Vec<u8>) {
+        let video = b"\0\0\0\x18ftypmp42\0\0\0\0mp42isomfake video".to_vec();
+
+        let xmp = format!(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:GCamera="http://ns.google.com/photos/1.0/camera/"
+    xmlns:Container="http://ns.google.com/photos/1.0/container/"
+    xmlns:Item="http://ns.google.com/photos/1.0/container/item/"
+   GCamera:MotionPhoto="1"
+   GCamera:MotionPhotoPresentationTimestampUs="500000">
+   <Container:Directory>
+    <rdf:Seq>
+     <rdf:li rdf:parseType="Resource">
+      <Container:Item Item:Mime="image/jpeg" Item:Semantic="Primary" Item:Padding="{}"/>
+     </rdf:li>
+     <rdf:li rdf:parseType="Resource">
+      <Container:Item Item:Mime="video/mp4" Item:Semantic="MotionPhoto" Item:Length="{}"/>
+     </rdf:li>
+    </rdf:Seq>
+   </Container:Directory>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#,
+            padding.len(),
+            video.len()
+        );
+
+        let mut data = b"\xff\xd8\xff\xe1".to_vec();
+        data.extend(xmp.as_bytes());
+        data.extend(b"\xff\xd9");
+        data.extend(padding);
+        data.extend(&video);
+
+        let path = dir.join("PXL_20240601_120000000.MP.jpg");
+        std::fs::write(&path, &data).unwrap();
+        (path, video)
+    }
+
+    #[test]
+    fn test_find_google_motion_photo() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, video) = google_motion_photo_file(dir.path(), b"\0\0\0\0");
+
+        let embedded = find_video(&path).unwrap().unwrap();
+        assert_eq!(MotionPhotoFormat::GoogleMotionPhoto, embedded.format);
+        assert_eq!(Some(TimeDelta::milliseconds(500)), embedded.still_frame_ts);
+
+        let VideoSource::Range { offset, length } = embedded.source else {
+            panic!("Expected byte range");
+        };
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(video.len() as u64, length);
+        assert_eq!(video, data[offset as usize..]);
+    }
+
+    #[test]
+    fn test_samsung_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let video = b"\0\0\0\x18ftypmp42\0\0\0\0mp42isomfake video";
+
+        let mut data = b"\xff\xd8\xff\xd9".to_vec();
+
+        // MotionPhoto_Data block.
+        let block_pos = data.len();
+        data.extend(b"\0\0\x30\x0a");
+        data.extend(16u32.to_le_bytes());
+        data.extend(b"MotionPhoto_Data");
+        data.extend(video);
+        let block_length = data.len() - block_pos;
+
+        // SEF trailer with a directory of one block.
+        let directory_pos = data.len();
+        let mut directory = b"SEFH".to_vec();
+        directory.extend(107u32.to_le_bytes());
+        directory.extend(1u32.to_le_bytes());
+        directory.extend(b"\0\0\x30\x0a");
+        directory.extend(((directory_pos - block_pos) as u32).to_le_bytes());
+        directory.extend((block_length as u32).to_le_bytes());
+        data.extend(&directory);
+        data.extend((directory.len() as u32).to_le_bytes());
+        data.extend(b"SEFT");
+
+        let path = dir.path().join("20240601_120000.jpg");
+        std::fs::write(&path, &data).unwrap();
+
+        let (offset, length) = samsung_range(&path).unwrap().unwrap();
+        assert_eq!(video, &data[offset as usize..(offset + length) as usize]);
+
+        // No trailer
+        std::fs::write(&path, &data[..directory_pos]).unwrap();
+        assert!(samsung_range(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_copy_video() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("motion.jpg");
+        std::fs::write(&path, b"photo|video|trailer").unwrap();
+
+        let copy = copy_video(&path, 6..11, dir.path()).unwrap();
+        assert_eq!(b"video", &std::fs::read(copy.path()).unwrap()[..]);
+
+        // Copies are deleted when dropped or when left behind.
+        let copy_path = copy.path().to_path_buf();
+        drop(copy);
+        assert!(!copy_path.exists());
+
+        let copy = copy_video(&path, 6..11, dir.path()).unwrap();
+        let copy_path = copy.path().to_path_buf();
+        std::mem::forget(copy);
+        remove_video_copies(dir.path()).unwrap();
+        assert!(!copy_path.exists());
+
+        // Range past the end of the file.
+        assert!(copy_video(&path, 12..30, dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_find_google_motion_photo_bad_length() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Data after the video means the file doesn't match the XMP item lengths,
+        // so the video isn't found.
+        let (path, _) = google_motion_photo_file(dir.path(), b"");
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, [&data[..], b"trailing junk"].concat()).unwrap();
+
+        assert!(find_video(&path).unwrap().is_none());
     }
 }
@@ -0,0 +1,440 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Camera RAW files.
+//!
+//! Decoding RAW sensor data is slow and not supported by Glycin, but cameras embed
+//! a JPEG preview in each RAW file that is good enough for thumbnails and viewing.
+//!
+//! CR2, NEF, ARW, and DNG files are TIFF files with the preview JPEG in one of the
+//! image file directories (IFDs). CR3 files are ISO base media files with the preview
+//! JPEG, and the EXIF data, in Canon specific boxes.
+
+use crate::file_types::FileType;
+
+use anyhow::*;
+use exif::Exif;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// UUID of the CR3 box inside `moov` that holds the CMT boxes of EXIF data.
+const CR3_METADATA_UUID: [u8; 16] = [
+    0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48,
+];
+
+/// UUID of the top level CR3 box that holds the PRVW preview JPEG box.
+const CR3_PREVIEW_UUID: [u8; 16] = [
+    0xea, 0xf4, 0x2b, 0x5e, 0x1c, 0x98, 0x4b, 0x88, 0xb9, 0xfb, 0xb7, 0xdc, 0x40, 0x6e, 0x4d, 0x16,
+];
+
+/// Guard against IFD loops and corrupt files.
+const MAX_IFDS: usize = 64;
+
+/// Extracts the largest embedded preview JPEG from a RAW file.
+pub fn preview(path: &Path, file_type: FileType) -> Result<Vec<u8>> {
+    let file = File::open(path)?;
+    let mut file = BufReader::new(file);
+
+    let preview = match file_type {
+        FileType::Cr3 => cr3_preview(&mut file)?,
+        FileType::Arw | FileType::Cr2 | FileType::Dng | FileType::Nef => tiff_preview(&mut file)?,
+        _ => bail!("Not a RAW file: {:?}", path),
+    };
+
+    preview.ok_or_else(|| anyhow!("No preview JPEG in {:?}", path))
+}
+
+/// EXIF data of a CR3 file.
+///
+/// Each CMT box is a TIFF structure holding a single IFD, so every field is parsed
+/// as though it were in IFD0. Fields are looked up by tag number in the box for
+/// the IFD the tag belongs to.
+#[derive(Debug, Default)]
+pub struct Cr3Exif {
+    /// IFD0 from the CMT1 box
+    ifd0: Option<Exif>,
+
+    /// Exif IFD from the CMT2 box
+    exif: Option<Exif>,
+
+    /// GPS IFD from the CMT4 box
+    gps: Option<Exif>,
+}
+
+impl Cr3Exif {
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let mut file = BufReader::new(file);
+        let file_len = file.seek(SeekFrom::End(0))?;
+
+        let moov = find_box(&mut file, 0, file_len, b"moov", None)?
+            .ok_or_else(|| anyhow!("No moov box in {:?}", path))?;
+
+        let metadata = find_box(
+            &mut file,
+            moov.start,
+            moov.end,
+            b"uuid",
+            Some(&CR3_METADATA_UUID),
+        )?
+        .ok_or_else(|| anyhow!("No metadata box in {:?}", path))?;
+
+        let mut read_ifd = |kind: &[u8; 4]| -> Result<Option<Exif>> {
+            let Some(cmt) = find_box(&mut file, metadata.start, metadata.end, kind, None)? else {
+                return Ok(None);
+            };
+            let data = read_range(&mut file, cmt.start, cmt.end - cmt.start)?;
+            Ok(exif::Reader::new().read_raw(data).ok())
+        };
+
+        Ok(Self {
+            ifd0: read_ifd(b"CMT1")?,
+            exif: read_ifd(b"CMT2")?,
+            gps: read_ifd(b"CMT4")?,
+        })
+    }
+
+    pub fn get_field(&self, tag: exif::Tag) -> Option<&exif::Field> {
+        let ifd = match tag.context() {
+            exif::Context::Tiff => &self.ifd0,
+            exif::Context::Exif => &self.exif,
+            exif::Context::Gps => &self.gps,
+            _ => return None,
+        };
+
+        ifd.as_ref()?.get_field(
+            exif::Tag(exif::Context::Tiff, tag.number()),
+            exif::In::PRIMARY,
+        )
+    }
+}
+
+/// Location of an ISO base media box's payload in a file.
+#[derive(Debug, Clone, Copy)]
+struct BoxRange {
+    start: u64,
+    end: u64,
+}
+
+/// Finds the first child box of a type within a range of a file.
+/// Children of `uuid` boxes can be matched by their extended type.
+fn find_box<R: Read + Seek>(
+    r: &mut R,
+    start: u64,
+    end: u64,
+    kind: &[u8; 4],
+    uuid: Option<&[u8; 16]>,
+) -> Result<Option<BoxRange>> {
+    let mut offset = start;
+
+    while offset + 8 <= end {
+        r.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 8];
+        r.read_exact(&mut header)?;
+
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut payload_start = offset + 8;
+
+        let size = match size {
+            0 => end - offset,
+            1 => {
+                let mut large_size = [0u8; 8];
+                r.read_exact(&mut large_size)?;
+                payload_start += 8;
+                u64::from_be_bytes(large_size)
+            }
+            size => size,
+        };
+
+        let box_end = offset.saturating_add(size).min(end);
+        if box_end <= offset || payload_start > box_end {
+            bail!("Invalid box at offset {}", offset);
+        }
+
+        if &header[4..8] == kind {
+            if kind != b"uuid" {
+                return Ok(Some(BoxRange {
+                    start: payload_start,
+                    end: box_end,
+                }));
+            }
+
+            let mut extended_type = [0u8; 16];
+            r.read_exact(&mut extended_type)?;
+            if uuid.is_none_or(|uuid| *uuid == extended_type) {
+                return Ok(Some(BoxRange {
+                    start: payload_start + 16,
+                    end: box_end,
+                }));
+            }
+        }
+
+        offset = box_end;
+    }
+
+    Ok(None)
+}
+
+/// The CR3 preview is a PRVW box inside a `uuid` box, following eight bytes of
+/// unknown purpose. The PRVW box has a small header describing the preview
+/// followed by the JPEG.
+fn cr3_preview<R: Read + Seek>(r: &mut R) -> Result<Option<Vec<u8>>> {
+    let file_len = r.seek(SeekFrom::End(0))?;
+
+    let Some(container) = find_box(r, 0, file_len, b"uuid", Some(&CR3_PREVIEW_UUID))? else {
+        return Ok(None);
+    };
+
+    let Some(prvw) = find_box(r, container.start + 8, container.end, b"PRVW", None)? else {
+        return Ok(None);
+    };
+
+    let data = read_range(r, prvw.start, prvw.end - prvw.start)?;
+    let jpeg = data
+        .windows(3)
+        .position(|w| w == [0xFF, 0xD8, 0xFF])
+        .map(|start| data[start..].to_vec());
+
+    Ok(jpeg.filter(|jpeg| is_decodable_jpeg(jpeg)))
+}
+
+/// Walks the IFDs of a TIFF based RAW file looking for JPEGs.
+///
+/// Previews are either referenced by the JPEGInterchangeFormat tags, or are a single
+/// JPEG compressed strip. The sensor data of some formats is also a JPEG compressed
+/// strip, but with lossless JPEG, which isn't a preview and can't be decoded.
+fn tiff_preview<R: Read + Seek>(r: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 8];
+    r.seek(SeekFrom::Start(0))?;
+    r.read_exact(&mut header)?;
+
+    let is_le = match &header[0..2] {
+        b"II" => true,
+        b"MM" => false,
+        _ => bail!("Not a TIFF file"),
+    };
+
+    let u16_at = |b: &[u8]| {
+        let b = [b[0], b[1]];
+        if is_le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        }
+    };
+
+    let u32_at = |b: &[u8]| {
+        let b = [b[0], b[1], b[2], b[3]];
+        if is_le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        }
+    };
+
+    // Value of a SHORT or LONG entry with a count of one, which is stored inline.
+    let value_at = |entry: &[u8]| match u16_at(&entry[2..4]) {
+        3 => u16_at(&entry[8..10]) as u32,
+        _ => u32_at(&entry[8..12]),
+    };
+
+    let mut candidates: Vec<(u64, u64)> = Vec::new();
+    let mut pending = vec![u32_at(&header[4..8]) as u64];
+    let mut seen = HashSet::new();
+
+    while let Some(ifd_offset) = pending.pop() {
+        if ifd_offset == 0 || seen.len() >= MAX_IFDS || !seen.insert(ifd_offset) {
+            continue;
+        }
+
+        r.seek(SeekFrom::Start(ifd_offset))?;
+        let mut count = [0u8; 2];
+        r.read_exact(&mut count)?;
+        let count = u16_at(&count) as usize;
+
+        let mut entries = vec![0u8; count * 12 + 4];
+        r.read_exact(&mut entries)?;
+
+        let mut jpeg_offset = None;
+        let mut jpeg_length = None;
+        let mut strip_offset = None;
+        let mut strip_length = None;
+        let mut compression = None;
+
+        for entry in entries[..count * 12].chunks_exact(12) {
+            let tag = u16_at(&entry[0..2]);
+            let value_count = u32_at(&entry[4..8]);
+
+            match tag {
+                0x0103 => compression = Some(value_at(entry)),
+                0x0111 if value_count == 1 => strip_offset = Some(value_at(entry)),
+                0x0117 if value_count == 1 => strip_length = Some(value_at(entry)),
+                0x0201 => jpeg_offset = Some(value_at(entry)),
+                0x0202 => jpeg_length = Some(value_at(entry)),
+                // SubIFDs
+                0x014A if value_count == 1 => pending.push(value_at(entry) as u64),
+                0x014A => {
+                    let offsets =
+                        read_range(r, u32_at(&entry[8..12]) as u64, value_count as u64 * 4)?;
+                    pending.extend(offsets.chunks_exact(4).map(|b| u32_at(b) as u64));
+                }
+                // Exif IFD
+                0x8769 => pending.push(value_at(entry) as u64),
+                _ => {}
+            }
+        }
+
+        if let (Some(offset), Some(length)) = (jpeg_offset, jpeg_length) {
+            candidates.push((offset as u64, length as u64));
+        }
+
+        if let (Some(6 | 7), Some(offset), Some(length)) = (compression, strip_offset, strip_length)
+        {
+            candidates.push((offset as u64, length as u64));
+        }
+
+        // Next IFD in chain
+        pending.push(u32_at(&entries[count * 12..]) as u64);
+    }
+
+    // Largest first
+    candidates.sort_by_key(|(_, length)| std::cmp::Reverse(*length));
+    candidates.dedup();
+
+    for (offset, length) in candidates {
+        if length == 0 {
+            continue;
+        }
+        let std::result::Result::Ok(data) = read_range(r, offset, length) else {
+            continue;
+        };
+        if is_decodable_jpeg(&data) {
+            return Ok(Some(data));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Is the data a baseline or progressive JPEG?
+/// Lossless JPEGs are used for sensor data, not previews.
+fn is_decodable_jpeg(data: &[u8]) -> bool {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+
+    let mut i = 2;
+    while i + 4 <= data.len() {
+        if data[i] != 0xFF {
+            return false;
+        }
+
+        let marker = data[i + 1];
+        match marker {
+            // Fill byte
+            0xFF => i += 1,
+            // Baseline, extended sequential, and progressive start of frame markers
+            0xC0..=0xC2 => return true,
+            // Start of scan before a supported start of frame, or another start of frame.
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | 0xDA => return false,
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => i += 2,
+            _ => {
+                let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+                i += 2 + length;
+            }
+        }
+    }
+
+    false
+}
+
+fn read_range<R: Read + Seek>(r: &mut R, offset: u64, length: u64) -> Result<Vec<u8>> {
+    r.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    r.take(length).read_to_end(&mut data)?;
+    if (data.len() as u64) < length {
+        bail!("Truncated data at offset {}", offset);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Smallest JPEG header with a baseline or lossless start of frame.
+    fn jpeg(start_of_frame: u8) -> Vec<u8> {
+        vec![
+            0xFF,
+            0xD8, // start of image
+            0xFF,
+            0xE0,
+            0x00,
+            0x04,
+            0x00,
+            0x00, // APP0
+            0xFF,
+            start_of_frame,
+            0x00,
+            0x02, // start of frame
+            0xFF,
+            0xD9, // end of image
+        ]
+    }
+
+    #[test]
+    fn test_is_decodable_jpeg() {
+        assert!(is_decodable_jpeg(&jpeg(0xC0)));
+        assert!(is_decodable_jpeg(&jpeg(0xC2)));
+        assert!(!is_decodable_jpeg(&jpeg(0xC3)));
+        assert!(!is_decodable_jpeg(b"not a jpeg"));
+    }
+
+    #[test]
+    fn test_tiff_preview() {
+        // Little endian TIFF with IFD0 pointing at a lossless JPEG strip and
+        // a SubIFD with a baseline JPEG preview.
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+
+        let entry = |tag: u16, kind: u16, value: u32| {
+            let mut e = tag.to_le_bytes().to_vec();
+            e.extend_from_slice(&kind.to_le_bytes());
+            e.extend_from_slice(&1u32.to_le_bytes());
+            e.extend_from_slice(&value.to_le_bytes());
+            e
+        };
+
+        let ifd0_len = 2 + 4 * 12 + 4;
+        let sub_ifd_offset = 8 + ifd0_len;
+        let sub_ifd_len = 2 + 2 * 12 + 4;
+        let lossless_offset = sub_ifd_offset + sub_ifd_len;
+        let lossless = jpeg(0xC3);
+        let preview_offset = lossless_offset + lossless.len() as u32;
+        let preview = jpeg(0xC0);
+
+        tiff.extend_from_slice(&4u16.to_le_bytes());
+        tiff.extend(entry(0x0103, 3, 7));
+        tiff.extend(entry(0x0111, 4, lossless_offset));
+        tiff.extend(entry(0x0117, 4, lossless.len() as u32 + 100));
+        tiff.extend(entry(0x014A, 4, sub_ifd_offset));
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        tiff.extend(entry(0x0201, 4, preview_offset));
+        tiff.extend(entry(0x0202, 4, preview.len() as u32));
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+
+        tiff.extend(lossless);
+        tiff.extend(preview.clone());
+        tiff.extend(vec![0; 100]);
+
+        let result = tiff_preview(&mut Cursor::new(tiff)).unwrap();
+        assert_eq!(Some(preview), result);
+    }
+}
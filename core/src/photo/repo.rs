@@ -4,6 +4,8 @@
 
 use crate::FlatpakPathBuf;
 use crate::ScannedFile;
+use crate::file_types::FileType;
+use crate::library_root::{LibraryRootId, LibraryRoots};
 use crate::path_encoding;
 use crate::people::model::{DetectedFace, FaceDetectionCandidate, FaceId, Rect};
 use crate::photo::model::{Picture, PictureId};
@@ -17,6 +19,7 @@ use rusqlite;
 use rusqlite::Row;
 use rusqlite::params;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use tracing::error;
 
@@ -24,8 +27,8 @@ use tracing::error;
 /// Repository is backed by a Sqlite database.
 #[derive(Debug, Clone)]
 pub struct Repository {
-    /// Base path to library
-    library_base_dir: FlatpakPathBuf,
+    /// Library root directories that picture paths are relative to
+    library_roots: LibraryRoots,
 
     /// Base path cache directory for motion photo videos
     cache_dir_base_path: PathBuf,
@@ -40,17 +43,17 @@ pub struct Repository {
 impl Repository {
     /// Builds a Repository and creates operational tables.
     pub fn open(
-        library_base_dir: &FlatpakPathBuf,
+        library_roots: &LibraryRoots,
         cache_dir_base_path: &Path,
         data_dir_base_path: &Path,
         con: Arc<Mutex<rusqlite::Connection>>,
     ) -> Result<Repository> {
-        if !library_base_dir.sandbox_path.is_dir() {
-            bail!("{:?} is not a directory", library_base_dir);
+        if library_roots.is_empty() {
+            bail!("No library root directories");
         }
 
         let repo = Repository {
-            library_base_dir: library_base_dir.clone(),
+            library_roots: library_roots.clone(),
             cache_dir_base_path: cache_dir_base_path.into(),
             data_dir_base_path: data_dir_base_path.into(),
             con,
@@ -74,10 +77,59 @@ impl Repository {
                     content_id = ?6,
                     orientation = ?7,
                     fs_created_ts = ?8,
-                    fs_modified_ts = ?9
+                    fs_modified_ts = ?9,
+                    rating = ?10,
+                    label = ?11,
+                    title = ?12,
+                    description = ?13,
+                    exif_created_local_ts = ?14,
+                    exif_created_utc_ts = ?15,
+                    exif_created_offset_source = ?16
                 WHERE picture_id = ?1",
             )?;
 
+            let mut delete_keywords =
+                tx.prepare_cached("DELETE FROM pictures_keywords WHERE picture_id = ?1")?;
+
+            let mut insert_keyword = tx.prepare_cached(
+                "INSERT OR IGNORE INTO pictures_keywords (picture_id, keyword) VALUES (?1, ?2)",
+            )?;
+
+            let mut upsert_exif = tx.prepare_cached(
+                "INSERT INTO pictures_exif (
+                    picture_id,
+                    camera_make,
+                    camera_model,
+                    lens_model,
+                    focal_length,
+                    focal_length_35mm,
+                    f_number,
+                    exposure_time,
+                    iso,
+                    is_flash_fired,
+                    white_balance,
+                    width,
+                    height
+                ) VALUES (
+                    ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13
+                ) ON CONFLICT (picture_id) DO UPDATE SET
+                    camera_make = ?2,
+                    camera_model = ?3,
+                    lens_model = ?4,
+                    focal_length = ?5,
+                    focal_length_35mm = ?6,
+                    f_number = ?7,
+                    exposure_time = ?8,
+                    iso = ?9,
+                    is_flash_fired = ?10,
+                    white_balance = ?11,
+                    width = ?12,
+                    height = ?13",
+            )?;
+
+            let mut delete_exif =
+                tx.prepare_cached("DELETE FROM pictures_exif WHERE picture_id = ?1")?;
+
             let mut update_geo = tx.prepare_cached(
                 "INSERT INTO pictures_geo (
                     picture_id,
@@ -102,8 +154,49 @@ impl Repository {
                     metadata.orientation.map(|x| x as u8),
                     metadata.fs_created_at,
                     metadata.fs_modified_at,
+                    metadata.rating,
+                    metadata.label,
+                    metadata.title,
+                    metadata.description,
+                    metadata.capture_time.map(|x| x.local),
+                    // Only a capture time with a known offset is a true UTC time.
+                    metadata
+                        .capture_time
+                        .filter(|x| x.offset.is_some())
+                        .and_then(|x| x.to_date_time())
+                        .map(|x| x.to_utc()),
+                    metadata
+                        .capture_time
+                        .and_then(|x| x.offset_source)
+                        .map(|x| x.as_ref().to_string()),
                 ])?;
 
+                let exif = &metadata.exif;
+                if exif.is_empty() {
+                    delete_exif.execute([picture_id.id()])?;
+                } else {
+                    upsert_exif.execute(params![
+                        picture_id.id(),
+                        exif.camera_make,
+                        exif.camera_model,
+                        exif.lens_model,
+                        exif.focal_length,
+                        exif.focal_length_35mm,
+                        exif.f_number,
+                        exif.exposure_time,
+                        exif.iso,
+                        exif.is_flash_fired,
+                        exif.white_balance.as_ref().map(|x| x.as_ref()),
+                        exif.width,
+                        exif.height,
+                    ])?;
+                }
+
+                delete_keywords.execute([picture_id.id()])?;
+                for keyword in &metadata.keywords {
+                    insert_keyword.execute(params![picture_id.id(), keyword])?;
+                }
+
                 if let Some(location) = metadata.location {
                     // Belts and braces.
                     // SQLite will treat a "nan" (not-a-number) as a null and cause
@@ -140,6 +233,49 @@ impl Repository {
         Ok(())
     }
 
+    /// Detects and records the file type of pictures added before file types were recorded.
+    /// Returns the number of pictures updated.
+    pub fn backfill_file_types(&mut self) -> Result<usize> {
+        let pics: Vec<(PictureId, FlatpakPathBuf)> = {
+            let con = self.con.lock().unwrap();
+            let mut stmt = con.prepare(
+                "SELECT picture_id, root_id, picture_path_b64
+                FROM pictures
+                WHERE file_type IS NULL",
+            )?;
+
+            stmt.query_map([], |row| {
+                let picture_id = row.get("picture_id").map(PictureId::new)?;
+                Ok((picture_id, self.to_library_path(row)?))
+            })?
+            .flatten()
+            .collect()
+        };
+
+        // Don't hold the lock while reading files.
+        let file_types: Vec<(PictureId, FileType)> = pics
+            .into_iter()
+            .filter_map(|(picture_id, path)| {
+                FileType::detect(&path.sandbox_path).map(|file_type| (picture_id, file_type))
+            })
+            .collect();
+
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut stmt =
+                tx.prepare_cached("UPDATE pictures SET file_type = ?2 WHERE picture_id = ?1")?;
+
+            for (picture_id, file_type) in &file_types {
+                stmt.execute(params![picture_id.id(), file_type.as_ref()])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(file_types.len())
+    }
+
     /// Add all Pictures received from a vector.
     pub fn add_all(&mut self, pics: &Vec<ScannedFile>) -> Result<()> {
         let mut con = self.con.lock().unwrap();
@@ -149,36 +285,35 @@ impl Repository {
         {
             let mut pic_insert_stmt = tx.prepare_cached(
                 "INSERT INTO pictures (
+                    root_id,
                     picture_path_b64,
                     picture_path_lossy,
                     link_path_b64,
                     link_path_lossy,
+                    file_type,
                     insert_ts
                 ) VALUES (
-                    ?1, ?2, ?3, ?4, CURRENT_TIMESTAMP
-                ) ON CONFLICT(picture_path_b64) DO NOTHING",
+                    ?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP
+                ) ON CONFLICT(root_id, picture_path_b64) DO UPDATE SET
+                    file_type = ?6",
             )?;
 
             for scanned_file in pics {
-                if let ScannedFile::Photo(path) = scanned_file {
+                if let ScannedFile::Photo(path, file_type) = scanned_file {
                     // convert to relative path before saving to database
-                    let picture_path = path.strip_prefix(&self.library_base_dir.sandbox_path)?;
+                    let (root_id, picture_path) = self.library_roots.to_relative_path(path)?;
                     let picture_path_b64 = path_encoding::to_base64(picture_path);
 
-                    // Path without suffix so sibling pictures and videos can be related
-                    let link_path = picture_path
-                        .file_stem()
-                        .and_then(|x| x.to_str())
-                        .expect("Must exist");
-
-                    let link_path = picture_path.with_file_name(link_path);
+                    let link_path = Self::to_link_path(picture_path);
                     let link_path_b64 = path_encoding::to_base64(&link_path);
 
                     pic_insert_stmt.execute(params![
+                        root_id.id(),
                         picture_path_b64,
                         picture_path.to_string_lossy(),
                         link_path_b64,
                         link_path.to_string_lossy(),
+                        file_type.as_ref(),
                     ])?;
                 } else {
                     error!("Expected a photo, but got: {:?}", scanned_file);
@@ -190,12 +325,222 @@ impl Repository {
         Ok(())
     }
 
+    /// Mark pictures that have changed on the file system as needing their metadata
+    /// and motion photo video extracted again, and their hashes computed again.
+    /// Returns the changed pictures so callers can discard other derived files, such as thumbnails,
+    /// and the paths of the motion photo videos that were extracted from them, which must be deleted.
+    pub fn mark_changed(
+        &mut self,
+        pics: &Vec<ScannedFile>,
+    ) -> Result<(Vec<Picture>, Vec<PathBuf>)> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let mut changed = Vec::new();
+        let mut video_paths = Vec::new();
+
+        {
+            let mut update_stmt = tx.prepare_cached(
+                "UPDATE pictures
+                SET
+                    metadata_version = 0,
+                    is_broken = NULL,
+                    file_type = ?3
+                WHERE root_id = ?1
+                AND picture_path_b64 = ?2
+                RETURNING
+                    picture_id,
+                    root_id,
+                    picture_path_b64,
+                    COALESCE(
+                        exif_created_ts,
+                        exif_modified_ts,
+                        fs_created_ts,
+                        fs_modified_ts,
+                        insert_ts
+                      ) AS ordering_ts,
+                    is_selfie,
+                    file_type",
+            )?;
+
+            let mut motion_photo_stmt = tx.prepare_cached(
+                "DELETE FROM motion_photos
+                WHERE picture_id = ?1
+                RETURNING video_path, transcoded_path",
+            )?;
+
+            let mut hashes_stmt =
+                tx.prepare_cached("DELETE FROM pictures_hashes WHERE picture_id = ?1")?;
+
+            for scanned_file in pics {
+                if let ScannedFile::Photo(path, file_type) = scanned_file {
+                    let (root_id, picture_path) = self.library_roots.to_relative_path(path)?;
+                    let picture_path_b64 = path_encoding::to_base64(picture_path);
+
+                    let pictures: Vec<Picture> = update_stmt
+                        .query_map(
+                            params![root_id.id(), picture_path_b64, file_type.as_ref()],
+                            |row| self.to_picture(row),
+                        )?
+                        .flatten()
+                        .collect();
+
+                    for pic in pictures {
+                        let paths: Vec<(Option<String>, Option<String>)> = motion_photo_stmt
+                            .query_map([pic.picture_id.id()], |row| Ok((row.get(0)?, row.get(1)?)))?
+                            .flatten()
+                            .collect();
+
+                        video_paths.extend(
+                            paths
+                                .into_iter()
+                                .flat_map(|(video_path, transcoded_path)| {
+                                    [video_path, transcoded_path]
+                                })
+                                .flatten()
+                                .map(|p| self.cache_dir_base_path.join(p)),
+                        );
+
+                        hashes_stmt.execute([pic.picture_id.id()])?;
+                        changed.push(pic);
+                    }
+                } else {
+                    error!("Expected a photo, but got: {:?}", scanned_file);
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok((changed, video_paths))
+    }
+
+    /// Update the paths of pictures that have been moved or renamed, so that faces,
+    /// motion photo videos, and other data derived from the picture are kept.
+    /// Takes pairs of old path and new file.
+    /// Returns the old paths of moved pictures so callers can discard other derived files,
+    /// such as thumbnails.
+    pub fn mark_moved(
+        &mut self,
+        moves: &Vec<(PathBuf, ScannedFile)>,
+    ) -> Result<Vec<FlatpakPathBuf>> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let mut moved = Vec::new();
+
+        {
+            // Ignore conflicts with a picture already at the new path. The picture at
+            // the old path will be removed by the clean task.
+            let mut update_stmt = tx.prepare_cached(
+                "UPDATE OR IGNORE pictures
+                SET
+                    root_id = ?3,
+                    picture_path_b64 = ?4,
+                    picture_path_lossy = ?5,
+                    link_path_b64 = ?6,
+                    link_path_lossy = ?7,
+                    file_type = ?8
+                WHERE root_id = ?1
+                AND picture_path_b64 = ?2",
+            )?;
+
+            for (from, scanned_file) in moves {
+                if let ScannedFile::Photo(to, file_type) = scanned_file {
+                    let (from_root_id, from_path) = self.library_roots.to_relative_path(from)?;
+                    let (to_root_id, to_path) = self.library_roots.to_relative_path(to)?;
+                    let link_path = Self::to_link_path(to_path);
+
+                    let count = update_stmt.execute(params![
+                        from_root_id.id(),
+                        path_encoding::to_base64(from_path),
+                        to_root_id.id(),
+                        path_encoding::to_base64(to_path),
+                        to_path.to_string_lossy(),
+                        path_encoding::to_base64(&link_path),
+                        link_path.to_string_lossy(),
+                        file_type.as_ref(),
+                    ])?;
+
+                    if count > 0 {
+                        moved.extend(self.library_roots.resolve(from_root_id, from_path));
+                    }
+                } else {
+                    error!("Expected a photo, but got: {:?}", scanned_file);
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(moved)
+    }
+
+    /// Gets pictures that the library scanner no longer has a record of.
+    /// These are candidates for removal, but callers should check that each picture
+    /// really is absent from the file system before removing it.
+    pub fn find_vanished(&self) -> Result<Vec<Picture>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                    pictures.picture_id,
+                    pictures.root_id,
+                    pictures.picture_path_b64,
+                    COALESCE(
+                        pictures.exif_created_ts,
+                        pictures.exif_modified_ts,
+                        pictures.fs_created_ts,
+                        pictures.fs_modified_ts,
+                        pictures.insert_ts
+                      ) AS ordering_ts,
+                    pictures.is_selfie,
+                    pictures.file_type
+                FROM pictures
+                LEFT OUTER JOIN scanned_files
+                    ON scanned_files.root_id = pictures.root_id
+                    AND scanned_files.file_path_b64 = pictures.picture_path_b64
+                WHERE scanned_files.file_path_b64 IS NULL",
+        )?;
+
+        // Don't treat pictures as vanished if their library root is missing, because
+        // the library root is likely on a disk that isn't mounted.
+        let result = stmt
+            .query_map([], |row| self.to_picture(row))?
+            .flatten()
+            .filter(|pic| {
+                self.library_roots
+                    .relativize(pic.sandbox_path())
+                    .is_some_and(|(root, _)| root.path.exists())
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    /// Gets pictures in library roots that have been removed from the library.
+    pub fn find_orphaned(&self) -> Result<Vec<PictureId>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                    pictures.picture_id
+                FROM pictures
+                LEFT OUTER JOIN library_roots USING (root_id)
+                WHERE library_roots.root_id IS NULL",
+        )?;
+
+        let result = stmt
+            .query_map([], |row| row.get("picture_id").map(PictureId::new))?
+            .flatten()
+            .collect();
+
+        Ok(result)
+    }
+
     /// Gets all pictures in the repository, in ascending order of modification timestamp.
     pub fn all(&self) -> Result<Vec<Picture>> {
         let con = self.con.lock().unwrap();
         let mut stmt = con.prepare(
             "SELECT
                     pictures.picture_id,
+                    pictures.root_id,
                     pictures.picture_path_b64,
                     COALESCE(
                         pictures.exif_created_ts,
@@ -204,7 +549,8 @@ impl Repository {
                         pictures.fs_modified_ts,
                         pictures.insert_ts
                       ) AS ordering_ts,
-                    pictures.is_selfie
+                    pictures.is_selfie,
+                    pictures.file_type
                 FROM pictures
                 WHERE COALESCE(is_broken, FALSE) IS FALSE
                 ORDER BY ordering_ts ASC",
@@ -221,11 +567,13 @@ impl Repository {
     /// Gets all pictures that haven't had their metadata extracted.
     /// Will return all pictures that are not broken and have a metadata version
     /// lower than the current metadata scanner.
+    /// RAW pictures hidden behind a sibling picture are skipped.
     pub fn find_need_metadata_update(&self) -> Result<Vec<Picture>> {
         let con = self.con.lock().unwrap();
         let mut stmt = con.prepare(
             "SELECT
                     pictures.picture_id,
+                    pictures.root_id,
                     pictures.picture_path_b64,
                     COALESCE(
                         pictures.exif_created_ts,
@@ -234,10 +582,12 @@ impl Repository {
                         pictures.fs_modified_ts,
                         pictures.insert_ts
                       ) AS ordering_ts,
-                    pictures.is_selfie
+                    pictures.is_selfie,
+                    pictures.file_type
                 FROM pictures
                 WHERE metadata_version < ?1
                 AND COALESCE(is_broken, FALSE) IS FALSE
+                AND picture_id NOT IN (SELECT picture_id FROM hidden_raw_pictures)
                 ORDER BY ordering_ts ASC",
         )?;
 
@@ -255,6 +605,7 @@ impl Repository {
         let mut stmt = con.prepare(
             "SELECT
                     pictures.picture_id,
+                    pictures.root_id,
                     pictures.picture_path_b64,
                     COALESCE(
                         pictures.exif_created_ts,
@@ -263,7 +614,8 @@ impl Repository {
                         pictures.fs_modified_ts,
                         pictures.insert_ts
                       ) AS ordering_ts,
-                    pictures.is_selfie
+                    pictures.is_selfie,
+                    pictures.file_type
                 FROM pictures
                 FULL OUTER JOIN motion_photos USING (picture_id)
                 WHERE COALESCE(motion_photos.extract_version, 0) < ?1
@@ -310,22 +662,33 @@ impl Repository {
                         duration_millis,
                         video_codec,
                         rotation,
-                        transcoded_path
+                        transcoded_path,
+                        format,
+                        still_frame_micros,
+                        video_offset,
+                        video_length
                     ) VALUES (
-                        ?1, ?2, ?3, ?4, ?5, ?6, ?7
+                        ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11
                     ) ON CONFLICT (picture_id) DO UPDATE SET
                         extract_version = ?2,
                         video_path = ?3,
                         duration_millis = ?4,
                         video_codec = ?5,
                         rotation = ?6,
-                        transcoded_path = ?7
+                        transcoded_path = ?7,
+                        format = ?8,
+                        still_frame_micros = ?9,
+                        video_offset = ?10,
+                        video_length = ?11
                     ",
                 )?;
 
                 // convert to relative path before saving to database
                 // path relative to cache directory so no need to base64 encode
-                let video_path = video.path.strip_prefix(&self.cache_dir_base_path).ok();
+                let video_path = video
+                    .path
+                    .as_ref()
+                    .and_then(|x| x.strip_prefix(&self.cache_dir_base_path).ok());
                 let transcoded_path = video
                     .transcoded_path
                     .as_ref()
@@ -339,6 +702,10 @@ impl Repository {
                     video.video_codec,
                     video.rotation,
                     transcoded_path.as_ref().map(|p| p.to_string_lossy()),
+                    video.format.as_ref(),
+                    video.still_frame_ts.and_then(|x| x.num_microseconds()),
+                    video.range.as_ref().map(|x| x.start),
+                    video.range.as_ref().map(|x| x.end - x.start),
                 ])?;
             } else {
                 let mut stmt = tx.prepare(
@@ -348,15 +715,23 @@ impl Repository {
                     video_path,
                     duration_millis,
                     video_codec,
-                    transcoded_path
+                    transcoded_path,
+                    format,
+                    still_frame_micros,
+                    video_offset,
+                    video_length
                 ) VALUES (
-                    ?1, ?2, NULL, NULL, NULL, NULL
+                    ?1, ?2, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL
                 ) ON CONFLICT (picture_id) DO UPDATE SET
                     extract_version = ?2,
                     video_path = NULL,
                     duration_millis = NULL,
                     video_codec = NULL,
-                    transcoded_path = NULL
+                    transcoded_path = NULL,
+                    format = NULL,
+                    still_frame_micros = NULL,
+                    video_offset = NULL,
+                    video_length = NULL
                 ",
                 )?;
 
@@ -371,21 +746,22 @@ impl Repository {
     fn to_picture(&self, row: &Row<'_>) -> rusqlite::Result<Picture> {
         let picture_id = row.get("picture_id").map(PictureId::new)?;
 
-        let relative_path: String = row.get("picture_path_b64")?;
-        let relative_path = path_encoding::from_base64(&relative_path)
-            .map_err(|_| rusqlite::Error::InvalidQuery)?;
-
-        let sandbox_path = self.library_base_dir.sandbox_path.join(&relative_path);
-        let host_path = self.library_base_dir.host_path.join(&relative_path);
+        let path = self.to_library_path(row)?;
 
         let ordering_ts = row.get("ordering_ts").expect("must have ordering_ts");
         let is_selfie = row.get("is_selfie").ok();
 
+        let file_type = row
+            .get("file_type")
+            .ok()
+            .and_then(|x: String| FileType::from_str(&x).ok());
+
         std::result::Result::Ok(Picture {
             picture_id,
-            path: FlatpakPathBuf::build(host_path, sandbox_path),
+            path,
             ordering_ts,
             is_selfie,
+            file_type,
         })
     }
 
@@ -422,6 +798,7 @@ impl Repository {
                 detected_at,
 
                 is_source_original,
+                pictures.root_id AS root_id,
                 pictures.picture_path_b64 AS picture_path_b64,
 
                 bounds_path,
@@ -465,17 +842,26 @@ impl Repository {
     }
 
     fn to_library_path(&self, row: &Row<'_>) -> rusqlite::Result<FlatpakPathBuf> {
+        let root_id = row.get("root_id").map(LibraryRootId::new)?;
+
         let relative_path: String = row.get("picture_path_b64")?;
-        let relative_path = path_encoding::from_base64(&relative_path)
-            .map_err(|_| rusqlite::Error::InvalidQuery)?;
 
-        let sandbox_path = self.library_base_dir.sandbox_path.join(&relative_path);
-        let host_path = self.library_base_dir.host_path.join(&relative_path);
+        // Pictures in removed library roots can't be resolved.
+        self.library_roots
+            .resolve_base64(root_id, &relative_path)
+            .ok()
+            .flatten()
+            .ok_or(rusqlite::Error::InvalidQuery)
+    }
 
-        std::result::Result::Ok(FlatpakPathBuf {
-            host_path,
-            sandbox_path,
-        })
+    /// Path without suffix so sibling pictures and videos can be related
+    fn to_link_path(picture_path: &Path) -> PathBuf {
+        let link_path = picture_path
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .expect("Must exist");
+
+        picture_path.with_file_name(link_path)
     }
 
     /// FIXME a copy-n-paste from people repo :-()
@@ -537,6 +923,8 @@ impl Repository {
     }
 
     /// Gets all pictures that haven't been scanned for faces.
+    /// RAW pictures hidden behind a sibling picture are skipped, as faces are
+    /// detected in the sibling.
     /// This method is not on the people repo because I don't what that repo
     /// to need a pic_base_dir.
     /// FIXME move to people repo
@@ -545,6 +933,7 @@ impl Repository {
         let mut stmt = con.prepare(
             "SELECT
                     pictures.picture_id,
+                    pictures.root_id,
                     pictures.picture_path_b64,
                     COALESCE(
                         pictures.exif_created_ts,
@@ -557,6 +946,7 @@ impl Repository {
                 LEFT OUTER JOIN pictures_face_scans USING (picture_id)
                 WHERE pictures_face_scans.picture_id IS NULL
                 AND COALESCE(pictures.is_broken, FALSE) IS FALSE
+                AND pictures.picture_id NOT IN (SELECT picture_id FROM hidden_raw_pictures)
                 ORDER BY ordering_ts DESC",
         )?;
 
@@ -580,6 +970,7 @@ impl Repository {
         let mut stmt = con.prepare(
             "SELECT
                     pictures.picture_id,
+                    pictures.root_id,
                     pictures.picture_path_b64
                 FROM pictures
                 WHERE pictures.picture_id = ?1",
@@ -602,23 +993,8 @@ impl Repository {
     ) -> rusqlite::Result<FaceDetectionCandidate> {
         let picture_id = row.get("picture_id").map(PictureId::new)?;
 
-        let relative_path = row
-            .get("picture_path_b64")
-            .ok()
-            .and_then(|x: String| path_encoding::from_base64(&x).ok());
-
-        let host_path = relative_path
-            .as_ref()
-            .map(|x| self.library_base_dir.host_path.join(x));
-
-        let sandbox_path = relative_path.map(|x| self.library_base_dir.sandbox_path.join(x));
+        let path = self.to_library_path(row)?;
 
-        Ok(FaceDetectionCandidate {
-            picture_id,
-            path: FlatpakPathBuf::build(
-                host_path.expect("Must have host path"),
-                sandbox_path.expect("Must have sandbox path"),
-            ),
-        })
+        Ok(FaceDetectionCandidate { picture_id, path })
     }
 }
@@ -12,6 +12,8 @@ use std::io::Cursor;
 use tracing::error;
 
 use crate::FlatpakPathBuf;
+use crate::file_types::FileType;
+use crate::photo::{metadata, raw};
 use crate::thumbnailify;
 
 /// Thumbnail operations for photos.
@@ -27,17 +29,48 @@ impl PhotoThumbnailer {
 
     /// Computes a preview square for an image that has been inserted
     /// into the Repository. Preview image will be written to file system and path returned.
-    pub async fn thumbnail(&self, path: &FlatpakPathBuf) -> Result<()> {
+    /// Camera RAW thumbnails are made from the preview JPEG embedded in the RAW file.
+    pub async fn thumbnail(
+        &self,
+        path: &FlatpakPathBuf,
+        file_type: Option<FileType>,
+    ) -> Result<()> {
         if self.thumbnailer.is_failed(&path.host_path) {
             anyhow::bail!("Failed thumbnail marker exists for {:?}", path.host_path);
         }
 
-        self.thumbnail_internal(path).await.map_err(|err| {
+        let result = match file_type {
+            Some(file_type) if file_type.is_raw() => self.thumbnail_raw(path, file_type),
+            _ => self.thumbnail_internal(path).await,
+        };
+
+        result.map_err(|err| {
             let _ = self.thumbnailer.write_failed_thumbnail(path);
             err
         })
     }
 
+    fn thumbnail_raw(&self, path: &FlatpakPathBuf, file_type: FileType) -> Result<()> {
+        let preview = raw::preview(&path.sandbox_path, file_type)?;
+
+        let mut src_image =
+            ImageReader::with_format(Cursor::new(preview), image::ImageFormat::Jpeg).decode()?;
+
+        // Preview JPEGs aren't rotated, so apply the orientation of the RAW file.
+        let orientation = metadata::from_path(&path.sandbox_path)
+            .ok()
+            .and_then(|metadata| metadata.orientation)
+            .and_then(|orientation| image::metadata::Orientation::from_exif(orientation as u8));
+
+        if let Some(orientation) = orientation {
+            src_image.apply_orientation(orientation);
+        }
+
+        let _ = self.thumbnailer.generate_all_thumbnails(path, src_image)?;
+
+        Ok(())
+    }
+
     async fn thumbnail_internal(&self, path: &FlatpakPathBuf) -> Result<()> {
         let file = gio::File::for_path(&path.sandbox_path);
         let loader = glycin::Loader::new(file);
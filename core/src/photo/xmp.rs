@@ -0,0 +1,885 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! XMP metadata from sidecar files and from XMP packets embedded in pictures.
+//!
+//! XMP is RDF serialized as XML. Photo managers such as darktable and digiKam write
+//! simple properties as attributes of `rdf:Description` and structured properties,
+//! such as keyword bags, as child elements. Both forms are read.
+//!
+//! Only the small subset of XML used by XMP packets is supported, so this isn't
+//! a general purpose XML parser.
+
+use anyhow::*;
+use chrono::prelude::*;
+use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const NS_RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const NS_XML: &str = "http://www.w3.org/XML/1998/namespace";
+const NS_XMP: &str = "http://ns.adobe.com/xap/1.0/";
+const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
+const NS_EXIF: &str = "http://ns.adobe.com/exif/1.0/";
+const NS_PHOTOSHOP: &str = "http://ns.adobe.com/photoshop/1.0/";
+const NS_GCAMERA: &str = "http://ns.google.com/photos/1.0/camera/";
+const NS_CONTAINER: &str = "http://ns.google.com/photos/1.0/container/";
+const NS_ITEM: &str = "http://ns.google.com/photos/1.0/container/item/";
+
+/// How much of a picture to search for an embedded XMP packet.
+/// Packets are normally written near the start of a file.
+const EMBEDDED_SEARCH_LEN: u64 = 1024 * 1024;
+
+/// Metadata from an XMP packet.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Xmp {
+    /// Star rating from 1 to 5. Zero is unrated and -1 is rejected.
+    pub rating: Option<i32>,
+
+    /// Colour label, such as "Red".
+    pub label: Option<String>,
+
+    /// Keywords or tags.
+    pub keywords: Vec<String>,
+
+    pub title: Option<String>,
+
+    pub description: Option<String>,
+
+    /// When the photo was taken.
+    pub created_at: Option<XmpDateTime>,
+
+    /// When the photo was last modified.
+    pub modified_at: Option<XmpDateTime>,
+
+    /// Google motion photo details.
+    pub motion_photo: Option<GoogleMotionPhoto>,
+}
+
+/// XMP date and time. The time zone is optional, so a time without one is only
+/// the local wall-clock time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XmpDateTime {
+    pub local: NaiveDateTime,
+
+    pub offset: Option<FixedOffset>,
+}
+
+impl XmpDateTime {
+    fn new(local: NaiveDateTime, offset: Option<FixedOffset>) -> Self {
+        Self { local, offset }
+    }
+}
+
+/// Google motion photo details from the `GCamera` and `Container` namespaces.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GoogleMotionPhoto {
+    /// Media items in file order, with the primary image first. Written by
+    /// Motion Photo v2 (`GCamera:MotionPhoto`).
+    pub items: Vec<ContainerItem>,
+
+    /// Length of the video at the end of the file. Written by the older
+    /// MicroVideo format (`GCamera:MicroVideoOffset`).
+    pub micro_video_offset: Option<u64>,
+
+    /// Presentation timestamp of the video frame that matches the still image,
+    /// in microseconds. -1 means unspecified.
+    pub presentation_timestamp_us: Option<i64>,
+}
+
+/// Media item of a Google motion photo container.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ContainerItem {
+    /// Such as `image/jpeg` or `video/mp4`.
+    pub mime: Option<String>,
+
+    /// Such as `Primary` or `MotionPhoto`.
+    pub semantic: Option<String>,
+
+    /// Length in bytes. Zero or missing for the primary item.
+    pub length: u64,
+
+    /// Length in bytes of padding between this item and the next.
+    pub padding: u64,
+}
+
+impl Xmp {
+    /// Reads the XMP sidecar file for a picture, if one exists.
+    pub fn from_sidecar(picture_path: &Path) -> Result<Option<Xmp>> {
+        let Some(sidecar_path) = sidecar_path(picture_path) else {
+            return Ok(None);
+        };
+
+        let packet = fs::read(&sidecar_path)?;
+        let packet = String::from_utf8_lossy(&packet);
+        Self::parse(&packet).map(Some)
+    }
+
+    /// Reads an XMP packet embedded near the start of a picture, if one exists.
+    pub fn from_embedded(picture_path: &Path) -> Result<Option<Xmp>> {
+        let mut data = Vec::new();
+        fs::File::open(picture_path)?
+            .take(EMBEDDED_SEARCH_LEN)
+            .read_to_end(&mut data)?;
+
+        let packet = find_packet(&data, b"<x:xmpmeta", b"</x:xmpmeta>")
+            .or_else(|| find_packet(&data, b"<rdf:RDF", b"</rdf:RDF>"));
+
+        let Some(packet) = packet else {
+            return Ok(None);
+        };
+
+        let packet = String::from_utf8_lossy(packet);
+        Self::parse(&packet).map(Some)
+    }
+
+    /// Parses an XMP packet.
+    pub fn parse(packet: &str) -> Result<Xmp> {
+        let root = Parser::new(packet).parse()?;
+
+        let mut properties = Properties::default();
+        collect_properties(&root, &mut properties);
+
+        let rating = properties
+            .first(NS_XMP, "Rating")
+            .and_then(|x| x.trim().parse::<f64>().ok())
+            .map(|x| x.round() as i32);
+
+        let created_at = properties
+            .first(NS_EXIF, "DateTimeOriginal")
+            .or_else(|| properties.first(NS_PHOTOSHOP, "DateCreated"))
+            .or_else(|| properties.first(NS_XMP, "CreateDate"))
+            .and_then(parse_date_time);
+
+        let modified_at = properties
+            .first(NS_XMP, "ModifyDate")
+            .and_then(parse_date_time);
+
+        let motion_photo = google_motion_photo(&root, &properties);
+
+        Ok(Xmp {
+            rating,
+            label: properties.first(NS_XMP, "Label").map(String::from),
+            keywords: properties.all(NS_DC, "subject").to_vec(),
+            title: properties.first(NS_DC, "title").map(String::from),
+            description: properties.first(NS_DC, "description").map(String::from),
+            created_at,
+            modified_at,
+            motion_photo,
+        })
+    }
+}
+
+/// Reads Google Motion Photo v2 and MicroVideo properties.
+fn google_motion_photo(root: &Element, properties: &Properties) -> Option<GoogleMotionPhoto> {
+    let is_set = |name| properties.first(NS_GCAMERA, name).map(str::trim) == Some("1");
+    let number = |name| {
+        properties
+            .first(NS_GCAMERA, name)
+            .and_then(|x| x.trim().parse::<i64>().ok())
+    };
+
+    let motion_photo = if is_set("MotionPhoto") {
+        GoogleMotionPhoto {
+            items: container_items(root),
+            micro_video_offset: None,
+            presentation_timestamp_us: number("MotionPhotoPresentationTimestampUs"),
+        }
+    } else if is_set("MicroVideo") {
+        GoogleMotionPhoto {
+            items: Vec::new(),
+            micro_video_offset: number("MicroVideoOffset").and_then(|x| u64::try_from(x).ok()),
+            presentation_timestamp_us: number("MicroVideoPresentationTimestampUs"),
+        }
+    } else {
+        return None;
+    };
+
+    Some(motion_photo)
+}
+
+/// Items of the first `Container:Directory`, which is a sequence of `Container:Item`.
+fn container_items(element: &Element) -> Vec<ContainerItem> {
+    if element.name.is(NS_CONTAINER, "Directory") {
+        let seq = element.elements().find(|e| e.name.is(NS_RDF, "Seq"));
+        return seq
+            .into_iter()
+            .flat_map(|seq| seq.elements())
+            .filter(|e| e.name.is(NS_RDF, "li"))
+            .map(|li| {
+                // Item properties are either on a `Container:Item` element in the
+                // `rdf:li`, or on the `rdf:li` itself.
+                let item = li
+                    .elements()
+                    .find(|e| e.name.is(NS_CONTAINER, "Item"))
+                    .unwrap_or(li);
+
+                let value = |name| {
+                    item.attr(NS_ITEM, name).map(String::from).or_else(|| {
+                        item.elements()
+                            .find(|e| e.name.is(NS_ITEM, name))
+                            .map(|e| e.text())
+                    })
+                };
+                let number = |name| {
+                    value(name)
+                        .and_then(|x| x.trim().parse::<u64>().ok())
+                        .unwrap_or(0)
+                };
+
+                ContainerItem {
+                    mime: value("Mime"),
+                    semantic: value("Semantic"),
+                    length: number("Length"),
+                    padding: number("Padding"),
+                }
+            })
+            .collect();
+    }
+
+    element
+        .elements()
+        .map(container_items)
+        .find(|items| !items.is_empty())
+        .unwrap_or_default()
+}
+
+/// Finds the sidecar file for a picture.
+/// darktable and digiKam append `.xmp` to the full file name, such as `IMG_0001.CR3.xmp`,
+/// while other tools replace the suffix, such as `IMG_0001.xmp`.
+pub fn sidecar_path(picture_path: &Path) -> Option<PathBuf> {
+    let file_name = picture_path.file_name()?.to_str()?;
+
+    [
+        picture_path.with_file_name(format!("{}.xmp", file_name)),
+        picture_path.with_file_name(format!("{}.XMP", file_name)),
+        picture_path.with_extension("xmp"),
+        picture_path.with_extension("XMP"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+fn find_packet<'a>(data: &'a [u8], start: &[u8], end: &[u8]) -> Option<&'a [u8]> {
+    let from = data.windows(start.len()).position(|w| w == start)?;
+    let to = data[from..].windows(end.len()).position(|w| w == end)?;
+    Some(&data[from..from + to + end.len()])
+}
+
+/// Parses an XMP date, which is ISO 8601 with optional parts, or an EXIF date
+/// as written by some tools. Dates without a time zone have no offset.
+fn parse_date_time(value: &str) -> Option<XmpDateTime> {
+    let value = value.trim();
+
+    let with_offset = DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z"));
+
+    if let std::result::Result::Ok(date_time) = with_offset {
+        return Some(XmpDateTime::new(
+            date_time.naive_local(),
+            Some(*date_time.offset()),
+        ));
+    }
+
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y:%m:%d %H:%M:%S%.f",
+        "%Y:%m:%d %H:%M:%S",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })?;
+
+    Some(XmpDateTime::new(naive, None))
+}
+
+/// Values of XMP properties by namespace and name. Language alternatives
+/// have the default language first.
+#[derive(Debug, Default)]
+struct Properties(HashMap<(String, String), Vec<String>>);
+
+impl Properties {
+    fn all(&self, ns: &str, name: &str) -> &[String] {
+        self.0
+            .get(&(ns.into(), name.into()))
+            .map(|values| values.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn first(&self, ns: &str, name: &str) -> Option<&str> {
+        self.all(ns, name)
+            .first()
+            .map(|x| x.as_str())
+            .filter(|x| !x.trim().is_empty())
+    }
+
+    fn insert(&mut self, name: &Name, values: Vec<String>) {
+        self.0
+            .entry((name.ns.clone(), name.local.clone()))
+            .or_default()
+            .extend(values);
+    }
+}
+
+fn collect_properties(element: &Element, properties: &mut Properties) {
+    if element.name.is(NS_RDF, "Description") {
+        for (name, value) in &element.attrs {
+            if name.ns != NS_RDF && name.ns != NS_XML && !name.ns.is_empty() {
+                properties.insert(name, vec![value.clone()]);
+            }
+        }
+
+        for property in element.elements() {
+            properties.insert(&property.name, property_values(property));
+        }
+    }
+
+    for child in element.elements() {
+        collect_properties(child, properties);
+    }
+}
+
+/// Values of a property element, which is either simple text or an array
+/// of `rdf:li` items in an `rdf:Bag`, `rdf:Seq`, or `rdf:Alt`.
+fn property_values(property: &Element) -> Vec<String> {
+    let array = property
+        .elements()
+        .find(|e| e.name.is(NS_RDF, "Bag") || e.name.is(NS_RDF, "Seq") || e.name.is(NS_RDF, "Alt"));
+
+    let Some(array) = array else {
+        return vec![property.text().trim().to_string()];
+    };
+
+    let mut items: Vec<&Element> = array
+        .elements()
+        .filter(|e| e.name.is(NS_RDF, "li"))
+        .collect();
+
+    // Language alternatives should have the default first, but make sure.
+    items.sort_by_key(|item| item.attr(NS_XML, "lang") != Some("x-default"));
+
+    items
+        .into_iter()
+        .map(|item| item.text().trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// XML name resolved to a namespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Name {
+    ns: String,
+    local: String,
+}
+
+impl Name {
+    fn is(&self, ns: &str, local: &str) -> bool {
+        self.ns == ns && self.local == local
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: Name,
+    attrs: Vec<(Name, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+    }
+
+    fn attr(&self, ns: &str, local: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(name, _)| name.is(ns, local))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Element before its namespace prefixes are resolved.
+struct RawElement {
+    qname: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>,
+    namespaces: HashMap<String, String>,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    /// Parses the document, returning a synthetic root element containing the
+    /// top level elements.
+    fn parse(mut self) -> Result<Element> {
+        let mut stack = vec![RawElement {
+            qname: String::new(),
+            attrs: Vec::new(),
+            children: Vec::new(),
+            namespaces: HashMap::from([("xml".into(), NS_XML.into())]),
+        }];
+
+        while !self.rest().is_empty() {
+            let rest = self.rest();
+
+            if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += "<![CDATA[".len();
+                let end = self
+                    .rest()
+                    .find("]]>")
+                    .ok_or_else(|| anyhow!("Unclosed CDATA"))?;
+                let text = self.rest()[..end].to_string();
+                self.pos += end + "]]>".len();
+                stack.last_mut().unwrap().children.push(Node::Text(text));
+            } else if rest.starts_with("<!") {
+                self.skip_past(">")?;
+            } else if rest.starts_with("</") {
+                self.skip_past(">")?;
+                if stack.len() < 2 {
+                    bail!("Unexpected closing tag");
+                }
+                let raw = stack.pop().unwrap();
+                let element = Self::resolve(raw, &stack)?;
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .children
+                    .push(Node::Element(element));
+            } else if rest.starts_with('<') {
+                let (raw, is_empty) = self.start_tag()?;
+                stack.push(raw);
+                if is_empty {
+                    let raw = stack.pop().unwrap();
+                    let element = Self::resolve(raw, &stack)?;
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .children
+                        .push(Node::Element(element));
+                }
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                let text = decode_entities(&rest[..end]);
+                self.pos += end;
+                stack.last_mut().unwrap().children.push(Node::Text(text));
+            }
+        }
+
+        if stack.len() != 1 {
+            bail!("Unclosed element");
+        }
+
+        let root = stack.pop().unwrap();
+        Ok(Element {
+            name: Name {
+                ns: String::new(),
+                local: String::new(),
+            },
+            attrs: Vec::new(),
+            children: root.children,
+        })
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<()> {
+        let i = self
+            .rest()
+            .find(end)
+            .ok_or_else(|| anyhow!("Expected {:?}", end))?;
+        self.pos += i + end.len();
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        if end == 0 {
+            bail!("Expected name at {}", self.pos);
+        }
+        self.pos += end;
+        Ok(rest[..end].to_string())
+    }
+
+    /// Parses a start tag, returning the element and whether it is an empty element.
+    fn start_tag(&mut self) -> Result<(RawElement, bool)> {
+        self.pos += 1; // '<'
+        let qname = self.name()?;
+        let mut attrs = Vec::new();
+        let mut namespaces = HashMap::new();
+
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok((
+                    RawElement {
+                        qname,
+                        attrs,
+                        children: Vec::new(),
+                        namespaces,
+                    },
+                    true,
+                ));
+            } else if rest.starts_with('>') {
+                self.pos += 1;
+                return Ok((
+                    RawElement {
+                        qname,
+                        attrs,
+                        children: Vec::new(),
+                        namespaces,
+                    },
+                    false,
+                ));
+            } else if rest.is_empty() {
+                bail!("Unclosed start tag");
+            }
+
+            let name = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                bail!("Expected '=' after attribute {:?}", name);
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+
+            let quote = self
+                .rest()
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(|| anyhow!("Expected quoted value for attribute {:?}", name))?;
+            self.pos += 1;
+            let end = self
+                .rest()
+                .find(quote)
+                .ok_or_else(|| anyhow!("Unclosed attribute value"))?;
+            let value = decode_entities(&self.rest()[..end]);
+            self.pos += end + 1;
+
+            if name == "xmlns" {
+                namespaces.insert(String::new(), value);
+            } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+                namespaces.insert(prefix.to_string(), value);
+            } else {
+                attrs.push((name, value));
+            }
+        }
+    }
+
+    /// Resolves the namespace prefixes of an element using the declarations
+    /// on the element and its ancestors.
+    fn resolve(raw: RawElement, ancestors: &[RawElement]) -> Result<Element> {
+        let lookup = |prefix: &str| -> Option<String> {
+            raw.namespaces
+                .get(prefix)
+                .or_else(|| {
+                    ancestors
+                        .iter()
+                        .rev()
+                        .find_map(|ancestor| ancestor.namespaces.get(prefix))
+                })
+                .cloned()
+        };
+
+        let resolve_name = |qname: &str, is_attr: bool| -> Name {
+            match qname.split_once(':') {
+                Some((prefix, local)) => Name {
+                    ns: lookup(prefix).unwrap_or_default(),
+                    local: local.to_string(),
+                },
+                // Unprefixed attributes have no namespace.
+                None if is_attr => Name {
+                    ns: String::new(),
+                    local: qname.to_string(),
+                },
+                None => Name {
+                    ns: lookup("").unwrap_or_default(),
+                    local: qname.to_string(),
+                },
+            }
+        };
+
+        let name = resolve_name(&raw.qname, false);
+        let attrs = raw
+            .attrs
+            .iter()
+            .map(|(qname, value)| (resolve_name(qname, true), value.clone()))
+            .collect();
+
+        Ok(Element {
+            name,
+            attrs,
+            children: raw.children,
+        })
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let replacement = entity.and_then(|(entity, _)| match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        });
+
+        match (replacement, entity) {
+            (Some(c), Some((_, end))) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_time(year: i32, month: u32, day: u32, h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        // darktable style, with simple properties as attributes.
+        let packet = r#"<?xml version="1.0" encoding="UTF-8"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="XMP Core 4.4.0-Exiv2">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+   exif:DateTimeOriginal="2019:08:08 14:33:28.000"
+   xmp:Rating="4"
+   xmp:Label="Red">
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>holiday</rdf:li>
+     <rdf:li>fish &amp; chips</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        let xmp = Xmp::parse(packet).unwrap();
+
+        assert_eq!(Some(4), xmp.rating);
+        assert_eq!(Some("Red".to_string()), xmp.label);
+        assert_eq!(vec!["holiday", "fish & chips"], xmp.keywords);
+        assert_eq!(
+            Some(XmpDateTime::new(
+                date_time(2019, 8, 8, 14, 33, 28),
+                FixedOffset::east_opt(0)
+            )),
+            xmp.created_at
+        );
+    }
+
+    #[test]
+    fn test_parse_elements() {
+        // digiKam style, with properties as elements and a non-standard prefix.
+        let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:xap="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/">
+   <xap:Rating>-1</xap:Rating>
+   <xap:ModifyDate>2024-05-01T10:15:00+02:00</xap:ModifyDate>
+   <photoshop:DateCreated>2024-04-30T18:00</photoshop:DateCreated>
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="de-DE">Strand</rdf:li>
+     <rdf:li xml:lang="x-default">Beach</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <dc:description><rdf:Alt><rdf:li xml:lang="x-default"><![CDATA[Sunset <3]]></rdf:li></rdf:Alt></dc:description>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        let xmp = Xmp::parse(packet).unwrap();
+
+        assert_eq!(Some(-1), xmp.rating);
+        assert_eq!(Some("Beach".to_string()), xmp.title);
+        assert_eq!(Some("Sunset <3".to_string()), xmp.description);
+        assert!(xmp.keywords.is_empty());
+        assert_eq!(
+            Some(XmpDateTime::new(
+                date_time(2024, 5, 1, 10, 15, 0),
+                FixedOffset::east_opt(2 * 60 * 60)
+            )),
+            xmp.modified_at
+        );
+
+        // No time zone, so only the local time is known.
+        assert_eq!(
+            Some(XmpDateTime::new(date_time(2024, 4, 30, 18, 0, 0), None)),
+            xmp.created_at
+        );
+    }
+
+    #[test]
+    fn test_parse_google_motion_photo() {
+        let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:GCamera="http://ns.google.com/photos/1.0/camera/"
+    xmlns:Container="http://ns.google.com/photos/1.0/container/"
+    xmlns:Item="http://ns.google.com/photos/1.0/container/item/"
+   GCamera:MotionPhoto="1"
+   GCamera:MotionPhotoVersion="1"
+   GCamera:MotionPhotoPresentationTimestampUs="968644">
+   <Container:Directory>
+    <rdf:Seq>
+     <rdf:li rdf:parseType="Resource">
+      <Container:Item Item:Mime="image/jpeg" Item:Semantic="Primary" Item:Length="0" Item:Padding="0"/>
+     </rdf:li>
+     <rdf:li rdf:parseType="Resource">
+      <Container:Item Item:Mime="video/mp4" Item:Semantic="MotionPhoto" Item:Length="2548225"/>
+     </rdf:li>
+    </rdf:Seq>
+   </Container:Directory>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        let motion_photo = Xmp::parse(packet).unwrap().motion_photo.unwrap();
+
+        assert_eq!(Some(968644), motion_photo.presentation_timestamp_us);
+        assert_eq!(2, motion_photo.items.len());
+        assert_eq!(Some("Primary".into()), motion_photo.items[0].semantic);
+        assert_eq!(
+            ContainerItem {
+                mime: Some("video/mp4".into()),
+                semantic: Some("MotionPhoto".into()),
+                length: 2548225,
+                padding: 0,
+            },
+            motion_photo.items[1]
+        );
+    }
+
+    #[test]
+    fn test_parse_google_micro_video() {
+        let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:GCamera="http://ns.google.com/photos/1.0/camera/"
+   GCamera:MicroVideo="1"
+   GCamera:MicroVideoVersion="1"
+   GCamera:MicroVideoOffset="1862573"
+   GCamera:MicroVideoPresentationTimestampUs="1019099"/>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        let motion_photo = Xmp::parse(packet).unwrap().motion_photo.unwrap();
+
+        assert_eq!(Some(1862573), motion_photo.micro_video_offset);
+        assert_eq!(Some(1019099), motion_photo.presentation_timestamp_us);
+        assert!(motion_photo.items.is_empty());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Xmp::parse("<x:xmpmeta><rdf:RDF>").is_err());
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = dir.path().join("IMG_0001.CR3");
+        let jpeg = dir.path().join("IMG_0001.JPG");
+        let other = dir.path().join("IMG_0002.JPG");
+
+        std::fs::write(dir.path().join("IMG_0001.CR3.xmp"), "").unwrap();
+        std::fs::write(dir.path().join("IMG_0001.xmp"), "").unwrap();
+
+        assert_eq!(
+            Some(dir.path().join("IMG_0001.CR3.xmp")),
+            sidecar_path(&raw)
+        );
+        assert_eq!(Some(dir.path().join("IMG_0001.xmp")), sidecar_path(&jpeg));
+        assert_eq!(None, sidecar_path(&other));
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::FlatpakPathBuf;
+
+use glob::{MatchOptions, Pattern};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Files that mark a directory, and everything beneath it, as not part of the library.
+/// `.nomedia` is the Android convention and keeps out app caches and sticker packs.
+pub const MARKER_FILES: [&str; 2] = [".nomedia", ".nophotos"];
+
+/// Rules for keeping directories and files out of the library.
+#[derive(Debug, Clone, Default)]
+pub struct ExcludeRules {
+    /// Glob patterns matched against paths relative to a library root,
+    /// and against file and directory names.
+    patterns: Vec<Pattern>,
+
+    /// Host paths of directories to exclude, along with everything beneath them.
+    dirs: Vec<PathBuf>,
+}
+
+impl ExcludeRules {
+    /// Builds rules from glob patterns and directories.
+    /// Invalid patterns are logged and ignored.
+    pub fn build(patterns: &[String], dirs: &[FlatpakPathBuf]) -> Self {
+        let patterns = patterns
+            .iter()
+            .filter_map(|pattern| {
+                Pattern::new(pattern)
+                    .inspect_err(|e| warn!("Ignoring invalid exclude pattern {:?}: {}", pattern, e))
+                    .ok()
+            })
+            .collect();
+
+        let dirs = dirs.iter().map(|dir| dir.host_path.clone()).collect();
+
+        Self { patterns, dirs }
+    }
+
+    /// Is the pattern a valid glob pattern?
+    pub fn is_valid_pattern(pattern: &str) -> bool {
+        Pattern::new(pattern).is_ok()
+    }
+
+    /// Is the file name one of the marker files that excludes a directory?
+    pub fn is_marker(file_name: &OsStr) -> bool {
+        MARKER_FILES.iter().any(|marker| file_name == *marker)
+    }
+
+    /// Does the directory contain a marker file?
+    pub fn has_marker(dir: &Path) -> bool {
+        MARKER_FILES.iter().any(|marker| dir.join(marker).exists())
+    }
+
+    /// Is the path excluded by a rule?
+    /// The `host_path` is checked against excluded directories and the `relative_path`,
+    /// which is relative to the library root, is checked against the glob patterns.
+    pub fn is_excluded(&self, host_path: &Path, relative_path: &Path) -> bool {
+        if self.dirs.iter().any(|dir| host_path.starts_with(dir)) {
+            return true;
+        }
+
+        if relative_path.as_os_str().is_empty() {
+            return false;
+        }
+
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        let file_name = relative_path.file_name().and_then(|name| name.to_str());
+
+        self.patterns.iter().any(|pattern| {
+            pattern.matches_path_with(relative_path, options)
+                || file_name.is_some_and(|name| pattern.matches_with(name, options))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_excluded() {
+        let rules = ExcludeRules::build(
+            &[
+                "Android/data".into(),
+                "WhatsApp Stickers".into(),
+                "*.tmp.jpg".into(),
+                "[invalid".into(),
+            ],
+            &[FlatpakPathBuf::build("/host/pics/target", "/doc/1234")],
+        );
+
+        let is_excluded = |relative_path: &str| {
+            let host_path = Path::new("/host/pics").join(relative_path);
+            rules.is_excluded(&host_path, Path::new(relative_path))
+        };
+
+        assert!(is_excluded("Android/data"));
+        assert!(!is_excluded("Android"));
+        assert!(is_excluded("WhatsApp/Media/WhatsApp Stickers"));
+        assert!(is_excluded("Camera/IMG_001.tmp.jpg"));
+        assert!(!is_excluded("Camera/IMG_001.jpg"));
+        assert!(is_excluded("target/debug"));
+        assert!(!is_excluded("targets"));
+        assert!(!is_excluded(""));
+    }
+
+    #[test]
+    fn test_is_marker() {
+        assert!(ExcludeRules::is_marker(OsStr::new(".nomedia")));
+        assert!(ExcludeRules::is_marker(OsStr::new(".nophotos")));
+        assert!(!ExcludeRules::is_marker(OsStr::new(".hidden")));
+    }
+}
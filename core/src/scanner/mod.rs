@@ -2,8 +2,18 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod exclude;
 pub mod model;
+pub mod repo;
 pub mod scanner;
+pub mod watcher;
 
+pub use exclude::ExcludeRules;
+pub use model::FileStat;
+pub use model::Fingerprint;
+pub use model::ScanDelta;
+pub use model::ScanState;
 pub use model::ScannedFile;
+pub use repo::Repository;
 pub use scanner::Scanner;
+pub use watcher::Watcher;
@@ -2,10 +2,176 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::path::PathBuf;
+use crate::file_types::FileType;
+
+use anyhow::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Number of bytes hashed from each of the start and end of a file.
+const PARTIAL_HASH_LEN: u64 = 16 * 1024;
 
 #[derive(Debug, Clone)]
 pub enum ScannedFile {
-    Photo(PathBuf),
-    Video(PathBuf),
+    Photo(PathBuf, FileType),
+    Video(PathBuf, FileType),
+}
+
+impl ScannedFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            ScannedFile::Photo(path, _) => path,
+            ScannedFile::Video(path, _) => path,
+        }
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match self {
+            ScannedFile::Photo(_, file_type) => *file_type,
+            ScannedFile::Video(_, file_type) => *file_type,
+        }
+    }
+}
+
+/// Cheap identity of a file's content, used to recognise a file that has been moved
+/// or renamed. Made of the file size and a hash of the start and end of the file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    /// Size in bytes
+    pub size: u64,
+
+    /// MD5 hash of the start and end of the file.
+    pub partial_hash: String,
+}
+
+impl Fingerprint {
+    pub fn new(size: u64, partial_hash: String) -> Self {
+        Self { size, partial_hash }
+    }
+
+    /// Computes the fingerprint of a file of `size` bytes.
+    pub fn compute(path: &Path, size: u64) -> Result<Self> {
+        let mut file = fs::File::open(path)?;
+        let mut buf = Vec::with_capacity(2 * PARTIAL_HASH_LEN as usize);
+
+        file.by_ref().take(PARTIAL_HASH_LEN).read_to_end(&mut buf)?;
+
+        if size > 2 * PARTIAL_HASH_LEN {
+            file.seek(SeekFrom::End(-(PARTIAL_HASH_LEN as i64)))?;
+            file.take(PARTIAL_HASH_LEN).read_to_end(&mut buf)?;
+        } else if size > PARTIAL_HASH_LEN {
+            file.read_to_end(&mut buf)?;
+        }
+
+        let partial_hash = format!("{:x}", md5::compute(&buf));
+        Ok(Self::new(size, partial_hash))
+    }
+}
+
+/// Size and modification time of a file or directory.
+/// Used to decide if a file or directory has changed since the last scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    /// Size in bytes
+    pub size: u64,
+
+    /// Modification time in nanoseconds since the UNIX epoch.
+    pub modified_ns: i64,
+}
+
+impl FileStat {
+    /// Placeholder recorded for directories that contain excluded entries.
+    /// It never matches a real directory, so such directories are listed on every scan
+    /// and entries that stop being excluded are found.
+    pub const RELIST: FileStat = FileStat {
+        size: 0,
+        modified_ns: i64::MIN,
+    };
+
+    pub fn new(size: u64, modified_ns: i64) -> Self {
+        Self { size, modified_ns }
+    }
+
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        let modified_ns = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_default();
+
+        Self {
+            size: metadata.len(),
+            modified_ns,
+        }
+    }
+}
+
+/// Directories and files recorded by a previous scan.
+/// All paths are absolute paths inside the sandbox.
+#[derive(Debug, Clone, Default)]
+pub struct ScanState {
+    pub dirs: HashMap<PathBuf, FileStat>,
+    pub files: HashMap<PathBuf, FileStat>,
+
+    /// Fingerprints of files. Files recorded before fingerprints existed don't have one.
+    pub fingerprints: HashMap<PathBuf, Fingerprint>,
+}
+
+/// Differences between the file system and a previous scan.
+#[derive(Debug, Clone, Default)]
+pub struct ScanDelta {
+    /// Pictures and videos that weren't present in the previous scan.
+    pub added: Vec<(ScannedFile, FileStat)>,
+
+    /// Pictures and videos whose size or modification time has changed.
+    pub changed: Vec<(ScannedFile, FileStat)>,
+
+    /// Pictures and videos that have vanished from the file system.
+    pub removed: Vec<PathBuf>,
+
+    /// Pictures and videos that have moved, as the old path and the new file.
+    pub moved: Vec<(PathBuf, ScannedFile, FileStat)>,
+
+    /// Fingerprints of added, changed, and moved pictures and videos.
+    pub fingerprints: HashMap<PathBuf, Fingerprint>,
+
+    /// Directories that were listed because they are new or have changed.
+    pub dirs: Vec<(PathBuf, FileStat)>,
+
+    /// Directories that have vanished from the file system.
+    pub removed_dirs: Vec<PathBuf>,
+
+    /// Count of pictures and videos that are unchanged.
+    pub unchanged: usize,
+}
+
+impl ScanDelta {
+    /// Count of added, changed, removed, and moved files.
+    pub fn len(&self) -> usize {
+        self.added.len() + self.changed.len() + self.removed.len() + self.moved.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn added_files(&self) -> Vec<ScannedFile> {
+        self.added.iter().map(|(f, _)| f.clone()).collect()
+    }
+
+    pub fn changed_files(&self) -> Vec<ScannedFile> {
+        self.changed.iter().map(|(f, _)| f.clone()).collect()
+    }
+
+    /// Moved files as old path and new file.
+    pub fn moved_files(&self) -> Vec<(PathBuf, ScannedFile)> {
+        self.moved
+            .iter()
+            .map(|(from, to, _)| (from.clone(), to.clone()))
+            .collect()
+    }
 }
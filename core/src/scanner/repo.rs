@@ -0,0 +1,204 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::model::{FileStat, Fingerprint, ScanDelta, ScanState};
+use crate::library_root::{LibraryRootId, LibraryRoots};
+use crate::path_encoding;
+
+use anyhow::*;
+use rusqlite;
+use rusqlite::params;
+use std::sync::{Arc, Mutex};
+
+/// Repository of directories and files seen by the library scanner.
+/// Repository is backed by a Sqlite database.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    /// Library root directories that scanned paths are relative to
+    library_roots: LibraryRoots,
+
+    /// Connection to backing Sqlite database.
+    con: Arc<Mutex<rusqlite::Connection>>,
+}
+
+impl Repository {
+    pub fn open(
+        library_roots: &LibraryRoots,
+        con: Arc<Mutex<rusqlite::Connection>>,
+    ) -> Result<Self> {
+        Ok(Repository {
+            library_roots: library_roots.clone(),
+            con,
+        })
+    }
+
+    /// Loads the directories and files recorded by the previous scan.
+    pub fn load(&self) -> Result<ScanState> {
+        let con = self.con.lock().unwrap();
+
+        let mut state = ScanState::default();
+
+        let mut stmt = con.prepare(
+            "SELECT
+                root_id,
+                dir_path_b64,
+                fs_size,
+                fs_modified_ns
+            FROM scanned_dirs",
+        )?;
+
+        let dirs = stmt
+            .query_map([], |row| {
+                let root_id = row.get("root_id").map(LibraryRootId::new)?;
+                let relative_path: String = row.get("dir_path_b64")?;
+                let stat = FileStat::new(row.get("fs_size")?, row.get("fs_modified_ns")?);
+                std::result::Result::Ok((root_id, relative_path, stat))
+            })?
+            .flatten();
+
+        for (root_id, relative_path, stat) in dirs {
+            if let Some(path) = self.library_roots.resolve_base64(root_id, &relative_path)? {
+                state.dirs.insert(path.sandbox_path, stat);
+            }
+        }
+
+        let mut stmt = con.prepare(
+            "SELECT
+                root_id,
+                file_path_b64,
+                fs_size,
+                fs_modified_ns,
+                partial_hash
+            FROM scanned_files",
+        )?;
+
+        let files = stmt
+            .query_map([], |row| {
+                let root_id = row.get("root_id").map(LibraryRootId::new)?;
+                let relative_path: String = row.get("file_path_b64")?;
+                let stat = FileStat::new(row.get("fs_size")?, row.get("fs_modified_ns")?);
+                let partial_hash: Option<String> = row.get("partial_hash")?;
+                std::result::Result::Ok((root_id, relative_path, stat, partial_hash))
+            })?
+            .flatten();
+
+        for (root_id, relative_path, stat, partial_hash) in files {
+            if let Some(path) = self.library_roots.resolve_base64(root_id, &relative_path)? {
+                let path = path.sandbox_path;
+                if let Some(partial_hash) = partial_hash {
+                    let fingerprint = Fingerprint::new(stat.size, partial_hash);
+                    state.fingerprints.insert(path.clone(), fingerprint);
+                }
+                state.files.insert(path, stat);
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Records the results of a scan so the next scan can skip unchanged directories.
+    pub fn apply(&mut self, delta: &ScanDelta) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut upsert_dir = tx.prepare_cached(
+                "INSERT INTO scanned_dirs (
+                    root_id,
+                    dir_path_b64,
+                    dir_path_lossy,
+                    fs_size,
+                    fs_modified_ns
+                ) VALUES (
+                    ?1, ?2, ?3, ?4, ?5
+                ) ON CONFLICT (root_id, dir_path_b64) DO UPDATE SET
+                    fs_size = ?4,
+                    fs_modified_ns = ?5",
+            )?;
+
+            let mut delete_dir = tx.prepare_cached(
+                "DELETE FROM scanned_dirs WHERE root_id = ?1 AND dir_path_b64 = ?2",
+            )?;
+
+            let mut upsert_file = tx.prepare_cached(
+                "INSERT INTO scanned_files (
+                    root_id,
+                    file_path_b64,
+                    file_path_lossy,
+                    fs_size,
+                    fs_modified_ns,
+                    partial_hash
+                ) VALUES (
+                    ?1, ?2, ?3, ?4, ?5, ?6
+                ) ON CONFLICT (root_id, file_path_b64) DO UPDATE SET
+                    fs_size = ?4,
+                    fs_modified_ns = ?5,
+                    partial_hash = ?6",
+            )?;
+
+            let mut delete_file = tx.prepare_cached(
+                "DELETE FROM scanned_files WHERE root_id = ?1 AND file_path_b64 = ?2",
+            )?;
+
+            for (path, stat) in &delta.dirs {
+                let (root_id, relative_path) = self.library_roots.to_relative_path(path)?;
+                upsert_dir.execute(params![
+                    root_id.id(),
+                    path_encoding::to_base64(relative_path),
+                    relative_path.to_string_lossy(),
+                    stat.size,
+                    stat.modified_ns,
+                ])?;
+            }
+
+            for path in &delta.removed_dirs {
+                let (root_id, relative_path) = self.library_roots.to_relative_path(path)?;
+                delete_dir.execute(params![
+                    root_id.id(),
+                    path_encoding::to_base64(relative_path)
+                ])?;
+            }
+
+            let added = delta
+                .added
+                .iter()
+                .chain(delta.changed.iter())
+                .map(|(scanned_file, stat)| (scanned_file, stat))
+                .chain(delta.moved.iter().map(|(_, to, stat)| (to, stat)));
+
+            for (scanned_file, stat) in added {
+                let path = scanned_file.path();
+                let (root_id, relative_path) = self.library_roots.to_relative_path(path)?;
+                let partial_hash = delta
+                    .fingerprints
+                    .get(path)
+                    .map(|fingerprint| fingerprint.partial_hash.as_str());
+                upsert_file.execute(params![
+                    root_id.id(),
+                    path_encoding::to_base64(relative_path),
+                    relative_path.to_string_lossy(),
+                    stat.size,
+                    stat.modified_ns,
+                    partial_hash,
+                ])?;
+            }
+
+            let removed = delta
+                .removed
+                .iter()
+                .chain(delta.moved.iter().map(|(from, _, _)| from));
+
+            for path in removed {
+                let (root_id, relative_path) = self.library_roots.to_relative_path(path)?;
+                delete_file.execute(params![
+                    root_id.id(),
+                    path_encoding::to_base64(relative_path)
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+}
@@ -2,38 +2,60 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use super::ExcludeRules;
 use super::ScannedFile;
-use crate::file_types;
+use super::model::{FileStat, Fingerprint, ScanDelta, ScanState};
+use crate::file_types::FileType;
+use crate::library_root::LibraryRoots;
+use crate::photo::xmp;
 
 use anyhow::*;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs;
+use std::io;
 use std::path::Path;
 use std::path::PathBuf;
-use tracing::error;
+use tracing::{error, warn};
 use walkdir::{DirEntry, WalkDir};
 
 /// Scans a file system for pictures.
 #[derive(Debug, Clone)]
 pub struct Scanner {
-    /// File system path to scan.
-    scan_base: PathBuf,
+    /// Library roots to scan.
+    library_roots: LibraryRoots,
+
+    /// File system paths to scan. One for each library root.
+    scan_bases: Vec<PathBuf>,
+
+    /// Rules for directories and files to keep out of the library.
+    exclude_rules: ExcludeRules,
 }
 
 impl Scanner {
-    pub fn build(scan_base: &Path) -> Result<Self> {
-        fs::create_dir_all(scan_base)?;
-        let scan_base = PathBuf::from(scan_base);
-        Ok(Self { scan_base })
+    pub fn build(library_roots: &LibraryRoots, exclude_rules: ExcludeRules) -> Result<Self> {
+        let scan_bases = library_roots.sandbox_paths();
+        Ok(Self {
+            library_roots: library_roots.clone(),
+            scan_bases,
+            exclude_rules,
+        })
     }
 
-    /// Scans all pictures in the base directory for function `func` to visit.
+    /// Scans all pictures in the base directories for function `func` to visit.
     pub fn scan_all_visit<F>(&self, func: F)
     where
         F: FnMut(ScannedFile),
     {
-        WalkDir::new(&self.scan_base)
-            .into_iter()
-            .filter_entry(|e| !Scanner::is_hidden(e))
+        self.scan_bases
+            .iter()
+            .flat_map(|scan_base| {
+                WalkDir::new(scan_base).into_iter().filter_entry(|e| {
+                    !Scanner::is_hidden(e.file_name())
+                        && !self.is_excluded_entry(e.path(), e.file_type().is_dir())
+                })
+            })
             .inspect(Self::inspect_err)
             .filter_map(|e| e.ok()) // skip files we failed to read
             .filter(|x| x.path().is_file()) // only process files
@@ -49,26 +71,54 @@ impl Scanner {
     }
 
     fn to_scanned_file(entry: DirEntry) -> Result<ScannedFile> {
+        Self::classify(entry.path())
+    }
+
+    fn classify(path: &Path) -> Result<ScannedFile> {
         // only process supported image types
-        let path = entry.path();
-        let scanned_file = if file_types::is_supported_picture(path) {
-            Ok(ScannedFile::Photo(path.into()))
-        } else if file_types::is_supported_video(path) {
-            Ok(ScannedFile::Video(path.into()))
-        } else {
-            Err(anyhow!("Not a picture or video: {:?}", path))
-        };
-        scanned_file
+        match FileType::detect(path) {
+            Some(file_type) if file_type.is_picture() => {
+                Ok(ScannedFile::Photo(path.into(), file_type))
+            }
+            Some(file_type) => Ok(ScannedFile::Video(path.into(), file_type)),
+            None => Err(anyhow!("Not a picture or video: {:?}", path)),
+        }
     }
 
-    fn is_hidden(entry: &DirEntry) -> bool {
-        entry
-            .file_name()
+    pub(crate) fn is_hidden(file_name: &OsStr) -> bool {
+        file_name
             .to_str()
             .map(|s| s.starts_with("."))
             .unwrap_or(false)
     }
 
+    /// Is a directory or file excluded by the exclude rules or, for a directory, by
+    /// a marker file? Only the entry itself is checked, not its parent directories.
+    fn is_excluded_entry(&self, path: &Path, is_dir: bool) -> bool {
+        let Some((root, relative_path)) = self.library_roots.relativize(path) else {
+            return false;
+        };
+
+        let host_path = root.path.host_path.join(relative_path);
+
+        self.exclude_rules.is_excluded(&host_path, relative_path)
+            || (is_dir && ExcludeRules::has_marker(path))
+    }
+
+    /// Is a picture or video excluded from the library, either by one of its parent
+    /// directories being excluded or hidden, or by the file itself being excluded?
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let Some((root, relative_path)) = self.library_roots.relativize(path) else {
+            return false;
+        };
+
+        relative_path.ancestors().any(|ancestor| {
+            let is_hidden = ancestor.file_name().is_some_and(Self::is_hidden);
+            let is_dir = ancestor != relative_path;
+            is_hidden || self.is_excluded_entry(&root.path.sandbox_path.join(ancestor), is_dir)
+        })
+    }
+
     pub fn scan_all(&self) -> Result<Vec<ScannedFile>> {
         // Count of files in scan_base.
         // Note: no filtering here, so count could be greater than number of pictures.
@@ -77,4 +127,557 @@ impl Scanner {
         self.scan_all_visit(|pic| pics.push(pic));
         Ok(pics)
     }
+
+    /// Scans the base directories for pictures and videos that have been added, changed,
+    /// or removed since the `previous` scan.
+    ///
+    /// A directory is only listed if its modification time has changed or it is one of
+    /// the `dirty_dirs`. Otherwise only the files recorded by the previous scan are
+    /// checked and only the known subdirectories are visited. Editing a file in place
+    /// doesn't change the modification time of its parent directory, so known files
+    /// are always checked.
+    ///
+    /// A base directory that is missing, such as one on a disk that isn't mounted,
+    /// is skipped and its files are not treated as removed. Likewise for a directory
+    /// that can't be read.
+    ///
+    /// Excluded directories and files are not listed, so any that were previously
+    /// scanned are treated as removed, including known files in a directory that
+    /// hasn't changed.
+    ///
+    /// A removed file and an added file with the same fingerprint are treated as a move.
+    ///
+    /// XMP sidecars aren't scanned themselves, but a picture is changed when its sidecar changes.
+    pub fn scan_changes(
+        &self,
+        previous: &ScanState,
+        dirty_dirs: &HashSet<PathBuf>,
+    ) -> Result<ScanDelta> {
+        let mut known_subdirs: HashMap<&Path, Vec<&Path>> = HashMap::new();
+        for dir in previous.dirs.keys() {
+            if let Some(parent) = dir.parent() {
+                known_subdirs.entry(parent).or_default().push(dir);
+            }
+        }
+
+        let mut known_files: HashMap<&Path, Vec<&Path>> = HashMap::new();
+        for file in previous.files.keys() {
+            if let Some(parent) = file.parent() {
+                known_files.entry(parent).or_default().push(file);
+            }
+        }
+
+        let mut delta = ScanDelta::default();
+        let mut seen_dirs: HashSet<PathBuf> = HashSet::new();
+        let mut seen_files: HashSet<PathBuf> = HashSet::new();
+
+        let (mut pending_dirs, missing_bases): (Vec<PathBuf>, Vec<PathBuf>) = self
+            .scan_bases
+            .iter()
+            .cloned()
+            .partition(|base| base.is_dir());
+
+        for base in &missing_bases {
+            warn!("Skipping missing library directory {:?}", base);
+        }
+
+        // Directories that exist but couldn't be read. Their files are kept as they were.
+        let mut unreadable_dirs: Vec<PathBuf> = Vec::new();
+
+        while let Some(dir) = pending_dirs.pop() {
+            // Directories are checked before being listed, so a known directory is only
+            // excluded here if it wasn't found by listing its parent. The parent must be
+            // listed on the next scan in case the directory stops being excluded.
+            if self.is_excluded_entry(&dir, true) {
+                if let Some(parent) = dir.parent().filter(|p| previous.dirs.contains_key(*p)) {
+                    delta.dirs.push((parent.into(), FileStat::RELIST));
+                }
+                continue;
+            }
+
+            let dir_stat = match fs::metadata(&dir) {
+                Ok(metadata) => FileStat::from_metadata(&metadata),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    error!("Failed reading metadata for {:?}: {}", dir, e);
+                    unreadable_dirs.push(dir);
+                    continue;
+                }
+            };
+
+            if !dirty_dirs.contains(&dir) && previous.dirs.get(&dir) == Some(&dir_stat) {
+                if let Some(subdirs) = known_subdirs.get(dir.as_path()) {
+                    pending_dirs.extend(subdirs.iter().map(PathBuf::from));
+                }
+                // Exclude rules may have changed since the previous scan, so a known
+                // file that is now excluded is treated as removed. The directory must be
+                // listed on the next scan in case the file stops being excluded.
+                if let Some(files) = known_files.get(dir.as_path()) {
+                    let mut has_excluded = false;
+                    for file in files {
+                        if self.is_excluded_entry(file, false) {
+                            has_excluded = true;
+                            continue;
+                        }
+                        Self::check_known_file(file, previous, &mut delta, &mut seen_files);
+                    }
+                    if has_excluded {
+                        delta.dirs.push((dir.clone(), FileStat::RELIST));
+                    }
+                }
+                seen_dirs.insert(dir);
+                continue;
+            }
+
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    error!("Failed listing {:?}: {}", dir, e);
+                    unreadable_dirs.push(dir);
+                    continue;
+                }
+            };
+
+            let mut has_excluded = false;
+
+            for entry in entries.flatten() {
+                if Self::is_hidden(&entry.file_name()) {
+                    continue;
+                }
+
+                let path = entry.path();
+                let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+
+                if self.is_excluded_entry(&path, is_dir) {
+                    has_excluded = true;
+                    continue;
+                }
+
+                // Don't follow symlinks to directories, but do follow symlinks to files.
+                if is_dir {
+                    pending_dirs.push(path);
+                    continue;
+                }
+
+                let Ok(metadata) = fs::metadata(&path) else {
+                    continue;
+                };
+
+                if !metadata.is_file() {
+                    continue;
+                }
+
+                let Ok(scanned_file) = Self::classify(&path) else {
+                    continue;
+                };
+
+                let stat = Self::file_stat(&path, &metadata);
+                match previous.files.get(&path) {
+                    None => delta.added.push((scanned_file, stat)),
+                    Some(previous_stat) if *previous_stat != stat => {
+                        delta.changed.push((scanned_file, stat))
+                    }
+                    Some(_) => delta.unchanged += 1,
+                }
+
+                seen_files.insert(path);
+            }
+
+            if has_excluded {
+                delta.dirs.push((dir.clone(), FileStat::RELIST));
+            } else {
+                delta.dirs.push((dir.clone(), dir_stat));
+            }
+            seen_dirs.insert(dir);
+        }
+
+        let is_unavailable = |path: &Path| {
+            missing_bases
+                .iter()
+                .chain(unreadable_dirs.iter())
+                .any(|base| path.starts_with(base))
+        };
+
+        delta.removed = previous
+            .files
+            .keys()
+            .filter(|path| !seen_files.contains(*path) && !is_unavailable(path))
+            .cloned()
+            .collect();
+
+        delta.removed_dirs = previous
+            .dirs
+            .keys()
+            .filter(|path| !seen_dirs.contains(*path) && !is_unavailable(path))
+            .cloned()
+            .collect();
+
+        Self::fingerprint(&mut delta);
+        Self::detect_moves(previous, &mut delta);
+
+        Ok(delta)
+    }
+
+    /// Checks a file recorded by the previous scan in a directory that hasn't been listed.
+    /// A file that can't be read, but might still exist, is kept as it was.
+    fn check_known_file(
+        path: &Path,
+        previous: &ScanState,
+        delta: &mut ScanDelta,
+        seen_files: &mut HashSet<PathBuf>,
+    ) {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => return,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                error!("Failed reading metadata for {:?}: {}", path, e);
+                delta.unchanged += 1;
+                seen_files.insert(path.into());
+                return;
+            }
+        };
+
+        let stat = Self::file_stat(path, &metadata);
+        if previous.files.get(path) == Some(&stat) {
+            delta.unchanged += 1;
+        } else if let Ok(scanned_file) = Self::classify(path) {
+            delta.changed.push((scanned_file, stat));
+        } else {
+            return;
+        }
+
+        seen_files.insert(path.into());
+    }
+
+    /// Stat of a file, including its XMP sidecar if it has one, so that editing, adding,
+    /// or removing a sidecar marks the picture as changed and its metadata is read again.
+    /// A file with a sidecar is recorded as modified one nanosecond after the later of the
+    /// two modification times, so adding a sidecar older than the file is also noticed.
+    fn file_stat(path: &Path, metadata: &fs::Metadata) -> FileStat {
+        let stat = FileStat::from_metadata(metadata);
+
+        let sidecar_stat = xmp::sidecar_path(path)
+            .and_then(|sidecar_path| fs::metadata(sidecar_path).ok())
+            .map(|metadata| FileStat::from_metadata(&metadata));
+
+        match sidecar_stat {
+            Some(sidecar_stat) => FileStat::new(
+                stat.size,
+                stat.modified_ns.max(sidecar_stat.modified_ns) + 1,
+            ),
+            None => stat,
+        }
+    }
+
+    /// Computes fingerprints for added and changed files.
+    fn fingerprint(delta: &mut ScanDelta) {
+        delta.fingerprints = delta
+            .added
+            .par_iter()
+            .chain(delta.changed.par_iter())
+            .filter_map(|(scanned_file, stat)| {
+                let path = scanned_file.path();
+                Fingerprint::compute(path, stat.size)
+                    .inspect_err(|e| error!("Failed fingerprinting {:?}: {}", path, e))
+                    .ok()
+                    .map(|fingerprint| (path.to_path_buf(), fingerprint))
+            })
+            .collect();
+    }
+
+    /// Pairs removed files with added files that have the same fingerprint, so that
+    /// a file that has been moved or renamed keeps its identity.
+    /// Files recorded without a fingerprint can't be paired.
+    fn detect_moves(previous: &ScanState, delta: &mut ScanDelta) {
+        let mut candidates: HashMap<&Fingerprint, Vec<PathBuf>> = HashMap::new();
+        for path in &delta.removed {
+            if let Some(fingerprint) = previous.fingerprints.get(path) {
+                candidates
+                    .entry(fingerprint)
+                    .or_default()
+                    .push(path.clone());
+            }
+        }
+
+        if candidates.is_empty() {
+            return;
+        }
+
+        let mut moved_from = HashSet::new();
+
+        for (scanned_file, stat) in std::mem::take(&mut delta.added) {
+            let from = delta
+                .fingerprints
+                .get(scanned_file.path())
+                .and_then(|fingerprint| candidates.get_mut(fingerprint))
+                .and_then(|paths| paths.pop());
+
+            if let Some(from) = from {
+                moved_from.insert(from.clone());
+                delta.moved.push((from, scanned_file, stat));
+            } else {
+                delta.added.push((scanned_file, stat));
+            }
+        }
+
+        delta.removed.retain(|path| !moved_from.contains(path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FlatpakPathBuf;
+    use crate::library_root::{LibraryRoot, LibraryRootId};
+
+    fn build_scanner(base: &Path, exclude_rules: ExcludeRules) -> Scanner {
+        let roots = LibraryRoots::new(vec![LibraryRoot {
+            root_id: LibraryRootId::new(1),
+            path: FlatpakPathBuf::build(base, base),
+        }]);
+        Scanner::build(&roots, exclude_rules).unwrap()
+    }
+
+    fn state_from(delta: &ScanDelta) -> ScanState {
+        let mut state = ScanState::default();
+        for (dir, stat) in &delta.dirs {
+            state.dirs.insert(dir.clone(), *stat);
+        }
+        for (file, stat) in delta.added.iter().chain(delta.changed.iter()) {
+            state.files.insert(file.path().into(), *stat);
+        }
+        for (_, file, stat) in &delta.moved {
+            state.files.insert(file.path().into(), *stat);
+        }
+        state.fingerprints = delta.fingerprints.clone();
+        state
+    }
+
+    fn apply(state: &mut ScanState, delta: &ScanDelta) {
+        for path in delta
+            .removed
+            .iter()
+            .chain(delta.moved.iter().map(|(from, _, _)| from))
+        {
+            state.files.remove(path);
+            state.fingerprints.remove(path);
+        }
+        for path in &delta.removed_dirs {
+            state.dirs.remove(path);
+        }
+        let next = state_from(delta);
+        state.dirs.extend(next.dirs);
+        state.files.extend(next.files);
+        state.fingerprints.extend(next.fingerprints);
+    }
+
+    #[test]
+    fn test_scan_changes() {
+        let base = tempfile::tempdir().unwrap();
+        let album = base.path().join("album");
+        fs::create_dir_all(&album).unwrap();
+        fs::write(album.join("one.jpg"), b"one").unwrap();
+        fs::write(album.join("two.mp4"), b"two").unwrap();
+        fs::write(album.join("notes.txt"), b"not a picture").unwrap();
+
+        let scanner = build_scanner(base.path(), ExcludeRules::default());
+
+        let delta = scanner
+            .scan_changes(&ScanState::default(), &HashSet::new())
+            .unwrap();
+        assert_eq!(2, delta.added.len());
+        assert!(delta.changed.is_empty());
+        assert!(delta.removed.is_empty());
+
+        // Nothing has changed, so second scan should find nothing new.
+        let state = state_from(&delta);
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert!(delta.is_empty());
+        assert_eq!(2, delta.unchanged);
+
+        // Removing a file changes the directory modification time.
+        fs::remove_file(album.join("one.jpg")).unwrap();
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(vec![album.join("one.jpg")], delta.removed);
+        assert_eq!(1, delta.unchanged);
+
+        // Editing a file in place is noticed without the directory being listed.
+        let mut state = state;
+        apply(&mut state, &delta);
+        fs::write(album.join("two.mp4"), b"two, but longer").unwrap();
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(1, delta.changed.len());
+        assert_eq!(album.join("two.mp4"), delta.changed[0].0.path());
+        assert_eq!(0, delta.unchanged);
+    }
+
+    #[test]
+    fn test_scan_changes_with_sidecar() {
+        let base = tempfile::tempdir().unwrap();
+        fs::write(base.path().join("IMG_0001.jpg"), b"one").unwrap();
+        fs::write(base.path().join("IMG_0001.jpg.xmp"), b"<x:xmpmeta/>").unwrap();
+
+        let scanner = build_scanner(base.path(), ExcludeRules::default());
+
+        // Sidecars aren't pictures.
+        let delta = scanner
+            .scan_changes(&ScanState::default(), &HashSet::new())
+            .unwrap();
+        assert_eq!(1, delta.added.len());
+        let mut state = state_from(&delta);
+
+        // Removing the sidecar changes the picture.
+        fs::remove_file(base.path().join("IMG_0001.jpg.xmp")).unwrap();
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(1, delta.changed.len());
+        assert_eq!(base.path().join("IMG_0001.jpg"), delta.changed[0].0.path());
+        assert!(delta.removed.is_empty());
+
+        // Adding a sidecar that replaces the suffix changes the picture.
+        apply(&mut state, &delta);
+        fs::write(base.path().join("IMG_0001.xmp"), b"<x:xmpmeta/>").unwrap();
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(1, delta.changed.len());
+        assert!(delta.added.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_changes_with_unreadable_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = tempfile::tempdir().unwrap();
+        let album = base.path().join("album");
+        let nested = album.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(album.join("one.jpg"), b"one").unwrap();
+        fs::write(nested.join("two.jpg"), b"two").unwrap();
+
+        let scanner = build_scanner(base.path(), ExcludeRules::default());
+
+        let delta = scanner
+            .scan_changes(&ScanState::default(), &HashSet::new())
+            .unwrap();
+        assert_eq!(2, delta.added.len());
+        let state = state_from(&delta);
+
+        fs::set_permissions(&album, fs::Permissions::from_mode(0o000)).unwrap();
+        let dirty_dirs = HashSet::from([album.clone()]);
+        let delta = scanner.scan_changes(&state, &dirty_dirs);
+        fs::set_permissions(&album, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let delta = delta.unwrap();
+        assert!(delta.removed.is_empty());
+        assert!(delta.removed_dirs.is_empty());
+    }
+
+    #[test]
+    fn test_scan_changes_with_exclusions() {
+        let base = tempfile::tempdir().unwrap();
+        let camera = base.path().join("Camera");
+        let stickers = base.path().join("WhatsApp").join("Stickers");
+        let cache = base.path().join("Android").join("data");
+        for dir in [&camera, &stickers, &cache] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(camera.join("one.jpg"), b"one").unwrap();
+        fs::write(camera.join("one.tmp.jpg"), b"partial").unwrap();
+        fs::write(stickers.join("sticker.png"), b"sticker").unwrap();
+        fs::write(cache.join("thumb.jpg"), b"thumb").unwrap();
+
+        let rules = ExcludeRules::build(&["Android/data".into(), "*.tmp.jpg".into()], &[]);
+        let scanner = build_scanner(base.path(), rules);
+
+        let delta = scanner
+            .scan_changes(&ScanState::default(), &HashSet::new())
+            .unwrap();
+        assert_eq!(2, delta.added.len());
+
+        // Marker file excludes a directory that was previously scanned.
+        fs::write(stickers.join(".nomedia"), b"").unwrap();
+        let mut state = state_from(&delta);
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(vec![stickers.join("sticker.png")], delta.removed);
+        assert!(scanner.is_excluded(&stickers.join("sticker.png")));
+
+        // Removing the marker file brings the directory back.
+        apply(&mut state, &delta);
+        fs::remove_file(stickers.join(".nomedia")).unwrap();
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(1, delta.added.len());
+        assert_eq!(stickers.join("sticker.png"), delta.added[0].0.path());
+
+        assert!(scanner.is_excluded(&cache.join("thumb.jpg")));
+        assert!(scanner.is_excluded(&camera.join("one.tmp.jpg")));
+        assert!(!scanner.is_excluded(&camera.join("one.jpg")));
+    }
+
+    #[test]
+    fn test_scan_changes_with_new_exclusions() {
+        let base = tempfile::tempdir().unwrap();
+        let camera = base.path().join("Camera");
+        fs::create_dir_all(&camera).unwrap();
+        fs::write(camera.join("one.jpg"), b"one").unwrap();
+        fs::write(camera.join("one.tmp.jpg"), b"partial").unwrap();
+
+        let scanner = build_scanner(base.path(), ExcludeRules::default());
+        let delta = scanner
+            .scan_changes(&ScanState::default(), &HashSet::new())
+            .unwrap();
+        assert_eq!(2, delta.added.len());
+        let mut state = state_from(&delta);
+
+        // Adding a pattern removes known files even though their directory hasn't changed.
+        let rules = ExcludeRules::build(&["*.tmp.jpg".into()], &[]);
+        let scanner = build_scanner(base.path(), rules);
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(vec![camera.join("one.tmp.jpg")], delta.removed);
+        assert_eq!(1, delta.unchanged);
+
+        // Removing the pattern brings the file back.
+        apply(&mut state, &delta);
+        let scanner = build_scanner(base.path(), ExcludeRules::default());
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(1, delta.added.len());
+        assert_eq!(camera.join("one.tmp.jpg"), delta.added[0].0.path());
+    }
+
+    #[test]
+    fn test_scan_changes_with_moves() {
+        let base = tempfile::tempdir().unwrap();
+        let inbox = base.path().join("inbox");
+        let holiday = base.path().join("holiday");
+        fs::create_dir_all(&inbox).unwrap();
+        fs::create_dir_all(&holiday).unwrap();
+        fs::write(inbox.join("one.jpg"), b"one").unwrap();
+        fs::write(inbox.join("two.jpg"), b"two").unwrap();
+
+        let scanner = build_scanner(base.path(), ExcludeRules::default());
+
+        let delta = scanner
+            .scan_changes(&ScanState::default(), &HashSet::new())
+            .unwrap();
+        assert_eq!(2, delta.added.len());
+        assert_eq!(2, delta.fingerprints.len());
+        let mut state = state_from(&delta);
+
+        // Moving and renaming a file keeps its identity.
+        fs::rename(inbox.join("one.jpg"), holiday.join("beach.jpg")).unwrap();
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert!(delta.added.is_empty());
+        assert!(delta.removed.is_empty());
+        assert_eq!(1, delta.moved.len());
+        assert_eq!(inbox.join("one.jpg"), delta.moved[0].0);
+        assert_eq!(holiday.join("beach.jpg"), delta.moved[0].1.path());
+
+        // A file with different content is not a move.
+        apply(&mut state, &delta);
+        fs::remove_file(inbox.join("two.jpg")).unwrap();
+        fs::write(holiday.join("three.jpg"), b"three").unwrap();
+        let delta = scanner.scan_changes(&state, &HashSet::new()).unwrap();
+        assert_eq!(vec![inbox.join("two.jpg")], delta.removed);
+        assert_eq!(1, delta.added.len());
+        assert!(delta.moved.is_empty());
+    }
 }
@@ -0,0 +1,251 @@
+// SPDX-FileCopyrightText: © 2026 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::ExcludeRules;
+use super::Scanner;
+
+use anyhow::*;
+use gio::glib;
+use gio::prelude::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+use walkdir::WalkDir;
+
+/// How often to check if the file system has gone quiet.
+const TICK: Duration = Duration::from_millis(500);
+
+/// Watches the library directory trees for pictures and videos being created,
+/// changed, deleted, or moved.
+///
+/// Events are debounced so that a burst of changes, such as copying a camera dump
+/// into the library, is reported once after the file system has been quiet for a while.
+/// The watcher reports the directories that have changed so that a scan can re-list
+/// them even if their modification times haven't changed.
+///
+/// Watching stops when the watcher is dropped.
+pub struct Watcher {
+    context: glib::MainContext,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Directories changed since changes were last reported.
+#[derive(Debug, Default)]
+struct Pending {
+    dirs: HashSet<PathBuf>,
+    last_event_at: Option<Instant>,
+}
+
+/// Directory monitors. Only accessed from the watcher thread.
+struct Monitors {
+    monitors: RefCell<HashMap<PathBuf, gio::FileMonitor>>,
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl Watcher {
+    /// Starts watching the `scan_bases` directory trees. The `on_change` function is called
+    /// with the set of changed directories once no events have been seen for `quiet_period`.
+    pub fn watch<F>(scan_bases: &[PathBuf], quiet_period: Duration, on_change: F) -> Result<Self>
+    where
+        F: Fn(HashSet<PathBuf>) + Send + Sync + 'static,
+    {
+        let context = glib::MainContext::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let context = context.clone();
+            let stop = stop.clone();
+            let scan_bases = scan_bases.to_vec();
+
+            std::thread::Builder::new()
+                .name("library-watcher".into())
+                .spawn(move || {
+                    let result = context.with_thread_default(|| {
+                        Self::run(&context, &stop, &scan_bases, quiet_period, on_change)
+                    });
+                    if let Err(e) = result {
+                        error!("Failed running library watcher: {}", e);
+                    }
+                })?
+        };
+
+        Ok(Self {
+            context,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn run<F>(
+        context: &glib::MainContext,
+        stop: &AtomicBool,
+        scan_bases: &[PathBuf],
+        quiet_period: Duration,
+        on_change: F,
+    ) where
+        F: Fn(HashSet<PathBuf>) + Send + Sync + 'static,
+    {
+        let pending = Arc::new(Mutex::new(Pending::default()));
+
+        let monitors = Rc::new(Monitors {
+            monitors: RefCell::new(HashMap::new()),
+            pending: pending.clone(),
+        });
+
+        for scan_base in scan_bases {
+            monitors.watch_tree(scan_base);
+        }
+
+        info!(
+            "Watching {} directories for changes",
+            monitors.monitors.borrow().len()
+        );
+
+        let tick = glib::timeout_source_new(TICK, None, glib::Priority::DEFAULT_IDLE, move || {
+            let dirs = {
+                let mut pending = pending.lock().unwrap();
+                match pending.last_event_at {
+                    Some(at) if at.elapsed() >= quiet_period => {
+                        pending.last_event_at = None;
+                        std::mem::take(&mut pending.dirs)
+                    }
+                    _ => HashSet::new(),
+                }
+            };
+
+            if !dirs.is_empty() {
+                info!("Library changed in {} directories", dirs.len());
+                on_change(dirs);
+            }
+
+            glib::ControlFlow::Continue
+        });
+        tick.attach(Some(context));
+
+        while !stop.load(Ordering::Relaxed) {
+            context.iteration(true);
+        }
+
+        tick.destroy();
+        for (_, monitor) in monitors.monitors.borrow_mut().drain() {
+            monitor.cancel();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.context.wakeup();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Monitors {
+    /// Watch a directory and all non-hidden directories beneath it.
+    fn watch_tree(self: &Rc<Self>, dir: &Path) {
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(|e| !Scanner::is_hidden(e.file_name()))
+            .flatten()
+            .filter(|e| e.file_type().is_dir())
+            .for_each(|e| self.watch_dir(e.path()));
+    }
+
+    fn watch_dir(self: &Rc<Self>, dir: &Path) {
+        if self.monitors.borrow().contains_key(dir) {
+            return;
+        }
+
+        let monitor = match gio::File::for_path(dir).monitor_directory(
+            gio::FileMonitorFlags::WATCH_MOVES,
+            None::<&gio::Cancellable>,
+        ) {
+            std::result::Result::Ok(monitor) => monitor,
+            Err(e) => {
+                warn!("Failed watching {:?}: {}", dir, e);
+                return;
+            }
+        };
+
+        let this = Rc::downgrade(self);
+        monitor.connect_changed(move |_, file, other_file, event| {
+            if let Some(this) = this.upgrade() {
+                this.on_event(file, other_file, event);
+            }
+        });
+
+        self.monitors.borrow_mut().insert(dir.into(), monitor);
+    }
+
+    fn unwatch_tree(&self, dir: &Path) {
+        self.monitors.borrow_mut().retain(|path, monitor| {
+            let keep = !path.starts_with(dir);
+            if !keep {
+                monitor.cancel();
+            }
+            keep
+        });
+    }
+
+    fn on_event(
+        self: &Rc<Self>,
+        file: &gio::File,
+        other_file: Option<&gio::File>,
+        event: gio::FileMonitorEvent,
+    ) {
+        let Some(path) = file.path() else {
+            return;
+        };
+
+        // Marker files are hidden, but adding or removing one changes what is excluded.
+        if path
+            .file_name()
+            .is_some_and(|name| Scanner::is_hidden(name) && !ExcludeRules::is_marker(name))
+        {
+            return;
+        }
+
+        debug!("{:?} {:?}", event, path);
+
+        let mut changed = vec![path.clone()];
+
+        match event {
+            gio::FileMonitorEvent::Created | gio::FileMonitorEvent::MovedIn => {
+                if path.is_dir() {
+                    self.watch_tree(&path);
+                }
+            }
+            gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::MovedOut => {
+                self.unwatch_tree(&path);
+            }
+            gio::FileMonitorEvent::Renamed => {
+                self.unwatch_tree(&path);
+                if let Some(new_path) = other_file.and_then(|f| f.path()) {
+                    if new_path.is_dir() {
+                        self.watch_tree(&new_path);
+                    }
+                    changed.push(new_path);
+                }
+            }
+            gio::FileMonitorEvent::ChangesDoneHint => {}
+            _ => return,
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        pending
+            .dirs
+            .extend(changed.iter().filter_map(|p| p.parent()).map(PathBuf::from));
+        pending.last_event_at = Some(Instant::now());
+    }
+}
@@ -48,6 +48,22 @@ pub fn get_thumbnail_hash_output(
     path
 }
 
+/// Gets the output path of the scrub preview sprite sheet of a video using hash.
+/// Format: `{cache_dir}/thumbnails/sprites/{md5_hash}.jpg`
+pub fn get_sprite_sheet_hash_output(thumbnails_base_dir: &Path, hash: &str) -> PathBuf {
+    thumbnails_base_dir
+        .join("sprites")
+        .join(format!("{}.jpg", hash))
+}
+
+/// Gets the output path of the timestamp index of a sprite sheet using hash.
+/// Format: `{cache_dir}/thumbnails/sprites/{md5_hash}.txt`
+pub fn get_sprite_sheet_index_hash_output(thumbnails_base_dir: &Path, hash: &str) -> PathBuf {
+    thumbnails_base_dir
+        .join("sprites")
+        .join(format!("{}.txt", hash))
+}
+
 pub fn get_failed_thumbnail_output(thumbnails_base_dir: &Path, hash: &str) -> PathBuf {
     // FIXME don't hardcode app-id.
     let fail_dir = thumbnails_base_dir.join("fail").join("app.fotema.Fotema");
@@ -70,6 +86,49 @@ pub fn is_failed(thumbnails_base_dir: &Path, host_path: &Path) -> bool {
     failed_path.exists()
 }
 
+/// Deletes all thumbnails and any failed thumbnail marker for a file.
+/// Used when the source file has changed and its thumbnails are stale.
+pub fn delete_thumbnails(
+    thumbnails_base_dir: &Path,
+    host_path: &Path,
+) -> Result<(), ThumbnailError> {
+    let file_uri = get_file_uri(host_path)?;
+    let file_uri_hash = hash::compute_hash(&file_uri);
+
+    let sizes = [
+        ThumbnailSize::Small,
+        ThumbnailSize::Normal,
+        ThumbnailSize::Large,
+        ThumbnailSize::XLarge,
+        ThumbnailSize::XXLarge,
+    ];
+
+    let paths = sizes
+        .iter()
+        .map(|size| get_thumbnail_hash_output(thumbnails_base_dir, &file_uri_hash, *size))
+        .chain(std::iter::once(get_failed_thumbnail_output(
+            thumbnails_base_dir,
+            &file_uri_hash,
+        )))
+        .chain(std::iter::once(get_sprite_sheet_hash_output(
+            thumbnails_base_dir,
+            &file_uri_hash,
+        )))
+        .chain(std::iter::once(get_sprite_sheet_index_hash_output(
+            thumbnails_base_dir,
+            &file_uri_hash,
+        )));
+
+    for path in paths {
+        if path.exists() {
+            debug!("Deleting stale thumbnail {:?}", path);
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Writes a failed thumbnail using an empty (1x1 transparent) DynamicImage.
 pub fn write_failed_thumbnail(
     thumbnails_base_dir: &Path,
@@ -12,7 +12,10 @@ pub mod sizes;
 pub mod thumbnailer;
 
 pub use error::ThumbnailError;
+pub use file::delete_thumbnails;
 pub use file::get_file_uri;
+pub use file::get_sprite_sheet_hash_output;
+pub use file::get_sprite_sheet_index_hash_output;
 pub use file::get_thumbnail_hash_output;
 pub use file::get_thumbnail_path;
 pub use file::is_failed;
@@ -56,6 +59,14 @@ impl Thumbnailer {
         get_thumbnail_path(&self.thumbnails_path, host_path, size)
     }
 
+    pub fn get_sprite_sheet_path(&self, hash: &str) -> PathBuf {
+        get_sprite_sheet_hash_output(&self.thumbnails_path, hash)
+    }
+
+    pub fn get_sprite_sheet_index_path(&self, hash: &str) -> PathBuf {
+        get_sprite_sheet_index_hash_output(&self.thumbnails_path, hash)
+    }
+
     //pub fn nearest_thumbnail_by_dimension(&self, hash: &str, dimension: u32) -> Option<PathBuf> {
     //}
 
@@ -114,6 +125,10 @@ impl Thumbnailer {
         thumbnailer::generate_all_thumbnails(&self.thumbnails_path, path, src_image)
     }
 
+    pub fn delete_thumbnails(&self, path: &FlatpakPathBuf) -> Result<(), ThumbnailError> {
+        file::delete_thumbnails(&self.thumbnails_path, &path.host_path)
+    }
+
     pub fn write_failed_thumbnail(&self, path: &FlatpakPathBuf) -> Result<(), ThumbnailError> {
         file::write_failed_thumbnail(&self.thumbnails_path, path)
     }
@@ -0,0 +1,206 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Decodes still frames from arbitrary positions of a video.
+
+use crate::photo::model::Orientation;
+use crate::video::display_matrix::av_display_rotation_get;
+
+use anyhow::*;
+use chrono::TimeDelta;
+use image::imageops;
+use image::{ImageBuffer, RgbImage};
+use std::path::Path;
+use std::result::Result::Ok;
+
+use ffmpeg::Rational;
+use ffmpeg::format::{Pixel, context::Input, input};
+use ffmpeg::media::Type;
+use ffmpeg::rescale::{Rescale, TIME_BASE};
+use ffmpeg::software::scaling::{context::Context, flag::Flags};
+use ffmpeg::util::frame::video::Video;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::frame::side_data::Type as SideDataType;
+
+/// Reads frames of a video as RGB images, rotated to display the right way up.
+pub(crate) struct FrameReader {
+    ictx: Input,
+    decoder: ffmpeg::decoder::Video,
+    scaler: Context,
+    video_stream_index: usize,
+    time_base: Rational,
+
+    /// Whether the input is at the start of the video.
+    at_start: bool,
+
+    /// Orientation to use instead of the display matrix of each frame.
+    orientation: Option<Orientation>,
+}
+
+impl FrameReader {
+    /// Opens a video. If `max_size` is set, then frames are scaled down so that
+    /// neither edge is longer than `max_size`.
+    pub(crate) fn open(video_path: &Path, max_size: Option<u32>) -> Result<Self> {
+        let ictx = input(video_path)?;
+
+        let stream = ictx
+            .streams()
+            .best(Type::Video)
+            .ok_or(ffmpeg::Error::StreamNotFound)?;
+
+        let video_stream_index = stream.index();
+        let time_base = stream.time_base();
+
+        let context_decoder =
+            ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+        let decoder = context_decoder.decoder().video()?;
+
+        let (width, height) = scaled_size(decoder.width(), decoder.height(), max_size);
+
+        let scaler = Context::get(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            Pixel::RGB24,
+            width,
+            height,
+            Flags::BILINEAR,
+        )?;
+
+        Ok(Self {
+            ictx,
+            decoder,
+            scaler,
+            video_stream_index,
+            time_base,
+            at_start: true,
+            orientation: None,
+        })
+    }
+
+    /// Rotates frames to an orientation already known for the video, rather than
+    /// by the display matrix of each frame.
+    pub(crate) fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = Some(orientation);
+        self
+    }
+
+    /// Duration of the video, if the container knows it.
+    pub(crate) fn duration(&self) -> Option<TimeDelta> {
+        // FFmpeg's TIME_BASE is microseconds.
+        Some(self.ictx.duration())
+            .filter(|x| *x > 0)
+            .map(TimeDelta::microseconds)
+    }
+
+    /// Decodes the first frame at or after `position`, or the last frame of the video
+    /// if `position` is past the end. Returns `None` if no frames could be decoded.
+    pub(crate) fn read_at(&mut self, position: TimeDelta) -> Result<Option<RgbImage>> {
+        let position = position.num_microseconds().unwrap_or_default();
+
+        if !self.at_start || position > 0 {
+            self.ictx.seek(position, ..position)?;
+            self.decoder.flush();
+        }
+        self.at_start = false;
+
+        let target = position.rescale(TIME_BASE, self.time_base);
+        let Some(decoded) = self.decode_frame(target)? else {
+            return Ok(None);
+        };
+
+        // MatrixData contains rotation.
+        let orientation = self.orientation.unwrap_or_else(|| {
+            decoded
+                .side_data(SideDataType::DisplayMatrix)
+                .map(|display_matrix| av_display_rotation_get(display_matrix.data()))
+                .map(|rotation| Orientation::from_degrees(rotation as i32))
+                .unwrap_or_default()
+        });
+
+        let mut rgb_frame = Video::empty();
+        self.scaler.run(&decoded, &mut rgb_frame)?;
+        let image = to_rgb_image(&rgb_frame)?;
+
+        let image = match orientation {
+            Orientation::East => imageops::rotate90(&image),
+            Orientation::South => imageops::rotate180(&image),
+            Orientation::West => imageops::rotate270(&image),
+            _ => image,
+        };
+
+        Ok(Some(image))
+    }
+
+    fn decode_frame(&mut self, target: i64) -> Result<Option<Video>> {
+        let mut decoded = Video::empty();
+        let mut last = None;
+
+        for (stream, packet) in self.ictx.packets() {
+            if stream.index() != self.video_stream_index {
+                continue;
+            }
+
+            self.decoder.send_packet(&packet)?;
+            while self.decoder.receive_frame(&mut decoded).is_ok() {
+                if decoded.timestamp().is_none_or(|pts| pts >= target) {
+                    return Ok(Some(decoded));
+                }
+                last = Some(decoded.clone());
+            }
+        }
+
+        self.decoder.send_eof()?;
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            if decoded.timestamp().is_none_or(|pts| pts >= target) {
+                return Ok(Some(decoded));
+            }
+            last = Some(decoded.clone());
+        }
+
+        Ok(last)
+    }
+}
+
+/// Size of a frame scaled down to fit in `max_size`, preserving the aspect ratio.
+fn scaled_size(width: u32, height: u32, max_size: Option<u32>) -> (u32, u32) {
+    let Some(max_size) = max_size.filter(|max| *max < width.max(height)) else {
+        return (width, height);
+    };
+
+    let scale = f64::from(max_size) / f64::from(width.max(height));
+    let width = ((f64::from(width) * scale).round() as u32).max(1);
+    let height = ((f64::from(height) * scale).round() as u32).max(1);
+    (width, height)
+}
+
+/// Copies an RGB24 frame into an image. Rows of the frame may be padded.
+fn to_rgb_image(frame: &Video) -> Result<RgbImage> {
+    let width = frame.width();
+    let height = frame.height();
+    let row_len = width as usize * 3;
+
+    let bytes: Vec<u8> = frame
+        .data(0)
+        .chunks(frame.stride(0))
+        .take(height as usize)
+        .flat_map(|row| &row[..row_len])
+        .copied()
+        .collect();
+
+    ImageBuffer::from_raw(width, height, bytes).ok_or_else(|| anyhow!("Video frame to image"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_size() {
+        assert_eq!((1920, 1080), scaled_size(1920, 1080, None));
+        assert_eq!((160, 90), scaled_size(1920, 1080, Some(160)));
+        assert_eq!((90, 160), scaled_size(1080, 1920, Some(160)));
+        assert_eq!((100, 50), scaled_size(100, 50, Some(160)));
+    }
+}
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use super::Metadata;
+use crate::photo::gps::GPSLocation;
 use anyhow::*;
 use chrono::prelude::*;
 use chrono::{DateTime, TimeDelta};
@@ -24,15 +25,45 @@ use std::fs;
 //
 // 1. ???
 // 2. ???
+// 3. Read GPS location from ISO 6709 location tags.
+
+pub const VERSION: u32 = 3;
+
+/// Metadata keys that might hold an ISO 6709 location. FFmpeg exposes the
+/// QuickTime `©xyz` atom as `location`.
+const LOCATION_KEYS: [&str; 4] = [
+    "com.apple.quicktime.location.ISO6709",
+    "location",
+    "location-eng",
+    "\u{a9}xyz",
+];
+
+fn location(dict: &ffmpeg::DictionaryRef) -> Option<GPSLocation> {
+    LOCATION_KEYS
+        .iter()
+        .filter_map(|key| dict.get(key))
+        .find_map(GPSLocation::for_iso6709)
+}
 
-pub const VERSION: u32 = 2;
+/// UTC offset where a video was recorded. Apple devices record the local creation
+/// date with its offset, such as `2024-06-01T12:30:45+0200`, as well as the UTC
+/// `creation_time`.
+fn created_offset(dict: &ffmpeg::DictionaryRef) -> Option<FixedOffset> {
+    let creation_date = dict.get("com.apple.quicktime.creationdate")?;
+    DateTime::parse_from_str(creation_date, "%Y-%m-%dT%H:%M:%S%z")
+        .or_else(|_| DateTime::parse_from_rfc3339(creation_date))
+        .ok()
+        .map(|x| *x.offset())
+}
 
 pub fn from_path(path: &Path) -> Result<Metadata> {
     let mut metadata = Metadata::default();
 
-    let fs_metadata = fs::metadata(path)?;
-    metadata.fs_created_at = fs_metadata.created().map(Into::<DateTime<Utc>>::into).ok();
-    metadata.fs_modified_at = fs_metadata.modified().map(Into::<DateTime<Utc>>::into).ok();
+    // Path might be an ffmpeg URL, such as a subfile of a motion photo, rather than a file.
+    if let Ok(fs_metadata) = fs::metadata(path) {
+        metadata.fs_created_at = fs_metadata.created().map(Into::<DateTime<Utc>>::into).ok();
+        metadata.fs_modified_at = fs_metadata.modified().map(Into::<DateTime<Utc>>::into).ok();
+    }
 
     let context = ffmpeg::format::input(path)?;
 
@@ -43,10 +74,14 @@ pub fn from_path(path: &Path) -> Result<Metadata> {
         dt.map(|y| y.to_utc())
     });
 
+    metadata.stream_created_offset = created_offset(&context_metadata);
+
     metadata.content_id = context_metadata
         .get("com.apple.quicktime.content.identifier")
         .map(String::from);
 
+    metadata.location = location(&context_metadata);
+
     metadata.container_format = Some(String::from(context.format().description()));
 
     if let Some(stream) = context.streams().best(ffmpeg::media::Type::Video) {
@@ -62,6 +97,8 @@ pub fn from_path(path: &Path) -> Result<Metadata> {
             })
         });
 
+        metadata.location = metadata.location.or_else(|| location(&stream_metadata));
+
         let codec = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
         metadata.video_codec = Some(String::from(codec.id().name()));
 
@@ -4,9 +4,12 @@
 
 pub mod display_matrix;
 
+pub(crate) mod frames;
+
 pub mod metadata;
 pub mod model;
 pub mod repo;
+pub mod sprite_sheet;
 pub mod thumbnailer;
 pub mod transcode;
 
@@ -14,5 +17,11 @@ pub use model::Metadata;
 pub use model::Video;
 pub use model::VideoId;
 pub use repo::Repository;
+pub use sprite_sheet::SpriteSheet;
+pub use sprite_sheet::SpriteSheetGenerator;
 pub use thumbnailer::VideoThumbnailer;
+pub use transcode::TranscodeError;
+pub use transcode::TranscodeProfile;
+pub use transcode::TranscodeQuality;
 pub use transcode::Transcoder;
+pub use transcode::VideoCodec;
@@ -3,8 +3,9 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use crate::FlatpakPathBuf;
+use crate::file_types::FileType;
 use crate::photo::gps::GPSLocation;
-use chrono::{DateTime, TimeDelta, Utc};
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
 use std::fmt::Display;
 use std::path::PathBuf;
 
@@ -49,6 +50,9 @@ pub struct Video {
 
     /// Video codec
     pub video_codec: Option<String>,
+
+    /// Type of video. Not known for videos added before file types were detected.
+    pub file_type: Option<FileType>,
 }
 
 impl Video {
@@ -69,6 +73,9 @@ impl Video {
 pub struct Metadata {
     pub stream_created_at: Option<DateTime<Utc>>,
 
+    // UTC offset where the video was recorded, if the video records it.
+    pub stream_created_offset: Option<FixedOffset>,
+
     pub fs_created_at: Option<DateTime<Utc>>,
 
     pub fs_modified_at: Option<DateTime<Utc>>,
@@ -4,9 +4,11 @@
 
 use super::Metadata;
 use super::metadata;
+use super::thumbnailer;
 use crate::FlatpakPathBuf;
 use crate::ScannedFile;
-use crate::file_types;
+use crate::file_types::FileType;
+use crate::library_root::{LibraryRootId, LibraryRoots};
 use crate::path_encoding;
 use crate::video::model::{Video, VideoId};
 
@@ -16,6 +18,7 @@ use rusqlite;
 use rusqlite::Row;
 use rusqlite::params;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use tracing::error;
 
@@ -23,8 +26,8 @@ use tracing::error;
 /// Repository is backed by a Sqlite database.
 #[derive(Debug, Clone)]
 pub struct Repository {
-    /// Base path to library
-    library_base_dir: FlatpakPathBuf,
+    /// Library root directories that video paths are relative to
+    library_roots: LibraryRoots,
 
     /// Base path for transcoded videos
     cache_dir_base_path: PathBuf,
@@ -39,7 +42,7 @@ pub struct Repository {
 impl Repository {
     /// Builds a Repository and creates operational tables.
     pub fn open(
-        library_base_dir: &FlatpakPathBuf,
+        library_roots: &LibraryRoots,
         cache_dir_base_path: &Path,
         data_dir_base_path: &Path,
         con: Arc<Mutex<rusqlite::Connection>>,
@@ -47,7 +50,7 @@ impl Repository {
         std::fs::create_dir_all(cache_dir_base_path)?;
 
         let repo = Repository {
-            library_base_dir: library_base_dir.clone(),
+            library_roots: library_roots.clone(),
             cache_dir_base_path: cache_dir_base_path.into(),
             data_dir_base_path: data_dir_base_path.into(),
             con,
@@ -75,6 +78,26 @@ impl Repository {
         Ok(())
     }
 
+    /// Records that the thumbnails of a video are from the current thumbnail generation.
+    pub fn mark_thumbnailed(&mut self, video_id: &VideoId) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE videos
+                SET
+                    thumbnail_version = ?2
+                WHERE video_id = ?1",
+            )?;
+
+            stmt.execute(params![video_id.id(), thumbnailer::VERSION])?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn add_transcode(&mut self, video_id: VideoId, transcoded_path: &Path) -> Result<()> {
         let mut con = self.con.lock().unwrap();
         let tx = con.transaction()?;
@@ -83,7 +106,8 @@ impl Repository {
             let mut stmt = tx.prepare(
                 "UPDATE videos
                 SET
-                    transcoded_path = ?2
+                    transcoded_path = ?2,
+                    transcode_error = NULL
                 WHERE video_id = ?1",
             )?;
 
@@ -100,6 +124,27 @@ impl Repository {
         Ok(())
     }
 
+    /// Records why a video couldn't be transcoded.
+    pub fn mark_transcode_failed(&mut self, video_id: VideoId, error: &str) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE videos
+                SET
+                    transcoded_path = NULL,
+                    transcode_error = ?2
+                WHERE video_id = ?1",
+            )?;
+
+            stmt.execute(params![video_id.id(), error])?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn add_metadata(&mut self, vids: Vec<(VideoId, Metadata)>) -> Result<()> {
         let mut con = self.con.lock().unwrap();
         let tx = con.transaction()?;
@@ -162,6 +207,57 @@ impl Repository {
         Ok(())
     }
 
+    /// Detects and records the file type of videos added before file types were recorded.
+    /// Returns the number of videos updated.
+    pub fn backfill_file_types(&mut self) -> Result<usize> {
+        let vids: Vec<(VideoId, LibraryRootId, String)> = {
+            let con = self.con.lock().unwrap();
+            let mut stmt = con.prepare(
+                "SELECT video_id, root_id, video_path_b64
+                FROM videos
+                WHERE file_type IS NULL",
+            )?;
+
+            stmt.query_map([], |row| {
+                std::result::Result::Ok((
+                    row.get("video_id").map(VideoId::new)?,
+                    row.get("root_id").map(LibraryRootId::new)?,
+                    row.get("video_path_b64")?,
+                ))
+            })?
+            .flatten()
+            .collect()
+        };
+
+        // Reading files can be slow, so is done without the database lock.
+        let file_types: Vec<(VideoId, FileType)> = vids
+            .into_iter()
+            .filter_map(|(video_id, root_id, relative_path)| {
+                let path = self
+                    .library_roots
+                    .resolve_base64(root_id, &relative_path)
+                    .ok()
+                    .flatten()?;
+                FileType::detect(&path.sandbox_path).map(|file_type| (video_id, file_type))
+            })
+            .collect();
+
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut stmt =
+                tx.prepare_cached("UPDATE videos SET file_type = ?2 WHERE video_id = ?1")?;
+
+            for (video_id, file_type) in &file_types {
+                stmt.execute(params![video_id.id(), file_type.as_ref()])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(file_types.len())
+    }
+
     pub fn add_all(&mut self, vids: &Vec<ScannedFile>) -> Result<()> {
         let mut con = self.con.lock().unwrap();
         let tx = con.transaction()?;
@@ -170,47 +266,37 @@ impl Repository {
         {
             let mut vid_stmt = tx.prepare_cached(
                 "INSERT INTO videos (
+                        root_id,
                         video_path_b64,
                         video_path_lossy,
                         link_path_b64,
                         link_path_lossy,
+                        file_type,
                         insert_ts
                     ) VALUES (
-                        ?1, ?2, ?3, ?4, CURRENT_TIMESTAMP
-                    ) ON CONFLICT(video_path_b64) DO UPDATE SET
-                        link_path_b64 = ?3,
-                        link_path_lossy = ?4",
+                        ?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP
+                    ) ON CONFLICT(root_id, video_path_b64) DO UPDATE SET
+                        link_path_b64 = ?4,
+                        link_path_lossy = ?5,
+                        file_type = ?6",
             )?;
 
             for scanned_file in vids {
-                if let ScannedFile::Video(path) = scanned_file {
+                if let ScannedFile::Video(path, file_type) = scanned_file {
                     // convert to relative path before saving to database
-                    let video_path = path.strip_prefix(&self.library_base_dir.sandbox_path)?;
+                    let (root_id, video_path) = self.library_roots.to_relative_path(path)?;
                     let video_path_b64 = path_encoding::to_base64(video_path);
 
-                    // Path without suffix so sibling pictures and videos can be related
-                    // Some Apple photo exports name the video component after the
-                    // picture component, so you get names like 'img_1234.heic.mp4' instead
-                    // of 'img_1234.mp4'. After removing the file suffix, check if a supported
-                    // picture suffix is now present and remove it.
-                    let link_path = match video_path.file_stem() {
-                        Some(stem) if file_types::is_supported_picture(&stem.as_ref()) => {
-                            let path: &Path = stem.as_ref();
-                            path.file_stem()
-                        }
-                        any => any,
-                    };
-
-                    let link_path = link_path.and_then(|x| x.to_str()).expect("Must exist");
-
-                    let link_path = video_path.with_file_name(link_path);
+                    let link_path = Self::to_link_path(video_path);
                     let link_path_b64 = path_encoding::to_base64(&link_path);
 
                     vid_stmt.execute(params![
+                        root_id.id(),
                         video_path_b64,
                         video_path.to_string_lossy(),
                         link_path_b64,
                         link_path.to_string_lossy(),
+                        file_type.as_ref(),
                     ])?;
                 } else {
                     error!("Expected a video, but got: {:?}", scanned_file);
@@ -222,12 +308,190 @@ impl Repository {
         Ok(())
     }
 
+    /// Update the paths of videos that have been moved or renamed, so that data
+    /// derived from the video, such as the transcoded video, is kept.
+    /// Takes pairs of old path and new file.
+    /// Returns the old paths of moved videos so callers can discard other derived files,
+    /// such as thumbnails.
+    pub fn mark_moved(
+        &mut self,
+        moves: &Vec<(PathBuf, ScannedFile)>,
+    ) -> Result<Vec<FlatpakPathBuf>> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let mut moved = Vec::new();
+
+        {
+            // Ignore conflicts with a video already at the new path. The video at
+            // the old path will be removed by the clean task.
+            let mut update_stmt = tx.prepare_cached(
+                "UPDATE OR IGNORE videos
+                SET
+                    root_id = ?3,
+                    video_path_b64 = ?4,
+                    video_path_lossy = ?5,
+                    link_path_b64 = ?6,
+                    link_path_lossy = ?7,
+                    file_type = ?8
+                WHERE root_id = ?1
+                AND video_path_b64 = ?2",
+            )?;
+
+            for (from, scanned_file) in moves {
+                if let ScannedFile::Video(to, file_type) = scanned_file {
+                    let (from_root_id, from_path) = self.library_roots.to_relative_path(from)?;
+                    let (to_root_id, to_path) = self.library_roots.to_relative_path(to)?;
+                    let link_path = Self::to_link_path(to_path);
+
+                    let count = update_stmt.execute(params![
+                        from_root_id.id(),
+                        path_encoding::to_base64(from_path),
+                        to_root_id.id(),
+                        path_encoding::to_base64(to_path),
+                        to_path.to_string_lossy(),
+                        path_encoding::to_base64(&link_path),
+                        link_path.to_string_lossy(),
+                        file_type.as_ref(),
+                    ])?;
+
+                    if count > 0 {
+                        moved.extend(self.library_roots.resolve(from_root_id, from_path));
+                    }
+                } else {
+                    error!("Expected a video, but got: {:?}", scanned_file);
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(moved)
+    }
+
+    /// Mark videos that have changed on the file system as needing their metadata extracted again.
+    /// A changed video may now transcode, so any previous transcode error is forgotten.
+    /// Returns the changed videos so callers can discard other derived files, such as
+    /// thumbnails and transcoded videos.
+    pub fn mark_changed(&mut self, vids: &Vec<ScannedFile>) -> Result<Vec<Video>> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        let mut changed = Vec::new();
+
+        {
+            let mut update_stmt = tx.prepare_cached(
+                "UPDATE videos
+                SET
+                    metadata_version = 0,
+                    thumbnail_version = 0,
+                    is_broken = NULL,
+                    transcode_error = NULL,
+                    file_type = ?3
+                WHERE root_id = ?1
+                AND video_path_b64 = ?2
+                RETURNING
+                    video_id,
+                    root_id,
+                    video_path_b64,
+                    COALESCE(
+                        stream_created_ts,
+                        fs_created_ts,
+                        fs_modified_ts,
+                        insert_ts
+                    ) AS ordering_ts,
+                    duration_millis,
+                    video_codec,
+                    transcoded_path,
+                    file_type",
+            )?;
+
+            for scanned_file in vids {
+                if let ScannedFile::Video(path, file_type) = scanned_file {
+                    let (root_id, video_path) = self.library_roots.to_relative_path(path)?;
+                    let video_path_b64 = path_encoding::to_base64(video_path);
+
+                    let videos = update_stmt
+                        .query_map(
+                            params![root_id.id(), video_path_b64, file_type.as_ref()],
+                            |row| self.to_video(row),
+                        )?
+                        .flatten();
+
+                    changed.extend(videos);
+                } else {
+                    error!("Expected a video, but got: {:?}", scanned_file);
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Gets videos that the library scanner no longer has a record of.
+    /// These are candidates for removal, but callers should check that each video
+    /// really is absent from the file system before removing it.
+    pub fn find_vanished(&self) -> Result<Vec<Video>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                    video_id,
+                    videos.root_id,
+                    video_path_b64,
+                    COALESCE(
+                        videos.stream_created_ts,
+                        videos.fs_created_ts,
+                        videos.fs_modified_ts,
+                        videos.insert_ts
+                    ) AS ordering_ts,
+                    duration_millis,
+                    video_codec,
+                    transcoded_path,
+                    file_type
+                FROM videos
+                LEFT OUTER JOIN scanned_files
+                    ON scanned_files.root_id = videos.root_id
+                    AND scanned_files.file_path_b64 = videos.video_path_b64
+                WHERE scanned_files.file_path_b64 IS NULL",
+        )?;
+
+        // Don't treat videos as vanished if their library root is missing, because
+        // the library root is likely on a disk that isn't mounted.
+        let result = stmt.query_map([], |row| self.to_video(row))?;
+        let result = result
+            .flatten()
+            .filter(|vid| {
+                self.library_roots
+                    .relativize(vid.sandbox_path())
+                    .is_some_and(|(root, _)| root.path.exists())
+            })
+            .collect();
+        Ok(result)
+    }
+
+    /// Gets videos in library roots that have been removed from the library.
+    pub fn find_orphaned(&self) -> Result<Vec<VideoId>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                    videos.video_id
+                FROM videos
+                LEFT OUTER JOIN library_roots USING (root_id)
+                WHERE library_roots.root_id IS NULL",
+        )?;
+
+        let result = stmt.query_map([], |row| row.get("video_id").map(VideoId::new))?;
+        let result = result.flatten().collect();
+        Ok(result)
+    }
+
     /// Gets all videos in the repository, in ascending order of modification timestamp.
     pub fn all(&self) -> Result<Vec<Video>> {
         let con = self.con.lock().unwrap();
         let mut stmt = con.prepare(
             "SELECT
                     video_id,
+                    videos.root_id,
                     video_path_b64,
                     COALESCE(
                         videos.stream_created_ts,
@@ -237,7 +501,8 @@ impl Repository {
                     ) AS ordering_ts,
                     duration_millis,
                     video_codec,
-                    transcoded_path
+                    transcoded_path,
+                    file_type
                 FROM videos
                 WHERE COALESCE(is_broken, FALSE) IS FALSE
                 ORDER BY ordering_ts ASC",
@@ -254,6 +519,7 @@ impl Repository {
         let mut stmt = con.prepare(
             "SELECT
                     video_id,
+                    videos.root_id,
                     video_path_b64,
                     COALESCE(
                         videos.stream_created_ts,
@@ -263,7 +529,8 @@ impl Repository {
                     ) AS ordering_ts,
                     duration_millis,
                     video_codec,
-                    transcoded_path
+                    transcoded_path,
+                    file_type
                 FROM videos
                 WHERE metadata_version < ?1
                 AND COALESCE(is_broken, FALSE) IS FALSE
@@ -275,6 +542,36 @@ impl Repository {
         Ok(result)
     }
 
+    /// Gets videos with thumbnails from an older version of the thumbnail generation,
+    /// or with no thumbnails at all.
+    pub fn find_need_thumbnail_update(&self) -> Result<Vec<Video>> {
+        let con = self.con.lock().unwrap();
+        let mut stmt = con.prepare(
+            "SELECT
+                    video_id,
+                    videos.root_id,
+                    video_path_b64,
+                    COALESCE(
+                        videos.stream_created_ts,
+                        videos.fs_created_ts,
+                        videos.fs_modified_ts,
+                        videos.insert_ts
+                    ) AS ordering_ts,
+                    duration_millis,
+                    video_codec,
+                    transcoded_path,
+                    file_type
+                FROM videos
+                WHERE thumbnail_version < ?1
+                AND COALESCE(is_broken, FALSE) IS FALSE
+                ORDER BY ordering_ts ASC",
+        )?;
+
+        let result = stmt.query_map([thumbnailer::VERSION], |row| self.to_video(row))?;
+        let result = result.flatten().collect();
+        Ok(result)
+    }
+
     /// Gets paths of files to delete when a video is no longer present.
     pub fn find_files_to_cleanup(&self, video_id: VideoId) -> Result<Vec<PathBuf>> {
         let con = self.con.lock().unwrap();
@@ -292,11 +589,17 @@ impl Repository {
     fn to_video(&self, row: &Row<'_>) -> rusqlite::Result<Video> {
         let video_id = row.get("video_id").map(VideoId::new)?;
 
+        let root_id = row.get("root_id").map(LibraryRootId::new)?;
+
         let relative_path: String = row.get("video_path_b64")?;
-        let relative_path = path_encoding::from_base64(&relative_path)
-            .map_err(|_| rusqlite::Error::InvalidQuery)?;
-        let sandbox_path = self.library_base_dir.sandbox_path.join(&relative_path);
-        let host_path = self.library_base_dir.host_path.join(&relative_path);
+
+        // Videos in removed library roots can't be resolved.
+        let path = self
+            .library_roots
+            .resolve_base64(root_id, &relative_path)
+            .ok()
+            .flatten()
+            .ok_or(rusqlite::Error::InvalidQuery)?;
 
         let ordering_ts = row.get("ordering_ts").expect("must have ordering_ts");
 
@@ -312,16 +615,43 @@ impl Repository {
             .map(|p: String| self.cache_dir_base_path.join(p))
             .ok();
 
+        let file_type = row
+            .get("file_type")
+            .ok()
+            .and_then(|x: String| FileType::from_str(&x).ok());
+
         std::result::Result::Ok(Video {
             video_id,
-            path: FlatpakPathBuf::build(host_path, sandbox_path),
+            path,
             ordering_ts,
             stream_duration,
             video_codec,
             transcoded_path,
+            file_type,
         })
     }
 
+    /// Path without suffix so sibling pictures and videos can be related
+    /// Some Apple photo exports name the video component after the
+    /// picture component, so you get names like 'img_1234.heic.mp4' instead
+    /// of 'img_1234.mp4'. After removing the file suffix, check if a supported
+    /// picture suffix is now present and remove it.
+    fn to_link_path(video_path: &Path) -> PathBuf {
+        let link_path = match video_path.file_stem() {
+            Some(stem)
+                if FileType::from_extension(stem.as_ref()).is_some_and(|t| t.is_picture()) =>
+            {
+                let path: &Path = stem.as_ref();
+                path.file_stem()
+            }
+            any => any,
+        };
+
+        let link_path = link_path.and_then(|x| x.to_str()).expect("Must exist");
+
+        video_path.with_file_name(link_path)
+    }
+
     fn to_cleanup_path(&self, row: &Row<'_>) -> rusqlite::Result<PathBuf> {
         let root_name: String = row.get("root_name")?;
 
@@ -0,0 +1,259 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Sprite sheets of evenly spaced frames, for previewing a video by scrubbing
+//! over it without playing it.
+//!
+//! A sprite sheet is a JPEG of frames tiled left-to-right, top-to-bottom, along
+//! with a small text index of the layout and the timestamp of each frame.
+
+use crate::FlatpakPathBuf;
+use crate::thumbnailify;
+use crate::video::frames::FrameReader;
+
+use anyhow::*;
+use chrono::TimeDelta;
+use image::{ImageFormat, RgbImage, imageops};
+use std::path::Path;
+use std::result::Result::Ok;
+use tracing::debug;
+
+/// Number of frames in a sprite sheet.
+const FRAME_COUNT: i32 = 25;
+
+/// Number of frames in each row of a sprite sheet.
+const COLUMNS: u32 = 5;
+
+/// Longest edge of a frame in a sprite sheet.
+const FRAME_SIZE: u32 = 160;
+
+/// Layout of a sprite sheet image and the timestamp of each frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteSheet {
+    /// Number of frames in each row.
+    pub columns: u32,
+
+    /// Width of each frame in pixels.
+    pub frame_width: u32,
+
+    /// Height of each frame in pixels.
+    pub frame_height: u32,
+
+    /// Position in the video of each frame, in the order the frames are tiled.
+    pub timestamps: Vec<TimeDelta>,
+}
+
+impl SpriteSheet {
+    /// Loads the index of a sprite sheet.
+    pub fn load(index_path: &Path) -> Result<SpriteSheet> {
+        let index = std::fs::read_to_string(index_path)?;
+        Self::parse(&index)
+    }
+
+    /// Index of the frame to show for a fraction of the way through the video,
+    /// such as the position of the pointer across a thumbnail.
+    pub fn frame_at_fraction(&self, fraction: f64) -> Option<usize> {
+        if self.timestamps.is_empty() {
+            return None;
+        }
+        let index = (fraction.clamp(0.0, 1.0) * self.timestamps.len() as f64) as usize;
+        Some(index.min(self.timestamps.len() - 1))
+    }
+
+    /// Index of the frame nearest to a position in the video.
+    pub fn frame_at(&self, position: TimeDelta) -> Option<usize> {
+        self.timestamps
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, ts)| (**ts - position).abs())
+            .map(|(index, _)| index)
+    }
+
+    /// Position and size of a frame in the sprite sheet image, as (x, y, width, height).
+    pub fn frame_rect(&self, index: usize) -> (u32, u32, u32, u32) {
+        let index = index as u32;
+        let x = (index % self.columns) * self.frame_width;
+        let y = (index / self.columns) * self.frame_height;
+        (x, y, self.frame_width, self.frame_height)
+    }
+
+    /// Index format is a line of "columns frame_width frame_height" followed
+    /// by the timestamp of each frame in milliseconds, one per line.
+    fn parse(index: &str) -> Result<SpriteSheet> {
+        let mut lines = index.lines();
+
+        let layout: Vec<u32> = lines
+            .next()
+            .ok_or_else(|| anyhow!("Empty sprite sheet index"))?
+            .split_whitespace()
+            .map(|x| x.parse::<u32>())
+            .collect::<std::result::Result<_, _>>()?;
+
+        let [columns, frame_width, frame_height] = layout[..] else {
+            bail!("Invalid sprite sheet layout: {:?}", layout);
+        };
+
+        if columns == 0 {
+            bail!("Sprite sheet has no columns");
+        }
+
+        let timestamps = lines
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(TimeDelta::milliseconds(line.trim().parse::<i64>()?)))
+            .collect::<Result<Vec<TimeDelta>>>()?;
+
+        Ok(SpriteSheet {
+            columns,
+            frame_width,
+            frame_height,
+            timestamps,
+        })
+    }
+
+    fn to_index(&self) -> String {
+        let mut index = format!(
+            "{} {} {}\n",
+            self.columns, self.frame_width, self.frame_height
+        );
+        for ts in &self.timestamps {
+            index.push_str(&format!("{}\n", ts.num_milliseconds()));
+        }
+        index
+    }
+}
+
+/// Generates sprite sheets for videos. Sprite sheets are stored alongside thumbnails.
+#[derive(Debug, Clone)]
+pub struct SpriteSheetGenerator {
+    thumbnailer: thumbnailify::Thumbnailer,
+}
+
+impl SpriteSheetGenerator {
+    pub fn build(thumbnailer: thumbnailify::Thumbnailer) -> Result<SpriteSheetGenerator> {
+        Ok(SpriteSheetGenerator { thumbnailer })
+    }
+
+    /// Whether a video already has a sprite sheet.
+    pub fn exists(&self, path: &FlatpakPathBuf) -> bool {
+        let hash = path.thumbnail_hash();
+        self.thumbnailer.get_sprite_sheet_index_path(&hash).exists()
+    }
+
+    /// Generates a sprite sheet for a video.
+    pub fn generate(&self, path: &FlatpakPathBuf) -> Result<()> {
+        let hash = path.thumbnail_hash();
+        let image_path = self.thumbnailer.get_sprite_sheet_path(&hash);
+        let index_path = self.thumbnailer.get_sprite_sheet_index_path(&hash);
+
+        let mut reader = FrameReader::open(&path.sandbox_path, Some(FRAME_SIZE))?;
+
+        // Take frames from the middle of evenly sized segments of the video so that
+        // the first and last frames aren't black fades.
+        // Duration is unknown for some containers, in which case only the first frame
+        // can be used.
+        let timestamps: Vec<TimeDelta> = match reader.duration() {
+            Some(duration) => (0..FRAME_COUNT)
+                .map(|i| duration * (2 * i + 1) / (2 * FRAME_COUNT))
+                .collect(),
+            None => vec![TimeDelta::zero()],
+        };
+
+        let mut frames: Vec<(TimeDelta, RgbImage)> = Vec::with_capacity(timestamps.len());
+        for ts in timestamps {
+            match reader.read_at(ts)? {
+                Some(frame) => frames.push((ts, frame)),
+                None => break,
+            }
+        }
+
+        let Some((_, first)) = frames.first() else {
+            bail!("No frames decoded for {:?}", path.sandbox_path);
+        };
+
+        let sheet = SpriteSheet {
+            columns: COLUMNS.min(frames.len() as u32),
+            frame_width: first.width(),
+            frame_height: first.height(),
+            timestamps: frames.iter().map(|(ts, _)| *ts).collect(),
+        };
+
+        let rows = (frames.len() as u32).div_ceil(sheet.columns);
+        let mut image = RgbImage::new(sheet.columns * sheet.frame_width, rows * sheet.frame_height);
+
+        for (index, (_, frame)) in frames.iter().enumerate() {
+            let (x, y, _, _) = sheet.frame_rect(index);
+            imageops::replace(&mut image, frame, x.into(), y.into());
+        }
+
+        if let Some(parent) = image_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        debug!(
+            "Writing sprite sheet of {} frames for {:?} to {:?}",
+            frames.len(),
+            path.sandbox_path,
+            image_path
+        );
+
+        image.save_with_format(&image_path, ImageFormat::Jpeg)?;
+
+        // Written last because the index marks the sprite sheet as complete.
+        std::fs::write(&index_path, sheet.to_index())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet() -> SpriteSheet {
+        SpriteSheet {
+            columns: 5,
+            frame_width: 160,
+            frame_height: 90,
+            timestamps: (0..7).map(|i| TimeDelta::seconds(i * 2 + 1)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let sheet = sheet();
+        let index = sheet.to_index();
+
+        assert!(index.starts_with("5 160 90\n1000\n3000\n"));
+        assert_eq!(sheet, SpriteSheet::parse(&index).unwrap());
+    }
+
+    #[test]
+    fn test_parse_invalid_index() {
+        assert!(SpriteSheet::parse("").is_err());
+        assert!(SpriteSheet::parse("5 160\n1000\n").is_err());
+        assert!(SpriteSheet::parse("0 160 90\n1000\n").is_err());
+    }
+
+    #[test]
+    fn test_frame_at() {
+        let sheet = sheet();
+
+        assert_eq!(Some(0), sheet.frame_at_fraction(0.0));
+        assert_eq!(Some(3), sheet.frame_at_fraction(0.5));
+        assert_eq!(Some(6), sheet.frame_at_fraction(1.0));
+
+        assert_eq!(Some(0), sheet.frame_at(TimeDelta::zero()));
+        assert_eq!(Some(2), sheet.frame_at(TimeDelta::milliseconds(5200)));
+        assert_eq!(Some(6), sheet.frame_at(TimeDelta::minutes(1)));
+    }
+
+    #[test]
+    fn test_frame_rect() {
+        let sheet = sheet();
+
+        assert_eq!((0, 0, 160, 90), sheet.frame_rect(0));
+        assert_eq!((640, 0, 160, 90), sheet.frame_rect(4));
+        assert_eq!((160, 90, 160, 90), sheet.frame_rect(6));
+    }
+}
@@ -4,21 +4,36 @@
 
 use crate::FlatpakPathBuf;
 use crate::thumbnailify;
-use crate::video::display_matrix::av_display_rotation_get;
+use crate::video::frames::FrameReader;
 
 use anyhow::*;
-use image::imageops;
-use image::{ImageBuffer, ImageFormat, ImageReader, RgbImage};
+use chrono::TimeDelta;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbImage};
 use std::path::Path;
 use std::result::Result::Ok;
-use tempfile;
+use tracing::debug;
 
-use ffmpeg::format::{Pixel, input};
-use ffmpeg::media::Type;
-use ffmpeg::software::scaling::{context::Context, flag::Flags};
-use ffmpeg::util::frame::video::Video;
-use ffmpeg_next as ffmpeg;
-use ffmpeg_next::frame::side_data::Type as SideDataType;
+/// Version of the video thumbnail generation.
+/// Bump this to regenerate the thumbnails of all videos.
+pub const VERSION: u32 = 2;
+
+/// Number of frames to consider for the thumbnail.
+const CANDIDATE_FRAMES: i32 = 8;
+
+/// Candidate frames are sampled from the start of a video, which is usually
+/// representative of the whole video, up to this percentage of the video...
+const SAMPLE_PERCENT: i32 = 25;
+
+/// ...and up to this duration.
+const MAX_SAMPLE_DURATION: TimeDelta = TimeDelta::seconds(10);
+
+/// Frames are scored at this size, which is plenty for judging exposure and focus.
+const SCORING_SIZE: u32 = 160;
+
+/// Frames with a mean luma outside of this range are black or white.
+const MIN_BRIGHTNESS: f64 = 16.0;
+const MAX_BRIGHTNESS: f64 = 240.0;
 
 /// Thumbnail operations for videos.
 #[derive(Debug, Clone)]
@@ -44,91 +59,162 @@ impl VideoThumbnailer {
     }
 
     pub fn thumbnail_internal(&self, path: &FlatpakPathBuf) -> Result<()> {
-        // Extract first frame of video for thumbnail
-
-        // Temporary output file for frame.
-        let temporary_png_file = tempfile::Builder::new().suffix(".png").tempfile()?;
-
-        // See https://docs.rs/ffmpeg-next/latest/src/dump_frames/dump-frames.rs.html
-        if let Ok(mut ictx) = input(path.sandbox_path.as_os_str()) {
-            let input = ictx
-                .streams()
-                .best(Type::Video)
-                .ok_or(ffmpeg::Error::StreamNotFound)?;
-
-            let video_stream_index = input.index();
-
-            let context_decoder =
-                ffmpeg::codec::context::Context::from_parameters(input.parameters())?;
-            let mut decoder = context_decoder.decoder().video()?;
-
-            let mut scaler = Context::get(
-                decoder.format(),
-                decoder.width(),
-                decoder.height(),
-                Pixel::RGB24,
-                decoder.width(),
-                decoder.height(),
-                Flags::BILINEAR,
-            )?;
-
-            // Lambda for decoding video
-            let mut receive_and_process_decoded_frames =
-                |decoder: &mut ffmpeg::decoder::Video| -> Result<(), ffmpeg::Error> {
-                    let mut decoded = Video::empty();
-                    if decoder.receive_frame(&mut decoded).is_ok() {
-                        // MatrixData contains rotation.
-                        let display_matrix = decoded.side_data(SideDataType::DisplayMatrix);
-                        let rotation = if let Some(display_matrix) = display_matrix {
-                            av_display_rotation_get(display_matrix.data())
-                        } else {
-                            f64::NAN
-                        };
-
-                        let mut rgb_frame = Video::empty();
-                        scaler.run(&decoded, &mut rgb_frame)?;
-                        Self::convert_rgb_to_png(&rgb_frame, rotation, temporary_png_file.path())
-                            .map_err(|_| ffmpeg::Error::Unknown)?;
-                    }
-                    Ok(())
-                };
-
-            for (stream, packet) in ictx.packets() {
-                if stream.index() == video_stream_index {
-                    // Note to self: can also get side data and display matrix
-                    // from packet side data.
-                    decoder.send_packet(&packet)?;
-                    receive_and_process_decoded_frames(&mut decoder)?;
+        let frame = Self::best_frame(&path.sandbox_path)?;
+
+        // Thumbnails from an older version are still up-to-date with the video file,
+        // so must be removed to be replaced.
+        self.thumbnailer.delete_thumbnails(path)?;
+
+        self.thumbnailer
+            .generate_all_thumbnails(path, DynamicImage::ImageRgb8(frame))?;
+
+        Ok(())
+    }
+
+    /// Decodes several frames from the start of a video and returns the one that will
+    /// make the best thumbnail.
+    /// The first frame of a video is often black, or blurry because the camera
+    /// was still focusing.
+    fn best_frame(video_path: &Path) -> Result<RgbImage> {
+        let mut reader = FrameReader::open(video_path, None)?;
+
+        // Duration is unknown for some containers, in which case only the
+        // first frame can be used.
+        let sample_duration = reader
+            .duration()
+            .map(|duration| (duration * SAMPLE_PERCENT / 100).min(MAX_SAMPLE_DURATION))
+            .unwrap_or_default();
+
+        let mut best: Option<(RgbImage, f64)> = None;
+
+        for candidate in 0..CANDIDATE_FRAMES {
+            let position = sample_duration * candidate / CANDIDATE_FRAMES;
+
+            let image = match reader.read_at(position) {
+                Ok(Some(image)) => image,
+                Ok(None) => break,
+                Err(e) if best.is_some() => {
+                    debug!("Stopped sampling {:?} at {}: {}", video_path, position, e);
                     break;
                 }
+                Err(e) => return Err(e),
+            };
+
+            let score = score(&scoring_image(&image));
+            debug!(
+                "Candidate thumbnail frame {} for {:?} has score {}",
+                candidate, video_path, score
+            );
+
+            if best
+                .as_ref()
+                .is_none_or(|(_, best_score)| score > *best_score)
+            {
+                best = Some((image, score));
             }
-            decoder.send_eof()?;
-            receive_and_process_decoded_frames(&mut decoder)?;
         }
 
-        let src_image = ImageReader::open(&temporary_png_file)?.decode()?;
+        best.map(|(image, _)| image)
+            .ok_or_else(|| anyhow!("No frames decoded for {:?}", video_path))
+    }
+}
 
-        let _ = self.thumbnailer.generate_all_thumbnails(path, src_image)?;
+/// Small greyscale copy of a frame for scoring.
+fn scoring_image(image: &RgbImage) -> GrayImage {
+    let image = DynamicImage::ImageRgb8(image.clone()).resize(
+        SCORING_SIZE,
+        SCORING_SIZE,
+        FilterType::Triangle,
+    );
+    image.to_luma8()
+}
 
-        Ok(())
+/// Scores how good a frame is as a thumbnail. Higher is better, and zero means the
+/// frame is unusable.
+/// A good frame is well exposed, has contrast, and is in focus. Sharpness is measured
+/// as the standard deviation of the Laplacian, which is low for blurry frames.
+fn score(image: &GrayImage) -> f64 {
+    let (width, height) = image.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
     }
 
-    fn convert_rgb_to_png(frame: &Video, rotation: f64, png_path: &Path) -> Result<()> {
-        let image_width = frame.width();
-        let image_height = frame.height();
-        let frame_bytes: Vec<u8> = frame.data(0).to_vec();
+    let count = f64::from(width * height);
+    let brightness = image.pixels().map(|p| f64::from(p.0[0])).sum::<f64>() / count;
+    if !(MIN_BRIGHTNESS..=MAX_BRIGHTNESS).contains(&brightness) {
+        return 0.0;
+    }
 
-        let buffer: RgbImage = ImageBuffer::from_raw(image_width, image_height, frame_bytes)
-            .expect("Video frame to image");
+    let contrast = (image
+        .pixels()
+        .map(|p| (f64::from(p.0[0]) - brightness).powi(2))
+        .sum::<f64>()
+        / count)
+        .sqrt();
+
+    let luma = |x: u32, y: u32| f64::from(image.get_pixel(x, y).0[0]);
+    let laplacians: Vec<f64> = (1..height - 1)
+        .flat_map(|y| (1..width - 1).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            luma(x - 1, y) + luma(x + 1, y) + luma(x, y - 1) + luma(x, y + 1) - 4.0 * luma(x, y)
+        })
+        .collect();
 
-        let buffer = match rotation {
-            90.0 => imageops::rotate90(&buffer),
-            180.0 | -180.0 => imageops::rotate180(&buffer),
-            -90.0 => imageops::rotate270(&buffer),
-            _ => buffer,
-        };
+    let laplacian_count = laplacians.len() as f64;
+    let laplacian_mean = laplacians.iter().sum::<f64>() / laplacian_count;
+    let sharpness = (laplacians
+        .iter()
+        .map(|l| (l - laplacian_mean).powi(2))
+        .sum::<f64>()
+        / laplacian_count)
+        .sqrt();
 
-        buffer.save_with_format(png_path, ImageFormat::Png)?;
-        Ok(())
+    // Mid-tones are best. Dark and bright frames are penalised, but not rejected.
+    let exposure = 1.0 - (brightness - 128.0).abs() / 128.0;
+
+    exposure * contrast * (1.0 + sharpness)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+    use image::imageops;
+
+    fn checkerboard(cell: u32) -> GrayImage {
+        GrayImage::from_fn(64, 64, |x, y| {
+            if (x / cell + y / cell) % 2 == 0 {
+                Luma([60])
+            } else {
+                Luma([190])
+            }
+        })
+    }
+
+    #[test]
+    fn test_score_rejects_black_frame() {
+        let black = GrayImage::from_pixel(64, 64, Luma([2]));
+        assert_eq!(0.0, score(&black));
+
+        let flat = GrayImage::from_pixel(64, 64, Luma([128]));
+        assert_eq!(0.0, score(&flat));
+
+        assert!(score(&checkerboard(8)) > 0.0);
+    }
+
+    #[test]
+    fn test_score_prefers_sharp_frame() {
+        let sharp = checkerboard(8);
+        let blurry = imageops::blur(&sharp, 4.0);
+
+        assert!(score(&sharp) > score(&blurry));
+    }
+
+    #[test]
+    fn test_score_prefers_mid_tones() {
+        let dark = GrayImage::from_fn(64, 64, |x, y| Luma([20 + ((x + y) % 2) as u8 * 10]));
+        let mid = GrayImage::from_fn(64, 64, |x, y| Luma([120 + ((x + y) % 2) as u8 * 10]));
+
+        assert!(score(&mid) > score(&dark));
     }
 }
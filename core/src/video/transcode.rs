@@ -4,12 +4,103 @@
 
 use anyhow::*;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::result::Result::Ok;
+use std::sync::atomic::{AtomicBool, Ordering};
+use strum::{AsRefStr, EnumIter, EnumString, FromRepr};
+use thiserror::Error;
 
 use crate::video::VideoId;
+use crate::video::metadata as video_metadata;
+
+use ffmpeg::Rational;
+use ffmpeg::media::Type;
+use ffmpeg::util::frame::video::Video;
+use ffmpeg_next as ffmpeg;
 
 use tracing::{Level, event};
 
+/// Time base of filtered frames, and so of the encoder.
+const ENCODER_TIME_BASE: (i32, i32) = (1, 1000);
+
+#[derive(Debug, Error)]
+pub enum TranscodeError {
+    #[error("Transcode cancelled")]
+    Cancelled,
+}
+
+/// Video codec to transcode to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString, EnumIter, FromRepr)]
+#[strum(serialize_all = "snake_case")]
+#[repr(u32)]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    Vp9,
+    Av1,
+}
+
+impl VideoCodec {
+    fn encoder(&self) -> Option<ffmpeg::Codec> {
+        match self {
+            VideoCodec::H264 => ffmpeg::encoder::find_by_name("libx264")
+                .or_else(|| ffmpeg::encoder::find(ffmpeg::codec::Id::H264)),
+            VideoCodec::Vp9 => ffmpeg::encoder::find_by_name("libvpx-vp9")
+                .or_else(|| ffmpeg::encoder::find(ffmpeg::codec::Id::VP9)),
+            VideoCodec::Av1 => ffmpeg::encoder::find_by_name("libsvtav1")
+                .or_else(|| ffmpeg::encoder::find_by_name("libaom-av1"))
+                .or_else(|| ffmpeg::encoder::find(ffmpeg::codec::Id::AV1)),
+        }
+    }
+
+    /// Constant rate factor for a quality. Each codec has its own scale.
+    fn crf(&self, quality: TranscodeQuality) -> u32 {
+        match (self, quality) {
+            (VideoCodec::H264, TranscodeQuality::Low) => 28,
+            (VideoCodec::H264, TranscodeQuality::Medium) => 23,
+            (VideoCodec::H264, TranscodeQuality::High) => 18,
+            (VideoCodec::Vp9, TranscodeQuality::Low) => 40,
+            (VideoCodec::Vp9, TranscodeQuality::Medium) => 33,
+            (VideoCodec::Vp9, TranscodeQuality::High) => 24,
+            (VideoCodec::Av1, TranscodeQuality::Low) => 40,
+            (VideoCodec::Av1, TranscodeQuality::Medium) => 32,
+            (VideoCodec::Av1, TranscodeQuality::High) => 24,
+        }
+    }
+
+    fn encoder_options(&self, quality: TranscodeQuality) -> Vec<(&'static str, String)> {
+        let mut options = vec![("crf", self.crf(quality).to_string())];
+        if *self == VideoCodec::Vp9 {
+            // Constant quality mode needs the bit rate to be unconstrained.
+            options.push(("b:v", "0".into()));
+            options.push(("row-mt", "1".into()));
+        }
+        options
+    }
+}
+
+/// Quality target for transcoded videos.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString, EnumIter, FromRepr)]
+#[strum(serialize_all = "snake_case")]
+#[repr(u32)]
+pub enum TranscodeQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+/// How to transcode a video.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TranscodeProfile {
+    pub codec: VideoCodec,
+
+    /// Maximum width and height in pixels, preserving the aspect ratio.
+    /// Smaller videos are never scaled up.
+    pub max_size: Option<u32>,
+
+    pub quality: TranscodeQuality,
+}
+
 #[derive(Debug, Clone)]
 pub struct Transcoder {
     /// Base path for storing transcoded videos
@@ -24,7 +115,15 @@ impl Transcoder {
     }
 
     /// Transcodes the video at 'path' and returns a path to the transcoded video.
-    pub fn transcode(&self, video_id: VideoId, video_path: &Path) -> Result<PathBuf> {
+    /// Progress is reported as a fraction of the video transcoded.
+    pub fn transcode(
+        &self,
+        video_id: VideoId,
+        video_path: &Path,
+        profile: &TranscodeProfile,
+        stop: &AtomicBool,
+        progress: &mut dyn FnMut(f64),
+    ) -> Result<PathBuf> {
         let transcoded_path = {
             // Create a directory per 1000 videos
             let partition = (video_id.id() / 1000) as i32;
@@ -33,39 +132,463 @@ impl Transcoder {
             self.base_path.join(partition).join(file_name)
         };
 
-        transcode(video_path, &transcoded_path)?;
+        transcode(video_path, &transcoded_path, profile, stop, progress)?;
 
         Ok(transcoded_path)
     }
 }
 
-pub fn transcode(video_path: &Path, transcoded_path: &Path) -> Result<()> {
+/// Transcodes a video to a Matroska file, copying the audio as is.
+/// The video is written to a temporary file that is only moved to `transcoded_path`
+/// once transcoding has succeeded.
+pub fn transcode(
+    video_path: &Path,
+    transcoded_path: &Path,
+    profile: &TranscodeProfile,
+    stop: &AtomicBool,
+    progress: &mut dyn FnMut(f64),
+) -> Result<()> {
     if transcoded_path.exists() {
         return Ok(());
     } else if let Some(p) = transcoded_path.parent() {
         let _ = std::fs::create_dir_all(p);
     }
 
-    event!(Level::DEBUG, "Transcoding video: {:?}", video_path);
+    event!(
+        Level::DEBUG,
+        "Transcoding video to {}: {:?}",
+        profile.codec.as_ref(),
+        video_path
+    );
+
+    let rotation = video_metadata::from_path(video_path)
+        .ok()
+        .and_then(|meta| meta.rotation);
+
+    let mut filters: Vec<String> = rotation_filter(rotation).into_iter().collect();
+    filters.push(
+        profile
+            .max_size
+            .map(scale_filter)
+            .unwrap_or_else(|| EVEN_SIZE_FILTER.into()),
+    );
+    filters.push("format=yuv420p".into());
+
+    let encoding = Encoding {
+        muxer: "matroska",
+        codec: profile.codec.encoder(),
+        encoder_options: profile.codec.encoder_options(profile.quality),
+        muxer_options: vec![],
+        filter_spec: filters.join(","),
+        frame_rate: None,
+        copy_audio: true,
+    };
 
     let temporary_transcoded_path = transcoded_path.with_extension("tmp.mkv");
 
-    Command::new("ffmpeg")
-        .arg("-y")
-        .arg("-loglevel")
-        .arg("error")
-        .arg("-hwaccel")
-        .arg("auto")
-        .arg("-i")
-        .arg(video_path.as_os_str())
-        .arg("-c:a")
-        .arg("copy")
-        .arg("-c:v")
-        .arg("h264")
-        .arg(temporary_transcoded_path.as_os_str())
-        .status()?;
+    let result = encode(
+        video_path,
+        &temporary_transcoded_path,
+        &encoding,
+        stop,
+        progress,
+    );
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temporary_transcoded_path);
+        return Err(e);
+    }
 
     std::fs::rename(&temporary_transcoded_path, transcoded_path)?;
 
     Ok(())
 }
+
+/// Rounds dimensions down to even numbers, which 4:2:0 chroma subsampling needs.
+pub(crate) const EVEN_SIZE_FILTER: &str = "scale=trunc(iw/2)*2:trunc(ih/2)*2";
+
+/// Filter to scale a video to fit within a square, preserving the aspect ratio
+/// and never scaling up.
+pub(crate) fn scale_filter(max_size: u32) -> String {
+    format!(
+        "scale=w='min({0},iw)':h='min({0},ih)':force_original_aspect_ratio=decrease:force_divisible_by=2",
+        max_size
+    )
+}
+
+/// Decoded frames aren't rotated, so apply the display matrix rotation
+/// in the same way as the ffmpeg CLI does.
+pub(crate) fn rotation_filter(rotation: Option<i32>) -> Option<&'static str> {
+    match rotation.map(|x| (-x).rem_euclid(360)) {
+        Some(90) => Some("transpose=clock"),
+        Some(180) => Some("hflip,vflip"),
+        Some(270) => Some("transpose=cclock"),
+        _ => None,
+    }
+}
+
+/// How to encode the video stream of a file.
+pub(crate) struct Encoding {
+    /// FFmpeg muxer name.
+    pub muxer: &'static str,
+
+    pub codec: Option<ffmpeg::Codec>,
+
+    pub encoder_options: Vec<(&'static str, String)>,
+
+    pub muxer_options: Vec<(&'static str, String)>,
+
+    /// FFmpeg filter graph description applied to decoded frames.
+    pub filter_spec: String,
+
+    /// Frame rate of filtered frames, if the filter changes it.
+    pub frame_rate: Option<Rational>,
+
+    /// Copy the audio stream without re-encoding it.
+    pub copy_audio: bool,
+}
+
+/// Decodes the video stream of a file, filters it, and encodes it into a new file.
+/// Progress is reported as a fraction of the video processed.
+pub(crate) fn encode(
+    input_path: &Path,
+    output_path: &Path,
+    encoding: &Encoding,
+    stop: &AtomicBool,
+    progress: &mut dyn FnMut(f64),
+) -> Result<()> {
+    let mut ictx = ffmpeg::format::input(&input_path)?;
+    let input = ictx
+        .streams()
+        .best(Type::Video)
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+
+    let video_stream_index = input.index();
+    let input_time_base = input.time_base();
+    let input_frame_rate = input.avg_frame_rate();
+    let duration = input.duration() as f64 * f64::from(input_time_base);
+
+    let context_decoder = ffmpeg::codec::context::Context::from_parameters(input.parameters())?;
+    let mut decoder = context_decoder.decoder().video()?;
+
+    let audio = if encoding.copy_audio {
+        ictx.streams().best(Type::Audio).map(|stream| AudioCopy {
+            input_index: stream.index(),
+            input_time_base: stream.time_base(),
+            parameters: stream.parameters(),
+            output: None,
+            pending: Vec::new(),
+        })
+    } else {
+        None
+    };
+
+    let mut filter_spec = encoding.filter_spec.clone();
+    filter_spec.push_str(&format!(
+        ",settb={}/{}",
+        ENCODER_TIME_BASE.0, ENCODER_TIME_BASE.1
+    ));
+    let mut graph = filter_graph(&decoder, input_time_base, &filter_spec)?;
+
+    let frame_rate = encoding
+        .frame_rate
+        .or_else(|| Some(input_frame_rate).filter(|x| x.numerator() > 0))
+        .unwrap_or(Rational::new(30, 1));
+
+    let mut output = Output {
+        octx: ffmpeg::format::output_as(output_path, encoding.muxer)?,
+        encoding,
+        frame_rate,
+        encoder: None,
+        audio,
+    };
+
+    for (stream, packet) in ictx.packets() {
+        if stop.load(Ordering::Relaxed) {
+            return Err(TranscodeError::Cancelled.into());
+        }
+
+        if stream.index() == video_stream_index {
+            decoder.send_packet(&packet)?;
+            decode(&mut decoder, &mut graph, &mut output)?;
+
+            if duration > 0.0
+                && let Some(pts) = packet.pts()
+            {
+                let fraction = pts as f64 * f64::from(input_time_base) / duration;
+                progress(fraction.clamp(0.0, 1.0));
+            }
+        } else {
+            output.copy_audio(stream.index(), packet)?;
+        }
+    }
+
+    decoder.send_eof()?;
+    decode(&mut decoder, &mut graph, &mut output)?;
+
+    graph
+        .get("in")
+        .ok_or(ffmpeg::Error::FilterNotFound)?
+        .source()
+        .flush()?;
+    filter(&mut graph, &mut output)?;
+
+    output.finish()?;
+    progress(1.0);
+
+    Ok(())
+}
+
+fn filter_graph(
+    decoder: &ffmpeg::decoder::Video,
+    time_base: Rational,
+    spec: &str,
+) -> Result<ffmpeg::filter::Graph> {
+    let pixel_format = decoder
+        .format()
+        .descriptor()
+        .map(|x| x.name())
+        .ok_or(ffmpeg::Error::InvalidData)?;
+
+    let aspect_ratio = Some(decoder.aspect_ratio())
+        .filter(|x| x.numerator() > 0)
+        .unwrap_or(Rational::new(1, 1));
+
+    let args = format!(
+        "video_size={}x{}:pix_fmt={}:time_base={}/{}:pixel_aspect={}/{}",
+        decoder.width(),
+        decoder.height(),
+        pixel_format,
+        time_base.numerator(),
+        time_base.denominator(),
+        aspect_ratio.numerator(),
+        aspect_ratio.denominator()
+    );
+
+    let buffer = ffmpeg::filter::find("buffer").ok_or(ffmpeg::Error::FilterNotFound)?;
+    let buffer_sink = ffmpeg::filter::find("buffersink").ok_or(ffmpeg::Error::FilterNotFound)?;
+
+    let mut graph = ffmpeg::filter::Graph::new();
+    graph.add(&buffer, "in", &args)?;
+    graph.add(&buffer_sink, "out", "")?;
+    graph.output("in", 0)?.input("out", 0)?.parse(spec)?;
+    graph.validate()?;
+
+    Ok(graph)
+}
+
+/// Sends decoded frames through the filter graph.
+fn decode(
+    decoder: &mut ffmpeg::decoder::Video,
+    graph: &mut ffmpeg::filter::Graph,
+    output: &mut Output,
+) -> Result<()> {
+    let mut decoded = Video::empty();
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        decoded.set_pts(decoded.timestamp());
+        graph
+            .get("in")
+            .ok_or(ffmpeg::Error::FilterNotFound)?
+            .source()
+            .add(&decoded)?;
+        filter(graph, output)?;
+    }
+    Ok(())
+}
+
+/// Sends filtered frames to the encoder.
+fn filter(graph: &mut ffmpeg::filter::Graph, output: &mut Output) -> Result<()> {
+    let mut filtered = Video::empty();
+    while graph
+        .get("out")
+        .ok_or(ffmpeg::Error::FilterNotFound)?
+        .sink()
+        .frame(&mut filtered)
+        .is_ok()
+    {
+        output.encode(&filtered)?;
+    }
+    Ok(())
+}
+
+/// Audio stream copied as is.
+struct AudioCopy {
+    input_index: usize,
+    input_time_base: Rational,
+    parameters: ffmpeg::codec::Parameters,
+
+    /// Index and time base of the output stream, once the output has been opened.
+    output: Option<(usize, Rational)>,
+
+    /// Packets read before the output was opened.
+    pending: Vec<ffmpeg::Packet>,
+}
+
+/// Encoded file. The encoder can't be opened until the size and pixel format of
+/// filtered frames are known, so is opened when the first frame arrives.
+struct Output<'a> {
+    octx: ffmpeg::format::context::Output,
+    encoding: &'a Encoding,
+    frame_rate: Rational,
+
+    /// Encoder, and the index and time base of the output stream.
+    encoder: Option<(ffmpeg::encoder::Video, usize, Rational)>,
+
+    audio: Option<AudioCopy>,
+}
+
+impl Output<'_> {
+    fn open(&mut self, frame: &Video) -> Result<()> {
+        let codec = self.encoding.codec.ok_or(ffmpeg::Error::EncoderNotFound)?;
+
+        let global_header = self
+            .octx
+            .format()
+            .flags()
+            .contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+
+        let mut ost = self.octx.add_stream(codec)?;
+
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()?;
+        encoder.set_width(frame.width());
+        encoder.set_height(frame.height());
+        encoder.set_format(frame.format());
+        encoder.set_aspect_ratio(frame.aspect_ratio());
+        encoder.set_time_base(ENCODER_TIME_BASE);
+        encoder.set_frame_rate(Some(self.frame_rate));
+
+        if global_header {
+            encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+        }
+
+        let mut encoder_options = ffmpeg::Dictionary::new();
+        for (key, value) in &self.encoding.encoder_options {
+            encoder_options.set(key, value);
+        }
+
+        let encoder = encoder.open_with(encoder_options)?;
+        ost.set_parameters(&encoder);
+        let video_index = ost.index();
+
+        let audio_index = if let Some(audio) = self.audio.as_ref() {
+            let mut ost = self
+                .octx
+                .add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))?;
+            ost.set_parameters(audio.parameters.clone());
+
+            // Codec tags are container specific, so let the muxer choose.
+            unsafe {
+                (*ost.parameters().as_mut_ptr()).codec_tag = 0;
+            }
+            Some(ost.index())
+        } else {
+            None
+        };
+
+        let mut muxer_options = ffmpeg::Dictionary::new();
+        for (key, value) in &self.encoding.muxer_options {
+            muxer_options.set(key, value);
+        }
+
+        self.octx.write_header_with(muxer_options)?;
+
+        let time_base = |octx: &ffmpeg::format::context::Output, index| {
+            octx.stream(index)
+                .map(|stream| stream.time_base())
+                .ok_or(ffmpeg::Error::StreamNotFound)
+        };
+
+        self.encoder = Some((encoder, video_index, time_base(&self.octx, video_index)?));
+
+        if let Some(audio_index) = audio_index
+            && let Some(audio) = self.audio.as_mut()
+        {
+            audio.output = Some((audio_index, time_base(&self.octx, audio_index)?));
+            for packet in std::mem::take(&mut audio.pending) {
+                self.copy_audio(audio_index, packet)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn copy_audio(&mut self, stream_index: usize, mut packet: ffmpeg::Packet) -> Result<()> {
+        let Some(audio) = self.audio.as_mut() else {
+            return Ok(());
+        };
+
+        if stream_index != audio.input_index {
+            return Ok(());
+        }
+
+        let Some((output_index, output_time_base)) = audio.output else {
+            audio.pending.push(packet);
+            return Ok(());
+        };
+
+        packet.rescale_ts(audio.input_time_base, output_time_base);
+        packet.set_position(-1);
+        packet.set_stream(output_index);
+        packet.write_interleaved(&mut self.octx)?;
+        Ok(())
+    }
+
+    fn encode(&mut self, frame: &Video) -> Result<()> {
+        if self.encoder.is_none() {
+            self.open(frame)?;
+        }
+
+        if let Some((encoder, _, _)) = self.encoder.as_mut() {
+            encoder.send_frame(frame)?;
+        }
+        self.write_packets()
+    }
+
+    fn write_packets(&mut self) -> Result<()> {
+        let Some((encoder, stream_index, stream_time_base)) = self.encoder.as_mut() else {
+            return Ok(());
+        };
+
+        let mut packet = ffmpeg::Packet::empty();
+        while encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(*stream_index);
+            packet.rescale_ts(ENCODER_TIME_BASE, *stream_time_base);
+            packet.write_interleaved(&mut self.octx)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        let Some((encoder, _, _)) = self.encoder.as_mut() else {
+            bail!("No video frames to encode");
+        };
+
+        encoder.send_eof()?;
+        self.write_packets()?;
+        self.octx.write_trailer()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_filter() {
+        assert_eq!(Some("transpose=clock"), rotation_filter(Some(-90)));
+        assert_eq!(Some("transpose=cclock"), rotation_filter(Some(90)));
+        assert_eq!(Some("hflip,vflip"), rotation_filter(Some(180)));
+        assert_eq!(None, rotation_filter(Some(0)));
+        assert_eq!(None, rotation_filter(None));
+    }
+
+    #[test]
+    fn test_codec_from_str() {
+        assert_eq!(VideoCodec::H264, "h264".parse().unwrap());
+        assert_eq!(VideoCodec::Vp9, "vp9".parse().unwrap());
+        assert_eq!(VideoCodec::Av1, "av1".parse().unwrap());
+    }
+}
@@ -0,0 +1,229 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Exports the video part of a live photo or motion photo as a standalone file.
+
+use crate::photo::motion_photo;
+use crate::video::metadata as video_metadata;
+use crate::video::transcode::{self, Encoding};
+use crate::visual::model::Visual;
+
+use anyhow::*;
+use chrono::TimeDelta;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use strum::{AsRefStr, EnumIter, EnumString};
+use tracing::debug;
+
+use ffmpeg::Rational;
+use ffmpeg_next as ffmpeg;
+
+/// Frame rate of exported GIFs. GIFs are large, so there is no point keeping the
+/// full frame rate of the video.
+const GIF_FRAME_RATE: u32 = 15;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum ExportFormat {
+    /// H.264 MP4 video.
+    #[default]
+    Mp4,
+
+    /// Looping animated GIF.
+    Gif,
+
+    /// Looping animated WebP.
+    #[strum(serialize = "webp")]
+    WebP,
+}
+
+impl ExportFormat {
+    /// File name extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Mp4 => "mp4",
+            ExportFormat::Gif => "gif",
+            ExportFormat::WebP => "webp",
+        }
+    }
+
+    /// FFmpeg muxer name for the format.
+    fn muxer(&self) -> &'static str {
+        self.extension()
+    }
+
+    fn encoder(&self) -> Option<ffmpeg::Codec> {
+        match self {
+            ExportFormat::Mp4 => ffmpeg::encoder::find_by_name("libx264")
+                .or_else(|| ffmpeg::encoder::find(ffmpeg::codec::Id::H264)),
+            ExportFormat::Gif => ffmpeg::encoder::find(ffmpeg::codec::Id::GIF),
+            ExportFormat::WebP => ffmpeg::encoder::find_by_name("libwebp_anim"),
+        }
+    }
+}
+
+/// How to export a live photo.
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+
+    /// Start of the exported clip. Defaults to the start of the video.
+    pub start: Option<TimeDelta>,
+
+    /// End of the exported clip. Defaults to the end of the video.
+    pub end: Option<TimeDelta>,
+
+    /// Maximum width and height of the exported clip in pixels, preserving the
+    /// aspect ratio. Defaults to the size of the video.
+    pub max_size: Option<u32>,
+}
+
+/// Exports the video part of a live photo to `output_path`.
+/// Audio is not exported because GIFs and WebPs can't have any, and the short clips
+/// of live photos are meant to be shared as silent loops.
+pub fn export(visual: &Visual, options: &ExportOptions, output_path: &Path) -> Result<()> {
+    if !visual.is_live_photo {
+        bail!("{} is not a live photo", visual.visual_id);
+    }
+
+    let input_path =
+        video_input(visual).ok_or_else(|| anyhow!("{} has no video", visual.visual_id))?;
+
+    debug!(
+        "Exporting {:?} as {} to {:?}",
+        input_path,
+        options.format.as_ref(),
+        output_path
+    );
+
+    let rotation = video_metadata::from_path(&input_path)
+        .ok()
+        .and_then(|meta| meta.rotation);
+
+    let encoding = Encoding {
+        muxer: options.format.muxer(),
+        codec: options.format.encoder(),
+        encoder_options: match options.format {
+            ExportFormat::Mp4 => vec![("crf", "23".into())],
+            ExportFormat::Gif | ExportFormat::WebP => vec![],
+        },
+        muxer_options: match options.format {
+            ExportFormat::Mp4 => vec![("movflags", "+faststart".into())],
+            ExportFormat::Gif | ExportFormat::WebP => vec![("loop", "0".into())],
+        },
+        filter_spec: filter_spec(options, rotation),
+        frame_rate: (options.format == ExportFormat::Gif)
+            .then_some(Rational::new(GIF_FRAME_RATE as i32, 1)),
+        copy_audio: false,
+    };
+
+    transcode::encode(
+        &input_path,
+        output_path,
+        &encoding,
+        &AtomicBool::new(false),
+        &mut |_| {},
+    )
+}
+
+/// Path that FFmpeg can read the video of a live photo from. The video is either
+/// embedded in a motion photo, or is a separate file paired with the photo.
+pub(crate) fn video_input(visual: &Visual) -> Option<PathBuf> {
+    visual
+        .motion_photo_video_path
+        .clone()
+        .filter(|x| x.exists())
+        .or_else(|| {
+            let picture_path = visual.picture_path.as_ref()?;
+            let range = visual.motion_photo_video_range.clone()?;
+            Some(motion_photo::subfile(
+                &picture_path.sandbox_path,
+                range.start,
+                range.end - range.start,
+            ))
+        })
+        .or_else(|| visual.video_path.as_ref().map(|x| x.sandbox_path.clone()))
+}
+
+/// FFmpeg filter graph description for trimming, rotating, scaling, and converting
+/// decoded frames into something the encoder accepts.
+fn filter_spec(options: &ExportOptions, rotation: Option<i32>) -> String {
+    let mut filters: Vec<String> = Vec::new();
+
+    if options.start.is_some() || options.end.is_some() {
+        let mut trim = vec![];
+        if let Some(start) = options.start {
+            trim.push(format!("start={}", seconds(start)));
+        }
+        if let Some(end) = options.end {
+            trim.push(format!("end={}", seconds(end)));
+        }
+        filters.push(format!("trim={}", trim.join(":")));
+        filters.push("setpts=PTS-STARTPTS".into());
+    }
+
+    filters.extend(transcode::rotation_filter(rotation).map(String::from));
+
+    if let Some(max_size) = options.max_size {
+        filters.push(transcode::scale_filter(max_size));
+    } else if options.format == ExportFormat::Mp4 {
+        filters.push(transcode::EVEN_SIZE_FILTER.into());
+    }
+
+    match options.format {
+        ExportFormat::Mp4 | ExportFormat::WebP => {
+            filters.push("format=yuv420p".into());
+        }
+        ExportFormat::Gif => {
+            // Generate a palette from the whole clip for much better colours than
+            // the default GIF palette.
+            filters.push(format!("fps={}", GIF_FRAME_RATE));
+            filters.push("split[a][b];[a]palettegen[p];[b][p]paletteuse".into());
+        }
+    }
+
+    filters.join(",")
+}
+
+fn seconds(delta: TimeDelta) -> f64 {
+    delta.num_milliseconds() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_spec_mp4() {
+        let options = ExportOptions::default();
+        assert_eq!(
+            "scale=trunc(iw/2)*2:trunc(ih/2)*2,format=yuv420p",
+            filter_spec(&options, None)
+        );
+    }
+
+    #[test]
+    fn test_filter_spec_gif_trimmed_and_rotated() {
+        let options = ExportOptions {
+            format: ExportFormat::Gif,
+            start: Some(TimeDelta::milliseconds(500)),
+            end: Some(TimeDelta::milliseconds(2250)),
+            max_size: Some(480),
+        };
+
+        assert_eq!(
+            "trim=start=0.5:end=2.25,setpts=PTS-STARTPTS,\
+             transpose=clock,\
+             scale=w='min(480,iw)':h='min(480,ih)':force_original_aspect_ratio=decrease:force_divisible_by=2,\
+             fps=15,split[a][b];[a]palettegen[p];[b][p]paletteuse",
+            filter_spec(&options, Some(-90))
+        );
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(ExportFormat::WebP, "webp".parse().unwrap());
+        assert_eq!(ExportFormat::Mp4, "mp4".parse().unwrap());
+    }
+}
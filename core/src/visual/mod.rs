@@ -2,8 +2,10 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod export;
 pub mod model;
 pub mod repo;
+pub mod still;
 
 pub use model::Visual;
 pub use model::VisualId;
@@ -3,10 +3,13 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::fmt::Display;
+use std::ops::Range;
 use std::path::PathBuf;
 
 use crate::FlatpakPathBuf;
+use crate::file_types::FileType;
 use crate::photo::model::Orientation;
+use crate::photo::model::PictureExif;
 use crate::thumbnailify;
 use crate::{PictureId, VideoId, YearMonth};
 
@@ -52,6 +55,9 @@ pub struct Visual {
     // Transcoded version of video_path of video_codec is not supported.
     pub video_transcoded_path: Option<PathBuf>,
 
+    /// Why video_path couldn't be transcoded.
+    pub video_transcode_error: Option<String>,
+
     // Duration of video content
     pub video_duration: Option<TimeDelta>,
 
@@ -64,11 +70,23 @@ pub struct Visual {
 
     pub picture_orientation: Option<Orientation>,
 
+    // Camera RAW file. Either a sibling of picture_path or picture_path itself.
+    pub raw_path: Option<FlatpakPathBuf>,
+
+    /// File type of raw_path.
+    pub raw_file_type: Option<FileType>,
+
     pub motion_photo_video_path: Option<PathBuf>,
 
+    /// Byte range of the motion photo video within picture_path.
+    pub motion_photo_video_range: Option<Range<u64>>,
+
     /// Best candidate for ordering visual items. With a final fallback of the current timestamp.
     pub ordering_ts: DateTime<Utc>,
 
+    /// Wall-clock time where the picture was taken.
+    pub capture_local_ts: Option<NaiveDateTime>,
+
     // Is this a selfie?
     pub is_selfie: Option<bool>,
 
@@ -80,6 +98,9 @@ pub struct Visual {
 
     // Where photo was taken
     pub location: Option<LatLng>,
+
+    // Camera and exposure details of picture
+    pub exif: Option<PictureExif>,
 }
 
 impl Visual {
@@ -114,16 +135,37 @@ impl Visual {
         self.picture_id.is_some() && self.video_id.is_none() && !self.is_live_photo
     }
 
+    /// Does this visual item have a camera RAW file?
+    pub fn is_raw(&self) -> bool {
+        self.raw_path.is_some()
+    }
+
+    /// Is the picture a camera RAW file without a sibling, such as a JPEG, to show instead?
+    pub fn is_raw_only(&self) -> bool {
+        self.raw_path.is_some() && self.raw_path == self.picture_path
+    }
+
     pub fn is_video_only(&self) -> bool {
         self.picture_id.is_none() && self.video_id.is_some()
     }
 
+    /// Time the visual item was captured, in local time where it was captured if known.
+    pub fn local_ts(&self) -> NaiveDateTime {
+        self.capture_local_ts
+            .unwrap_or_else(|| self.ordering_ts.naive_utc())
+    }
+
+    /// Day the visual item was captured, in local time where it was captured if known.
+    pub fn local_date(&self) -> NaiveDate {
+        self.local_ts().date()
+    }
+
     pub fn year(&self) -> u32 {
-        self.ordering_ts.date_naive().year_ce().1
+        self.local_date().year_ce().1
     }
 
     pub fn year_month(&self) -> YearMonth {
-        let date = self.ordering_ts.date_naive();
+        let date = self.local_date();
         let year = date.year();
         let month = date.month();
         let month = chrono::Month::try_from(u8::try_from(month).unwrap()).unwrap();
@@ -2,8 +2,10 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use crate::FlatpakPathBuf;
+use crate::file_types::FileType;
+use crate::library_root::{LibraryRootId, LibraryRoots};
 use crate::photo::PictureId;
+use crate::photo::model::{PictureExif, WhiteBalance};
 use crate::video::VideoId;
 use crate::visual::model::{PictureOrientation, Visual, VisualId};
 
@@ -13,17 +15,19 @@ use chrono::*;
 use h3o::LatLng;
 use rusqlite;
 use rusqlite::Row;
+use rusqlite::params;
 use std::path;
 use std::path::PathBuf;
 use std::result::Result::Ok;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 /// Repository of picture metadata.
 /// Repository is backed by a Sqlite database.
 #[derive(Debug, Clone)]
 pub struct Repository {
-    /// Base path to library
-    library_base_dir: FlatpakPathBuf,
+    /// Library root directories that picture and video paths are relative to
+    library_roots: LibraryRoots,
 
     /// Base path for transcoded videos
     cache_dir_base_path: path::PathBuf,
@@ -35,12 +39,12 @@ pub struct Repository {
 impl Repository {
     /// Builds a Repository and creates operational tables.
     pub fn open(
-        library_base_dir: &FlatpakPathBuf,
+        library_roots: &LibraryRoots,
         cache_dir_base_path: &path::Path,
         con: Arc<Mutex<rusqlite::Connection>>,
     ) -> Result<Repository> {
         let repo = Repository {
-            library_base_dir: library_base_dir.clone(),
+            library_roots: library_roots.clone(),
             cache_dir_base_path: cache_dir_base_path.into(),
             con,
         };
@@ -53,6 +57,7 @@ impl Repository {
         let mut stmt = con.prepare(
             "SELECT
                     visual_id,
+                    root_id,
                     link_path_b64,
 
                     picture_id,
@@ -60,15 +65,35 @@ impl Repository {
                     picture_orientation,
                     is_selfie,
 
+                    raw_path_b64,
+                    raw_file_type,
+
+                    exif_camera_make,
+                    exif_camera_model,
+                    exif_lens_model,
+                    exif_focal_length,
+                    exif_focal_length_35mm,
+                    exif_f_number,
+                    exif_exposure_time,
+                    exif_iso,
+                    exif_is_flash_fired,
+                    exif_white_balance,
+                    exif_width,
+                    exif_height,
+
                     video_id,
                     video_path_b64,
 
                     motion_photo_video_path,
+                    motion_photo_video_offset,
+                    motion_photo_video_length,
 
                     ordering_ts,
+                    capture_local_ts,
                     is_live_photo,
 
                     video_transcoded_path,
+                    video_transcode_error,
                     is_transcode_required,
                     duration_millis,
                     video_rotation,
@@ -84,38 +109,109 @@ impl Repository {
         Ok(visuals)
     }
 
+    /// Shifts the capture time of pictures and videos to correct a mis-set camera clock.
+    /// Shifts accumulate, so shifting by an hour twice is a shift of two hours.
+    /// A shift overrides, but doesn't replace, the capture time from the metadata.
+    pub fn add_time_shift(
+        &mut self,
+        picture_ids: &[PictureId],
+        video_ids: &[VideoId],
+        shift: TimeDelta,
+    ) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut shift_picture = tx.prepare_cached(
+                "INSERT INTO pictures_time_shift (
+                    picture_id,
+                    shift_seconds
+                ) VALUES (
+                    ?1, ?2
+                ) ON CONFLICT (picture_id) DO UPDATE SET
+                    shift_seconds = shift_seconds + ?2",
+            )?;
+
+            let mut shift_video = tx.prepare_cached(
+                "INSERT INTO videos_time_shift (
+                    video_id,
+                    shift_seconds
+                ) VALUES (
+                    ?1, ?2
+                ) ON CONFLICT (video_id) DO UPDATE SET
+                    shift_seconds = shift_seconds + ?2",
+            )?;
+
+            for picture_id in picture_ids {
+                shift_picture.execute(params![picture_id.id(), shift.num_seconds()])?;
+            }
+
+            for video_id in video_ids {
+                shift_video.execute(params![video_id.id(), shift.num_seconds()])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Removes capture time shifts, restoring the capture time from the metadata.
+    pub fn remove_time_shift(
+        &mut self,
+        picture_ids: &[PictureId],
+        video_ids: &[VideoId],
+    ) -> Result<()> {
+        let mut con = self.con.lock().unwrap();
+        let tx = con.transaction()?;
+
+        {
+            let mut delete_picture =
+                tx.prepare_cached("DELETE FROM pictures_time_shift WHERE picture_id = ?1")?;
+
+            let mut delete_video =
+                tx.prepare_cached("DELETE FROM videos_time_shift WHERE video_id = ?1")?;
+
+            for picture_id in picture_ids {
+                delete_picture.execute(params![picture_id.id()])?;
+            }
+
+            for video_id in video_ids {
+                delete_video.execute(params![video_id.id()])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
     fn to_visual(&self, row: &Row<'_>) -> rusqlite::Result<Visual> {
         let visual_id = row
             .get("visual_id")
             .map(VisualId::new)
             .expect("Must have visual_id");
 
+        let root_id = row.get("root_id").map(LibraryRootId::new)?;
+
+        // Visual items in removed library roots can't be resolved.
+        let root = self
+            .library_roots
+            .get(root_id)
+            .ok_or(rusqlite::Error::InvalidQuery)?;
+
         let link_path: String = row.get("link_path_b64")?;
         let link_path =
             path_encoding::from_base64(&link_path).map_err(|_| rusqlite::Error::InvalidQuery)?;
-        let link_path = self.library_base_dir.host_path.join(link_path);
+        let link_path = root.path.host_path.join(link_path);
 
         let picture_id: Option<PictureId> = row.get("picture_id").map(PictureId::new).ok();
 
-        let picture_relative_path: Option<PathBuf> = row
+        let picture_path = row
             .get("picture_path_b64")
             .ok()
-            .and_then(|x: String| path_encoding::from_base64(&x).ok());
-
-        let picture_host_path = picture_relative_path
-            .as_ref()
-            .map(|x| self.library_base_dir.host_path.join(x));
-
-        let picture_sandbox_path =
-            picture_relative_path.map(|x| self.library_base_dir.sandbox_path.join(x));
-
-        let picture_path = if let (Some(host_path), Some(sandbox_path)) =
-            (picture_host_path, picture_sandbox_path)
-        {
-            Some(FlatpakPathBuf::build(host_path, sandbox_path))
-        } else {
-            None
-        };
+            .and_then(|x: String| path_encoding::from_base64(&x).ok())
+            .and_then(|x| self.library_roots.resolve(root_id, &x));
 
         let picture_orientation: Option<PictureOrientation> = row
             .get("picture_orientation")
@@ -124,26 +220,24 @@ impl Repository {
 
         let is_selfie: Option<bool> = row.get("is_selfie").ok();
 
-        let video_id: Option<VideoId> = row.get("video_id").map(VideoId::new).ok();
-
-        let video_relative_path: Option<PathBuf> = row
-            .get("video_path_b64")
+        let raw_path = row
+            .get("raw_path_b64")
             .ok()
-            .and_then(|x: String| path_encoding::from_base64(&x).ok());
+            .and_then(|x: String| path_encoding::from_base64(&x).ok())
+            .and_then(|x| self.library_roots.resolve(root_id, &x));
 
-        let video_host_path = video_relative_path
-            .as_ref()
-            .map(|x| self.library_base_dir.host_path.join(x));
+        let raw_file_type = row
+            .get("raw_file_type")
+            .ok()
+            .and_then(|x: String| FileType::from_str(&x).ok());
 
-        let video_sandbox_path =
-            video_relative_path.map(|x| self.library_base_dir.sandbox_path.join(x));
+        let video_id: Option<VideoId> = row.get("video_id").map(VideoId::new).ok();
 
-        let video_path =
-            if let (Some(host_path), Some(sandbox_path)) = (video_host_path, video_sandbox_path) {
-                Some(FlatpakPathBuf::build(host_path, sandbox_path))
-            } else {
-                None
-            };
+        let video_path = row
+            .get("video_path_b64")
+            .ok()
+            .and_then(|x: String| path_encoding::from_base64(&x).ok())
+            .and_then(|x| self.library_roots.resolve(root_id, &x));
 
         let video_orientation: Option<PictureOrientation> = row
             .get("video_rotation")
@@ -156,8 +250,16 @@ impl Repository {
             .map(|x| self.cache_dir_base_path.join(x))
             .ok();
 
+        let motion_photo_video_offset: Option<u64> = row.get("motion_photo_video_offset").ok();
+        let motion_photo_video_length: Option<u64> = row.get("motion_photo_video_length").ok();
+        let motion_photo_video_range = motion_photo_video_offset
+            .zip(motion_photo_video_length)
+            .map(|(offset, length)| offset..offset + length);
+
         let ordering_ts: DateTime<Utc> = row.get("ordering_ts").expect("Must have ordering_ts");
 
+        let capture_local_ts: Option<NaiveDateTime> = row.get("capture_local_ts").ok();
+
         let is_live_photo: Option<bool> = row.get("is_live_photo").ok();
 
         let is_live_photo = is_live_photo.is_some_and(|x| x);
@@ -168,6 +270,8 @@ impl Repository {
             .map(|x: String| PathBuf::from(x))
             .map(|x| self.cache_dir_base_path.join(x));
 
+        let video_transcode_error: Option<String> = row.get("video_transcode_error").ok();
+
         let is_transcode_required: Option<bool> = row.get("is_transcode_required").ok();
 
         let video_duration: Option<TimeDelta> = row
@@ -184,24 +288,110 @@ impl Repository {
             None
         };
 
+        let exif = PictureExif {
+            camera_make: row.get("exif_camera_make").ok(),
+            camera_model: row.get("exif_camera_model").ok(),
+            lens_model: row.get("exif_lens_model").ok(),
+            focal_length: row.get("exif_focal_length").ok(),
+            focal_length_35mm: row.get("exif_focal_length_35mm").ok(),
+            f_number: row.get("exif_f_number").ok(),
+            exposure_time: row.get("exif_exposure_time").ok(),
+            iso: row.get("exif_iso").ok(),
+            is_flash_fired: row.get("exif_is_flash_fired").ok(),
+            white_balance: row
+                .get("exif_white_balance")
+                .ok()
+                .and_then(|x: String| WhiteBalance::from_str(&x).ok()),
+            width: row.get("exif_width").ok(),
+            height: row.get("exif_height").ok(),
+        };
+
+        let exif = (!exif.is_empty()).then_some(exif);
+
         let v = Visual {
             visual_id,
             parent_path: link_path.parent().map(PathBuf::from).expect("Parent path"),
             picture_id,
             picture_path,
             picture_orientation,
+            raw_path,
+            raw_file_type,
             video_id,
             video_path,
             ordering_ts,
+            capture_local_ts,
             is_selfie,
             is_live_photo,
             video_transcoded_path,
+            video_transcode_error,
             video_orientation,
             is_transcode_required,
             video_duration,
             motion_photo_video_path,
+            motion_photo_video_range,
             location,
+            exif,
         };
         Ok(v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FlatpakPathBuf;
+    use crate::database;
+    use crate::library_root;
+
+    #[test]
+    fn test_time_shift() {
+        let con = Arc::new(Mutex::new(database::setup_in_memory().unwrap()));
+
+        let library_roots = library_root::Repository::open(con.clone())
+            .unwrap()
+            .sync(&[FlatpakPathBuf::build("/pictures", "/pictures")])
+            .unwrap();
+
+        let path = path_encoding::to_base64(path::Path::new("holiday/IMG_0001.JPG"));
+        con.lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO pictures (
+                    picture_id, root_id,
+                    picture_path_b64, picture_path_lossy,
+                    link_path_b64, link_path_lossy,
+                    exif_created_utc_ts, exif_created_local_ts
+                ) VALUES (
+                    1, 1, ?1, '', ?1, '', '2024-06-01 10:00:00+00:00', '2024-06-01 12:00:00'
+                )",
+                params![path],
+            )
+            .unwrap();
+
+        let mut repo = Repository::open(&library_roots, path::Path::new("/cache"), con).unwrap();
+
+        let picture_ids = [PictureId::new(1)];
+        repo.add_time_shift(&picture_ids, &[], TimeDelta::hours(1))
+            .unwrap();
+        repo.add_time_shift(&picture_ids, &[], TimeDelta::days(-1))
+            .unwrap();
+
+        let visual = repo.all().unwrap().pop().unwrap();
+        assert_eq!(
+            "2024-05-31 11:00:00",
+            visual.ordering_ts.format("%F %T").to_string()
+        );
+        assert_eq!(
+            "2024-05-31 13:00:00",
+            visual.capture_local_ts.unwrap().format("%F %T").to_string()
+        );
+
+        repo.remove_time_shift(&picture_ids, &[]).unwrap();
+
+        let visual = repo.all().unwrap().pop().unwrap();
+        assert_eq!(
+            "2024-06-01 10:00:00",
+            visual.ordering_ts.format("%F %T").to_string()
+        );
+    }
+}
@@ -0,0 +1,445 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Saves a frame of a video as a standalone photo.
+//!
+//! The photo is written next to the video at full resolution, with the capture
+//! time and location of the video copied into its EXIF so that it sorts and maps
+//! alongside the video it came from.
+
+use crate::photo::gps::GPSLocation;
+use crate::video::frames::FrameReader;
+use crate::video::metadata as video_metadata;
+use crate::visual::export;
+use crate::visual::model::Visual;
+
+use anyhow::*;
+use chrono::{DateTime, FixedOffset, Local, Offset, TimeDelta, TimeZone, Utc};
+use exif::{Field, In, Rational, Tag, Value};
+use image::RgbImage;
+use image::codecs::jpeg::JpegEncoder;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Cursor, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result::Ok;
+use strum::{AsRefStr, EnumIter, EnumString};
+use tracing::debug;
+
+/// Quality of saved JPEGs. High, because the frame is already lossy.
+const JPEG_QUALITY: u8 = 95;
+
+/// JPEG start of image marker.
+const JPEG_SOI: [u8; 2] = [0xFF, 0xD8];
+
+/// JPEG APP0 marker, used by JFIF.
+const JPEG_APP0: [u8; 2] = [0xFF, 0xE0];
+
+/// JPEG APP1 marker, used by EXIF.
+const JPEG_APP1: [u8; 2] = [0xFF, 0xE1];
+
+/// Identifies an APP1 segment as EXIF.
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum StillFormat {
+    #[default]
+    Jpeg,
+
+    /// Lossless, but much larger.
+    Png,
+}
+
+impl StillFormat {
+    /// File name extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StillFormat::Jpeg => "jpg",
+            StillFormat::Png => "png",
+        }
+    }
+}
+
+/// Saves the frame at `position` of a video, or of the video part of a live photo,
+/// as a photo in the same directory as the original.
+/// Returns the path of the new photo.
+pub fn extract(visual: &Visual, position: TimeDelta, format: StillFormat) -> Result<PathBuf> {
+    let input_path =
+        export::video_input(visual).ok_or_else(|| anyhow!("{} has no video", visual.visual_id))?;
+
+    let mut reader = FrameReader::open(&input_path, None)?;
+
+    // Prefer the orientation from the database so the photo matches the thumbnail
+    // and viewer, even if the frame side data disagrees.
+    if let Some(orientation) = visual.video_orientation {
+        reader = reader.with_orientation(orientation);
+    }
+
+    let frame = reader.read_at(position)?.ok_or_else(|| {
+        anyhow!(
+            "No frame at {} ms in {:?}",
+            position.num_milliseconds(),
+            input_path
+        )
+    })?;
+
+    let metadata = video_metadata::from_path(&input_path).unwrap_or_default();
+
+    // The frame was captured some time after the video started recording.
+    let created_at = metadata
+        .stream_created_at
+        .map(|ts| ts + position)
+        .map(|ts| ts.with_timezone(&created_offset(ts, metadata.stream_created_offset)));
+    let exif = exif_data(created_at, metadata.location.as_ref())?;
+
+    let (file, output_path) = create_output(visual.sandbox_path(), position, format)?;
+
+    debug!(
+        "Saving frame at {} ms of {:?} to {:?}",
+        position.num_milliseconds(),
+        input_path,
+        output_path
+    );
+
+    let result = match format {
+        StillFormat::Jpeg => write_jpeg(file, &frame, exif.as_deref()),
+        StillFormat::Png => write_png(file, &frame, exif.as_deref()),
+    };
+
+    // Don't leave a truncated photo behind to be picked up by the next scan.
+    if result.is_err() {
+        let _ = std::fs::remove_file(&output_path);
+    }
+
+    result.map(|_| output_path)
+}
+
+/// Creates a new file next to `original` named after the original and the position
+/// of the frame. Existing files are never overwritten; a numeric suffix is added instead.
+fn create_output(
+    original: &Path,
+    position: TimeDelta,
+    format: StillFormat,
+) -> Result<(File, PathBuf)> {
+    let parent = original
+        .parent()
+        .ok_or_else(|| anyhow!("No parent directory for {:?}", original))?;
+
+    let stem = original
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_else(|| "frame".into());
+
+    for attempt in 1..1000 {
+        let name = output_name(&stem, position, attempt, format);
+        let path = parent.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    bail!("Too many saved frames for {:?}", original);
+}
+
+fn output_name(stem: &str, position: TimeDelta, attempt: u32, format: StillFormat) -> String {
+    let millis = position.num_milliseconds().max(0);
+    let suffix = if attempt > 1 {
+        format!("-{}", attempt)
+    } else {
+        String::new()
+    };
+    format!(
+        "{}-frame-{}ms{}.{}",
+        stem,
+        millis,
+        suffix,
+        format.extension()
+    )
+}
+
+fn write_jpeg(file: File, frame: &RgbImage, exif: Option<&[u8]>) -> Result<()> {
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY).encode_image(frame)?;
+
+    let jpeg = match exif {
+        Some(exif) => insert_jpeg_exif(&jpeg, exif)?,
+        None => jpeg,
+    };
+
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&jpeg)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_png(file: File, frame: &RgbImage, exif: Option<&[u8]>) -> Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(file), frame.width(), frame.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+
+    // eXIf must come before the image data.
+    if let Some(exif) = exif {
+        writer.write_chunk(png::chunk::ChunkType(*b"eXIf"), exif)?;
+    }
+
+    writer.write_image_data(frame.as_raw())?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Inserts an EXIF APP1 segment into a JPEG. JFIF requires its APP0 segment to
+/// directly follow the start of image marker, so the EXIF goes after that if present.
+fn insert_jpeg_exif(jpeg: &[u8], exif: &[u8]) -> Result<Vec<u8>> {
+    if !jpeg.starts_with(&JPEG_SOI) {
+        bail!("Not a JPEG");
+    }
+
+    let mut offset = JPEG_SOI.len();
+    if jpeg[offset..].starts_with(&JPEG_APP0) && jpeg.len() >= offset + 4 {
+        let len = u16::from_be_bytes([jpeg[offset + 2], jpeg[offset + 3]]) as usize;
+        offset += JPEG_APP0.len() + len;
+        if offset > jpeg.len() {
+            bail!("Truncated JPEG APP0 segment");
+        }
+    }
+
+    // Segment length includes the two bytes of the length itself.
+    let len = u16::try_from(2 + EXIF_HEADER.len() + exif.len())
+        .map_err(|_| anyhow!("EXIF too large for a JPEG segment"))?;
+
+    let mut output = Vec::with_capacity(jpeg.len() + len as usize + JPEG_APP1.len());
+    output.extend_from_slice(&jpeg[..offset]);
+    output.extend_from_slice(&JPEG_APP1);
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(EXIF_HEADER);
+    output.extend_from_slice(exif);
+    output.extend_from_slice(&jpeg[offset..]);
+    Ok(output)
+}
+
+/// UTC offset to record the capture time with. Prefer the offset recorded by the video,
+/// otherwise assume the video was recorded in the time zone the frame is saved in.
+fn created_offset(created_at: DateTime<Utc>, video_offset: Option<FixedOffset>) -> FixedOffset {
+    video_offset.unwrap_or_else(|| {
+        Local
+            .offset_from_utc_datetime(&created_at.naive_utc())
+            .fix()
+    })
+}
+
+/// TIFF-structured EXIF recording when and where the frame was captured.
+/// None if there is nothing to record.
+fn exif_data(
+    created_at: Option<DateTime<FixedOffset>>,
+    location: Option<&GPSLocation>,
+) -> Result<Option<Vec<u8>>> {
+    let mut fields = Vec::new();
+
+    if let Some(created_at) = created_at {
+        // Photos record local wall-clock time, with the offset recorded separately.
+        fields.push(ascii_field(
+            Tag::DateTimeOriginal,
+            &created_at.format("%Y:%m:%d %H:%M:%S").to_string(),
+        ));
+        fields.push(ascii_field(
+            Tag::OffsetTimeOriginal,
+            &created_at.format("%:z").to_string(),
+        ));
+    }
+
+    let coords = location.and_then(|location| {
+        Some((
+            location.latitude.to_f64_safe()?,
+            location.longitude.to_f64_safe()?,
+        ))
+    });
+
+    if let Some((latitude, longitude)) = coords {
+        fields.push(Field {
+            tag: Tag::GPSVersionID,
+            ifd_num: In::PRIMARY,
+            value: Value::Byte(vec![2, 2, 0, 0]),
+        });
+        fields.push(ascii_field(
+            Tag::GPSLatitudeRef,
+            if latitude < 0.0 { "S" } else { "N" },
+        ));
+        fields.push(Field {
+            tag: Tag::GPSLatitude,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(to_dms(latitude).to_vec()),
+        });
+        fields.push(ascii_field(
+            Tag::GPSLongitudeRef,
+            if longitude < 0.0 { "W" } else { "E" },
+        ));
+        fields.push(Field {
+            tag: Tag::GPSLongitude,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(to_dms(longitude).to_vec()),
+        });
+    }
+
+    if fields.is_empty() {
+        return Ok(None);
+    }
+
+    let mut writer = exif::experimental::Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+
+    let mut data = Cursor::new(Vec::new());
+    writer.write(&mut data, false)?;
+    Ok(Some(data.into_inner()))
+}
+
+fn ascii_field(tag: Tag, value: &str) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    }
+}
+
+/// Converts decimal degrees to unsigned degrees, minutes, and seconds, with seconds
+/// to a hundredth. The sign is recorded separately in the reference tag.
+fn to_dms(decimal: f64) -> [Rational; 3] {
+    let centiseconds = (decimal.abs() * 60.0 * 60.0 * 100.0).round() as u32;
+    let degrees = centiseconds / (60 * 60 * 100);
+    let minutes = (centiseconds / (60 * 100)) % 60;
+    let centiseconds = centiseconds % (60 * 100);
+
+    [
+        Rational::from((degrees, 1)),
+        Rational::from((minutes, 1)),
+        Rational::from((centiseconds, 100)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location() -> GPSLocation {
+        GPSLocation::for_iso6709("-33.8568+151.2153/").unwrap()
+    }
+
+    #[test]
+    fn test_to_dms() {
+        let dms = to_dms(-33.8568);
+        assert_eq!((33, 1), (dms[0].num, dms[0].denom));
+        assert_eq!((51, 1), (dms[1].num, dms[1].denom));
+        assert_eq!((2448, 100), (dms[2].num, dms[2].denom));
+
+        // Rounding seconds up carries into minutes and degrees.
+        let dms = to_dms(10.999_999_9);
+        assert_eq!(11, dms[0].num);
+        assert_eq!(0, dms[1].num);
+        assert_eq!(0, dms[2].num);
+    }
+
+    #[test]
+    fn test_exif_data() {
+        let created_at = FixedOffset::east_opt(2 * 60 * 60)
+            .unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 12, 30, 45)
+            .unwrap();
+        let exif = exif_data(Some(created_at), Some(&location()))
+            .unwrap()
+            .unwrap();
+
+        let exif = exif::Reader::new().read_raw(exif).unwrap();
+        let value = |tag| &exif.get_field(tag, In::PRIMARY).unwrap().value;
+
+        let Value::Ascii(date_time) = value(Tag::DateTimeOriginal) else {
+            panic!("DateTimeOriginal is not ASCII");
+        };
+        assert_eq!(b"2024:06:01 12:30:45".to_vec(), date_time[0]);
+
+        let Value::Ascii(offset) = value(Tag::OffsetTimeOriginal) else {
+            panic!("OffsetTimeOriginal is not ASCII");
+        };
+        assert_eq!(b"+02:00".to_vec(), offset[0]);
+
+        let (
+            Value::Rational(lat),
+            Value::Ascii(lat_ref),
+            Value::Rational(lon),
+            Value::Ascii(lon_ref),
+        ) = (
+            value(Tag::GPSLatitude),
+            value(Tag::GPSLatitudeRef),
+            value(Tag::GPSLongitude),
+            value(Tag::GPSLongitudeRef),
+        )
+        else {
+            panic!("Unexpected GPS value types");
+        };
+
+        let location = GPSLocation::for_exif(lat, lat_ref, lon, lon_ref).unwrap();
+        assert!((location.latitude.to_f64() - -33.8568).abs() < 1e-5);
+        assert!((location.longitude.to_f64() - 151.2153).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_created_offset() {
+        let created_at = Utc.with_ymd_and_hms(2024, 6, 1, 10, 30, 45).unwrap();
+        let offset = FixedOffset::west_opt(5 * 60 * 60).unwrap();
+        assert_eq!(offset, created_offset(created_at, Some(offset)));
+
+        // Without an offset from the video, the capture time is still the same instant.
+        let local = created_at.with_timezone(&created_offset(created_at, None));
+        assert_eq!(created_at, local.to_utc());
+    }
+
+    #[test]
+    fn test_exif_data_empty() {
+        assert!(exif_data(None, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_insert_jpeg_exif() {
+        let frame = RgbImage::from_pixel(8, 8, image::Rgb([200, 100, 50]));
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+            .encode_image(&frame)
+            .unwrap();
+
+        let created_at = Utc
+            .with_ymd_and_hms(2024, 6, 1, 12, 30, 45)
+            .unwrap()
+            .fixed_offset();
+        let exif = exif_data(Some(created_at), None).unwrap().unwrap();
+        let jpeg = insert_jpeg_exif(&jpeg, &exif).unwrap();
+
+        // Still a valid JPEG of the same frame.
+        let decoded = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((8, 8), (decoded.width(), decoded.height()));
+
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(&jpeg))
+            .unwrap();
+        assert!(exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).is_some());
+
+        assert!(insert_jpeg_exif(b"not a jpeg", &[]).is_err());
+    }
+
+    #[test]
+    fn test_output_name() {
+        let position = TimeDelta::milliseconds(5250);
+        assert_eq!(
+            "IMG_1234-frame-5250ms.jpg",
+            output_name("IMG_1234", position, 1, StillFormat::Jpeg)
+        );
+        assert_eq!(
+            "IMG_1234-frame-5250ms-2.png",
+            output_name("IMG_1234", position, 2, StillFormat::Png)
+        );
+    }
+}
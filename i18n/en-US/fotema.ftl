@@ -33,6 +33,9 @@ folders-album = Folders
 # Title for album showing contents of one folder.
 folder-album = Folder
 
+# Title for album of photos that are copies of each other.
+duplicates-album = Duplicates
+
 # Title for places page which shows photos overlayed onto a map.
 places-page = Places
 
@@ -52,8 +55,63 @@ people-page-status-no-people =
   .description = { -app-name } will look for faces in new photos when launched.
   Name the people in your photos so { -app-name } can make an album for each person.
 
+# Card in people album suggesting that a group of similar unknown faces
+# is a new person.
+# Variables:
+#   $count - number of faces in group
+people-page-suggestion =
+  .title = Name this person?
+  .count = { $count ->
+        [one] One photo
+       *[other] { $count } photos
+    }
+  .name-button = Name
+
+# Status page shown for duplicates album when no duplicates are found.
+duplicates-album-status-none =
+  .title = No duplicates found
+  .description = { -app-name } looks for copies of photos when launched.
+
+# A group of photos that are copies of each other.
+# Attributes:
+#  .exact - Heading for photos that are byte-for-byte identical.
+#  .near - Heading for photos that look the same, but have been resized or re-saved.
+#  .best - Label on the copy that is best to keep.
+#  .trash-button - Button to move every copy except the best one to the trash.
+duplicates-album-group =
+  .exact = Identical Copies
+  .near = Similar Copies
+  .best = Best Copy
+  .trash-button = Trash Other Copies
+
+# Label for the size of a photo in the duplicates album.
+# Variables:
+#   $width - width in pixels.
+#   $height - height in pixels.
+duplicates-album-resolution = { $width } × { $height }
+
+# Dialog to confirm moving copies of a photo to the trash.
+# Attributes:
+#  .heading - Dialog title.
+#  .body - Dialog description.
+#  .cancel-button - Keep all copies.
+#  .trash-button - Move copies to the trash.
+# Variables:
+#   $count - number of copies to move to the trash.
+duplicates-album-trash-dialog =
+  .heading = Trash Copies?
+  .body = { $count ->
+        [one] One copy will be moved to the trash. The best copy will be kept.
+       *[other] { $count } copies will be moved to the trash. The best copy will be kept.
+    }
+  .cancel-button = Cancel
+  .trash-button = Trash
+
 ## Thumbnail decorations
 
+# Badge on thumbnails of photos that have a camera RAW file.
+photo-grid-raw-badge = RAW
+
 # Label on month album thumbnails.
 # Variables:
 #   $month - month number (1 through 12).
@@ -96,6 +154,38 @@ viewer-faces-menu =
   .ignore-unknown = Ignore all unknown faces
   .scan = Scan for more faces
 
+# Menu of actions for the photo or video being viewed.
+# Attributes:
+#  .tooltip - Tooltip on mouse hover.
+#  .adjust-time - Menu item to correct the date and time of photos and videos.
+viewer-menu =
+  .tooltip = More options
+  .adjust-time = Adjust Date and Time…
+
+# Dialog to correct the date and time of photos and videos taken with a camera
+# that had the wrong time set. The user enters the correct time for the item
+# being viewed and other items are shifted by the same amount. Undoing adjustments
+# restores the date and time recorded by the camera.
+# Variables:
+#   $count - number of items the adjustment applies to.
+viewer-adjust-time-dialog =
+  .heading = Adjust Date and Time
+  .body = Enter the date and time this item was actually taken.
+  .date-time = Date and time (YYYY-MM-DD HH:MM:SS)
+  .scope = Apply to
+  .scope-item = This item only
+  .scope-camera = { $count ->
+        [one] One item in this folder from the same camera
+       *[other] { $count } items in this folder from the same camera
+    }
+  .scope-folder = { $count ->
+        [one] One item in this folder
+       *[other] All { $count } items in this folder
+    }
+  .cancel-button = Cancel
+  .restore-button = Undo Adjustments
+  .adjust-button = Adjust
+
 # Go to next button when viewing photo or video.
 # Attributes:
 #  .tooltip - Tooltip on mouse hover.
@@ -132,12 +222,62 @@ viewer-skip-backwards-10-seconds =
 viewer-mute =
   .tooltip = Mute/Unmute
 
+# Export the moving part of a live photo or motion photo.
+# Attributes:
+#  .tooltip - Tooltip on mouse hover.
+viewer-export =
+  .tooltip = Export Video
+
+# Dialog to choose the format to export the moving part of a live photo as.
+# Attributes:
+#  .heading - Dialog title.
+#  .body - Dialog description.
+#  .cancel-button - Cancel export.
+#  .mp4-button - Export as an MP4 video.
+#  .gif-button - Export as a looping animated GIF.
+#  .webp-button - Export as a looping animated WebP.
+viewer-export-dialog =
+  .heading = Export Video
+  .body = Save the moving part of this live photo as a video or an animation.
+  .cancel-button = Cancel
+  .mp4-button = MP4
+  .gif-button = GIF
+  .webp-button = WebP
+
+# Notification after exporting the moving part of a live photo.
+# Attributes:
+#  .success - Export succeeded. $file_name is the name of the exported file.
+#  .failure - Export failed.
+viewer-export-toast =
+  .success = Exported { $file_name }
+  .failure = Couldn't export video
+
+# Save the currently shown frame of a video as a photo.
+# Attributes:
+#  .tooltip - Tooltip on mouse hover.
+viewer-save-frame =
+  .tooltip = Save Frame as Photo
+
+# Notification after saving the currently shown frame of a video as a photo.
+# Attributes:
+#  .success - Saving succeeded. $file_name is the name of the new photo.
+#  .failure - Saving failed.
+viewer-save-frame-toast =
+  .success = Saved { $file_name }
+  .failure = Couldn't save frame
+
 # Convert all incompatible videos description.
 viewer-convert-all-description = This video must be converted before it can be played. This only needs to happen once, but it takes a while to convert a video.
 
 # Button to convert all incompatible videos.
 viewer-convert-all-button = Convert all incompatible videos
 
+# Description of a video that couldn't be converted the last time it was tried.
+viewer-convert-failed-description = This video couldn't be converted. Details of the error are shown in the information panel.
+
+# Button to try converting a video again after it failed.
+viewer-convert-retry-button = Try Again
+
 # Viewer failed to load an image or video.
 viewer-error-failed-to-load = Failed to load
 
@@ -193,9 +333,50 @@ infobar-video-codec = Video Codec
 # Audio codec, such as "OPUS".
 infobar-audio-codec = Audio Codec
 
+# Why a video couldn't be converted to a format that can be played.
+infobar-video-transcode-error = Conversion Error
+
 # Width and height of photo or video.
 infobar-dimensions = Dimensions
 
+# Camera make and model, such as "Canon EOS R5".
+infobar-camera = Camera
+
+# Camera lens model.
+infobar-lens = Lens
+
+# Focal length of lens, such as "50 mm".
+infobar-focal-length = Focal Length
+
+# Aperture of lens, such as "ƒ/1.8".
+infobar-aperture = Aperture
+
+# Shutter speed, such as "1/250 s".
+infobar-exposure-time = Exposure
+
+# Sensitivity of camera sensor, such as "100".
+infobar-iso = ISO
+
+# Did the camera flash fire?
+# Attributes:
+#  .fired - flash fired.
+#  .not-fired - flash did not fire.
+infobar-flash = Flash
+  .fired = Fired
+  .not-fired = Did not fire
+
+# White balance mode.
+# Attributes:
+#  .auto - white balance set by camera.
+#  .manual - white balance set by photographer.
+infobar-white-balance = White Balance
+  .auto = Auto
+  .manual = Manual
+
+# Width and height recorded by the camera, which can differ from the
+# dimensions of a picture that has since been cropped or resized.
+infobar-camera-dimensions = Camera Dimensions
+
 ## Faces and People
 
 # Menu item to mark a face as the most import face for a person
@@ -255,6 +436,30 @@ prefs-processing-face-detection = Face detection
 prefs-processing-motion-photos = Motion photos
   .subtitle = Detect Android motion photos and extract the videos.
 
+# Video codec for converting videos that can't be played.
+# Attributes:
+#   .subtitle - Description of combo box.
+#   .h264 - H.264 codec. Widely supported.
+#   .vp9 - VP9 codec.
+#   .av1 - AV1 codec. Smallest files, but slowest to convert.
+prefs-processing-transcode-codec = Video conversion format
+  .subtitle = Format for converting videos that can't be played.
+  .h264 = H.264
+  .vp9 = VP9
+  .av1 = AV1
+
+# Quality of converted videos.
+# Attributes:
+#   .subtitle - Description of combo box.
+#   .low - Low quality, small files.
+#   .medium - Medium quality.
+#   .high - High quality, large files.
+prefs-processing-transcode-quality = Video conversion quality
+  .subtitle = Higher quality makes larger files.
+  .low = Low
+  .medium = Medium
+  .high = High
+
 prefs-library-section =
   .title = Library
   .description = Configure library directory.
@@ -264,6 +469,44 @@ prefs-library-section-pictures-dir =
   .title = Pictures Directory
   .tooltip = Choose pictures directory.
 
+prefs-library-section-other-dirs =
+  .title = Other Directories
+  .description = Include pictures and videos from other directories, such as external drives.
+  .add-tooltip = Add directory.
+  .remove-tooltip = Remove directory.
+  .nested-error = Directory is inside, or contains, another library directory.
+
+prefs-library-section-exclude =
+  .title = Excluded
+  .description = Keep directories and files out of the library. Directories containing a .nomedia or .nophotos file are always excluded.
+  .add-pattern = Add Pattern, such as Android/data or *.tmp
+  .add-dir-tooltip = Exclude directory.
+  .remove-tooltip = Stop excluding.
+  .invalid-pattern = Invalid pattern.
+
+prefs-models-section =
+  .title = Face Models
+  .description = Face detection and recognition need models that are usually downloaded when first used. Computers without internet access can use a directory of models or a local mirror.
+
+prefs-models-section-dir =
+  .title = Models Directory
+  .none = None
+  .tooltip = Choose models directory.
+  .clear-tooltip = Stop using models directory.
+
+prefs-models-section-mirror =
+  .title = Download Mirror, such as file:///srv/models/
+  .invalid = Invalid URL.
+
+# Whether a model has been found. $path is the location of a model file that doesn't match.
+prefs-models-section-status =
+  .system = Installed with the system.
+  .user = Found in models directory.
+  .cache = Downloaded.
+  .missing = Missing. Will be downloaded when needed.
+  .mismatch = Damaged or wrong version: { $path }
+  .unpinned = Can't be checked for damage, so won't be used.
+
 ## Progress bar for background tasks
 
 # Extracting details from photo EXIF data
@@ -293,6 +536,12 @@ progress-detect-faces-photos = Detecting faces in photos.
 # Recognize faces in photos as known people
 progress-recognize-faces-photos = Recognizing people in photos.
 
+# Group unknown faces into suggested people
+progress-cluster-faces-photos = Finding new people in photos.
+
+# Hashing photos to find duplicates
+progress-find-duplicates = Looking for duplicate photos.
+
 # Not doing any background work
 progress-idle = Idle.
 
@@ -330,12 +579,18 @@ banner-detect-faces-photos = Detecting faces in photos. This will take a while.
 # Recognize faces as people
 banner-recognize-faces-photos = Recognizing people in photos. This will take a while.
 
+# Group unknown faces into suggested people
+banner-cluster-faces-photos = Finding new people in photos.
+
 # Transcoding videos to a compatible format
 banner-convert-videos = Converting videos.
 
 # Generate face thumbnails
 banner-face-thumbnails = Generating face thumbnails
 
+# Hashing photos to find duplicates
+banner-find-duplicates = Looking for duplicate photos.
+
 # Button to stop all tasks doing background processing.
 banner-button-stop =
   .label = Stop
@@ -362,6 +617,36 @@ person-menu-rename = Rename person
 # Menu item to delete a person
 person-menu-delete = Delete person
 
+# Menu item to merge another person into a person
+person-menu-merge = Merge with…
+
+# Menu item to show a person's faces grouped by similarity
+person-menu-split = Split person…
+
+# Person merge dialog, for when a person has been created twice.
+# Variables:
+#   $name - name of person that will be kept
+person-merge-dialog =
+  .heading = Merge people?
+  .body = The chosen person's faces will be moved to { $name }.
+  .cancel-button = Cancel
+  .merge-button = Merge
+
+# Person split dialog, showing a person's faces grouped by similarity so that
+# groups belonging to someone else can be moved.
+#  .group - heading for a group of similar faces.
+# Variables:
+#   $count - number of faces in group
+person-split-dialog =
+  .title = Split Person
+  .subtitle = Faces grouped by similarity
+  .group = { $count ->
+        [one] One face
+       *[other] { $count } faces
+    }
+  .move-button = Move to…
+  .new-person = New person name
+
 # Person delete dialog
 person-delete-dialog =
   .heading = Delete person?
@@ -26,12 +26,17 @@ use crate::fl;
 
 use fotema_core::FlatpakPathBuf;
 use fotema_core::PictureId;
+use fotema_core::VideoId;
 use fotema_core::VisualId;
 use fotema_core::database;
+use fotema_core::duplicates;
+use fotema_core::machine_learning::model_registry::ModelRegistry;
 use fotema_core::path_encoding;
 use fotema_core::people;
 use fotema_core::thumbnailify::Thumbnailer;
+use fotema_core::video::{TranscodeProfile, TranscodeQuality, VideoCodec};
 
+use chrono::TimeDelta;
 use h3o::CellIndex;
 
 use std::path::PathBuf;
@@ -55,6 +60,7 @@ use self::components::{
         album::{Album, AlbumInput, AlbumOutput},
         album_filter::AlbumFilter,
         album_sort::AlbumSort,
+        duplicates_album::{DuplicatesAlbum, DuplicatesAlbumInput, DuplicatesAlbumOutput},
         folders_album::{FoldersAlbum, FoldersAlbumInput, FoldersAlbumOutput},
         people_album::{PeopleAlbum, PeopleAlbumInput, PeopleAlbumOutput},
         person_album::{PersonAlbum, PersonAlbumInput, PersonAlbumOutput},
@@ -69,7 +75,7 @@ use self::components::{
 mod background;
 
 use self::background::bootstrap::{
-    Bootstrap, BootstrapInput, BootstrapOutput, MediaType, TaskName, ThumbnailType,
+    Bootstrap, BootstrapInput, BootstrapOutput, LibraryConfig, MediaType, TaskName, ThumbnailType,
 };
 
 use self::components::progress_monitor::ProgressMonitor;
@@ -92,6 +98,7 @@ pub enum ViewName {
     Person,
     Places,
     Selfies,
+    Duplicates,
 }
 
 // FIXME Strum 0.28 changes to EnumString have defeated me :-(
@@ -114,6 +121,7 @@ impl FromStr for ViewName {
             "Person" => ::core::result::Result::Ok(ViewName::Person),
             "Places" => ::core::result::Result::Ok(ViewName::Places),
             "Selfies" => ::core::result::Result::Ok(ViewName::Selfies),
+            "Duplicates" => ::core::result::Result::Ok(ViewName::Duplicates),
             _ => ::core::result::Result::Err(::strum::ParseError::VariantNotFound),
         }
     }
@@ -161,13 +169,40 @@ pub struct Settings {
     /// Enable processing of Android motion photos.
     pub process_motion_photos: bool,
 
+    /// How to transcode videos that can't be played.
+    pub transcode_profile: TranscodeProfile,
+
     /// Has the user completed the onboarding processes to select
     /// the picture library root directory?
     pub is_onboarding_complete: bool,
 
-    /// Base path of pictures directory inside Flatpak sandbox.
+    /// Base paths of library root directories inside Flatpak sandbox.
     /// Will be under `/run/users/<uid>/docs/<doc-id>/...`
-    pub library_base_dir: FlatpakPathBuf,
+    /// The first root is the pictures directory chosen during onboarding.
+    pub library_roots: Vec<FlatpakPathBuf>,
+
+    /// Glob patterns for directories and files to keep out of the library.
+    pub exclude_patterns: Vec<String>,
+
+    /// Directories to keep out of the library.
+    pub exclude_dirs: Vec<FlatpakPathBuf>,
+
+    /// Directory of face detection and recognition models, for computers that
+    /// can't download them.
+    pub models_dir: Option<FlatpakPathBuf>,
+
+    /// Base URL to download models from instead of their usual locations.
+    pub model_mirror: String,
+}
+
+impl Settings {
+    /// Model registry that also looks in the models directory and downloads from the mirror.
+    pub fn model_registry(&self, models: &ModelRegistry) -> ModelRegistry {
+        models
+            .clone()
+            .with_user_dir(self.models_dir.as_ref().map(|dir| dir.sandbox_path.clone()))
+            .with_mirror(Some(self.model_mirror.clone()))
+    }
 }
 
 /// Active settings
@@ -217,6 +252,9 @@ pub(super) struct App {
     // Grid of folders of photos
     folders_album: Controller<FoldersAlbum>,
 
+    // Groups of photos that are copies of each other
+    duplicates_album: Controller<DuplicatesAlbum>,
+
     // Folder album currently being viewed
     folder_album: Controller<Album>,
 
@@ -274,6 +312,9 @@ pub(super) enum AppMsg {
 
     PersonRenamed,
 
+    // People merged or faces moved between people.
+    PeopleChanged,
+
     // A background task has started.
     TaskStarted(TaskName),
 
@@ -282,11 +323,22 @@ pub(super) enum AppMsg {
 
     TranscodeAll,
 
+    RetryTranscode(VisualId),
+
     ScanPictureForFaces(PictureId),
     ScanPicturesForFaces,
 
+    // Recognize unknown faces as people
+    RecognizeFaces,
+
     ProcessMotionPhotos,
 
+    // Shift capture time of pictures and videos to correct a mis-set camera clock.
+    ShiftTime(Vec<PictureId>, Vec<VideoId>, TimeDelta),
+
+    // Remove capture time shifts of pictures and videos.
+    RemoveTimeShift(Vec<PictureId>, Vec<VideoId>),
+
     // Stop all background tasks
     StopBackgroundTasks,
 
@@ -509,6 +561,16 @@ impl SimpleAsyncComponent for App {
                                             // NOTE gtk::StackSidebar doesn't show icon :-/
                                             set_icon_name: "folder-symbolic",
                                         },
+
+                                        add_child = &gtk::Box {
+                                            set_orientation: gtk::Orientation::Vertical,
+                                            container_add: model.duplicates_album.widget(),
+                                        } -> {
+                                            set_title: &fl!("duplicates-album"),
+                                            set_name: ViewName::Duplicates.as_ref(),
+                                            // NOTE gtk::StackSidebar doesn't show icon :-/
+                                            set_icon_name: "edit-copy-symbolic",
+                                        },
                                     },
                                 },
                             },
@@ -589,6 +651,10 @@ impl SimpleAsyncComponent for App {
 
         let people_repo = people::Repository::open(&cache_dir, &data_dir, con.clone()).unwrap();
 
+        let duplicates_repo = duplicates::Repository::open(con.clone()).unwrap();
+
+        let models = ModelRegistry::build(&cache_dir).unwrap();
+
         let state = SharedState::new(relm4::SharedState::new());
         let active_view = ActiveView::new(relm4::SharedState::new());
         let adaptive_layout = Arc::new(adaptive::LayoutState::new());
@@ -619,6 +685,7 @@ impl SimpleAsyncComponent for App {
                 state.clone(),
                 settings_state.clone(),
                 bootstrap_progress_monitor.clone(),
+                models.clone(),
             ))
             .forward(sender.input_sender(), |msg| match msg {
                 BootstrapOutput::TaskStarted(msg) => AppMsg::TaskStarted(msg),
@@ -656,9 +723,18 @@ impl SimpleAsyncComponent for App {
                 bootstrap_progress_monitor,
                 adaptive_layout.clone(),
                 people_repo.clone(),
+                thumbnailer.clone(),
             ))
             .forward(sender.input_sender(), |msg| match msg {
                 ViewNavOutput::TranscodeAll => AppMsg::TranscodeAll,
+                ViewNavOutput::RetryTranscode(visual_id) => AppMsg::RetryTranscode(visual_id),
+                ViewNavOutput::ShiftTime(picture_ids, video_ids, shift) => {
+                    AppMsg::ShiftTime(picture_ids, video_ids, shift)
+                }
+                ViewNavOutput::RemoveTimeShift(picture_ids, video_ids) => {
+                    AppMsg::RemoveTimeShift(picture_ids, video_ids)
+                }
+                ViewNavOutput::RecognizeFaces => AppMsg::RecognizeFaces,
             });
 
         settings_state.subscribe(view_nav.sender(), |settings| {
@@ -733,6 +809,7 @@ impl SimpleAsyncComponent for App {
             .forward(sender.input_sender(), |msg| match msg {
                 PeopleAlbumOutput::Selected(person) => AppMsg::ViewPerson(person),
                 PeopleAlbumOutput::EnableFaceDetection => AppMsg::ScanPicturesForFaces,
+                PeopleAlbumOutput::PersonNamed => AppMsg::RecognizeFaces,
             });
 
         adaptive_layout.subscribe(people_page.sender(), |layout| {
@@ -750,6 +827,7 @@ impl SimpleAsyncComponent for App {
                 PersonAlbumOutput::Selected(id, filter) => AppMsg::View(id, filter),
                 PersonAlbumOutput::Deleted => AppMsg::PersonDeleted,
                 PersonAlbumOutput::Renamed => AppMsg::PersonRenamed,
+                PersonAlbumOutput::PeopleChanged => AppMsg::PeopleChanged,
             });
 
         state.subscribe(person_album.sender(), |_| PersonAlbumInput::Refresh);
@@ -787,6 +865,19 @@ impl SimpleAsyncComponent for App {
             FoldersAlbumInput::Adapt(*layout)
         });
 
+        let duplicates_album = DuplicatesAlbum::builder()
+            .launch((
+                state.clone(),
+                active_view.clone(),
+                duplicates_repo,
+                thumbnailer.clone(),
+            ))
+            .forward(sender.input_sender(), |msg| match msg {
+                DuplicatesAlbumOutput::Selected(id, filter) => AppMsg::View(id, filter),
+            });
+
+        state.subscribe(duplicates_album.sender(), |_| DuplicatesAlbumInput::Refresh);
+
         let folder_album = Album::builder()
             .launch((
                 state.clone(),
@@ -809,7 +900,7 @@ impl SimpleAsyncComponent for App {
         let about_dialog = AboutDialog::builder().launch(root.clone()).detach();
 
         let preferences_dialog = PreferencesDialog::builder()
-            .launch((settings_state.clone(), root.clone()))
+            .launch((settings_state.clone(), root.clone(), models))
             .forward(sender.input_sender(), |msg| match msg {
                 PreferencesOutput::EnableFaceDetection => AppMsg::ScanPicturesForFaces,
                 PreferencesOutput::ProcessMotionPhotos => AppMsg::ProcessMotionPhotos,
@@ -852,6 +943,7 @@ impl SimpleAsyncComponent for App {
             show_selfies,
             folders_album,
             folder_album,
+            duplicates_album,
 
             main_navigation: main_navigation.clone(),
             main_stack: main_stack.clone(),
@@ -896,14 +988,14 @@ impl SimpleAsyncComponent for App {
         sender.input(AppMsg::Activate(widgets.main_window.default_width()));
 
         let settings = settings_state.read();
-        let is_onboarding_complete =
-            settings.is_onboarding_complete && settings.library_base_dir.exists();
+        let is_onboarding_complete = settings.is_onboarding_complete
+            && settings.library_roots.iter().any(|dir| dir.exists());
         if is_onboarding_complete {
             model.picture_navigation_view.set_visible(true);
             model.onboard_view.set_visible(false);
-            sender.input(AppMsg::OnboardDone(
-                settings.library_base_dir.sandbox_path.clone(),
-            ));
+            model
+                .bootstrap
+                .emit(BootstrapInput::Configure(LibraryConfig::from(&*settings)));
         } else {
             model.picture_navigation_view.set_visible(false);
             model.onboard_view.set_visible(true);
@@ -982,6 +1074,9 @@ impl SimpleAsyncComponent for App {
                     ViewName::People => self.people_page.emit(PeopleAlbumInput::Activate),
                     ViewName::Person => self.person_album.emit(PersonAlbumInput::Activate),
                     ViewName::Places => self.places_page.emit(PlacesAlbumInput::Activate),
+                    ViewName::Duplicates => {
+                        self.duplicates_album.emit(DuplicatesAlbumInput::Activate)
+                    }
                     ViewName::Nothing => warn!("Nothing activated... which should not happen"),
                 }
             }
@@ -1021,6 +1116,12 @@ impl SimpleAsyncComponent for App {
             AppMsg::PersonRenamed => {
                 self.people_page.emit(PeopleAlbumInput::Refresh);
             }
+            AppMsg::PeopleChanged => {
+                self.people_page.emit(PeopleAlbumInput::Refresh);
+
+                // Confirmed faces have changed, so recognize faces again.
+                self.bootstrap.emit(BootstrapInput::RecognizeFaces);
+            }
             AppMsg::TaskStarted(task_name) => {
                 self.spinner
                     .set_visible(!self.main_navigation.shows_sidebar());
@@ -1059,6 +1160,12 @@ impl SimpleAsyncComponent for App {
                     TaskName::RecognizeFaces => {
                         self.banner.set_title(&fl!("banner-recognize-faces-photos"));
                     }
+                    TaskName::ClusterFaces => {
+                        self.banner.set_title(&fl!("banner-cluster-faces-photos"));
+                    }
+                    TaskName::FindDuplicates => {
+                        self.banner.set_title(&fl!("banner-find-duplicates"));
+                    }
                     TaskName::Clean(MediaType::Photo) => {
                         self.banner.set_title(&fl!("banner-clean-photos"));
                     }
@@ -1080,11 +1187,22 @@ impl SimpleAsyncComponent for App {
                 info!("Bootstrap completed.");
                 self.spinner.set_visible(false);
                 self.banner.set_revealed(false);
+
+                // Photos may have been hashed since duplicates were last shown.
+                self.duplicates_album.emit(DuplicatesAlbumInput::Refresh);
+
+                // Faces may have been grouped into new suggested people.
+                self.people_page.emit(PeopleAlbumInput::Refresh);
             }
             AppMsg::TranscodeAll => {
                 info!("Transcode all");
                 self.bootstrap.emit(BootstrapInput::TranscodeAll);
             }
+            AppMsg::RetryTranscode(visual_id) => {
+                info!("Retry transcode: {}", visual_id);
+                self.bootstrap
+                    .emit(BootstrapInput::RetryTranscode(visual_id));
+            }
             AppMsg::ScanPictureForFaces(picture_id) => {
                 info!("Scan picture for faces: {}", picture_id);
                 self.bootstrap
@@ -1094,10 +1212,24 @@ impl SimpleAsyncComponent for App {
                 info!("Scan pictures for faces");
                 self.bootstrap.emit(BootstrapInput::ScanPicturesForFaces);
             }
+            AppMsg::RecognizeFaces => {
+                info!("Recognize faces");
+                self.bootstrap.emit(BootstrapInput::RecognizeFaces);
+            }
             AppMsg::ProcessMotionPhotos => {
                 info!("Process motion photos");
                 self.bootstrap.emit(BootstrapInput::ProcessMotionPhotos);
             }
+            AppMsg::ShiftTime(picture_ids, video_ids, shift) => {
+                info!("Shift time by {}", shift);
+                self.bootstrap
+                    .emit(BootstrapInput::ShiftTime(picture_ids, video_ids, shift));
+            }
+            AppMsg::RemoveTimeShift(picture_ids, video_ids) => {
+                info!("Remove time shift");
+                self.bootstrap
+                    .emit(BootstrapInput::RemoveTimeShift(picture_ids, video_ids));
+            }
             AppMsg::StopBackgroundTasks => {
                 info!("Stop all background tasks");
                 self.banner.set_button_label(None);
@@ -1127,13 +1259,15 @@ impl SimpleAsyncComponent for App {
             AppMsg::OnboardDone(library_base_dir) => {
                 let mut settings = self.settings_state.read().clone();
                 settings.is_onboarding_complete = true;
-                settings.library_base_dir = host_path::host_path(&library_base_dir)
-                    .await
-                    .unwrap_or(FlatpakPathBuf::build(&library_base_dir, &library_base_dir));
+                settings.library_roots = vec![
+                    host_path::host_path(&library_base_dir)
+                        .await
+                        .unwrap_or(FlatpakPathBuf::build(&library_base_dir, &library_base_dir)),
+                ];
                 *self.settings_state.write() = settings.clone();
 
                 self.bootstrap
-                    .emit(BootstrapInput::Configure(settings.library_base_dir.clone()));
+                    .emit(BootstrapInput::Configure(LibraryConfig::from(&settings)));
                 self.picture_navigation_view.set_visible(true);
                 self.onboard_view.set_visible(false);
             }
@@ -1151,16 +1285,62 @@ impl App {
 
         let gio_settings = gio::Settings::new(APP_ID);
 
-        let pic_base_dir: PathBuf =
-            path_encoding::from_base64(&gio_settings.string("pictures-base-dir-b64").into())?;
+        let mut library_dirs: Vec<String> = gio_settings
+            .strv("library-dirs-b64")
+            .iter()
+            .map(|dir| dir.to_string())
+            .collect();
 
-        let library_base_dir = host_path::host_path(&pic_base_dir)
-            .await
-            .unwrap_or(FlatpakPathBuf::build(&pic_base_dir, &pic_base_dir));
+        // Settings from before multiple library roots only have the pictures directory.
+        if library_dirs.is_empty() {
+            library_dirs.push(gio_settings.string("pictures-base-dir-b64").into());
+        }
+
+        let mut library_roots = Vec::with_capacity(library_dirs.len());
+        for library_dir in library_dirs {
+            let library_dir: PathBuf = path_encoding::from_base64(&library_dir)?;
+            let library_root = host_path::host_path(&library_dir)
+                .await
+                .unwrap_or(FlatpakPathBuf::build(&library_dir, &library_dir));
+            library_roots.push(library_root);
+        }
+
+        let exclude_patterns = gio_settings
+            .strv("exclude-patterns")
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect();
+
+        let mut exclude_dirs = Vec::new();
+        for exclude_dir in gio_settings.strv("exclude-dirs-b64").iter() {
+            let exclude_dir: PathBuf = path_encoding::from_base64(&exclude_dir.to_string())?;
+            let exclude_dir = host_path::host_path(&exclude_dir)
+                .await
+                .unwrap_or(FlatpakPathBuf::build(&exclude_dir, &exclude_dir));
+            exclude_dirs.push(exclude_dir);
+        }
+
+        let models_dir = gio_settings.string("models-dir-b64");
+        let models_dir = if models_dir.is_empty() {
+            None
+        } else {
+            let models_dir: PathBuf = path_encoding::from_base64(&models_dir.to_string())?;
+            let models_dir = host_path::host_path(&models_dir)
+                .await
+                .unwrap_or(FlatpakPathBuf::build(&models_dir, &models_dir));
+            Some(models_dir)
+        };
 
         Ok(Settings {
             show_selfies: gio_settings.boolean("show-selfies"),
             process_motion_photos: gio_settings.boolean("process-motion-photos"),
+            transcode_profile: TranscodeProfile {
+                codec: VideoCodec::from_str(&gio_settings.string("transcode-codec"))
+                    .unwrap_or_default(),
+                max_size: Some(gio_settings.uint("transcode-max-size")).filter(|x| *x > 0),
+                quality: TranscodeQuality::from_str(&gio_settings.string("transcode-quality"))
+                    .unwrap_or_default(),
+            },
             face_detection_mode: FaceDetectionMode::from_str(
                 &gio_settings.string("face-detection-mode"),
             )
@@ -1168,7 +1348,11 @@ impl App {
             album_sort: AlbumSort::from_str(&gio_settings.string("album-sort"))
                 .unwrap_or(AlbumSort::Ascending),
             is_onboarding_complete: gio_settings.boolean("onboarding-complete"),
-            library_base_dir,
+            library_roots,
+            exclude_patterns,
+            exclude_dirs,
+            models_dir,
+            model_mirror: gio_settings.string("model-mirror-url").into(),
         })
     }
 
@@ -1177,13 +1361,49 @@ impl App {
         let gio_settings = gio::Settings::new(APP_ID);
         gio_settings.set_boolean("show-selfies", settings.show_selfies)?;
         gio_settings.set_boolean("process-motion-photos", settings.process_motion_photos)?;
+        gio_settings.set_string("transcode-codec", settings.transcode_profile.codec.as_ref())?;
+        gio_settings.set_uint(
+            "transcode-max-size",
+            settings.transcode_profile.max_size.unwrap_or(0),
+        )?;
+        gio_settings.set_string(
+            "transcode-quality",
+            settings.transcode_profile.quality.as_ref(),
+        )?;
         gio_settings.set_string("face-detection-mode", settings.face_detection_mode.as_ref())?;
         gio_settings.set_string("album-sort", settings.album_sort.as_ref())?;
         gio_settings.set_boolean("onboarding-complete", settings.is_onboarding_complete)?;
-        gio_settings.set_string(
-            "pictures-base-dir-b64",
-            &path_encoding::to_base64(&settings.library_base_dir.sandbox_path),
-        )?;
+        let library_dirs: Vec<String> = settings
+            .library_roots
+            .iter()
+            .map(|dir| path_encoding::to_base64(&dir.sandbox_path))
+            .collect();
+        gio_settings.set_strv("library-dirs-b64", library_dirs.as_slice())?;
+        if let Some(pictures_dir) = library_dirs.first() {
+            gio_settings.set_string("pictures-base-dir-b64", pictures_dir)?;
+        }
+
+        let exclude_patterns: Vec<&str> = settings
+            .exclude_patterns
+            .iter()
+            .map(String::as_str)
+            .collect();
+        gio_settings.set_strv("exclude-patterns", exclude_patterns.as_slice())?;
+
+        let exclude_dirs: Vec<String> = settings
+            .exclude_dirs
+            .iter()
+            .map(|dir| path_encoding::to_base64(&dir.sandbox_path))
+            .collect();
+        gio_settings.set_strv("exclude-dirs-b64", exclude_dirs.as_slice())?;
+
+        let models_dir = settings
+            .models_dir
+            .as_ref()
+            .map(|dir| path_encoding::to_base64(&dir.sandbox_path))
+            .unwrap_or_default();
+        gio_settings.set_string("models-dir-b64", &models_dir)?;
+        gio_settings.set_string("model-mirror-url", &settings.model_mirror)?;
         Ok(())
     }
 }
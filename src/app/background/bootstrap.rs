@@ -10,10 +10,17 @@ use crate::config::APP_ID;
 use fotema_core::FlatpakPathBuf;
 use fotema_core::PictureId;
 use fotema_core::Scanner;
+use fotema_core::VideoId;
+use fotema_core::VisualId;
 use fotema_core::database;
+use fotema_core::duplicates;
+use fotema_core::library_root;
+use fotema_core::machine_learning::model_registry::ModelRegistry;
 use fotema_core::people;
 use fotema_core::people::migrate::Migrate;
 use fotema_core::photo;
+use fotema_core::scanner;
+use fotema_core::scanner::ExcludeRules;
 use fotema_core::thumbnailify::Thumbnailer;
 use fotema_core::video;
 use fotema_core::visual;
@@ -22,12 +29,14 @@ use std::result::Result::Ok;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
-use std::collections::VecDeque;
-use std::time::Instant;
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use tracing::{error, info, warn};
 
 use anyhow;
+use chrono::TimeDelta;
 
 use super::{
     library_scan_task::{LibraryScanTask, LibraryScanTaskInput, LibraryScanTaskOutput},
@@ -37,6 +46,9 @@ use super::{
         PersonThumbnailTask, PersonThumbnailTaskInput, PersonThumbnailTaskOutput,
     },
     photo_clean_task::{PhotoCleanTask, PhotoCleanTaskInput, PhotoCleanTaskOutput},
+    photo_cluster_faces_task::{
+        PhotoClusterFacesTask, PhotoClusterFacesTaskInput, PhotoClusterFacesTaskOutput,
+    },
     photo_detect_faces_task::{
         PhotoDetectFacesTask, PhotoDetectFacesTaskInput, PhotoDetectFacesTaskOutput,
     },
@@ -44,6 +56,7 @@ use super::{
     photo_extract_motion_task::{
         PhotoExtractMotionTask, PhotoExtractMotionTaskInput, PhotoExtractMotionTaskOutput,
     },
+    photo_hash_task::{PhotoHashTask, PhotoHashTaskInput, PhotoHashTaskOutput},
     photo_recognize_faces_task::{
         PhotoRecognizeFacesTask, PhotoRecognizeFacesTaskInput, PhotoRecognizeFacesTaskOutput,
     },
@@ -87,15 +100,35 @@ pub enum TaskName {
     Clean(MediaType),
     DetectFaces,
     RecognizeFaces,
+    ClusterFaces,
+    FindDuplicates,
     Transcode,
     Tidy,
     Migrate,
 }
 
+/// Library directories and exclusion rules that background tasks are built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryConfig {
+    pub library_dirs: Vec<FlatpakPathBuf>,
+    pub exclude_patterns: Vec<String>,
+    pub exclude_dirs: Vec<FlatpakPathBuf>,
+}
+
+impl From<&Settings> for LibraryConfig {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            library_dirs: settings.library_roots.clone(),
+            exclude_patterns: settings.exclude_patterns.clone(),
+            exclude_dirs: settings.exclude_dirs.clone(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum BootstrapInput {
-    /// Configure the pictures library root and host path
-    Configure(FlatpakPathBuf),
+    /// Configure the library root directories, host paths, and exclusion rules
+    Configure(LibraryConfig),
 
     /// Settings updated
     SettingsUpdated(Settings),
@@ -113,12 +146,27 @@ pub enum BootstrapInput {
     ScanPictureForFaces(PictureId),
     ScanPicturesForFaces,
 
+    /// Queue task for recognizing unknown faces as people
+    RecognizeFaces,
+
     /// Queue task for transcoding videos
     TranscodeAll,
 
+    /// Queue task for transcoding a video that previously failed to transcode
+    RetryTranscode(VisualId),
+
     /// Queue task for processing motion photos
     ProcessMotionPhotos,
 
+    /// File system watcher saw changes in these directories.
+    LibraryChanged(HashSet<PathBuf>),
+
+    /// Shift capture time of pictures and videos to correct a mis-set camera clock.
+    ShiftTime(Vec<PictureId>, Vec<VideoId>, TimeDelta),
+
+    /// Remove capture time shifts, restoring the capture time from the metadata.
+    RemoveTimeShift(Vec<PictureId>, Vec<VideoId>),
+
     /// A background task has started.
     TaskStarted(TaskName),
 
@@ -170,8 +218,11 @@ pub struct Controllers {
 
     photo_extract_motion_task: Arc<WorkerController<PhotoExtractMotionTask>>,
 
+    photo_hash_task: Arc<WorkerController<PhotoHashTask>>,
+
     photo_detect_faces_task: Arc<WorkerController<PhotoDetectFacesTask>>,
     photo_recognize_faces_task: Arc<WorkerController<PhotoRecognizeFacesTask>>,
+    photo_cluster_faces_task: Arc<WorkerController<PhotoClusterFacesTask>>,
 
     video_transcode_task: Arc<WorkerController<VideoTranscodeTask>>,
 
@@ -179,6 +230,21 @@ pub struct Controllers {
     migrate_task: Arc<WorkerController<MigrateTask>>,
     person_thumbnail_task: Arc<WorkerController<PersonThumbnailTask>>,
 
+    /// For correcting capture times of visual items.
+    visual_repo: visual::Repository,
+
+    /// Watches library for changes. Watching stops when dropped.
+    _watcher: Option<scanner::Watcher>,
+
+    /// Directories changed since the last rescan was started.
+    dirty_dirs: Arc<Mutex<HashSet<PathBuf>>>,
+
+    /// Whether a rescan is queued but not yet started.
+    rescan_queued: Arc<AtomicBool>,
+
+    /// Whether recognizing faces is queued but not yet started.
+    recognize_queued: Arc<AtomicBool>,
+
     /// Pending ordered tasks to process
     /// Wow... figuring out a type signature that would compile was a nightmare.
     pending_tasks: Arc<Mutex<VecDeque<Box<Task>>>>,
@@ -210,24 +276,103 @@ impl Controllers {
                 info!("Queueing task to scan picture {} for faces", picture_id);
                 self.add_task_photo_detect_faces_for_one(picture_id);
                 self.add_task_photo_recognize_faces();
+                self.add_task_photo_cluster_faces();
                 self.run_if_idle();
             }
             BootstrapInput::ScanPicturesForFaces => {
                 info!("Queueing task to scan all pictures for faces");
                 self.add_task_photo_detect_faces();
                 self.add_task_photo_recognize_faces();
+                self.add_task_photo_cluster_faces();
                 self.run_if_idle();
             }
+            BootstrapInput::RecognizeFaces => {
+                // Confirming several faces in a row only needs one pass of recognizing
+                // and clustering faces, so don't queue another while one is waiting to start.
+                if self.recognize_queued.load(Ordering::Relaxed) {
+                    info!("Recognizing faces is already queued");
+                } else {
+                    info!("Queueing task to recognize faces");
+                    self.add_task_photo_recognize_faces();
+                    self.add_task_photo_cluster_faces();
+                    self.run_if_idle();
+                }
+            }
             BootstrapInput::TranscodeAll => {
                 info!("Queueing task to transcode all incompatible videos");
                 self.add_task_video_transcode();
                 self.run_if_idle();
             }
+            BootstrapInput::RetryTranscode(visual_id) => {
+                info!("Queueing task to retry transcoding {}", visual_id);
+                let sender = self.video_transcode_task.sender().clone();
+                self.enqueue(Box::new(move || {
+                    sender.emit(VideoTranscodeTaskInput::Retry(visual_id))
+                }));
+                self.run_if_idle();
+            }
             BootstrapInput::ProcessMotionPhotos => {
                 info!("Queueing task to process motion photos");
                 self.add_task_photo_extract_motion();
                 self.run_if_idle();
             }
+            BootstrapInput::LibraryChanged(dirs) => {
+                if let Ok(mut dirty_dirs) = self.dirty_dirs.lock() {
+                    dirty_dirs.extend(dirs);
+                }
+
+                // Changes seen while a rescan is queued will be picked up by that rescan.
+                if !self.rescan_queued.swap(true, Ordering::Relaxed) {
+                    info!("Queueing tasks to process library changes");
+                    self.add_task_library_rescan();
+                    self.add_task_photo_enrich();
+                    self.add_task_video_enrich();
+                    self.add_task_load_library(sender.input_sender().clone());
+                    self.add_task_photo_thumbnail();
+                    self.add_task_video_thumbnail();
+                    self.add_task_photo_clean();
+                    self.add_task_video_clean();
+                    self.add_task_photo_hash();
+                    self.add_task_photo_extract_motion();
+                    self.add_task_photo_detect_faces();
+                    self.add_task_photo_recognize_faces();
+                    self.add_task_photo_cluster_faces();
+                    self.add_task_load_library(sender.input_sender().clone());
+                    self.run_if_idle();
+                }
+            }
+            BootstrapInput::ShiftTime(picture_ids, video_ids, shift) => {
+                info!(
+                    "Shifting time of {} pictures and {} videos by {}",
+                    picture_ids.len(),
+                    video_ids.len(),
+                    shift
+                );
+                if let Err(e) = self
+                    .visual_repo
+                    .add_time_shift(&picture_ids, &video_ids, shift)
+                {
+                    error!("Failed to shift time: {:?}", e);
+                    return;
+                }
+                self.library_stale.store(true, Ordering::Relaxed);
+                self.add_task_load_library(sender.input_sender().clone());
+                self.run_if_idle();
+            }
+            BootstrapInput::RemoveTimeShift(picture_ids, video_ids) => {
+                info!(
+                    "Removing time shift of {} pictures and {} videos",
+                    picture_ids.len(),
+                    video_ids.len()
+                );
+                if let Err(e) = self.visual_repo.remove_time_shift(&picture_ids, &video_ids) {
+                    error!("Failed to remove time shift: {:?}", e);
+                    return;
+                }
+                self.library_stale.store(true, Ordering::Relaxed);
+                self.add_task_load_library(sender.input_sender().clone());
+                self.run_if_idle();
+            }
             BootstrapInput::TaskStarted(task_name) => {
                 info!("Task started: {:?}", task_name);
                 let _ = sender.output(BootstrapOutput::TaskStarted(task_name));
@@ -265,6 +410,8 @@ impl Controllers {
                     if let Ok(mut tasks) = self.pending_tasks.lock() {
                         tasks.clear();
                     }
+                    self.rescan_queued.store(false, Ordering::Relaxed);
+                    self.recognize_queued.store(false, Ordering::Relaxed);
                     self.stop.store(true, Ordering::Relaxed);
                 } else {
                     sender.input(BootstrapInput::Stopped);
@@ -281,6 +428,16 @@ impl Controllers {
         self.enqueue(Box::new(move || sender.emit(LibraryScanTaskInput::Start)));
     }
 
+    fn add_task_library_rescan(&mut self) {
+        let sender = self.library_scan_task.sender().clone();
+        let dirty_dirs = self.dirty_dirs.clone();
+        let rescan_queued = self.rescan_queued.clone();
+        self.enqueue(Box::new(move || {
+            rescan_queued.store(false, Ordering::Relaxed);
+            sender.emit(LibraryScanTaskInput::Rescan(dirty_dirs));
+        }));
+    }
+
     fn add_task_photo_enrich(&mut self) {
         let sender = self.photo_enrich_task.sender().clone();
         self.enqueue(Box::new(move || sender.emit(PhotoEnrichTaskInput::Start)));
@@ -315,6 +472,11 @@ impl Controllers {
         self.enqueue(Box::new(move || sender.emit(VideoCleanTaskInput::Start)));
     }
 
+    fn add_task_photo_hash(&mut self) {
+        let sender = self.photo_hash_task.sender().clone();
+        self.enqueue(Box::new(move || sender.emit(PhotoHashTaskInput::Start)));
+    }
+
     fn add_task_photo_extract_motion(&mut self) {
         let sender = self.photo_extract_motion_task.sender().clone();
         let enable = self.settings_state.read().process_motion_photos;
@@ -353,17 +515,33 @@ impl Controllers {
 
     fn add_task_photo_recognize_faces(&mut self) {
         let sender = self.photo_recognize_faces_task.sender().clone();
+        let recognize_queued = self.recognize_queued.clone();
         let mode = self.settings_state.read().face_detection_mode;
         match mode {
             FaceDetectionMode::Off => {}
             FaceDetectionMode::On => {
+                recognize_queued.store(true, Ordering::Relaxed);
                 self.enqueue(Box::new(move || {
+                    recognize_queued.store(false, Ordering::Relaxed);
                     sender.emit(PhotoRecognizeFacesTaskInput::Start)
                 }));
             }
         };
     }
 
+    fn add_task_photo_cluster_faces(&mut self) {
+        let sender = self.photo_cluster_faces_task.sender().clone();
+        let mode = self.settings_state.read().face_detection_mode;
+        match mode {
+            FaceDetectionMode::Off => {}
+            FaceDetectionMode::On => {
+                self.enqueue(Box::new(move || {
+                    sender.emit(PhotoClusterFacesTaskInput::Start)
+                }));
+            }
+        };
+    }
+
     fn add_task_person_thumbnails(&mut self) {
         let sender = self.person_thumbnail_task.sender().clone();
         let mode = self.settings_state.read().face_detection_mode;
@@ -438,17 +616,20 @@ pub struct Bootstrap {
 
     progress_monitor: Arc<Reducer<ProgressMonitor>>,
 
+    /// Face models, shared with the preferences so that they show which models loaded.
+    models: ModelRegistry,
+
     /// Background task runners. Only present after library path is set.
     controllers: Option<Controllers>,
 
-    /// Current pictures base directory used by background tasks.
-    library_base_dir: Option<FlatpakPathBuf>,
+    /// Current library root directories used by background tasks.
+    library_config: Option<LibraryConfig>,
 }
 
 impl Bootstrap {
     fn build_controllers(
         &mut self,
-        library_base_dir: &FlatpakPathBuf,
+        library_config: &LibraryConfig,
         sender: &ComponentSender<Self>,
     ) -> anyhow::Result<Controllers> {
         let data_dir = glib::user_data_dir().join(APP_ID);
@@ -466,26 +647,53 @@ impl Bootstrap {
 
         let thumbnailer = Thumbnailer::build(&thumbnail_dir);
 
+        let library_roots =
+            library_root::Repository::open(self.con.clone())?.sync(&library_config.library_dirs)?;
+
         let photo_repo =
-            photo::Repository::open(library_base_dir, &cache_dir, &data_dir, self.con.clone())?;
+            photo::Repository::open(&library_roots, &cache_dir, &data_dir, self.con.clone())?;
 
         let photo_thumbnailer = photo::PhotoThumbnailer::build(thumbnailer.clone())?;
 
-        let scanner = Scanner::build(&library_base_dir.sandbox_path)?;
+        let exclude_rules = ExcludeRules::build(
+            &library_config.exclude_patterns,
+            &library_config.exclude_dirs,
+        );
+
+        let scanner = Scanner::build(&library_roots, exclude_rules)?;
+
+        let scan_repo = scanner::Repository::open(&library_roots, self.con.clone())?;
 
         let video_repo =
-            video::Repository::open(library_base_dir, &cache_dir, &data_dir, self.con.clone())?;
+            video::Repository::open(&library_roots, &cache_dir, &data_dir, self.con.clone())?;
 
         let video_thumbnailer = video::VideoThumbnailer::build(thumbnailer.clone())?;
 
+        let sprite_sheet_generator = video::SpriteSheetGenerator::build(thumbnailer.clone())?;
+
         let motion_photo_extractor = photo::MotionPhotoExtractor::build(&cache_dir)?;
 
-        let visual_repo = visual::Repository::open(library_base_dir, &cache_dir, self.con.clone())?;
+        let visual_repo = visual::Repository::open(&library_roots, &cache_dir, self.con.clone())?;
 
         let people_repo = people::Repository::open(&cache_dir, &data_dir, self.con.clone())?;
 
         let stop = Arc::new(AtomicBool::new(false));
 
+        // Debounce file system events so that copying many files into the library
+        // results in one rescan rather than one per file.
+        let watcher = {
+            let sender = sender.input_sender().clone();
+            scanner::Watcher::watch(
+                &library_roots.sandbox_paths(),
+                Duration::from_secs(3),
+                move |dirs| sender.emit(BootstrapInput::LibraryChanged(dirs)),
+            )
+        };
+
+        let watcher = watcher
+            .inspect_err(|e| warn!("Failed to watch library for changes: {:?}", e))
+            .ok();
+
         let load_library_task = LoadLibraryTask::builder()
             .detach_worker((visual_repo.clone(), self.shared_state.clone()))
             .forward(sender.input_sender(), |msg| match msg {
@@ -495,11 +703,17 @@ impl Bootstrap {
             });
 
         let library_scan_task = LibraryScanTask::builder()
-            .detach_worker((scanner, photo_repo.clone(), video_repo.clone()))
+            .detach_worker((
+                scanner.clone(),
+                scan_repo,
+                photo_repo.clone(),
+                video_repo.clone(),
+                thumbnailer.clone(),
+            ))
             .forward(sender.input_sender(), |msg| match msg {
                 LibraryScanTaskOutput::Started => BootstrapInput::TaskStarted(TaskName::Scan),
-                LibraryScanTaskOutput::Completed => {
-                    BootstrapInput::TaskCompleted(TaskName::Scan, None)
+                LibraryScanTaskOutput::Completed(count) => {
+                    BootstrapInput::TaskCompleted(TaskName::Scan, Some(count))
                 }
             });
 
@@ -568,6 +782,7 @@ impl Bootstrap {
                 stop.clone(),
                 thumbnail_dir.clone(),
                 video_thumbnailer.clone(),
+                sprite_sheet_generator,
                 video_repo.clone(),
                 self.progress_monitor.clone(),
             ))
@@ -581,12 +796,35 @@ impl Bootstrap {
                 ),
             });
 
+        let picture_hasher = duplicates::PictureHasher::build(&thumbnail_dir)?;
+
+        let duplicates_repo = duplicates::Repository::open(self.con.clone())?;
+
+        // Hashing doesn't change any visual items, so the library doesn't need reloading.
+        let photo_hash_task = PhotoHashTask::builder()
+            .detach_worker((
+                stop.clone(),
+                picture_hasher,
+                photo_repo.clone(),
+                duplicates_repo,
+                self.progress_monitor.clone(),
+            ))
+            .forward(sender.input_sender(), |msg| match msg {
+                PhotoHashTaskOutput::Started => {
+                    BootstrapInput::TaskStarted(TaskName::FindDuplicates)
+                }
+                PhotoHashTaskOutput::Completed(_) => {
+                    BootstrapInput::TaskCompleted(TaskName::FindDuplicates, None)
+                }
+            });
+
         let transcoder = video::Transcoder::new(&cache_dir);
 
         let video_transcode_task = VideoTranscodeTask::builder()
             .detach_worker((
                 stop.clone(),
                 self.shared_state.clone(),
+                self.settings_state.clone(),
                 video_repo.clone(),
                 transcoder,
                 self.progress_monitor.clone(),
@@ -601,7 +839,7 @@ impl Bootstrap {
             });
 
         let photo_clean_task = PhotoCleanTask::builder()
-            .detach_worker((stop.clone(), photo_repo.clone()))
+            .detach_worker((stop.clone(), photo_repo.clone(), scanner.clone()))
             .forward(sender.input_sender(), |msg| match msg {
                 PhotoCleanTaskOutput::Started => {
                     BootstrapInput::TaskStarted(TaskName::Clean(MediaType::Photo))
@@ -612,7 +850,7 @@ impl Bootstrap {
             });
 
         let video_clean_task = VideoCleanTask::builder()
-            .detach_worker((stop.clone(), video_repo.clone()))
+            .detach_worker((stop.clone(), video_repo.clone(), scanner.clone()))
             .forward(sender.input_sender(), |msg| match msg {
                 VideoCleanTaskOutput::Started => {
                     BootstrapInput::TaskStarted(TaskName::Clean(MediaType::Video))
@@ -622,11 +860,15 @@ impl Bootstrap {
                 }
             });
 
+        let models = self.models.clone();
+
         let photo_detect_faces_task = PhotoDetectFacesTask::builder()
             .detach_worker((
                 stop.clone(),
                 data_dir.clone(),
                 thumbnailer.clone(),
+                models.clone(),
+                self.settings_state.clone(),
                 photo_repo.clone(),
                 people_repo.clone(),
                 self.progress_monitor.clone(),
@@ -643,7 +885,8 @@ impl Bootstrap {
         let photo_recognize_faces_task = PhotoRecognizeFacesTask::builder()
             .detach_worker((
                 stop.clone(),
-                cache_dir.clone(),
+                models.clone(),
+                self.settings_state.clone(),
                 people_repo.clone(),
                 self.progress_monitor.clone(),
             ))
@@ -656,6 +899,23 @@ impl Bootstrap {
                 }
             });
 
+        let photo_cluster_faces_task = PhotoClusterFacesTask::builder()
+            .detach_worker((
+                stop.clone(),
+                models,
+                self.settings_state.clone(),
+                people_repo.clone(),
+                self.progress_monitor.clone(),
+            ))
+            .forward(sender.input_sender(), |msg| match msg {
+                PhotoClusterFacesTaskOutput::Started => {
+                    BootstrapInput::TaskStarted(TaskName::ClusterFaces)
+                }
+                PhotoClusterFacesTaskOutput::Completed => {
+                    BootstrapInput::TaskCompleted(TaskName::ClusterFaces, None)
+                }
+            });
+
         let tidy_task =
             TidyTask::builder()
                 .detach_worker(stop.clone())
@@ -666,7 +926,7 @@ impl Bootstrap {
                     }
                 });
 
-        let migrate = Migrate::build(people_repo, &data_dir, library_base_dir.clone());
+        let migrate = Migrate::build(people_repo, &data_dir, library_roots.clone());
 
         let migrate_task = MigrateTask::builder()
             .detach_worker((stop.clone(), migrate))
@@ -706,16 +966,23 @@ impl Bootstrap {
             photo_enrich_task: Arc::new(photo_enrich_task),
             video_enrich_task: Arc::new(video_enrich_task),
             photo_extract_motion_task: Arc::new(photo_extract_motion_task),
+            photo_hash_task: Arc::new(photo_hash_task),
             photo_clean_task: Arc::new(photo_clean_task),
             video_clean_task: Arc::new(video_clean_task),
             photo_thumbnail_task: Arc::new(photo_thumbnail_task),
             video_thumbnail_task: Arc::new(video_thumbnail_task),
             photo_detect_faces_task: Arc::new(photo_detect_faces_task),
             photo_recognize_faces_task: Arc::new(photo_recognize_faces_task),
+            photo_cluster_faces_task: Arc::new(photo_cluster_faces_task),
             video_transcode_task: Arc::new(video_transcode_task),
             tidy_task: Arc::new(tidy_task),
             migrate_task: Arc::new(migrate_task),
             person_thumbnail_task: Arc::new(person_thumbnail_task),
+            visual_repo,
+            _watcher: watcher,
+            dirty_dirs: Arc::new(Mutex::new(HashSet::new())),
+            rescan_queued: Arc::new(AtomicBool::new(false)),
+            recognize_queued: Arc::new(AtomicBool::new(false)),
             pending_tasks: Arc::new(Mutex::new(VecDeque::new())),
             is_running: false,
             library_stale: Arc::new(AtomicBool::new(true)),
@@ -741,9 +1008,11 @@ impl Bootstrap {
         controllers.add_task_video_thumbnail();
         controllers.add_task_photo_clean();
         controllers.add_task_video_clean();
+        controllers.add_task_photo_hash();
         controllers.add_task_photo_extract_motion();
         controllers.add_task_photo_detect_faces();
         controllers.add_task_photo_recognize_faces();
+        controllers.add_task_photo_cluster_faces();
 
         controllers.add_task_tidy();
 
@@ -761,12 +1030,13 @@ impl Worker for Bootstrap {
         SharedState,
         SettingsState,
         Arc<Reducer<ProgressMonitor>>,
+        ModelRegistry,
     );
     type Input = BootstrapInput;
     type Output = BootstrapOutput;
 
     fn init(
-        (con, shared_state, settings_state, progress_monitor): Self::Init,
+        (con, shared_state, settings_state, progress_monitor, models): Self::Init,
         sender: ComponentSender<Self>,
     ) -> Self {
         settings_state.subscribe(sender.input_sender(), |settings| {
@@ -777,9 +1047,10 @@ impl Worker for Bootstrap {
             shared_state,
             settings_state,
             progress_monitor,
+            models,
             con,
             controllers: None,
-            library_base_dir: None,
+            library_config: None,
         }
     }
 
@@ -787,15 +1058,12 @@ impl Worker for Bootstrap {
         // This match block coordinates the background tasks launched immediately after
         // the app starts up.
         match msg {
-            BootstrapInput::Configure(library_base_dir) => {
-                info!(
-                    "Configuring with pictures base directory: {:?}",
-                    library_base_dir
-                );
+            BootstrapInput::Configure(library_config) => {
+                info!("Configuring with library: {:?}", library_config);
 
-                match self.build_controllers(&library_base_dir, &sender) {
+                match self.build_controllers(&library_config, &sender) {
                     Ok(controllers) => {
-                        self.library_base_dir = Some(library_base_dir);
+                        self.library_config = Some(library_config);
                         self.controllers = Some(controllers);
                         sender.input(BootstrapInput::Start);
                     }
@@ -806,11 +1074,12 @@ impl Worker for Bootstrap {
             }
             BootstrapInput::SettingsUpdated(settings) => {
                 info!("Settings updated.");
-                // Only stop, reconfigure, and restart tasks if pictures dir changes.
+                // Only stop, reconfigure, and restart tasks if library dirs or exclusions change.
+                let library_config = LibraryConfig::from(&settings);
                 if self
-                    .library_base_dir
+                    .library_config
                     .as_ref()
-                    .is_some_and(|dir| *dir != settings.library_base_dir)
+                    .is_some_and(|config| *config != library_config)
                 {
                     // If running, then shutdown running and queued tasks, and then reconfigure.
                     // Otherwise simply reconfigure with new path.
@@ -819,21 +1088,21 @@ impl Worker for Bootstrap {
                         .as_ref()
                         .is_some_and(|controllers| controllers.is_running)
                     {
-                        self.library_base_dir = None;
+                        self.library_config = None;
                         sender.input(BootstrapInput::Stop);
                     } else {
                         self.controllers = None;
-                        sender.input(BootstrapInput::Configure(settings.library_base_dir.clone()));
+                        sender.input(BootstrapInput::Configure(library_config));
                     }
                 }
             }
-            BootstrapInput::Stopped if self.library_base_dir.is_none() => {
-                // If stopped and no pictures base dir, then background tasks were
-                // shutdown in response to the user changing the pictures base directory.
+            BootstrapInput::Stopped if self.library_config.is_none() => {
+                // If stopped and no library dirs, then background tasks were
+                // shutdown in response to the user changing the library directories.
                 // Now that tasks are shutdown, it is safe to reconfigure with
-                // the new directory.
+                // the new directories.
                 let settings = self.settings_state.read();
-                sender.input(BootstrapInput::Configure(settings.library_base_dir.clone()));
+                sender.input(BootstrapInput::Configure(LibraryConfig::from(&*settings)));
             }
             BootstrapInput::TaskCompleted(TaskName::LoadLibrary, _)
                 if self.controllers.is_some() =>
@@ -3,91 +3,226 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use fotema_core::photo::Repository as PhotoRepository;
+use fotema_core::scanner::Repository as ScanRepository;
+use fotema_core::thumbnailify::Thumbnailer;
 use fotema_core::video::Repository as VideoRepository;
 use fotema_core::{ScannedFile, Scanner};
 use itertools::{Either, Itertools};
 use relm4::Worker;
 use relm4::prelude::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tracing::{error, info};
 
 #[derive(Debug)]
 pub enum LibraryScanTaskInput {
     Start,
+
+    /// Scan again, re-listing directories that the file system watcher saw change.
+    /// The directories are taken for the scan and put back if the scan fails.
+    Rescan(Arc<Mutex<HashSet<PathBuf>>>),
 }
 
 #[derive(Debug)]
 pub enum LibraryScanTaskOutput {
     Started,
-    Completed,
+
+    /// Scan completed with count of added, changed, removed, and moved files.
+    Completed(usize),
 }
 
 pub struct LibraryScanTask {
     scan: Scanner,
+    scan_repo: ScanRepository,
     photo_repo: PhotoRepository,
     video_repo: VideoRepository,
+    thumbnailer: Thumbnailer,
 }
 
 impl Worker for LibraryScanTask {
-    type Init = (Scanner, PhotoRepository, VideoRepository);
+    type Init = (
+        Scanner,
+        ScanRepository,
+        PhotoRepository,
+        VideoRepository,
+        Thumbnailer,
+    );
     type Input = LibraryScanTaskInput;
     type Output = LibraryScanTaskOutput;
 
-    fn init((scan, photo_repo, video_repo): Self::Init, _sender: ComponentSender<Self>) -> Self {
+    fn init(
+        (scan, scan_repo, photo_repo, video_repo, thumbnailer): Self::Init,
+        _sender: ComponentSender<Self>,
+    ) -> Self {
         Self {
             scan,
+            scan_repo,
             photo_repo,
             video_repo,
+            thumbnailer,
         }
     }
 
     fn update(&mut self, msg: LibraryScanTaskInput, sender: ComponentSender<Self>) {
         match msg {
             LibraryScanTaskInput::Start => {
-                let result = self.scan_and_add(sender);
+                let result = self.scan_and_add(&HashSet::new(), sender);
                 if let Err(e) = result {
                     error!("Failed scan with: {}", e);
                 }
             }
+            LibraryScanTaskInput::Rescan(dirty_dirs) => {
+                let dirs = dirty_dirs
+                    .lock()
+                    .map(|mut dirs| std::mem::take(&mut *dirs))
+                    .unwrap_or_default();
+
+                let result = self.scan_and_add(&dirs, sender);
+                if let Err(e) = result {
+                    error!("Failed rescan with: {}", e);
+                    // Keep the directories for the next rescan.
+                    if let Ok(mut dirty_dirs) = dirty_dirs.lock() {
+                        dirty_dirs.extend(dirs);
+                    }
+                }
+            }
         };
     }
 }
 
 impl LibraryScanTask {
-    fn scan_and_add(&mut self, sender: ComponentSender<Self>) -> std::result::Result<(), String> {
+    fn scan_and_add(
+        &mut self,
+        dirty_dirs: &HashSet<PathBuf>,
+        sender: ComponentSender<Self>,
+    ) -> std::result::Result<(), String> {
         let start = std::time::Instant::now();
 
         sender
             .output(LibraryScanTaskOutput::Started)
             .map_err(|e| format!("{:?}", e))?;
 
-        info!("Scanning file system for pictures...");
+        info!("Scanning file system for changed pictures and videos...");
+
+        let previous = self.scan_repo.load().map_err(|e| e.to_string())?;
+
+        let delta = self
+            .scan
+            .scan_changes(&previous, dirty_dirs)
+            .map_err(|e| e.to_string())?;
+
+        // Moved files keep their identity, so must be updated before any are added.
+        let (moved_photos, moved_videos): (Vec<_>, Vec<_>) = delta
+            .moved_files()
+            .into_iter()
+            .partition(|(_, scanned_file)| matches!(scanned_file, ScannedFile::Photo(..)));
 
-        let result = self.scan.scan_all().map_err(|e| e.to_string())?;
+        let mut moved_from = self
+            .photo_repo
+            .mark_moved(&moved_photos)
+            .map_err(|e| e.to_string())?;
+
+        moved_from.extend(
+            self.video_repo
+                .mark_moved(&moved_videos)
+                .map_err(|e| e.to_string())?,
+        );
+
+        // Thumbnails are named after the path, so are generated again for the new path.
+        for path in moved_from {
+            if let Err(e) = self.thumbnailer.delete_thumbnails(&path) {
+                error!("Failed deleting thumbnails for {:?}: {}", path, e);
+            }
+        }
+
+        // A moved file without a picture or video is added.
+        let mut added_files = delta.added_files();
+        added_files.extend(delta.moved_files().into_iter().map(|(_, to)| to));
 
-        let (photos, videos) =
-            result
-                .into_iter()
-                .partition_map(|scanned_file| match scanned_file {
-                    f @ ScannedFile::Photo(_) => Either::Left(f),
-                    f @ ScannedFile::Video(_) => Either::Right(f),
-                });
+        let (added_photos, added_videos) = Self::partition(added_files);
+        let (changed_photos, changed_videos) = Self::partition(delta.changed_files());
 
         self.photo_repo
-            .add_all(&photos)
+            .add_all(&added_photos)
             .map_err(|e| e.to_string())?;
         self.video_repo
-            .add_all(&videos)
+            .add_all(&added_videos)
             .map_err(|e| e.to_string())?;
 
+        // Changed files need their thumbnails regenerating.
+        let (changed_photos, motion_photo_videos) = self
+            .photo_repo
+            .mark_changed(&changed_photos)
+            .map_err(|e| e.to_string())?;
+
+        for pic in changed_photos {
+            if let Err(e) = self.thumbnailer.delete_thumbnails(&pic.path) {
+                error!("Failed deleting thumbnails for {:?}: {}", pic.path, e);
+            }
+        }
+
+        // Motion photo videos are extracted again, possibly to a different path.
+        for path in motion_photo_videos.into_iter().filter(|p| p.exists()) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                error!("Failed deleting {:?}: {}", path, e);
+            }
+        }
+
+        let changed_videos = self
+            .video_repo
+            .mark_changed(&changed_videos)
+            .map_err(|e| e.to_string())?;
+
+        for vid in changed_videos {
+            if let Err(e) = self.thumbnailer.delete_thumbnails(&vid.path) {
+                error!("Failed deleting thumbnails for {:?}: {}", vid.path, e);
+            }
+            if let Some(transcoded_path) = vid.transcoded_path.filter(|p| p.exists()) {
+                if let Err(e) = std::fs::remove_file(&transcoded_path) {
+                    error!("Failed deleting {:?}: {}", transcoded_path, e);
+                }
+            }
+        }
+
+        // Pictures and videos added before file types were detected from their content.
+        match self.photo_repo.backfill_file_types() {
+            Ok(count) if count > 0 => info!("Detected file type of {} pictures", count),
+            Ok(_) => {}
+            Err(e) => error!("Failed detecting picture file types: {}", e),
+        }
+
+        match self.video_repo.backfill_file_types() {
+            Ok(count) if count > 0 => info!("Detected file type of {} videos", count),
+            Ok(_) => {}
+            Err(e) => error!("Failed detecting video file types: {}", e),
+        }
+
+        // Record scan state last so that a failure above means
+        // the same changes will be found by the next scan.
+        self.scan_repo.apply(&delta).map_err(|e| e.to_string())?;
+
         info!(
-            "Scanned {} photos and {} videos in {} seconds.",
-            photos.len(),
-            videos.len(),
-            start.elapsed().as_secs()
+            "Scanned library in {} seconds. {} added, {} changed, {} removed, {} moved, {} unchanged.",
+            start.elapsed().as_secs(),
+            delta.added.len(),
+            delta.changed.len(),
+            delta.removed.len(),
+            delta.moved.len(),
+            delta.unchanged,
         );
 
         sender
-            .output(LibraryScanTaskOutput::Completed)
+            .output(LibraryScanTaskOutput::Completed(delta.len()))
             .map_err(|e| format!("{:?}", e))
     }
+
+    fn partition(files: Vec<ScannedFile>) -> (Vec<ScannedFile>, Vec<ScannedFile>) {
+        files
+            .into_iter()
+            .partition_map(|scanned_file| match scanned_file {
+                f @ ScannedFile::Photo(..) => Either::Left(f),
+                f @ ScannedFile::Video(..) => Either::Right(f),
+            })
+    }
 }
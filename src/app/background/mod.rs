@@ -10,9 +10,11 @@ pub mod load_library_task;
 pub mod person_thumbnail_task;
 
 pub mod photo_clean_task;
+pub mod photo_cluster_faces_task;
 pub mod photo_detect_faces_task;
 pub mod photo_enrich_task;
 pub mod photo_extract_motion_task;
+pub mod photo_hash_task;
 pub mod photo_recognize_faces_task;
 pub mod photo_thumbnail_task;
 
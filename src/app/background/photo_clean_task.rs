@@ -32,6 +32,9 @@ pub struct PhotoCleanTask {
 
     // Danger! Don't hold the repo mutex for too long as it blocks viewing images.
     repo: fotema_core::photo::Repository,
+
+    // Checks if files are excluded from the library.
+    scanner: fotema_core::Scanner,
 }
 
 impl PhotoCleanTask {
@@ -39,11 +42,21 @@ impl PhotoCleanTask {
         let start = std::time::Instant::now();
 
         // Scrub pics from database if they no longer exist on the file system.
-        let pics: Vec<fotema_core::photo::model::Picture> = self.repo.all()?;
+        // Only pictures the library scan didn't find are candidates.
+        let pics: Vec<fotema_core::photo::model::Picture> = self.repo.find_vanished()?;
 
         info!("Found {} photos as candidates for cleaning", pics.len());
 
-        let count = pics.par_iter().filter(|p| !p.path.exists()).count();
+        let mut ids: Vec<fotema_core::PictureId> = pics
+            .par_iter()
+            .filter(|pic| !pic.path.exists() || self.scanner.is_excluded(pic.sandbox_path()))
+            .map(|pic| pic.picture_id)
+            .collect();
+
+        // Photos in library root directories that have been removed from the library.
+        ids.extend(self.repo.find_orphaned()?);
+
+        let count = ids.len();
 
         // Short-circuit before sending progress messages to stop
         // banner from appearing and disappearing.
@@ -56,29 +69,27 @@ impl PhotoCleanTask {
             error!("Failed sending cleanup started: {:?}", e);
         }
 
-        pics.par_iter()
+        ids.par_iter()
             .take_any_while(|_| !self.stop.load(Ordering::Relaxed))
-            .for_each(|pic| {
-                if !pic.path.exists() {
-                    let mut repo = self.repo.clone();
-                    if let Ok(paths) = repo.find_files_to_cleanup(pic.picture_id) {
-                        for path in paths {
-                            if !path.exists() {
-                                continue;
-                            }
-                            debug!("Deleting {:?}", path);
-                            if let Err(e) = std::fs::remove_file(&path) {
-                                error!("Failed deleting {:?} with {}", path, e);
-                            }
+            .for_each(|picture_id| {
+                let mut repo = self.repo.clone();
+                if let Ok(paths) = repo.find_files_to_cleanup(*picture_id) {
+                    for path in paths {
+                        if !path.exists() {
+                            continue;
+                        }
+                        debug!("Deleting {:?}", path);
+                        if let Err(e) = std::fs::remove_file(&path) {
+                            error!("Failed deleting {:?} with {}", path, e);
                         }
                     }
+                }
 
-                    let result = repo.remove(pic.picture_id);
-                    if let Err(e) = result {
-                        error!("Failed remove {}: {:?}", pic.picture_id, e);
-                    } else {
-                        info!("Removed {}", pic.picture_id);
-                    }
+                let result = repo.remove(*picture_id);
+                if let Err(e) = result {
+                    error!("Failed remove {}: {:?}", picture_id, e);
+                } else {
+                    info!("Removed {}", picture_id);
                 }
             });
 
@@ -97,12 +108,20 @@ impl PhotoCleanTask {
 }
 
 impl Worker for PhotoCleanTask {
-    type Init = (Arc<AtomicBool>, fotema_core::photo::Repository);
+    type Init = (
+        Arc<AtomicBool>,
+        fotema_core::photo::Repository,
+        fotema_core::Scanner,
+    );
     type Input = PhotoCleanTaskInput;
     type Output = PhotoCleanTaskOutput;
 
-    fn init((stop, repo): Self::Init, _sender: ComponentSender<Self>) -> Self {
-        Self { stop, repo }
+    fn init((stop, repo, scanner): Self::Init, _sender: ComponentSender<Self>) -> Self {
+        Self {
+            stop,
+            repo,
+            scanner,
+        }
     }
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
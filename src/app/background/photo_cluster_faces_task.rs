@@ -0,0 +1,195 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use anyhow::*;
+use rayon::prelude::*;
+use relm4::Reducer;
+use relm4::Worker;
+use relm4::prelude::*;
+use std::result::Result::Ok;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info};
+
+use fotema_core::machine_learning::face_clusterer::FaceClusterer;
+use fotema_core::machine_learning::face_recognizer::FaceEmbedder;
+use fotema_core::machine_learning::model_registry::ModelRegistry;
+use fotema_core::people;
+use fotema_core::people::FaceId;
+use fotema_core::people::model::{DetectedFace, Embedding};
+
+use crate::app::SettingsState;
+use crate::app::components::progress_monitor::{ProgressMonitor, ProgressMonitorInput, TaskName};
+
+#[derive(Debug)]
+pub enum PhotoClusterFacesTaskInput {
+    Start,
+}
+
+#[derive(Debug)]
+pub enum PhotoClusterFacesTaskOutput {
+    // Face clustering has started.
+    Started,
+
+    // Face clustering has completed
+    Completed,
+}
+
+/// Groups faces that weren't recognized as anyone into suggested people.
+#[derive(Clone)]
+pub struct PhotoClusterFacesTask {
+    // Stop flag
+    stop: Arc<AtomicBool>,
+
+    // Danger! Don't hold the repo mutex for too long as it blocks viewing images.
+    repo: people::Repository,
+
+    progress_monitor: Arc<Reducer<ProgressMonitor>>,
+
+    /// Face recognition models, before applying the user's model settings.
+    models: ModelRegistry,
+
+    settings_state: SettingsState,
+}
+
+impl PhotoClusterFacesTask {
+    fn cluster(&self, sender: ComponentSender<Self>) -> Result<()> {
+        let start = std::time::Instant::now();
+
+        let unknown_faces: Vec<DetectedFace> = self.repo.find_unknown_faces()?;
+
+        info!(
+            "Found {} unknown faces as candidates for clustering",
+            unknown_faces.len()
+        );
+
+        let mut repo = self.repo.clone();
+
+        if unknown_faces.is_empty() {
+            repo.replace_suggestions(&[])?;
+            let _ = sender.output(PhotoClusterFacesTaskOutput::Completed);
+            return Ok(());
+        }
+
+        // Build before starting so that a missing face recognition model doesn't leave
+        // the progress banner showing.
+        let models = self.settings_state.read().model_registry(&self.models);
+        let embedder = FaceEmbedder::build(&models)?;
+
+        let mut embeddings = self.repo.find_embeddings(&FaceEmbedder::MODEL)?;
+
+        // Face recognition only embeds faces when there are people to recognize.
+        let unembedded: Vec<&DetectedFace> = unknown_faces
+            .iter()
+            .filter(|face| !embeddings.contains_key(&face.face_id))
+            .collect();
+
+        let _ = sender.output(PhotoClusterFacesTaskOutput::Started);
+        self.progress_monitor.emit(ProgressMonitorInput::Start(
+            TaskName::ClusterFaces,
+            unembedded.len(),
+        ));
+
+        let new_embeddings: Vec<(FaceId, Embedding)> = unembedded
+            .par_iter()
+            .take_any_while(|_| !self.stop.load(Ordering::Relaxed))
+            .filter_map(|face| {
+                let result = embedder
+                    .embed(face)
+                    .inspect_err(|e| {
+                        error!(
+                            "Failed computing embedding of face {}: {:?}",
+                            face.face_id, e
+                        )
+                    })
+                    .ok()
+                    .map(|embedding| (face.face_id, embedding));
+                self.progress_monitor.emit(ProgressMonitorInput::Advance);
+                result
+            })
+            .collect();
+
+        repo.add_embeddings(&FaceEmbedder::MODEL, &new_embeddings)?;
+        embeddings.extend(new_embeddings);
+
+        // Don't replace suggestions with groups made from only some of the faces.
+        if self.stop.load(Ordering::Relaxed) {
+            self.progress_monitor.emit(ProgressMonitorInput::Complete);
+            let _ = sender.output(PhotoClusterFacesTaskOutput::Completed);
+            return Ok(());
+        }
+
+        let faces: Vec<(FaceId, Embedding)> = unknown_faces
+            .iter()
+            .filter_map(|face| {
+                let embedding = embeddings.remove(&face.face_id)?;
+                Some((face.face_id, embedding))
+            })
+            .collect();
+
+        let Some(suggestions) = FaceClusterer::default().cluster(&faces, &self.stop) else {
+            info!("Stopped clustering faces");
+            self.progress_monitor.emit(ProgressMonitorInput::Complete);
+            let _ = sender.output(PhotoClusterFacesTaskOutput::Completed);
+            return Ok(());
+        };
+
+        repo.replace_suggestions(&suggestions)?;
+
+        info!(
+            "Clustered {} faces into {} suggested people in {} seconds.",
+            faces.len(),
+            suggestions.len(),
+            start.elapsed().as_secs()
+        );
+
+        self.progress_monitor.emit(ProgressMonitorInput::Complete);
+
+        let _ = sender.output(PhotoClusterFacesTaskOutput::Completed);
+
+        Ok(())
+    }
+}
+
+impl Worker for PhotoClusterFacesTask {
+    type Init = (
+        Arc<AtomicBool>,
+        ModelRegistry,
+        SettingsState,
+        people::Repository,
+        Arc<Reducer<ProgressMonitor>>,
+    );
+    type Input = PhotoClusterFacesTaskInput;
+    type Output = PhotoClusterFacesTaskOutput;
+
+    fn init(
+        (stop, models, settings_state, repo, progress_monitor): Self::Init,
+        _sender: ComponentSender<Self>,
+    ) -> Self {
+        PhotoClusterFacesTask {
+            stop,
+            repo,
+            progress_monitor,
+            models,
+            settings_state,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            PhotoClusterFacesTaskInput::Start => {
+                info!("Clustering photo faces...");
+                let this = self.clone();
+
+                // Avoid runtime panic from calling block_on
+                rayon::spawn(move || {
+                    if let Err(e) = this.cluster(sender.clone()) {
+                        error!("Failed to cluster photo faces: {}", e);
+                        let _ = sender.output(PhotoClusterFacesTaskOutput::Completed);
+                    }
+                });
+            }
+        };
+    }
+}
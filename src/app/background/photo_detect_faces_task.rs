@@ -17,12 +17,14 @@ use futures::executor::block_on;
 use tracing::{error, info};
 
 use fotema_core::machine_learning::face_extractor::FaceExtractor;
+use fotema_core::machine_learning::model_registry::{self, ModelRegistry};
 use fotema_core::people;
 use fotema_core::people::FaceDetectionCandidate;
 use fotema_core::photo;
 use fotema_core::photo::PictureId;
 use fotema_core::thumbnailify::Thumbnailer;
 
+use crate::app::SettingsState;
 use crate::app::components::progress_monitor::{ProgressMonitor, ProgressMonitorInput, TaskName};
 use deadpool::managed;
 
@@ -35,6 +37,7 @@ struct FaceDetectorPoolManager {
     /// Base directory for storing photo faces
     faces_base_dir: PathBuf,
     thumbnailer: Thumbnailer,
+    models: ModelRegistry,
 }
 
 impl managed::Manager for FaceDetectorPoolManager {
@@ -42,7 +45,7 @@ impl managed::Manager for FaceDetectorPoolManager {
     type Error = Error;
 
     async fn create(&self) -> Result<FaceExtractor, Error> {
-        FaceExtractor::build(&self.faces_base_dir, self.thumbnailer.clone())
+        FaceExtractor::build(&self.faces_base_dir, self.thumbnailer.clone(), &self.models)
     }
 
     async fn recycle(
@@ -80,6 +83,11 @@ pub struct PhotoDetectFacesTask {
     faces_base_dir: PathBuf,
     thumbnailer: Thumbnailer,
 
+    /// Face detection models, before applying the user's model settings.
+    models: ModelRegistry,
+
+    settings_state: SettingsState,
+
     photo_repo: photo::Repository,
     people_repo: people::Repository,
 
@@ -126,6 +134,11 @@ impl PhotoDetectFacesTask {
             return Ok(());
         }
 
+        // Fail before starting when the face detection model is missing, rather than
+        // failing for every photo.
+        let models = self.settings_state.read().model_registry(&self.models);
+        models.resolve(&model_registry::BLAZE_FACE_640)?;
+
         let _ = sender.output(PhotoDetectFacesTaskOutput::Started);
 
         self.progress_monitor
@@ -142,11 +155,12 @@ impl PhotoDetectFacesTask {
         // We must do this before using the object pool and parallel processing, otherwise
         // multiple threads will try to download the same model.
         // FIXME add a method to the face detection library to download models.
-        let _ = FaceExtractor::build(&self.faces_base_dir, self.thumbnailer.clone());
+        let _ = FaceExtractor::build(&self.faces_base_dir, self.thumbnailer.clone(), &models);
 
         let detector_pool_manager = FaceDetectorPoolManager {
             faces_base_dir: self.faces_base_dir.clone(),
             thumbnailer: self.thumbnailer.clone(),
+            models,
         };
         let detector_pool = FaceDetectorPool::builder(detector_pool_manager).build()?;
 
@@ -195,6 +209,8 @@ impl Worker for PhotoDetectFacesTask {
         Arc<AtomicBool>,
         PathBuf,
         Thumbnailer,
+        ModelRegistry,
+        SettingsState,
         photo::Repository,
         people::Repository,
         Arc<Reducer<ProgressMonitor>>,
@@ -203,13 +219,24 @@ impl Worker for PhotoDetectFacesTask {
     type Output = PhotoDetectFacesTaskOutput;
 
     fn init(
-        (stop, faces_base_dir, thumbnailer, photo_repo, people_repo, progress_monitor): Self::Init,
+        (
+            stop,
+            faces_base_dir,
+            thumbnailer,
+            models,
+            settings_state,
+            photo_repo,
+            people_repo,
+            progress_monitor,
+        ): Self::Init,
         _sender: ComponentSender<Self>,
     ) -> Self {
         PhotoDetectFacesTask {
             stop,
             faces_base_dir,
             thumbnailer,
+            models,
+            settings_state,
             photo_repo,
             people_repo,
             progress_monitor,
@@ -224,8 +251,9 @@ impl Worker for PhotoDetectFacesTask {
 
                 // Avoid runtime panic from calling block_on
                 rayon::spawn(move || {
-                    if let Err(e) = this.detect_for_all(sender) {
+                    if let Err(e) = this.detect_for_all(sender.clone()) {
                         error!("Failed to extract photo faces: {}", e);
+                        let _ = sender.output(PhotoDetectFacesTaskOutput::Completed);
                     }
                 });
             }
@@ -13,6 +13,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use tracing::{error, info};
 
 use crate::app::components::progress_monitor::{ProgressMonitor, ProgressMonitorInput, TaskName};
+use fotema_core::video::TranscodeError;
 
 #[derive(Debug)]
 pub enum PhotoExtractMotionTaskInput {
@@ -80,12 +81,17 @@ impl PhotoExtractMotionTask {
             .par_iter()
             .take_any_while(|_| !stop.load(Ordering::Relaxed))
             .for_each(|photo| {
-                let result = extractor.extract(&photo.picture_id, photo.sandbox_path());
+                let result = extractor.extract(&photo.picture_id, photo.sandbox_path(), &stop);
 
                 let result = match result {
                     Ok(opt_video) => repo
                         .clone()
                         .add_motion_photo_video(&photo.picture_id, opt_video),
+                    Err(e) if e.downcast_ref::<TranscodeError>().is_some() => {
+                        // Extracted again next time.
+                        info!("Cancelled extracting motion photo {:?}", photo.path);
+                        Ok(())
+                    }
                     Err(e) => {
                         error!(
                             "Failed extracting motion photo: {:?}: Photo path: {:?}",
@@ -0,0 +1,164 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use anyhow::*;
+use rayon::prelude::*;
+use relm4::Reducer;
+use relm4::Worker;
+use relm4::prelude::*;
+use std::result::Result::Ok;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info};
+
+use fotema_core::duplicates::{self, PictureHasher, PictureHashes};
+use fotema_core::photo::model::Picture;
+
+use crate::app::components::progress_monitor::{ProgressMonitor, ProgressMonitorInput, TaskName};
+
+#[derive(Debug)]
+pub enum PhotoHashTaskInput {
+    Start,
+}
+
+#[derive(Debug)]
+pub enum PhotoHashTaskOutput {
+    // Hashing has started.
+    Started,
+
+    // Hashing has completed
+    Completed(usize),
+}
+
+/// Hashes pictures so that duplicates can be found.
+pub struct PhotoHashTask {
+    // Stop flag
+    stop: Arc<AtomicBool>,
+
+    hasher: PictureHasher,
+
+    photo_repo: fotema_core::photo::Repository,
+
+    duplicates_repo: duplicates::Repository,
+
+    progress_monitor: Arc<Reducer<ProgressMonitor>>,
+}
+
+impl PhotoHashTask {
+    fn hash(
+        stop: Arc<AtomicBool>,
+        hasher: PictureHasher,
+        photo_repo: fotema_core::photo::Repository,
+        mut duplicates_repo: duplicates::Repository,
+        progress_monitor: Arc<Reducer<ProgressMonitor>>,
+        sender: ComponentSender<Self>,
+    ) -> Result<()> {
+        let start = std::time::Instant::now();
+
+        let hashed = duplicates_repo.find_hashed()?;
+
+        // Pictures without a large thumbnail will be hashed after their thumbnail is generated.
+        let unprocessed: Vec<Picture> = photo_repo
+            .all()?
+            .into_iter()
+            .filter(|pic| !hashed.contains(&pic.picture_id.id()))
+            .filter(|pic| pic.path.exists())
+            .filter(|pic| hasher.thumbnail_path(&pic.path).exists())
+            .collect();
+
+        let count = unprocessed.len();
+        info!("Found {} photos to hash", count);
+
+        // Short-circuit before sending progress messages to stop
+        // banner from appearing and disappearing.
+        if count == 0 {
+            let _ = sender.output(PhotoHashTaskOutput::Completed(count));
+            return Ok(());
+        }
+
+        let _ = sender.output(PhotoHashTaskOutput::Started);
+
+        progress_monitor.emit(ProgressMonitorInput::Start(TaskName::FindDuplicates, count));
+
+        let hashes: Vec<PictureHashes> = unprocessed
+            .par_iter()
+            .take_any_while(|_| !stop.load(Ordering::Relaxed))
+            .filter_map(|pic| {
+                let result = hasher.hash(pic.picture_id, &pic.path);
+                progress_monitor.emit(ProgressMonitorInput::Advance);
+                result
+                    .inspect_err(|e| {
+                        error!("Failed to hash {:?}: {:?}", pic.path.sandbox_path, e);
+                    })
+                    .ok()
+            })
+            .collect();
+
+        // Save whatever was hashed, even if stopped part way through.
+        duplicates_repo.add_hashes(&hashes)?;
+
+        info!(
+            "Hashed {} photos in {} seconds.",
+            hashes.len(),
+            start.elapsed().as_secs()
+        );
+
+        progress_monitor.emit(ProgressMonitorInput::Complete);
+
+        let _ = sender.output(PhotoHashTaskOutput::Completed(hashes.len()));
+
+        Ok(())
+    }
+}
+
+impl Worker for PhotoHashTask {
+    type Init = (
+        Arc<AtomicBool>,
+        PictureHasher,
+        fotema_core::photo::Repository,
+        duplicates::Repository,
+        Arc<Reducer<ProgressMonitor>>,
+    );
+    type Input = PhotoHashTaskInput;
+    type Output = PhotoHashTaskOutput;
+
+    fn init(
+        (stop, hasher, photo_repo, duplicates_repo, progress_monitor): Self::Init,
+        _sender: ComponentSender<Self>,
+    ) -> Self {
+        PhotoHashTask {
+            stop,
+            hasher,
+            photo_repo,
+            duplicates_repo,
+            progress_monitor,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            PhotoHashTaskInput::Start => {
+                info!("Hashing photos...");
+                let stop = self.stop.clone();
+                let hasher = self.hasher.clone();
+                let photo_repo = self.photo_repo.clone();
+                let duplicates_repo = self.duplicates_repo.clone();
+                let progress_monitor = self.progress_monitor.clone();
+
+                rayon::spawn(move || {
+                    if let Err(e) = PhotoHashTask::hash(
+                        stop,
+                        hasher,
+                        photo_repo,
+                        duplicates_repo,
+                        progress_monitor,
+                        sender,
+                    ) {
+                        error!("Failed to hash photos: {}", e);
+                    }
+                });
+            }
+        };
+    }
+}
@@ -7,16 +7,18 @@ use rayon::prelude::*;
 use relm4::Reducer;
 use relm4::Worker;
 use relm4::prelude::*;
-use std::path::PathBuf;
 use std::result::Result::Ok;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tracing::{error, info};
 
-use fotema_core::machine_learning::face_recognizer::FaceRecognizer;
+use fotema_core::machine_learning::face_recognizer::{FaceEmbedder, FaceRecognizer, PersonModel};
+use fotema_core::machine_learning::model_registry::ModelRegistry;
 use fotema_core::people;
-use fotema_core::people::model::{DetectedFace, PersonForRecognition};
+use fotema_core::people::FaceId;
+use fotema_core::people::model::{DetectedFace, Embedding, PersonForRecognition};
 
+use crate::app::SettingsState;
 use crate::app::components::progress_monitor::{ProgressMonitor, ProgressMonitorInput, TaskName};
 
 #[derive(Debug)]
@@ -43,7 +45,10 @@ pub struct PhotoRecognizeFacesTask {
 
     progress_monitor: Arc<Reducer<ProgressMonitor>>,
 
-    cache_dir: PathBuf,
+    /// Face recognition models, before applying the user's model settings.
+    models: ModelRegistry,
+
+    settings_state: SettingsState,
 }
 
 impl PhotoRecognizeFacesTask {
@@ -82,21 +87,76 @@ impl PhotoRecognizeFacesTask {
             return Ok(());
         }
 
+        // Build before starting so that a missing face recognition model doesn't leave
+        // the progress banner showing.
+        let models = self.settings_state.read().model_registry(&self.models);
+        let embedder = FaceEmbedder::build(&models)?;
+
+        // Embeddings are only computed once for each face, so recognition is mostly
+        // comparing vectors.
+        let mut embeddings = self.repo.find_embeddings(&FaceEmbedder::MODEL)?;
+
+        // Newly confirmed faces will not have been embedded yet.
+        let unembedded_person_faces: Vec<&DetectedFace> = people
+            .iter()
+            .flat_map(|person| person.faces.iter())
+            .filter(|face| !embeddings.contains_key(&face.face_id))
+            .collect();
+
         let _ = sender.output(PhotoRecognizeFacesTaskOutput::Started);
         self.progress_monitor.emit(ProgressMonitorInput::Start(
             TaskName::RecognizeFaces,
-            unprocessed.len(),
+            unembedded_person_faces.len() + unprocessed.len(),
         ));
 
-        let recognizer = FaceRecognizer::build(&self.cache_dir, people.clone())?;
+        let person_embeddings: Vec<(FaceId, Embedding)> = unembedded_person_faces
+            .par_iter()
+            .take_any_while(|_| !self.stop.load(Ordering::Relaxed))
+            .filter_map(|face| {
+                let result = Self::embed(&embedder, face);
+                self.progress_monitor.emit(ProgressMonitorInput::Advance);
+                result
+            })
+            .collect();
 
-        unprocessed
-            //.into_iter()
-            .into_par_iter()
+        self.repo
+            .clone()
+            .add_embeddings(&FaceEmbedder::MODEL, &person_embeddings)?;
+        embeddings.extend(person_embeddings);
+
+        let recognizer = FaceRecognizer::build(
+            people
+                .iter()
+                .filter_map(|person| {
+                    let person_embeddings = person
+                        .faces
+                        .iter()
+                        .filter_map(|face| embeddings.get(&face.face_id).cloned())
+                        .collect();
+                    PersonModel::build(person, person_embeddings)
+                })
+                .collect(),
+        );
+
+        let new_embeddings: Vec<(FaceId, Embedding)> = unprocessed
+            .par_iter()
             .take_any_while(|_| !self.stop.load(Ordering::Relaxed))
-            .for_each(|unknown_face| {
-                let is_match = recognizer.recognize(&unknown_face);
-                if let Ok(Some(person_id)) = is_match {
+            .filter_map(|unknown_face| {
+                let new_embedding = if embeddings.contains_key(&unknown_face.face_id) {
+                    None
+                } else {
+                    Self::embed(&embedder, unknown_face)
+                };
+
+                let embedding = new_embedding
+                    .as_ref()
+                    .map(|(_, embedding)| embedding)
+                    .or_else(|| embeddings.get(&unknown_face.face_id));
+
+                let is_match =
+                    embedding.and_then(|embedding| recognizer.recognize(unknown_face, embedding));
+
+                if let Some(person_id) = is_match {
                     info!(
                         "Face {} looks like person {}",
                         unknown_face.face_id, person_id
@@ -112,7 +172,13 @@ impl PhotoRecognizeFacesTask {
                 }
 
                 self.progress_monitor.emit(ProgressMonitorInput::Advance);
-            });
+                new_embedding
+            })
+            .collect();
+
+        self.repo
+            .clone()
+            .add_embeddings(&FaceEmbedder::MODEL, &new_embeddings)?;
 
         let mut repo = self.repo.clone();
         for person in people {
@@ -135,12 +201,26 @@ impl PhotoRecognizeFacesTask {
 
         Ok(())
     }
+
+    fn embed(embedder: &FaceEmbedder, face: &DetectedFace) -> Option<(FaceId, Embedding)> {
+        embedder
+            .embed(face)
+            .inspect_err(|e| {
+                error!(
+                    "Failed computing embedding of face {}: {:?}",
+                    face.face_id, e
+                )
+            })
+            .ok()
+            .map(|embedding| (face.face_id, embedding))
+    }
 }
 
 impl Worker for PhotoRecognizeFacesTask {
     type Init = (
         Arc<AtomicBool>,
-        PathBuf,
+        ModelRegistry,
+        SettingsState,
         people::Repository,
         Arc<Reducer<ProgressMonitor>>,
     );
@@ -148,14 +228,15 @@ impl Worker for PhotoRecognizeFacesTask {
     type Output = PhotoRecognizeFacesTaskOutput;
 
     fn init(
-        (stop, cache_dir, repo, progress_monitor): Self::Init,
+        (stop, models, settings_state, repo, progress_monitor): Self::Init,
         _sender: ComponentSender<Self>,
     ) -> Self {
         PhotoRecognizeFacesTask {
             stop,
-            cache_dir,
             repo,
             progress_monitor,
+            models,
+            settings_state,
         }
     }
 
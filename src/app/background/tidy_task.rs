@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use anyhow::Result;
+use fotema_core::photo::motion_photo;
 use relm4::Worker;
 use relm4::gtk::glib;
 use relm4::prelude::*;
@@ -58,6 +59,9 @@ impl TidyTask {
             std::fs::remove_dir_all(legacy_dir)?;
         }
 
+        // Delete copies of motion photo videos left behind by the viewer.
+        motion_photo::remove_video_copies(&glib::user_cache_dir().join(APP_ID))?;
+
         let _ = sender.output(TidyTaskOutput::Completed);
 
         Ok(())
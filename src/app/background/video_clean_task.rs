@@ -32,6 +32,9 @@ pub struct VideoCleanTask {
 
     // Danger! Don't hold the repo mutex for too long as it blocks viewing images.
     repo: fotema_core::video::Repository,
+
+    // Checks if files are excluded from the library.
+    scanner: fotema_core::Scanner,
 }
 
 impl VideoCleanTask {
@@ -39,11 +42,21 @@ impl VideoCleanTask {
         let start = std::time::Instant::now();
 
         // Scrub vids from database if they no longer exist on the file system.
-        let vids: Vec<fotema_core::video::model::Video> = self.repo.all()?;
+        // Only videos the library scan didn't find are candidates.
+        let vids: Vec<fotema_core::video::model::Video> = self.repo.find_vanished()?;
 
         info!("Found {} videos as candidates for cleaning", vids.len());
 
-        let count = vids.par_iter().filter(|v| !v.path.exists()).count();
+        let mut ids: Vec<fotema_core::VideoId> = vids
+            .par_iter()
+            .filter(|vid| !vid.path.exists() || self.scanner.is_excluded(vid.sandbox_path()))
+            .map(|vid| vid.video_id)
+            .collect();
+
+        // Videos in library root directories that have been removed from the library.
+        ids.extend(self.repo.find_orphaned()?);
+
+        let count = ids.len();
 
         // Short-circuit before sending progress messages to stop
         // banner from appearing and disappearing.
@@ -56,29 +69,27 @@ impl VideoCleanTask {
             error!("Failed sending cleanup started: {:?}", e);
         }
 
-        vids.par_iter()
+        ids.par_iter()
             .take_any_while(|_| !self.stop.load(Ordering::Relaxed))
-            .for_each(|vid| {
-                if !vid.path.exists() {
-                    let mut repo = self.repo.clone();
-                    if let Ok(paths) = repo.find_files_to_cleanup(vid.video_id) {
-                        for path in paths {
-                            if !path.exists() {
-                                continue;
-                            }
-                            debug!("Deleting {:?}", path);
-                            if let Err(e) = std::fs::remove_file(&path) {
-                                error!("Failed deleting {:?} with {}", path, e);
-                            }
+            .for_each(|video_id| {
+                let mut repo = self.repo.clone();
+                if let Ok(paths) = repo.find_files_to_cleanup(*video_id) {
+                    for path in paths {
+                        if !path.exists() {
+                            continue;
+                        }
+                        debug!("Deleting {:?}", path);
+                        if let Err(e) = std::fs::remove_file(&path) {
+                            error!("Failed deleting {:?} with {}", path, e);
                         }
                     }
+                }
 
-                    let result = repo.remove(vid.video_id);
-                    if let Err(e) = result {
-                        error!("Failed remove {}: {:?}", vid.video_id, e);
-                    } else {
-                        info!("Removed {}", vid.video_id);
-                    }
+                let result = repo.remove(*video_id);
+                if let Err(e) = result {
+                    error!("Failed remove {}: {:?}", video_id, e);
+                } else {
+                    info!("Removed {}", video_id);
                 }
             });
 
@@ -97,12 +108,20 @@ impl VideoCleanTask {
 }
 
 impl Worker for VideoCleanTask {
-    type Init = (Arc<AtomicBool>, fotema_core::video::Repository);
+    type Init = (
+        Arc<AtomicBool>,
+        fotema_core::video::Repository,
+        fotema_core::Scanner,
+    );
     type Input = VideoCleanTaskInput;
     type Output = VideoCleanTaskOutput;
 
-    fn init((stop, repo): Self::Init, _sender: ComponentSender<Self>) -> Self {
-        Self { stop, repo }
+    fn init((stop, repo, scanner): Self::Init, _sender: ComponentSender<Self>) -> Self {
+        Self {
+            stop,
+            repo,
+            scanner,
+        }
     }
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
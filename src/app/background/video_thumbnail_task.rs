@@ -7,6 +7,7 @@ use rayon::prelude::*;
 use relm4::Reducer;
 use relm4::Worker;
 use relm4::prelude::*;
+use std::collections::HashSet;
 use std::panic;
 use std::path::{Path, PathBuf};
 use std::result::Result::Ok;
@@ -16,7 +17,7 @@ use tracing::{error, info};
 
 use fotema_core::thumbnailify;
 use fotema_core::thumbnailify::ThumbnailSize;
-use fotema_core::video::{Repository, Video, VideoThumbnailer};
+use fotema_core::video::{Repository, SpriteSheetGenerator, Video, VideoThumbnailer};
 
 use crate::app::components::progress_monitor::{
     ProgressMonitor, ProgressMonitorInput, TaskName, ThumbnailType,
@@ -42,6 +43,7 @@ pub struct VideoThumbnailTask {
 
     thumbnails_path: PathBuf,
     thumbnailer: VideoThumbnailer,
+    sprite_sheet_generator: SpriteSheetGenerator,
 
     // Danger! Don't hold the repo mutex for too long as it blocks viewing images.
     repo: Repository,
@@ -55,11 +57,19 @@ impl VideoThumbnailTask {
         repo: Repository,
         thumbnails_path: &Path,
         thumbnailer: VideoThumbnailer,
+        sprite_sheet_generator: SpriteSheetGenerator,
         progress_monitor: Arc<Reducer<ProgressMonitor>>,
         sender: ComponentSender<VideoThumbnailTask>,
     ) -> Result<()> {
         let start = std::time::Instant::now();
 
+        // Videos with thumbnails from an older version of the thumbnail generation.
+        let outdated: HashSet<i64> = repo
+            .find_need_thumbnail_update()?
+            .into_iter()
+            .map(|vid| vid.video_id.id())
+            .collect();
+
         let mut unprocessed: Vec<Video> = repo
             .all()?
             .into_iter()
@@ -72,6 +82,8 @@ impl VideoThumbnailTask {
                     ThumbnailSize::Large,
                 );
                 !large_path.exists()
+                    || outdated.contains(&vid.video_id.id())
+                    || !sprite_sheet_generator.exists(&vid.path)
             })
             .collect();
 
@@ -118,6 +130,27 @@ impl VideoThumbnailTask {
                         vid.path
                     );
                     let _ = repo.clone().mark_broken(&vid.video_id);
+                } else {
+                    let _ = repo.clone().mark_thumbnailed(&vid.video_id);
+
+                    // A missing sprite sheet only means no scrub preview, so isn't
+                    // worth marking the video as broken over.
+                    if !sprite_sheet_generator.exists(&vid.path) {
+                        let result =
+                            panic::catch_unwind(|| sprite_sheet_generator.generate(&vid.path));
+                        if let Ok(Err(e)) = result {
+                            error!(
+                                "Failed generating sprite sheet: {:?}: Video path: {:?}",
+                                e.root_cause(),
+                                vid.path
+                            );
+                        } else if result.is_err() {
+                            error!(
+                                "Panicked generating sprite sheet: Video path: {:?}",
+                                vid.path
+                            );
+                        }
+                    }
                 }
 
                 progress_monitor.emit(ProgressMonitorInput::Advance);
@@ -142,6 +175,7 @@ impl Worker for VideoThumbnailTask {
         Arc<AtomicBool>,
         PathBuf,
         VideoThumbnailer,
+        SpriteSheetGenerator,
         Repository,
         Arc<Reducer<ProgressMonitor>>,
     );
@@ -149,13 +183,21 @@ impl Worker for VideoThumbnailTask {
     type Output = VideoThumbnailTaskOutput;
 
     fn init(
-        (stop, thumbnails_path, thumbnailer, repo, progress_monitor): Self::Init,
+        (
+            stop,
+            thumbnails_path,
+            thumbnailer,
+            sprite_sheet_generator,
+            repo,
+            progress_monitor,
+        ): Self::Init,
         _sender: ComponentSender<Self>,
     ) -> Self {
         Self {
             stop,
             thumbnails_path: thumbnails_path.into(),
             thumbnailer,
+            sprite_sheet_generator,
             repo,
             progress_monitor,
         }
@@ -169,6 +211,7 @@ impl Worker for VideoThumbnailTask {
                 let repo = self.repo.clone();
                 let thumbnails_path = self.thumbnails_path.clone();
                 let thumbnailer = self.thumbnailer.clone();
+                let sprite_sheet_generator = self.sprite_sheet_generator.clone();
                 let progress_monitor = self.progress_monitor.clone();
 
                 // Avoid runtime panic from calling block_on
@@ -178,6 +221,7 @@ impl Worker for VideoThumbnailTask {
                         repo,
                         &thumbnails_path,
                         thumbnailer,
+                        sprite_sheet_generator,
                         progress_monitor,
                         sender,
                     ) {
@@ -9,7 +9,9 @@ use relm4::prelude::*;
 use anyhow::*;
 
 use fotema_core::Visual;
+use fotema_core::VisualId;
 use fotema_core::video::Repository;
+use fotema_core::video::TranscodeError;
 use fotema_core::video::Transcoder;
 use tracing::{error, info};
 
@@ -18,12 +20,19 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::app::components::progress_monitor::{ProgressMonitor, ProgressMonitorInput, TaskName};
 
+use crate::app::SettingsState;
 use crate::app::SharedState;
 
+/// Progress steps per video, so that progress can be shown while a video transcodes.
+const PROGRESS_STEPS_PER_VIDEO: usize = 100;
+
 #[derive(Debug)]
 pub enum VideoTranscodeTaskInput {
-    /// Transcode all videos
+    /// Transcode all videos, except those that have previously failed to transcode.
     Start,
+
+    /// Transcode one video, even if it has previously failed to transcode.
+    Retry(VisualId),
 }
 
 #[derive(Debug)]
@@ -45,27 +54,64 @@ pub struct VideoTranscodeTask {
 
     state: SharedState,
 
+    settings_state: SettingsState,
+
     progress_monitor: Arc<Reducer<ProgressMonitor>>,
 }
 
 impl VideoTranscodeTask {
     fn transcode_all(&mut self, sender: &ComponentSender<Self>) -> Result<()> {
+        // A video that failed to transcode will most likely fail again, so it is only
+        // retried when the user asks or when the file changes.
         let unprocessed: Vec<Arc<Visual>> = {
             let data = self.state.read();
             data.iter()
-                .filter(|&x| x.video_id.is_some())
-                .filter(|&x| x.is_transcode_required.is_some_and(|y| y))
-                .filter(|&x| x.video_path.as_ref().is_some_and(|y| y.exists()))
-                .filter(|&x| !x.video_transcoded_path.as_ref().is_some_and(|y| y.exists()))
+                .filter(|&x| Self::is_transcode_required(x))
+                .filter(|&x| x.video_transcode_error.is_none())
                 .cloned()
                 .collect()
         };
 
         info!("Found {} videos to transcode", unprocessed.len());
 
+        self.transcode(unprocessed, sender)
+    }
+
+    fn retry(&mut self, visual_id: &VisualId, sender: &ComponentSender<Self>) -> Result<()> {
+        let unprocessed: Vec<Arc<Visual>> = {
+            let data = self.state.read();
+            data.iter()
+                .filter(|&x| x.visual_id == *visual_id)
+                .filter(|&x| Self::is_transcode_required(x))
+                .cloned()
+                .collect()
+        };
+
+        info!("Retrying transcode of {}", visual_id);
+
+        self.transcode(unprocessed, sender)
+    }
+
+    fn is_transcode_required(visual: &Visual) -> bool {
+        visual.video_id.is_some()
+            && visual.is_transcode_required.is_some_and(|y| y)
+            && visual.video_path.as_ref().is_some_and(|y| y.exists())
+            && !visual
+                .video_transcoded_path
+                .as_ref()
+                .is_some_and(|y| y.exists())
+    }
+
+    fn transcode(
+        &mut self,
+        unprocessed: Vec<Arc<Visual>>,
+        sender: &ComponentSender<Self>,
+    ) -> Result<()> {
+        let profile = self.settings_state.read().transcode_profile;
+
         self.progress_monitor.emit(ProgressMonitorInput::Start(
             TaskName::Transcode,
-            unprocessed.len(),
+            unprocessed.len() * PROGRESS_STEPS_PER_VIDEO,
         ));
 
         let _ = sender.output(VideoTranscodeTaskOutput::Started);
@@ -77,20 +123,50 @@ impl VideoTranscodeTask {
                 let video_id = visual.video_id.expect("Must have video_id");
                 let video_path = visual.video_path.as_ref().expect("Must have video_path");
 
+                let progress_monitor = &self.progress_monitor;
+                let mut steps = 0;
+
                 let result = self
                     .transcoder
-                    .transcode(video_id, &video_path.sandbox_path)
+                    .transcode(
+                        video_id,
+                        &video_path.sandbox_path,
+                        &profile,
+                        &self.stop,
+                        &mut |fraction| {
+                            let target = (fraction * PROGRESS_STEPS_PER_VIDEO as f64) as usize;
+                            if target > steps {
+                                progress_monitor
+                                    .emit(ProgressMonitorInput::AdvanceBy(target - steps));
+                                steps = target;
+                            }
+                        },
+                    )
                     .with_context(|| format!("Video path: {:?}", video_path));
 
-                if let std::result::Result::Ok(ref transcode_path) = result {
-                    if let Err(e) = self.repo.add_transcode(video_id, transcode_path) {
-                        error!("Failed adding transcode path: {:?}", e);
+                match result {
+                    std::result::Result::Ok(ref transcode_path) => {
+                        if let Err(e) = self.repo.add_transcode(video_id, transcode_path) {
+                            error!("Failed adding transcode path: {:?}", e);
+                        }
+                    }
+                    Err(e) if e.downcast_ref::<TranscodeError>().is_some() => {
+                        info!("Cancelled transcoding {:?}", video_path);
+                    }
+                    Err(e) => {
+                        error!("Failed transcoding: {:?}", e);
+                        if let Err(e) = self
+                            .repo
+                            .mark_transcode_failed(video_id, &format!("{:#}", e))
+                        {
+                            error!("Failed recording transcode failure: {:?}", e);
+                        }
                     }
-                } else if let Err(ref e) = result {
-                    error!("Failed transcoding: {:?}", e);
                 }
 
-                self.progress_monitor.emit(ProgressMonitorInput::Advance);
+                self.progress_monitor.emit(ProgressMonitorInput::AdvanceBy(
+                    PROGRESS_STEPS_PER_VIDEO.saturating_sub(steps),
+                ));
             });
 
         self.progress_monitor.emit(ProgressMonitorInput::Complete);
@@ -105,6 +181,7 @@ impl Worker for VideoTranscodeTask {
     type Init = (
         Arc<AtomicBool>,
         SharedState,
+        SettingsState,
         Repository,
         Transcoder,
         Arc<Reducer<ProgressMonitor>>,
@@ -113,12 +190,13 @@ impl Worker for VideoTranscodeTask {
     type Output = VideoTranscodeTaskOutput;
 
     fn init(
-        (stop, state, repo, transcoder, progress_monitor): Self::Init,
+        (stop, state, settings_state, repo, transcoder, progress_monitor): Self::Init,
         _sender: ComponentSender<Self>,
     ) -> Self {
         Self {
             stop,
             state,
+            settings_state,
             repo,
             transcoder,
             progress_monitor,
@@ -134,6 +212,11 @@ impl Worker for VideoTranscodeTask {
                     error!("Failed to transcode photo: {}", e);
                 }
             }
+            VideoTranscodeTaskInput::Retry(visual_id) => {
+                if let Err(e) = self.retry(&visual_id, &sender) {
+                    error!("Failed to transcode video: {}", e);
+                }
+            }
         };
     }
 }
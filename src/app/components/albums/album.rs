@@ -15,7 +15,8 @@ use relm4::gtk::prelude::AdjustmentExt;
 use relm4::gtk::prelude::*;
 use relm4::typed_view::grid::{RelmGridItem, TypedGridView};
 use relm4::*;
-use std::path::Path;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -25,6 +26,8 @@ use crate::app::ActiveView;
 use crate::app::SharedState;
 use crate::app::ViewName;
 use crate::app::adaptive;
+use crate::app::components::sprite_preview::SpritePreview;
+use crate::fl;
 
 use tracing::{debug, info};
 
@@ -83,8 +86,96 @@ struct PhotoGridItem {
     thumbnailer: Rc<Thumbnailer>,
 }
 
+/// Previews the frames of a video while the pointer moves over its grid item.
+#[derive(Debug, Default)]
+struct Scrub {
+    /// Thumbnailer and thumbnail hash of the video. None if the item isn't a video.
+    video: Option<(Rc<Thumbnailer>, String)>,
+
+    /// Thumbnail to show again when scrubbing ends.
+    thumbnail_path: Option<PathBuf>,
+
+    /// Sprite sheet, loaded when scrubbing starts.
+    preview: Option<SpritePreview>,
+
+    /// Whether loading the sprite sheet has been started.
+    is_loading: bool,
+
+    /// Index of sprite sheet frame being shown.
+    frame: Option<usize>,
+}
+
+impl Scrub {
+    fn reset(&mut self, video: Option<(Rc<Thumbnailer>, String)>, thumbnail_path: Option<PathBuf>) {
+        *self = Scrub {
+            video,
+            thumbnail_path,
+            ..Default::default()
+        };
+    }
+
+    /// Shows the frame for a pointer position across the picture.
+    /// The sprite sheet is decoded off the main thread the first time, so that moving
+    /// across a grid of videos doesn't stutter, and scrubbing starts once it is ready.
+    fn show(scrub: &Rc<RefCell<Scrub>>, picture: &gtk::Picture, x: f64) {
+        let mut this = scrub.borrow_mut();
+
+        let Some((thumbnailer, hash)) = this.video.as_ref() else {
+            return;
+        };
+
+        if !this.is_loading {
+            let thumbnailer = Thumbnailer::clone(thumbnailer);
+            let hash = hash.clone();
+            let scrub = scrub.clone();
+            this.is_loading = true;
+
+            relm4::spawn_local(async move {
+                let preview = {
+                    let hash = hash.clone();
+                    relm4::spawn_blocking(move || SpritePreview::load(&thumbnailer, &hash))
+                        .await
+                        .ok()
+                        .flatten()
+                };
+
+                // The grid item might have been bound to a different item while loading.
+                let mut scrub = scrub.borrow_mut();
+                if scrub.video.as_ref().is_some_and(|(_, h)| *h == hash) {
+                    scrub.preview = preview;
+                }
+            });
+            return;
+        }
+
+        let Some(preview) = this.preview.as_ref() else {
+            return;
+        };
+
+        let width = f64::from(picture.width());
+        let frame = (width > 0.0)
+            .then(|| preview.sheet().frame_at_fraction(x / width))
+            .flatten();
+
+        if frame != this.frame
+            && let Some(texture) = frame.and_then(|index| preview.frame(index))
+        {
+            picture.set_paintable(Some(&texture));
+            this.frame = frame;
+        }
+    }
+
+    /// Shows the thumbnail again.
+    fn restore(&mut self, picture: &gtk::Picture) {
+        if self.frame.take().is_some() {
+            picture.set_filename(self.thumbnail_path.clone());
+        }
+    }
+}
+
 struct PhotoGridItemWidgets {
     picture: gtk::Picture,
+    scrub: Rc<RefCell<Scrub>>,
     status_overlay: gtk::Frame,
     motion_type_icon: gtk::Image,
     duration_overlay: gtk::Frame,
@@ -143,8 +234,43 @@ impl RelmGridItem for PhotoGridItem {
             }
         }
 
+        let scrub = Rc::new(RefCell::new(Scrub::default()));
+
+        // Scrub through videos by hovering over them with a pointer...
+        let motion = gtk::EventControllerMotion::new();
+        {
+            let scrub = scrub.clone();
+            let picture = picture.clone();
+            motion.connect_motion(move |_, x, _| Scrub::show(&scrub, &picture, x));
+        }
+        {
+            let scrub = scrub.clone();
+            let picture = picture.clone();
+            motion.connect_leave(move |_| scrub.borrow_mut().restore(&picture));
+        }
+        picture.add_controller(motion);
+
+        // ...or by dragging over them on a touch screen.
+        let drag = gtk::GestureDrag::new();
+        {
+            let scrub = scrub.clone();
+            let picture = picture.clone();
+            drag.connect_drag_update(move |gesture, offset_x, _| {
+                if let Some((start_x, _)) = gesture.start_point() {
+                    Scrub::show(&scrub, &picture, start_x + offset_x);
+                }
+            });
+        }
+        {
+            let scrub = scrub.clone();
+            let picture = picture.clone();
+            drag.connect_drag_end(move |_, _, _| scrub.borrow_mut().restore(&picture));
+        }
+        picture.add_controller(drag);
+
         let widgets = PhotoGridItemWidgets {
             picture,
+            scrub,
             status_overlay,
             motion_type_icon,
             duration_overlay,
@@ -184,6 +310,15 @@ impl RelmGridItem for PhotoGridItem {
             .thumbnailer
             .nearest_thumbnail(&self.visual.thumbnail_hash(), thumbnail_size);
 
+        let video = self
+            .visual
+            .is_video_only()
+            .then(|| (self.thumbnailer.clone(), self.visual.thumbnail_hash()));
+        widgets
+            .scrub
+            .borrow_mut()
+            .reset(video, thumbnail_path.clone());
+
         if thumbnail_path.is_some() {
             widgets.picture.set_filename(thumbnail_path);
 
@@ -223,6 +358,13 @@ impl RelmGridItem for PhotoGridItem {
             widgets
                 .motion_type_icon
                 .set_icon_name(Some("play-symbolic"));
+        } else if self.visual.is_raw() {
+            widgets.status_overlay.set_visible(false);
+            widgets.motion_type_icon.set_icon_name(None);
+            widgets.duration_overlay.set_visible(true);
+            widgets
+                .duration_label
+                .set_label(&fl!("photo-grid-raw-badge"));
         } else {
             // is_photo_only()
             widgets.status_overlay.set_visible(false);
@@ -233,6 +375,7 @@ impl RelmGridItem for PhotoGridItem {
     }
 
     fn unbind(&mut self, widgets: &mut Self::Widgets, _root: &mut Self::Root) {
+        widgets.scrub.borrow_mut().reset(None, None);
         widgets.picture.set_filename(None::<&Path>);
         widgets.motion_type_icon.set_icon_name(None);
         widgets.status_overlay.set_visible(false);
@@ -0,0 +1,427 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use fotema_core::PictureId;
+use fotema_core::Visual;
+use fotema_core::VisualId;
+use fotema_core::duplicates::{self, DuplicateKind};
+use fotema_core::thumbnailify::{ThumbnailSize, Thumbnailer};
+
+use relm4::adw::prelude::*;
+use relm4::gtk;
+use relm4::gtk::gio;
+use relm4::gtk::prelude::*;
+use relm4::*;
+
+use crate::app::ActiveView;
+use crate::app::SharedState;
+use crate::app::ViewName;
+use crate::app::components::albums::album_filter::AlbumFilter;
+use crate::fl;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use tracing::{error, info};
+
+const EDGE_LENGTH: i32 = 150;
+
+/// Copies of a picture, as shown in the album.
+#[derive(Debug)]
+struct Group {
+    kind: DuplicateKind,
+
+    visuals: Vec<Arc<Visual>>,
+
+    /// Index into visuals of the copy to keep.
+    best: usize,
+}
+
+impl Group {
+    /// Copies that aren't the best copy.
+    fn others(&self) -> impl Iterator<Item = &Arc<Visual>> {
+        self.visuals
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != self.best)
+            .map(|(_, visual)| visual)
+    }
+
+    /// Pictures of the copies that aren't the best copy.
+    fn other_picture_ids(&self) -> Vec<PictureId> {
+        self.others().filter_map(|v| v.picture_id).collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum DuplicatesAlbumInput {
+    Activate,
+
+    // Reload duplicates from database
+    Refresh,
+
+    // A copy has been selected, along with the pictures of its group.
+    Selected(VisualId, Vec<PictureId>),
+
+    // Ask whether to trash all copies in a group except the best one.
+    // The pictures are the copies to trash.
+    TrashDialog(Vec<PictureId>),
+
+    // Trash all copies in a group except the best one.
+    // The pictures are the copies to trash, which must still match a group.
+    Trash(Vec<PictureId>),
+}
+
+#[derive(Debug)]
+pub enum DuplicatesAlbumOutput {
+    Selected(VisualId, AlbumFilter),
+}
+
+pub struct DuplicatesAlbum {
+    state: SharedState,
+    active_view: ActiveView,
+    repo: duplicates::Repository,
+    thumbnailer: Rc<Thumbnailer>,
+    groups: Vec<Group>,
+    groups_box: gtk::Box,
+    scrolled: gtk::ScrolledWindow,
+    status: adw::StatusPage,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for DuplicatesAlbum {
+    type Init = (
+        SharedState,
+        ActiveView,
+        duplicates::Repository,
+        Rc<Thumbnailer>,
+    );
+    type Input = DuplicatesAlbumInput;
+    type Output = DuplicatesAlbumOutput;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+
+            #[local_ref]
+            scrolled -> gtk::ScrolledWindow {
+                set_vexpand: true,
+
+                adw::Clamp {
+                    set_maximum_size: 1000,
+
+                    #[local_ref]
+                    groups_box -> gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 24,
+                        set_margin_all: 12,
+                    },
+                },
+            },
+
+            #[local_ref]
+            status -> adw::StatusPage {
+                set_valign: gtk::Align::Start,
+                set_vexpand: true,
+                set_visible: false,
+                set_icon_name: Some("edit-copy-symbolic"),
+                set_title: &fl!("duplicates-album-status-none", "title"),
+                set_description: Some(&fl!("duplicates-album-status-none", "description")),
+            },
+        }
+    }
+
+    fn init(
+        (state, active_view, repo, thumbnailer): Self::Init,
+        _root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let groups_box = gtk::Box::default();
+        let scrolled = gtk::ScrolledWindow::default();
+        let status = adw::StatusPage::new();
+
+        let model = DuplicatesAlbum {
+            state,
+            active_view,
+            repo,
+            thumbnailer,
+            groups: Vec::new(),
+            groups_box: groups_box.clone(),
+            scrolled: scrolled.clone(),
+            status: status.clone(),
+        };
+
+        let groups_box = &groups_box;
+        let scrolled = &scrolled;
+        let status = &status;
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            DuplicatesAlbumInput::Activate => {
+                *self.active_view.write() = ViewName::Duplicates;
+                self.refresh(&sender);
+            }
+            DuplicatesAlbumInput::Refresh => {
+                if *self.active_view.read() == ViewName::Duplicates {
+                    info!("Duplicates view is active so refreshing");
+                    self.refresh(&sender);
+                }
+            }
+            DuplicatesAlbumInput::Selected(visual_id, picture_ids) => {
+                let _ = sender.output(DuplicatesAlbumOutput::Selected(
+                    visual_id,
+                    AlbumFilter::Any(picture_ids),
+                ));
+            }
+            DuplicatesAlbumInput::TrashDialog(picture_ids) => {
+                let count = picture_ids.len();
+
+                let dialog = adw::AlertDialog::builder()
+                    .heading(fl!("duplicates-album-trash-dialog", "heading"))
+                    .body(fl!("duplicates-album-trash-dialog", "body", count = count))
+                    .build();
+
+                dialog.add_response(
+                    "cancel",
+                    &fl!("duplicates-album-trash-dialog", "cancel-button"),
+                );
+                dialog.add_response(
+                    "trash",
+                    &fl!("duplicates-album-trash-dialog", "trash-button"),
+                );
+                dialog.set_response_appearance("trash", adw::ResponseAppearance::Destructive);
+                dialog.set_default_response(Some("cancel"));
+                dialog.set_close_response("cancel");
+
+                let sender = sender.clone();
+                dialog.connect_response(None, move |_, response| {
+                    if response == "trash" {
+                        sender.input(DuplicatesAlbumInput::Trash(picture_ids.clone()));
+                    }
+                });
+
+                dialog.present(Some(&self.groups_box));
+            }
+            DuplicatesAlbumInput::Trash(picture_ids) => {
+                // Groups might have been refreshed while the dialog was open, so only trash
+                // copies if a group still has the same copies and the same best copy.
+                let Some(index) = self
+                    .groups
+                    .iter()
+                    .position(|group| group.other_picture_ids() == picture_ids)
+                else {
+                    info!("Duplicates have changed, so not trashing");
+                    return;
+                };
+
+                let group = self.groups.remove(index);
+
+                // The library watcher sees the trashed files and removes them from the library.
+                for visual in group.others() {
+                    let paths = [visual.picture_path.as_ref(), visual.video_path.as_ref()];
+                    for path in paths.into_iter().flatten() {
+                        info!("Trashing duplicate {:?}", path.sandbox_path);
+                        let file = gio::File::for_path(&path.sandbox_path);
+                        if let Err(e) = file.trash(gio::Cancellable::NONE) {
+                            error!("Failed to trash {:?}: {:?}", path.sandbox_path, e);
+                        }
+                    }
+                }
+
+                self.show_groups(&sender);
+            }
+        }
+    }
+}
+
+impl DuplicatesAlbum {
+    fn refresh(&mut self, sender: &ComponentSender<Self>) {
+        let duplicates = self.repo.find_duplicates().unwrap_or_else(|e| {
+            error!("Failed to find duplicates: {:?}", e);
+            Vec::new()
+        });
+
+        let by_picture_id: HashMap<i64, Arc<Visual>> = self
+            .state
+            .read()
+            .iter()
+            .filter_map(|visual| Some((visual.picture_id?.id(), visual.clone())))
+            .collect();
+
+        // Pictures not in the library, such as RAW files paired with a JPEG, are left out.
+        self.groups = duplicates
+            .into_iter()
+            .filter_map(|duplicate| {
+                let visuals: Vec<Arc<Visual>> = duplicate
+                    .picture_ids
+                    .iter()
+                    .filter_map(|id| by_picture_id.get(&id.id()).cloned())
+                    .collect();
+
+                if visuals.len() < 2 {
+                    return None;
+                }
+
+                let refs: Vec<&Visual> = visuals.iter().map(|v| v.as_ref()).collect();
+                let best = duplicates::model::best_copy(&refs)?;
+
+                Some(Group {
+                    kind: duplicate.kind,
+                    visuals,
+                    best,
+                })
+            })
+            .collect();
+
+        info!("Found {} groups of duplicates", self.groups.len());
+
+        self.show_groups(sender);
+    }
+
+    fn show_groups(&self, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.groups_box.first_child() {
+            self.groups_box.remove(&child);
+        }
+
+        self.status.set_visible(self.groups.is_empty());
+        self.scrolled.set_visible(!self.groups.is_empty());
+
+        for group in &self.groups {
+            let group_box = self.group_widget(group, sender);
+            self.groups_box.append(&group_box);
+        }
+    }
+
+    fn group_widget(&self, group: &Group, sender: &ComponentSender<Self>) -> gtk::Box {
+        let heading = match group.kind {
+            DuplicateKind::Exact => fl!("duplicates-album-group", "exact"),
+            DuplicateKind::Near => fl!("duplicates-album-group", "near"),
+        };
+
+        let picture_ids: Vec<PictureId> =
+            group.visuals.iter().filter_map(|v| v.picture_id).collect();
+
+        let trash_picture_ids = group.other_picture_ids();
+        let trash_sender = sender.clone();
+
+        relm4::view! {
+            group_box = gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 6,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+
+                    gtk::Label {
+                        set_label: &heading,
+                        add_css_class: "heading",
+                        set_hexpand: true,
+                        set_xalign: 0.0,
+                    },
+
+                    gtk::Button {
+                        set_label: &fl!("duplicates-album-group", "trash-button"),
+                        add_css_class: "destructive-action",
+                        connect_clicked => move |_| {
+                            trash_sender.input(DuplicatesAlbumInput::TrashDialog(
+                                trash_picture_ids.clone(),
+                            ));
+                        },
+                    },
+                },
+
+                #[name(copies)]
+                gtk::FlowBox {
+                    set_selection_mode: gtk::SelectionMode::None,
+                    set_homogeneous: true,
+                    set_max_children_per_line: 6,
+                    set_column_spacing: 6,
+                    set_row_spacing: 6,
+                },
+            }
+        }
+
+        for (visual_index, visual) in group.visuals.iter().enumerate() {
+            let is_best = visual_index == group.best;
+            let copy = self.copy_widget(visual, is_best, picture_ids.clone(), sender);
+            copies.append(&copy);
+        }
+
+        group_box
+    }
+
+    fn copy_widget(
+        &self,
+        visual: &Visual,
+        is_best: bool,
+        picture_ids: Vec<PictureId>,
+        sender: &ComponentSender<Self>,
+    ) -> gtk::Button {
+        let resolution = visual
+            .exif
+            .as_ref()
+            .and_then(|exif| Some((exif.width?, exif.height?)))
+            .map(|(width, height)| {
+                fl!(
+                    "duplicates-album-resolution",
+                    width = width.to_string(),
+                    height = height.to_string()
+                )
+            })
+            .unwrap_or_default();
+
+        let thumbnail_path = self
+            .thumbnailer
+            .nearest_thumbnail(&visual.thumbnail_hash(), ThumbnailSize::Normal);
+
+        let visual_id = visual.visual_id.clone();
+        let sender = sender.clone();
+
+        relm4::view! {
+            button = gtk::Button {
+                add_css_class: "flat",
+                set_tooltip_text: visual.path().host_path.to_str(),
+
+                connect_clicked => move |_| {
+                    sender.input(DuplicatesAlbumInput::Selected(
+                        visual_id.clone(),
+                        picture_ids.clone(),
+                    ));
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+
+                    gtk::Picture {
+                        set_content_fit: gtk::ContentFit::Cover,
+                        set_width_request: EDGE_LENGTH,
+                        set_height_request: EDGE_LENGTH,
+                        set_filename: thumbnail_path,
+                    },
+
+                    gtk::Label {
+                        set_label: &resolution,
+                        add_css_class: "caption",
+                    },
+
+                    gtk::Label {
+                        set_label: &fl!("duplicates-album-group", "best"),
+                        add_css_class: "caption-heading",
+                        add_css_class: "accent",
+                        set_visible: is_best,
+                    },
+                },
+            }
+        }
+
+        button
+    }
+}
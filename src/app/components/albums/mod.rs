@@ -5,9 +5,11 @@
 pub mod album;
 pub mod album_filter;
 pub mod album_sort;
+pub mod duplicates_album;
 pub mod folders_album;
 pub mod months_album;
 pub mod people_album;
 pub mod person_album;
+pub mod person_split;
 pub mod places_album;
 pub mod years_album;
@@ -21,11 +21,17 @@ use crate::app::SettingsState;
 use crate::app::ViewName;
 use crate::fl;
 
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 const NARROW_EDGE_LENGTH: i32 = 170;
 const WIDE_EDGE_LENGTH: i32 = 200;
 
+// Size of face avatars on suggested person cards.
+const SUGGESTION_FACE_SIZE: i32 = 48;
+
+// Number of faces to show on a suggested person card.
+const SUGGESTION_FACE_COUNT: usize = 4;
+
 #[derive(Debug)]
 struct PhotoGridItem {
     /// Person for avatar
@@ -58,6 +64,9 @@ pub enum PeopleAlbumInput {
     SettingsChanged,
 
     EnableFaceDetection,
+
+    /// Name a suggested person. Index into suggestions vector.
+    NameSuggestion(usize, String),
 }
 
 #[derive(Debug)]
@@ -65,6 +74,9 @@ pub enum PeopleAlbumOutput {
     Selected(people::Person),
 
     EnableFaceDetection,
+
+    /// A suggested person has been named, which may help recognize other faces.
+    PersonNamed,
 }
 
 impl RelmGridItem for PhotoGridItem {
@@ -138,6 +150,11 @@ pub struct PeopleAlbum {
     avatars: gtk::ScrolledWindow,
     status: adw::StatusPage,
     edge_length: I32Binding,
+
+    /// Groups of unknown faces that could be named as new people.
+    suggestions: Vec<people::SuggestedPerson>,
+    suggestions_scroll: gtk::ScrolledWindow,
+    suggestions_box: gtk::Box,
 }
 
 #[relm4::component(pub)]
@@ -150,6 +167,19 @@ impl SimpleComponent for PeopleAlbum {
         gtk::Box {
             set_orientation: gtk::Orientation::Vertical,
 
+            #[local_ref]
+            suggestions_scroll -> gtk::ScrolledWindow {
+                set_visible: false,
+                set_vscrollbar_policy: gtk::PolicyType::Never,
+
+                #[local_ref]
+                suggestions_box -> gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 12,
+                    set_margin_all: 12,
+                },
+            },
+
             #[local_ref]
             avatars -> gtk::ScrolledWindow {
                 set_vexpand: true,
@@ -207,6 +237,9 @@ impl SimpleComponent for PeopleAlbum {
 
         let avatars = gtk::ScrolledWindow::builder().build();
 
+        let suggestions_scroll = gtk::ScrolledWindow::builder().build();
+        let suggestions_box = gtk::Box::default();
+
         let model = PeopleAlbum {
             repo,
             active_view,
@@ -215,9 +248,14 @@ impl SimpleComponent for PeopleAlbum {
             avatars: avatars.clone(),
             status: status.clone(),
             edge_length: I32Binding::new(NARROW_EDGE_LENGTH),
+            suggestions: Vec::new(),
+            suggestions_scroll: suggestions_scroll.clone(),
+            suggestions_box: suggestions_box.clone(),
         };
 
         let pictures_box = &model.photo_grid.view;
+        let suggestions_scroll = &suggestions_scroll;
+        let suggestions_box = &suggestions_box;
 
         let widgets = view_output!();
 
@@ -229,7 +267,7 @@ impl SimpleComponent for PeopleAlbum {
             PeopleAlbumInput::Activate => {
                 info!("Activating people view");
                 *self.active_view.write() = ViewName::People;
-                self.refresh();
+                self.refresh(&sender);
             }
             PeopleAlbumInput::Selected(index) => {
                 debug!("Person selected index: {}", index);
@@ -248,25 +286,52 @@ impl SimpleComponent for PeopleAlbum {
                 self.edge_length.set_value(WIDE_EDGE_LENGTH);
             }
             PeopleAlbumInput::Refresh => {
-                self.refresh();
+                self.refresh(&sender);
             }
             PeopleAlbumInput::SettingsChanged => {
-                self.refresh();
+                self.refresh(&sender);
             }
             PeopleAlbumInput::EnableFaceDetection => {
                 let mut settings = self.settings_state.read().clone();
                 settings.face_detection_mode = FaceDetectionMode::On;
                 *self.settings_state.write() = settings;
-                self.refresh();
+                self.refresh(&sender);
                 let _ = sender.output(PeopleAlbumOutput::EnableFaceDetection);
             }
+            PeopleAlbumInput::NameSuggestion(index, name) => {
+                let name = name.trim();
+                if name.is_empty() {
+                    return;
+                }
+
+                let Some(suggestion) = self.suggestions.get(index) else {
+                    return;
+                };
+
+                info!(
+                    "Naming suggested person with {} faces",
+                    suggestion.faces.len()
+                );
+
+                let face_ids: Vec<people::FaceId> =
+                    suggestion.faces.iter().map(|face| face.face_id).collect();
+
+                if let Err(e) = self.repo.add_person_with_faces(&face_ids, name) {
+                    error!("Failed naming suggested person: {:?}", e);
+                }
+
+                self.refresh(&sender);
+                let _ = sender.output(PeopleAlbumOutput::PersonNamed);
+            }
         }
     }
 }
 
 impl PeopleAlbum {
-    fn refresh(&mut self) {
+    fn refresh(&mut self, sender: &ComponentSender<Self>) {
         if self.settings_state.read().face_detection_mode == FaceDetectionMode::Off {
+            self.suggestions.clear();
+            self.suggestions_scroll.set_visible(false);
             self.avatars.set_visible(false);
             self.status.set_visible(true);
             self.status
@@ -295,7 +360,14 @@ impl PeopleAlbum {
             items.push(item);
         }
 
-        self.status.set_visible(items.is_empty());
+        self.suggestions = self.repo.find_suggested_people().unwrap_or_else(|e| {
+            error!("Failed to find suggested people: {:?}", e);
+            Vec::new()
+        });
+        self.show_suggestions(sender);
+
+        self.status
+            .set_visible(items.is_empty() && self.suggestions.is_empty());
         self.avatars.set_visible(!items.is_empty());
 
         if items.is_empty() {
@@ -310,4 +382,100 @@ impl PeopleAlbum {
 
         self.photo_grid.extend_from_iter(items);
     }
+    fn show_suggestions(&self, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.suggestions_box.first_child() {
+            self.suggestions_box.remove(&child);
+        }
+
+        self.suggestions_scroll
+            .set_visible(!self.suggestions.is_empty());
+
+        for (index, suggestion) in self.suggestions.iter().enumerate() {
+            let card = Self::suggestion_widget(index, suggestion, sender);
+            self.suggestions_box.append(&card);
+        }
+    }
+
+    fn suggestion_widget(
+        index: usize,
+        suggestion: &people::SuggestedPerson,
+        sender: &ComponentSender<Self>,
+    ) -> gtk::Box {
+        let entry_sender = sender.clone();
+        let button_sender = sender.clone();
+
+        relm4::view! {
+            card = gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 6,
+                set_width_request: 240,
+                add_css_class: "card",
+
+                #[name(faces)]
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_halign: gtk::Align::Center,
+                    set_spacing: 4,
+                    set_margin_top: 12,
+                    set_margin_start: 12,
+                    set_margin_end: 12,
+                },
+
+                gtk::Label {
+                    set_label: &fl!("people-page-suggestion", "title"),
+                    add_css_class: "heading",
+                },
+
+                gtk::Label {
+                    set_label: &fl!("people-page-suggestion", "count", count = suggestion.faces.len()),
+                    add_css_class: "caption",
+                    add_css_class: "dim-label",
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 6,
+                    set_margin_start: 12,
+                    set_margin_end: 12,
+                    set_margin_bottom: 12,
+
+                    #[name(name_entry)]
+                    gtk::Entry {
+                        set_hexpand: true,
+                        set_placeholder_text: Some(&fl!("people-person-search", "placeholder")),
+                        connect_activate => move |entry| {
+                            entry_sender.input(PeopleAlbumInput::NameSuggestion(index, entry.text().to_string()));
+                        },
+                    },
+
+                    #[name(name_button)]
+                    gtk::Button {
+                        set_label: &fl!("people-page-suggestion", "name-button"),
+                        add_css_class: "suggested-action",
+                    },
+                },
+            }
+        }
+
+        {
+            let name_entry = name_entry.clone();
+            name_button.connect_clicked(move |_| {
+                button_sender.input(PeopleAlbumInput::NameSuggestion(
+                    index,
+                    name_entry.text().to_string(),
+                ));
+            });
+        }
+
+        for face in suggestion.faces.iter().take(SUGGESTION_FACE_COUNT) {
+            let avatar = adw::Avatar::new(SUGGESTION_FACE_SIZE, None, false);
+            if face.thumbnail_path.exists() {
+                let img = gdk::Texture::from_filename(&face.thumbnail_path).ok();
+                avatar.set_custom_image(img.as_ref());
+            }
+            faces.append(&avatar);
+        }
+
+        card
+    }
 }
@@ -19,10 +19,12 @@ use crate::app::components::albums::{
     album::{Album, AlbumInput, AlbumOutput},
     album_filter::AlbumFilter,
     album_sort::AlbumSort,
+    person_split::{PersonSplit, PersonSplitInput, PersonSplitOutput},
 };
 
 use crate::fl;
 
+use fotema_core::PersonId;
 use fotema_core::PictureId;
 use fotema_core::VisualId;
 use fotema_core::people;
@@ -43,6 +45,12 @@ relm4::new_stateless_action!(RenameAction, PersonActionGroup, "rename");
 // Delete a person
 relm4::new_stateless_action!(DeleteAction, PersonActionGroup, "delete");
 
+// Merge another person into a person
+relm4::new_stateless_action!(MergeAction, PersonActionGroup, "merge");
+
+// Split faces of a person
+relm4::new_stateless_action!(SplitAction, PersonActionGroup, "split");
+
 #[derive(Debug)]
 pub enum PersonAlbumInput {
     /// Album is visible
@@ -75,6 +83,18 @@ pub enum PersonAlbumInput {
     /// Actually delete person.
     Delete,
 
+    /// Start merge person flow.
+    MergeDialog,
+
+    /// Actually merge another person into person.
+    Merge(PersonId),
+
+    /// Show faces of person grouped by similarity.
+    SplitDialog,
+
+    /// Faces have been moved from or to person.
+    FacesMoved,
+
     Sort(AlbumSort),
 }
 
@@ -88,6 +108,9 @@ pub enum PersonAlbumOutput {
 
     /// Person renamed.
     Renamed,
+
+    /// People have been merged, or faces moved between people.
+    PeopleChanged,
 }
 
 pub struct PersonAlbum {
@@ -99,6 +122,9 @@ pub struct PersonAlbum {
     title: gtk::Label,
     active_view: ActiveView,
     edge_length: I32Binding,
+
+    split_dialog: adw::Dialog,
+    person_split: Controller<PersonSplit>,
 }
 
 #[relm4::component(pub)]
@@ -112,6 +138,8 @@ impl SimpleComponent for PersonAlbum {
             section! {
                 // FIXME I would like to have the person's name in these menu items.
                 &fl!("person-menu-rename") => RenameAction,
+                &fl!("person-menu-merge") => MergeAction,
+                &fl!("person-menu-split") => SplitAction,
                 &fl!("person-menu-delete") => DeleteAction,
             }
         }
@@ -171,6 +199,19 @@ impl SimpleComponent for PersonAlbum {
 
         let title = gtk::Label::builder().build();
 
+        let person_split =
+            PersonSplit::builder()
+                .launch(repo.clone())
+                .forward(sender.input_sender(), |msg| match msg {
+                    PersonSplitOutput::Changed => PersonAlbumInput::FacesMoved,
+                });
+
+        let split_dialog = adw::Dialog::builder()
+            .child(person_split.widget())
+            .content_width(600)
+            .content_height(600)
+            .build();
+
         let model = PersonAlbum {
             repo,
             person: None,
@@ -180,6 +221,8 @@ impl SimpleComponent for PersonAlbum {
             active_view,
             picture_ids: vec![],
             edge_length: I32Binding::new(NARROW_EDGE_LENGTH),
+            split_dialog,
+            person_split,
         };
 
         model
@@ -203,8 +246,24 @@ impl SimpleComponent for PersonAlbum {
             })
         };
 
+        let merge_action = {
+            let sender = sender.clone();
+            RelmAction::<MergeAction>::new_stateless(move |_| {
+                sender.input(PersonAlbumInput::MergeDialog);
+            })
+        };
+
+        let split_action = {
+            let sender = sender.clone();
+            RelmAction::<SplitAction>::new_stateless(move |_| {
+                sender.input(PersonAlbumInput::SplitDialog);
+            })
+        };
+
         actions.add_action(rename_action);
         actions.add_action(delete_action);
+        actions.add_action(merge_action);
+        actions.add_action(split_action);
         actions.register_for_widget(&root);
 
         ComponentParts { model, widgets }
@@ -391,6 +450,111 @@ impl SimpleComponent for PersonAlbum {
                     error!("Couldn't get root widget!");
                 }
             }
+            PersonAlbumInput::MergeDialog => {
+                let Some(ref person) = self.person else {
+                    info!("Asked to merge person, but no person for album");
+                    return;
+                };
+                info!("Starting merge flow for person: {}", person.person_id);
+
+                let others: Vec<people::Person> = self
+                    .repo
+                    .all_people()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|other| other.person_id != person.person_id)
+                    .collect();
+
+                if others.is_empty() {
+                    info!("No other people to merge with");
+                    return;
+                }
+
+                let names: Vec<&str> = others.iter().map(|other| other.name.as_str()).collect();
+                let other_people = gtk::DropDown::from_strings(&names);
+                other_people.set_enable_search(true);
+
+                let dialog = adw::AlertDialog::builder()
+                    .heading(fl!("person-merge-dialog", "heading"))
+                    .body(fl!(
+                        "person-merge-dialog",
+                        "body",
+                        name = person.name.clone()
+                    ))
+                    .extra_child(&other_people)
+                    .build();
+
+                dialog.add_response("cancel", &fl!("person-merge-dialog", "cancel-button"));
+                dialog.set_default_response(Some("cancel"));
+                dialog.set_close_response("cancel");
+
+                dialog.add_response("merge", &fl!("person-merge-dialog", "merge-button"));
+                dialog.set_response_appearance("merge", adw::ResponseAppearance::Destructive);
+
+                dialog.connect_response(None, move |_, response| {
+                    if response == "merge" {
+                        let index = other_people.selected() as usize;
+                        if let Some(other) = others.get(index) {
+                            sender.input(PersonAlbumInput::Merge(other.person_id));
+                        }
+                    }
+                });
+
+                if let Some(root) = gtk::Widget::root(self.avatar.widget_ref()) {
+                    dialog.present(Some(&root));
+                } else {
+                    error!("Couldn't get root widget!");
+                }
+            }
+            PersonAlbumInput::Merge(other_person_id) => {
+                let Some(ref person) = self.person else {
+                    info!("Asked to merge person, but no person for album");
+                    return;
+                };
+
+                info!("Merging {} into {}", other_person_id, person.person_id);
+
+                if let Err(e) = self.repo.merge_people(other_person_id, person.person_id) {
+                    error!("Failed to merge people: {}", e);
+                    return;
+                }
+
+                sender.input(PersonAlbumInput::FacesMoved);
+            }
+            PersonAlbumInput::SplitDialog => {
+                let Some(ref person) = self.person else {
+                    info!("Asked to split person, but no person for album");
+                    return;
+                };
+
+                self.person_split
+                    .emit(PersonSplitInput::View(person.person_id));
+
+                if let Some(root) = gtk::Widget::root(self.avatar.widget_ref()) {
+                    self.split_dialog.present(Some(&root));
+                } else {
+                    error!("Couldn't get root widget!");
+                }
+            }
+            PersonAlbumInput::FacesMoved => {
+                let Some(ref person) = self.person else {
+                    return;
+                };
+
+                // Thumbnail and photos of person may have changed.
+                let person = self
+                    .repo
+                    .all_people()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|p| p.person_id == person.person_id);
+
+                if let Some(person) = person {
+                    sender.input(PersonAlbumInput::View(person));
+                }
+
+                let _ = sender.output(PersonAlbumOutput::PeopleChanged);
+            }
             PersonAlbumInput::Delete => {
                 let Some(ref person) = self.person else {
                     info!("Asked to delete person, but no person for album");
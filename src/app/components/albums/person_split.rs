@@ -0,0 +1,341 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use relm4::adw::{self, prelude::*};
+use relm4::gtk::{self, gdk};
+use relm4::*;
+
+use crate::fl;
+use fotema_core::FaceId;
+use fotema_core::PersonId;
+use fotema_core::machine_learning::face_clusterer::FaceClusterer;
+use fotema_core::machine_learning::face_recognizer::FaceEmbedder;
+use fotema_core::people;
+
+use tracing::{error, info};
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+
+// Face thumbnails generated at this size in face_extractor.rs
+const AVATAR_SIZE: i32 = 64;
+
+// Number of faces to show for each group.
+const GROUP_FACE_COUNT: usize = 24;
+
+#[derive(Debug)]
+pub enum PersonSplitInput {
+    /// Show faces of a person grouped by similarity.
+    View(PersonId),
+
+    /// Move a group of faces to another person. Index into groups and index into other people.
+    MoveToPerson(usize, usize),
+
+    /// Move a group of faces to a new person with the given name. Index into groups.
+    MoveToNewPerson(usize, String),
+}
+
+#[derive(Debug)]
+pub enum PersonSplitOutput {
+    /// Faces have been moved to another person.
+    Changed,
+}
+
+/// Shows a person's faces grouped by similarity, so that groups of faces
+/// that aren't the person can be moved elsewhere.
+pub struct PersonSplit {
+    repo: people::Repository,
+
+    person_id: Option<PersonId>,
+
+    /// Groups of similar faces, largest first.
+    groups: Vec<Vec<people::Face>>,
+
+    /// People faces can be moved to.
+    /// MUST be in same order as people lists in move popovers.
+    other_people: Vec<people::Person>,
+
+    groups_box: gtk::Box,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for PersonSplit {
+    type Init = people::Repository;
+    type Input = PersonSplitInput;
+    type Output = PersonSplitOutput;
+
+    view! {
+        adw::ToolbarView {
+            add_top_bar = &adw::HeaderBar {
+                #[wrap(Some)]
+                set_title_widget = &adw::WindowTitle {
+                    set_title: &fl!("person-split-dialog", "title"),
+                    set_subtitle: &fl!("person-split-dialog", "subtitle"),
+                },
+            },
+
+            #[wrap(Some)]
+            set_content = &gtk::ScrolledWindow {
+                set_vexpand: true,
+                set_hscrollbar_policy: gtk::PolicyType::Never,
+
+                #[local_ref]
+                groups_box -> gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 24,
+                    set_margin_all: 12,
+                },
+            },
+        }
+    }
+
+    fn init(
+        repo: Self::Init,
+        _root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let groups_box = gtk::Box::default();
+
+        let model = PersonSplit {
+            repo,
+            person_id: None,
+            groups: Vec::new(),
+            other_people: Vec::new(),
+            groups_box: groups_box.clone(),
+        };
+
+        let groups_box = &groups_box;
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            PersonSplitInput::View(person_id) => {
+                self.person_id = Some(person_id);
+                self.refresh(&sender);
+            }
+            PersonSplitInput::MoveToPerson(group_index, person_index) => {
+                let (Some(group), Some(person)) = (
+                    self.groups.get(group_index),
+                    self.other_people.get(person_index),
+                ) else {
+                    return;
+                };
+
+                info!(
+                    "Moving {} faces to person {}",
+                    group.len(),
+                    person.person_id
+                );
+
+                let face_ids: Vec<FaceId> = group.iter().map(|face| face.face_id).collect();
+                if let Err(e) = self.repo.move_faces_to_person(&face_ids, person.person_id) {
+                    error!("Failed moving faces to person: {:?}", e);
+                }
+
+                self.refresh(&sender);
+                let _ = sender.output(PersonSplitOutput::Changed);
+            }
+            PersonSplitInput::MoveToNewPerson(group_index, name) => {
+                let name = name.trim();
+                if name.is_empty() {
+                    return;
+                }
+
+                let Some(group) = self.groups.get(group_index) else {
+                    return;
+                };
+
+                info!("Moving {} faces to new person", group.len());
+
+                let face_ids: Vec<FaceId> = group.iter().map(|face| face.face_id).collect();
+                if let Err(e) = self.repo.move_faces_to_new_person(&face_ids, name) {
+                    error!("Failed moving faces to new person: {:?}", e);
+                }
+
+                self.refresh(&sender);
+                let _ = sender.output(PersonSplitOutput::Changed);
+            }
+        }
+    }
+}
+
+impl PersonSplit {
+    fn refresh(&mut self, sender: &ComponentSender<Self>) {
+        let Some(person_id) = self.person_id else {
+            return;
+        };
+
+        let faces = self
+            .repo
+            .find_faces_for_person(person_id)
+            .unwrap_or_else(|e| {
+                error!("Failed getting faces for person: {:?}", e);
+                Vec::new()
+            });
+
+        let mut embeddings = self
+            .repo
+            .find_embeddings(&FaceEmbedder::MODEL)
+            .unwrap_or_else(|e| {
+                error!("Failed getting face embeddings: {:?}", e);
+                HashMap::new()
+            });
+
+        let to_cluster: Vec<_> = faces
+            .iter()
+            .filter_map(|face| Some((face.face_id, embeddings.remove(&face.face_id)?)))
+            .collect();
+
+        let mut by_face_id: HashMap<FaceId, people::Face> =
+            faces.into_iter().map(|face| (face.face_id, face)).collect();
+
+        // Every face belongs to a group, even if the group is just that face.
+        // One person has few enough faces that grouping them never needs stopping.
+        self.groups = FaceClusterer::default()
+            .with_min_faces(1)
+            .cluster(&to_cluster, &AtomicBool::new(false))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|face_ids| {
+                face_ids
+                    .into_iter()
+                    .filter_map(|face_id| by_face_id.remove(&face_id))
+                    .collect()
+            })
+            .collect();
+
+        // Faces that haven't been compared by face recognition yet.
+        let mut not_embedded: Vec<people::Face> = by_face_id.into_values().collect();
+        not_embedded.sort_by_key(|face| face.face_id.id());
+        self.groups
+            .extend(not_embedded.into_iter().map(|face| vec![face]));
+
+        self.other_people = self
+            .repo
+            .all_people()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|person| person.person_id != person_id)
+            .collect();
+
+        info!(
+            "Person {} has {} groups of faces",
+            person_id,
+            self.groups.len()
+        );
+
+        while let Some(child) = self.groups_box.first_child() {
+            self.groups_box.remove(&child);
+        }
+
+        for (index, group) in self.groups.iter().enumerate() {
+            let group_box = self.group_widget(index, group, sender);
+            self.groups_box.append(&group_box);
+        }
+    }
+
+    fn group_widget(
+        &self,
+        index: usize,
+        group: &[people::Face],
+        sender: &ComponentSender<Self>,
+    ) -> gtk::Box {
+        let new_person_sender = sender.clone();
+
+        relm4::view! {
+            group_box = gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 6,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+
+                    gtk::Label {
+                        set_label: &fl!("person-split-dialog", "group", count = group.len()),
+                        add_css_class: "heading",
+                        set_hexpand: true,
+                        set_xalign: 0.0,
+                    },
+
+                    gtk::MenuButton {
+                        set_label: &fl!("person-split-dialog", "move-button"),
+
+                        #[wrap(Some)]
+                        set_popover = &gtk::Popover {
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_spacing: 6,
+
+                                gtk::Entry {
+                                    set_placeholder_text: Some(&fl!("person-split-dialog", "new-person")),
+                                    set_input_purpose: gtk::InputPurpose::Name,
+                                    connect_activate => move |entry| {
+                                        new_person_sender.input(PersonSplitInput::MoveToNewPerson(index, entry.text().to_string()));
+                                    },
+                                },
+
+                                gtk::ScrolledWindow {
+                                    set_propagate_natural_height: true,
+                                    set_max_content_height: 300,
+                                    set_hscrollbar_policy: gtk::PolicyType::Never,
+
+                                    #[name(people_list)]
+                                    gtk::ListBox {
+                                        add_css_class: "boxed-list",
+                                        set_activate_on_single_click: true,
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+
+                #[name(faces)]
+                gtk::FlowBox {
+                    set_selection_mode: gtk::SelectionMode::None,
+                    set_max_children_per_line: 12,
+                    set_column_spacing: 6,
+                    set_row_spacing: 6,
+                },
+            }
+        }
+
+        for person in &self.other_people {
+            let avatar = adw::Avatar::new(32, Some(&person.name), true);
+            if let Some(path) = person.thumbnail_path() {
+                let img = gdk::Texture::from_filename(path).ok();
+                avatar.set_custom_image(img.as_ref());
+            }
+
+            let row = adw::ActionRow::builder().title(&person.name).build();
+            row.add_prefix(&avatar);
+            people_list.append(&row);
+        }
+
+        {
+            let sender = sender.clone();
+            people_list.connect_row_activated(move |list, row| {
+                list.unselect_all();
+                if let Ok(person_index) = usize::try_from(row.index()) {
+                    sender.input(PersonSplitInput::MoveToPerson(index, person_index));
+                }
+            });
+        }
+
+        for face in group.iter().take(GROUP_FACE_COUNT) {
+            let avatar = adw::Avatar::new(AVATAR_SIZE, None, false);
+            if face.thumbnail_path.exists() {
+                let img = gdk::Texture::from_filename(&face.thumbnail_path).ok();
+                avatar.set_custom_image(img.as_ref());
+            }
+            faces.append(&avatar);
+        }
+
+        group_box
+    }
+}
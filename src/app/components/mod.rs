@@ -9,4 +9,5 @@ pub mod onboard;
 pub mod preferences;
 pub mod progress_monitor;
 pub mod progress_panel;
+pub mod sprite_preview;
 pub mod viewer;
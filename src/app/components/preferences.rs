@@ -18,12 +18,35 @@ use crate::app::{Settings, SettingsState};
 use crate::fl;
 use crate::host_path;
 use fotema_core::FlatpakPathBuf;
+use fotema_core::machine_learning::model_registry::{
+    self, ModelLocation, ModelRegistry, ModelStatus,
+};
+use fotema_core::scanner::ExcludeRules;
+use fotema_core::video::{TranscodeQuality, VideoCodec};
 use std::path::PathBuf;
 
 pub struct PreferencesDialog {
     parent: adw::ApplicationWindow,
     dialog: adw::PreferencesDialog,
     album_sort: adw::ComboRow,
+    transcode_codec: adw::ComboRow,
+    transcode_quality: adw::ComboRow,
+
+    /// Group listing library directories in addition to the pictures directory.
+    library_dirs_group: adw::PreferencesGroup,
+    library_dir_rows: Vec<adw::ActionRow>,
+
+    /// Group listing exclude patterns and directories.
+    exclude_group: adw::PreferencesGroup,
+    exclude_rows: Vec<adw::ActionRow>,
+
+    /// Group for face models, with a row for each model showing if it has been found.
+    models_group: adw::PreferencesGroup,
+    model_rows: Vec<adw::ActionRow>,
+    model_mirror: adw::EntryRow,
+
+    /// Face models, before applying the model settings.
+    models: ModelRegistry,
 
     settings_state: SettingsState,
 
@@ -38,14 +61,207 @@ impl PreferencesDialog {
 
     pub fn picture_base_dir_host_path(&self) -> String {
         self.settings
-            .library_base_dir
-            .host_path
-            .to_string_lossy()
-            .to_string()
+            .library_roots
+            .first()
+            .map(|dir| dir.host_path.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Rebuild the rows for the additional library directories.
+    fn refresh_library_dirs(&mut self, sender: &AsyncComponentSender<Self>) {
+        for row in self.library_dir_rows.drain(..) {
+            self.library_dirs_group.remove(&row);
+        }
+
+        for (index, dir) in self.settings.library_roots.iter().enumerate().skip(1) {
+            let row = Self::removable_row(
+                &dir.host_path.to_string_lossy(),
+                &fl!("prefs-library-section-other-dirs", "remove-tooltip"),
+                sender,
+                PreferencesInput::RemoveLibraryDir(index),
+            );
+            self.library_dirs_group.add(&row);
+            self.library_dir_rows.push(row);
+        }
+    }
+
+    pub fn models_dir_host_path(&self) -> String {
+        self.settings
+            .models_dir
+            .as_ref()
+            .map(|dir| dir.host_path.to_string_lossy().to_string())
+            .unwrap_or_else(|| fl!("prefs-models-section-dir", "none"))
+    }
+
+    /// Rebuild the rows showing whether each model has been found.
+    async fn refresh_models(&mut self) {
+        let models = self.settings.model_registry(&self.models);
+
+        // Verifying a model can mean hashing a large file.
+        let statuses = relm4::spawn_blocking(move || {
+            model_registry::MODELS
+                .iter()
+                .map(|spec| (spec.name, models.status(spec)))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+
+        for row in self.model_rows.drain(..) {
+            self.models_group.remove(&row);
+        }
+
+        for (name, status) in statuses {
+            let (subtitle, icon_name) = match status {
+                ModelStatus::Available(ModelLocation::System, _) => (
+                    fl!("prefs-models-section-status", "system"),
+                    "emblem-ok-symbolic",
+                ),
+                ModelStatus::Available(ModelLocation::User, _) => (
+                    fl!("prefs-models-section-status", "user"),
+                    "emblem-ok-symbolic",
+                ),
+                ModelStatus::Available(ModelLocation::Cache, _) => (
+                    fl!("prefs-models-section-status", "cache"),
+                    "emblem-ok-symbolic",
+                ),
+                ModelStatus::Unpinned => (
+                    fl!("prefs-models-section-status", "unpinned"),
+                    "dialog-warning-symbolic",
+                ),
+                ModelStatus::Missing => (
+                    fl!("prefs-models-section-status", "missing"),
+                    "dialog-warning-symbolic",
+                ),
+                ModelStatus::Mismatch(path) => (
+                    fl!(
+                        "prefs-models-section-status",
+                        "mismatch",
+                        path = path.to_string_lossy().to_string()
+                    ),
+                    "dialog-warning-symbolic",
+                ),
+            };
+
+            let row = adw::ActionRow::builder()
+                .title(name)
+                .subtitle(subtitle)
+                .build();
+            row.add_suffix(&gtk::Image::from_icon_name(icon_name));
+
+            self.models_group.add(&row);
+            self.model_rows.push(row);
+        }
+    }
+
+    /// Rebuild the rows for the exclude patterns and directories.
+    fn refresh_excludes(&mut self, sender: &AsyncComponentSender<Self>) {
+        for row in self.exclude_rows.drain(..) {
+            self.exclude_group.remove(&row);
+        }
+
+        let tooltip = fl!("prefs-library-section-exclude", "remove-tooltip");
+
+        for (index, pattern) in self.settings.exclude_patterns.iter().enumerate() {
+            let row = Self::removable_row(
+                pattern,
+                &tooltip,
+                sender,
+                PreferencesInput::RemoveExcludePattern(index),
+            );
+            self.exclude_group.add(&row);
+            self.exclude_rows.push(row);
+        }
+
+        for (index, dir) in self.settings.exclude_dirs.iter().enumerate() {
+            let row = Self::removable_row(
+                &dir.host_path.to_string_lossy(),
+                &tooltip,
+                sender,
+                PreferencesInput::RemoveExcludeDir(index),
+            );
+            self.exclude_group.add(&row);
+            self.exclude_rows.push(row);
+        }
+    }
+
+    /// Row with a button to remove it.
+    fn removable_row(
+        title: &str,
+        tooltip: &str,
+        sender: &AsyncComponentSender<Self>,
+        remove_msg: PreferencesInput,
+    ) -> adw::ActionRow {
+        let row = adw::ActionRow::builder().title(title).build();
+
+        let remove_button = gtk::Button::builder()
+            .valign(gtk::Align::Center)
+            .icon_name("user-trash-symbolic")
+            .tooltip_text(tooltip)
+            .css_classes(["flat"])
+            .build();
+
+        let sender = sender.input_sender().clone();
+        remove_button.connect_clicked(move |_| {
+            sender.emit(remove_msg.clone());
+        });
+
+        row.add_suffix(&remove_button);
+        row
+    }
+
+    /// Ask the user to choose a directory with the file chooser portal.
+    async fn choose_dir(&self) -> Option<FlatpakPathBuf> {
+        let root = gtk::Widget::root(self.parent.widget_ref())?;
+        let identifier = WindowIdentifier::from_native(&root).await;
+        let request = OpenFileRequest::default()
+            .directory(true)
+            .identifier(identifier)
+            .modal(true) // can't be modal without identifier.
+            .multiple(false);
+
+        let files = match request.send().await.and_then(|r| r.response()) {
+            Ok(files) => files,
+            Err(err) => {
+                error!("Failed to open a file: {err}");
+                return None;
+            }
+        };
+
+        info!("Open: {:?}", files);
+        let dir = files.uris().first().and_then(|uri| {
+            glib::Uri::parse(uri.as_str(), glib::UriFlags::NONE)
+                .map(|glib_uri| PathBuf::from(glib_uri.path()))
+                .ok()
+        })?;
+
+        let dir = host_path::host_path(&dir)
+            .await
+            .unwrap_or(FlatpakPathBuf::build(&dir, &dir));
+        Some(dir)
+    }
+
+    /// Library directories can't be nested because every picture and video must
+    /// belong to exactly one library directory.
+    fn is_nested(&self, dir: &FlatpakPathBuf, skip_index: Option<usize>) -> bool {
+        self.settings
+            .library_roots
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| Some(*index) != skip_index)
+            .any(|(_, other)| {
+                dir.host_path.starts_with(&other.host_path)
+                    || other.host_path.starts_with(&dir.host_path)
+            })
+    }
+
+    fn show_nested_toast(&self) {
+        let toast = adw::Toast::new(&fl!("prefs-library-section-other-dirs", "nested-error"));
+        self.dialog.add_toast(toast);
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PreferencesInput {
     /// Show the preferences dialog.
     Present,
@@ -62,7 +278,38 @@ pub enum PreferencesInput {
 
     Sort(AlbumSort),
 
+    UpdateTranscodeCodec(VideoCodec),
+
+    UpdateTranscodeQuality(TranscodeQuality),
+
     ChoosePicturesDir,
+
+    /// Add a library directory in addition to the pictures directory.
+    AddLibraryDir,
+
+    /// Remove library directory at index.
+    RemoveLibraryDir(usize),
+
+    /// Exclude directories and files matching a glob pattern.
+    AddExcludePattern(String),
+
+    /// Remove exclude pattern at index.
+    RemoveExcludePattern(usize),
+
+    /// Exclude a directory chosen by the user.
+    AddExcludeDir,
+
+    /// Remove excluded directory at index.
+    RemoveExcludeDir(usize),
+
+    /// Look for face models in a directory chosen by the user.
+    ChooseModelsDir,
+
+    /// Stop looking for face models in the directory chosen by the user.
+    ClearModelsDir,
+
+    /// Download face models from a mirror. Empty to download from the usual locations.
+    UpdateModelMirror(String),
 }
 
 // Note that some settings update through the shared state, and others through output messages.
@@ -78,7 +325,7 @@ pub enum PreferencesOutput {
 
 #[relm4::component(pub async)]
 impl SimpleAsyncComponent for PreferencesDialog {
-    type Init = (SettingsState, adw::ApplicationWindow);
+    type Init = (SettingsState, adw::ApplicationWindow, ModelRegistry);
     type Input = PreferencesInput;
     type Output = PreferencesOutput;
 
@@ -106,6 +353,49 @@ impl SimpleAsyncComponent for PreferencesDialog {
                     }
                 },
 
+                #[local_ref]
+                library_dirs_group -> adw::PreferencesGroup {
+                    set_title: &fl!("prefs-library-section-other-dirs", "title"),
+                    set_description: Some(&fl!("prefs-library-section-other-dirs", "description")),
+
+                    #[wrap(Some)]
+                    set_header_suffix = &gtk::Button {
+                        set_valign: gtk::Align::Center,
+                        set_icon_name: "list-add-symbolic",
+                        set_tooltip_text: Some(&fl!("prefs-library-section-other-dirs", "add-tooltip")),
+                        add_css_class: "flat",
+                        connect_clicked => PreferencesInput::AddLibraryDir,
+                    }
+                },
+
+                #[local_ref]
+                exclude_group -> adw::PreferencesGroup {
+                    set_title: &fl!("prefs-library-section-exclude", "title"),
+                    set_description: Some(&fl!("prefs-library-section-exclude", "description")),
+
+                    #[wrap(Some)]
+                    set_header_suffix = &gtk::Button {
+                        set_valign: gtk::Align::Center,
+                        set_icon_name: "folder-open-symbolic",
+                        set_tooltip_text: Some(&fl!("prefs-library-section-exclude", "add-dir-tooltip")),
+                        add_css_class: "flat",
+                        connect_clicked => PreferencesInput::AddExcludeDir,
+                    },
+
+                    adw::EntryRow {
+                        set_title: &fl!("prefs-library-section-exclude", "add-pattern"),
+                        set_show_apply_button: true,
+
+                        connect_apply[sender] => move |row| {
+                            let pattern = row.text().trim().to_string();
+                            row.set_text("");
+                            if !pattern.is_empty() {
+                                let _ = sender.input_sender().send(PreferencesInput::AddExcludePattern(pattern));
+                            }
+                        },
+                    },
+                },
+
                 add = &adw::PreferencesGroup {
                     set_title: &fl!("prefs-albums-section"),
                     set_description: Some(&fl!("prefs-albums-section", "description")),
@@ -168,13 +458,78 @@ impl SimpleAsyncComponent for PreferencesDialog {
                         },
                     },
 
+                    #[local_ref]
+                    transcode_codec_row -> adw::ComboRow {
+                        set_title: &fl!("prefs-processing-transcode-codec"),
+                        set_subtitle: &fl!("prefs-processing-transcode-codec", "subtitle"),
+
+                        connect_selected_item_notify[sender] => move |row| {
+                            let codec = VideoCodec::from_repr(row.selected()).unwrap_or_default();
+                            let _ = sender.input_sender().send(PreferencesInput::UpdateTranscodeCodec(codec));
+                        }
+                    },
+
+                    #[local_ref]
+                    transcode_quality_row -> adw::ComboRow {
+                        set_title: &fl!("prefs-processing-transcode-quality"),
+                        set_subtitle: &fl!("prefs-processing-transcode-quality", "subtitle"),
+
+                        connect_selected_item_notify[sender] => move |row| {
+                            let quality = TranscodeQuality::from_repr(row.selected()).unwrap_or_default();
+                            let _ = sender.input_sender().send(PreferencesInput::UpdateTranscodeQuality(quality));
+                        }
+                    },
+
+                },
+
+                #[local_ref]
+                models_group -> adw::PreferencesGroup {
+                    set_title: &fl!("prefs-models-section", "title"),
+                    set_description: Some(&fl!("prefs-models-section", "description")),
+
+                    adw::ActionRow {
+                        set_title: &fl!("prefs-models-section-dir", "title"),
+
+                        #[watch]
+                        set_subtitle: &model.models_dir_host_path(),
+
+                        add_suffix = &gtk::Button {
+                            set_valign: gtk::Align::Center,
+                            set_icon_name: "edit-clear-symbolic",
+                            set_tooltip_text: Some(&fl!("prefs-models-section-dir", "clear-tooltip")),
+                            add_css_class: "flat",
+
+                            #[watch]
+                            set_visible: model.settings.models_dir.is_some(),
+
+                            connect_clicked => PreferencesInput::ClearModelsDir,
+                        },
+
+                        add_suffix = &gtk::Button {
+                            set_valign: gtk::Align::Center,
+                            set_icon_name: "folder-open-symbolic",
+                            set_tooltip_text: Some(&fl!("prefs-models-section-dir", "tooltip")),
+                            connect_clicked => PreferencesInput::ChooseModelsDir,
+                        }
+                    },
+
+                    #[local_ref]
+                    model_mirror_row -> adw::EntryRow {
+                        set_title: &fl!("prefs-models-section-mirror", "title"),
+                        set_show_apply_button: true,
+
+                        connect_apply[sender] => move |row| {
+                            let mirror = row.text().trim().to_string();
+                            let _ = sender.input_sender().send(PreferencesInput::UpdateModelMirror(mirror));
+                        },
+                    },
                 },
             }
         }
     }
 
     async fn init(
-        (settings_state, parent): Self::Init,
+        (settings_state, parent, models): Self::Init,
         dialog: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
@@ -193,12 +548,43 @@ impl SimpleAsyncComponent for PreferencesDialog {
         ]);
         album_sort_row.set_model(Some(&list));
 
+        let transcode_codec_row = adw::ComboRow::new();
+        let list = gtk::StringList::new(&[
+            &fl!("prefs-processing-transcode-codec", "h264"),
+            &fl!("prefs-processing-transcode-codec", "vp9"),
+            &fl!("prefs-processing-transcode-codec", "av1"),
+        ]);
+        transcode_codec_row.set_model(Some(&list));
+
+        let transcode_quality_row = adw::ComboRow::new();
+        let list = gtk::StringList::new(&[
+            &fl!("prefs-processing-transcode-quality", "low"),
+            &fl!("prefs-processing-transcode-quality", "medium"),
+            &fl!("prefs-processing-transcode-quality", "high"),
+        ]);
+        transcode_quality_row.set_model(Some(&list));
+
+        let library_dirs_group = adw::PreferencesGroup::new();
+        let exclude_group = adw::PreferencesGroup::new();
+        let models_group = adw::PreferencesGroup::new();
+        let model_mirror_row = adw::EntryRow::new();
+
         let model = Self {
             settings_state: settings_state.clone(),
             parent,
             dialog: dialog.clone(),
             settings: settings_state.read().clone(),
             album_sort: album_sort_row.clone(),
+            transcode_codec: transcode_codec_row.clone(),
+            transcode_quality: transcode_quality_row.clone(),
+            library_dirs_group: library_dirs_group.clone(),
+            library_dir_rows: Vec::new(),
+            exclude_group: exclude_group.clone(),
+            exclude_rows: Vec::new(),
+            models_group: models_group.clone(),
+            model_rows: Vec::new(),
+            model_mirror: model_mirror_row.clone(),
+            models,
         };
 
         let widgets = view_output!();
@@ -212,6 +598,7 @@ impl SimpleAsyncComponent for PreferencesDialog {
         match msg {
             PreferencesInput::Present => {
                 self.settings = self.settings_state.read().clone();
+                self.refresh_models().await;
                 self.dialog.present(Some(&self.parent));
             }
             PreferencesInput::SettingsChanged(settings) => {
@@ -224,6 +611,16 @@ impl SimpleAsyncComponent for PreferencesDialog {
                 };
 
                 self.album_sort.set_selected(index);
+                self.transcode_codec
+                    .set_selected(self.settings.transcode_profile.codec as u32);
+                self.transcode_quality
+                    .set_selected(self.settings.transcode_profile.quality as u32);
+                self.refresh_library_dirs(&sender);
+                self.refresh_excludes(&sender);
+
+                if self.model_mirror.text() != self.settings.model_mirror {
+                    self.model_mirror.set_text(&self.settings.model_mirror);
+                }
             }
             PreferencesInput::UpdateShowSelfies(show_selfies) => {
                 info!("Update show selfies: {}", show_selfies);
@@ -251,55 +648,142 @@ impl SimpleAsyncComponent for PreferencesDialog {
                 self.settings.album_sort = mode;
                 *self.settings_state.write() = self.settings.clone();
             }
+            PreferencesInput::UpdateTranscodeCodec(codec) => {
+                info!("Update transcode codec: {:?}", codec);
+                self.settings.transcode_profile.codec = codec;
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::UpdateTranscodeQuality(quality) => {
+                info!("Update transcode quality: {:?}", quality);
+                self.settings.transcode_profile.quality = quality;
+                *self.settings_state.write() = self.settings.clone();
+            }
             PreferencesInput::ChoosePicturesDir => {
                 info!("Presenting select pictures directory file chooser");
-                if let Some(root) = gtk::Widget::root(self.parent.widget_ref()) {
-                    let identifier = WindowIdentifier::from_native(&root).await;
-                    let request = OpenFileRequest::default()
-                        .directory(true)
-                        .identifier(identifier)
-                        .modal(true) // can't be modal without identifier.
-                        .multiple(false);
-
-                    match request.send().await.and_then(|r| r.response()) {
-                        Ok(files) => {
-                            info!("Open: {:?}", files);
-                            if let Some(first) = files.uris().first() {
-                                let some_library_base_dir = files.uris().first().and_then(|uri| {
-                                    glib::Uri::parse(uri.as_str(), glib::UriFlags::NONE)
-                                        .map(|glib_uri| PathBuf::from(glib_uri.path()))
-                                        .ok()
-                                });
-
-                                if let Some(library_base_dir) = some_library_base_dir {
-                                    info!(
-                                        "User has chosen picture library at: {:?}",
-                                        library_base_dir
-                                    );
-                                    if self.settings.library_base_dir.sandbox_path
-                                        != library_base_dir
-                                    {
-                                        info!(
-                                            "New pictures base director is: {:?}",
-                                            library_base_dir
-                                        );
-                                        self.settings.library_base_dir =
-                                            host_path::host_path(&library_base_dir)
-                                                .await
-                                                .unwrap_or(FlatpakPathBuf::build(
-                                                    &library_base_dir,
-                                                    &library_base_dir,
-                                                ));
-                                        *self.settings_state.write() = self.settings.clone();
-                                    }
-                                }
-                            }
-                        }
-                        Err(err) => {
-                            error!("Failed to open a file: {err}");
-                        }
-                    }
+                let Some(library_base_dir) = self.choose_dir().await else {
+                    return;
+                };
+
+                info!("User has chosen picture library at: {:?}", library_base_dir);
+                if self.settings.library_roots.first() == Some(&library_base_dir) {
+                    return;
+                }
+
+                if self.is_nested(&library_base_dir, Some(0)) {
+                    self.show_nested_toast();
+                    return;
+                }
+
+                info!("New pictures base director is: {:?}", library_base_dir);
+                if self.settings.library_roots.is_empty() {
+                    self.settings.library_roots.push(library_base_dir);
+                } else {
+                    self.settings.library_roots[0] = library_base_dir;
+                }
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::AddLibraryDir => {
+                info!("Presenting add library directory file chooser");
+                let Some(library_dir) = self.choose_dir().await else {
+                    return;
+                };
+
+                if self.is_nested(&library_dir, None) {
+                    self.show_nested_toast();
+                    return;
+                }
+
+                info!("Adding library directory: {:?}", library_dir);
+                self.settings.library_roots.push(library_dir);
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::RemoveLibraryDir(index) => {
+                // The pictures directory can be changed but not removed.
+                if index == 0 || index >= self.settings.library_roots.len() {
+                    return;
+                }
+
+                let library_dir = self.settings.library_roots.remove(index);
+                info!("Removing library directory: {:?}", library_dir);
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::AddExcludePattern(pattern) => {
+                if self.settings.exclude_patterns.contains(&pattern) {
+                    return;
+                }
+
+                if !ExcludeRules::is_valid_pattern(&pattern) {
+                    info!("Invalid exclude pattern {:?}", pattern);
+                    let toast =
+                        adw::Toast::new(&fl!("prefs-library-section-exclude", "invalid-pattern"));
+                    self.dialog.add_toast(toast);
+                    return;
+                }
+
+                info!("Adding exclude pattern: {:?}", pattern);
+                self.settings.exclude_patterns.push(pattern);
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::RemoveExcludePattern(index) => {
+                if index >= self.settings.exclude_patterns.len() {
+                    return;
+                }
+
+                let pattern = self.settings.exclude_patterns.remove(index);
+                info!("Removing exclude pattern: {:?}", pattern);
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::AddExcludeDir => {
+                info!("Presenting exclude directory file chooser");
+                let Some(exclude_dir) = self.choose_dir().await else {
+                    return;
+                };
+
+                if self.settings.exclude_dirs.contains(&exclude_dir) {
+                    return;
+                }
+
+                info!("Adding exclude directory: {:?}", exclude_dir);
+                self.settings.exclude_dirs.push(exclude_dir);
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::RemoveExcludeDir(index) => {
+                if index >= self.settings.exclude_dirs.len() {
+                    return;
+                }
+
+                let exclude_dir = self.settings.exclude_dirs.remove(index);
+                info!("Removing exclude directory: {:?}", exclude_dir);
+                *self.settings_state.write() = self.settings.clone();
+            }
+            PreferencesInput::ChooseModelsDir => {
+                info!("Presenting models directory file chooser");
+                let Some(models_dir) = self.choose_dir().await else {
+                    return;
+                };
+
+                info!("Using models directory: {:?}", models_dir);
+                self.settings.models_dir = Some(models_dir);
+                *self.settings_state.write() = self.settings.clone();
+                self.refresh_models().await;
+            }
+            PreferencesInput::ClearModelsDir => {
+                info!("Clearing models directory");
+                self.settings.models_dir = None;
+                *self.settings_state.write() = self.settings.clone();
+                self.refresh_models().await;
+            }
+            PreferencesInput::UpdateModelMirror(mirror) => {
+                if !mirror.is_empty() && glib::Uri::parse(&mirror, glib::UriFlags::NONE).is_err() {
+                    info!("Invalid model mirror {:?}", mirror);
+                    let toast = adw::Toast::new(&fl!("prefs-models-section-mirror", "invalid"));
+                    self.dialog.add_toast(toast);
+                    return;
                 }
+
+                info!("Update model mirror: {:?}", mirror);
+                self.settings.model_mirror = mirror;
+                *self.settings_state.write() = self.settings.clone();
             }
         }
     }
@@ -28,6 +28,8 @@ pub enum TaskName {
     MotionPhoto,
     DetectFaces,
     RecognizeFaces,
+    ClusterFaces,
+    FindDuplicates,
 
     /// FIXME figure out if 'Idle' will be used.
     Idle,
@@ -37,6 +39,9 @@ pub enum TaskName {
 pub enum ProgressMonitorInput {
     Start(TaskName, usize),
     Advance,
+
+    /// Advance by several steps, for tasks that report progress within an item.
+    AdvanceBy(usize),
     Complete,
 }
 
@@ -89,6 +94,9 @@ impl Reducible for ProgressMonitor {
                     self.current_count += 1;
                 }
             }
+            ProgressMonitorInput::AdvanceBy(count) => {
+                self.current_count = (self.current_count + count).min(self.end_count);
+            }
             ProgressMonitorInput::Complete => {
                 self.current_count = self.end_count;
             }
@@ -102,6 +102,14 @@ impl SimpleComponent for ProgressPanel {
                             self.progress_bar
                                 .set_text(Some(&fl!("progress-recognize-faces-photos")));
                         }
+                        TaskName::ClusterFaces => {
+                            self.progress_bar
+                                .set_text(Some(&fl!("progress-cluster-faces-photos")));
+                        }
+                        TaskName::FindDuplicates => {
+                            self.progress_bar
+                                .set_text(Some(&fl!("progress-find-duplicates")));
+                        }
                         TaskName::Idle => {
                             self.progress_bar.set_text(Some(&fl!("progress-idle")));
                         }
@@ -0,0 +1,43 @@
+// SPDX-FileCopyrightText: © 2024 David Bliss
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use fotema_core::thumbnailify::Thumbnailer;
+use fotema_core::video::SpriteSheet;
+use relm4::gtk::gdk;
+use relm4::gtk::gdk_pixbuf;
+
+/// Sprite sheet of a video, loaded for previewing frames while scrubbing over the video.
+#[derive(Debug)]
+pub struct SpritePreview {
+    pixbuf: gdk_pixbuf::Pixbuf,
+    sheet: SpriteSheet,
+}
+
+impl SpritePreview {
+    /// Loads the sprite sheet of a video from its thumbnail hash.
+    /// Returns None if a sprite sheet hasn't been generated yet.
+    /// Decoding the sprite sheet is slow, so avoid calling this on the main thread.
+    pub fn load(thumbnailer: &Thumbnailer, hash: &str) -> Option<SpritePreview> {
+        let sheet = SpriteSheet::load(&thumbnailer.get_sprite_sheet_index_path(hash)).ok()?;
+        let pixbuf = gdk_pixbuf::Pixbuf::from_file(thumbnailer.get_sprite_sheet_path(hash)).ok()?;
+        Some(SpritePreview { pixbuf, sheet })
+    }
+
+    pub fn sheet(&self) -> &SpriteSheet {
+        &self.sheet
+    }
+
+    /// Frame of the sprite sheet as a texture.
+    pub fn frame(&self, index: usize) -> Option<gdk::Texture> {
+        let (x, y, width, height) = self.sheet.frame_rect(index);
+        let (x, y, width, height) = (x as i32, y as i32, width as i32, height as i32);
+
+        if x + width > self.pixbuf.width() || y + height > self.pixbuf.height() {
+            return None;
+        }
+
+        let frame = self.pixbuf.new_subpixbuf(x, y, width, height);
+        Some(gdk::Texture::for_pixbuf(&frame))
+    }
+}
@@ -243,7 +243,10 @@ pub enum FaceThumbnailsInput {
 }
 
 #[derive(Debug)]
-pub enum FaceThumbnailsOutput {}
+pub enum FaceThumbnailsOutput {
+    /// A face has been confirmed as a person, which may help recognize other faces.
+    PersonConfirmed,
+}
 
 pub struct FaceThumbnails {
     people_repo: people::Repository,
@@ -366,6 +369,7 @@ impl SimpleAsyncComponent for FaceThumbnails {
                     error!("Failed setting thumbnail: {}", e);
                 }
                 sender.input(FaceThumbnailsInput::Refresh);
+                let _ = sender.output(FaceThumbnailsOutput::PersonConfirmed);
             }
             FaceThumbnailsInput::NotPerson(face_id) => {
                 debug!("Set not person for face: {}", face_id);
@@ -385,6 +389,7 @@ impl SimpleAsyncComponent for FaceThumbnails {
                 debug!("Dismissing dialog.");
                 self.person_dialog.close();
                 sender.input(FaceThumbnailsInput::Refresh);
+                let _ = sender.output(FaceThumbnailsOutput::PersonConfirmed);
             }
         }
     }
@@ -2,12 +2,13 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use super::face_thumbnails::{FaceThumbnails, FaceThumbnailsInput};
+use super::face_thumbnails::{FaceThumbnails, FaceThumbnailsInput, FaceThumbnailsOutput};
 use fotema_core::FlatpakPathBuf;
 /// Properties view for a photo.
 ///Inspired by how Loupe displays its property view.
 use fotema_core::VisualId;
 use fotema_core::people;
+use fotema_core::photo::model::WhiteBalance;
 
 use gtk::prelude::OrientableExt;
 
@@ -43,6 +44,12 @@ pub enum ViewInfoInput {
     RefreshFaces,
 }
 
+#[derive(Debug)]
+pub enum ViewInfoOutput {
+    /// A face has been confirmed as a person.
+    PersonConfirmed,
+}
+
 pub struct ViewInfo {
     state: SharedState,
 
@@ -64,6 +71,15 @@ pub struct ViewInfo {
     exif_details: adw::PreferencesGroup,
     exif_originally_created_at: adw::ActionRow,
     exif_originally_modified_at: adw::ActionRow,
+    exif_camera: adw::ActionRow,
+    exif_lens: adw::ActionRow,
+    exif_focal_length: adw::ActionRow,
+    exif_aperture: adw::ActionRow,
+    exif_exposure_time: adw::ActionRow,
+    exif_iso: adw::ActionRow,
+    exif_flash: adw::ActionRow,
+    exif_white_balance: adw::ActionRow,
+    exif_dimensions: adw::ActionRow,
 
     video_details: adw::PreferencesGroup,
     video_dimensions: adw::ActionRow,
@@ -73,6 +89,7 @@ pub struct ViewInfo {
     video_file_size: adw::ActionRow,
     video_originally_created_at: adw::ActionRow,
     video_duration: adw::ActionRow,
+    video_transcode_error: adw::ActionRow,
 
     faces_row: adw::ActionRow,
     face_thumbnails: AsyncController<FaceThumbnails>,
@@ -82,7 +99,7 @@ pub struct ViewInfo {
 impl SimpleComponent for ViewInfo {
     type Init = (SharedState, people::Repository);
     type Input = ViewInfoInput;
-    type Output = ();
+    type Output = ViewInfoOutput;
 
     view! {
         gtk::ScrolledWindow {
@@ -217,6 +234,96 @@ impl SimpleComponent for ViewInfo {
                             set_icon_name: Some("today-symbolic"),
                         }
                     },
+
+                    #[local_ref]
+                    exif_camera -> adw::ActionRow {
+                        set_title: &fl!("infobar-camera"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("camera-photo-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_lens -> adw::ActionRow {
+                        set_title: &fl!("infobar-lens"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("camera-photo-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_focal_length -> adw::ActionRow {
+                        set_title: &fl!("infobar-focal-length"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("ruler-corner-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_aperture -> adw::ActionRow {
+                        set_title: &fl!("infobar-aperture"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("sonar-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_exposure_time -> adw::ActionRow {
+                        set_title: &fl!("infobar-exposure-time"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("stopwatch-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_iso -> adw::ActionRow {
+                        set_title: &fl!("infobar-iso"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("scales-detail-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_flash -> adw::ActionRow {
+                        set_title: &fl!("infobar-flash"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("camera-flash-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_white_balance -> adw::ActionRow {
+                        set_title: &fl!("infobar-white-balance"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("weather-clear-symbolic"),
+                        }
+                    },
+
+                    #[local_ref]
+                    exif_dimensions -> adw::ActionRow {
+                        set_title: &fl!("infobar-camera-dimensions"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("ruler-corner-symbolic"),
+                        }
+                    },
                 },
 
 
@@ -291,6 +398,16 @@ impl SimpleComponent for ViewInfo {
                             set_icon_name: Some("sound-wave-symbolic"),
                         }
                     },
+
+                    #[local_ref]
+                    video_transcode_error -> adw::ActionRow {
+                        set_title: &fl!("infobar-video-transcode-error"),
+                        add_css_class: "property",
+                        set_subtitle_selectable: true,
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some("playback-error-symbolic"),
+                        }
+                    },
                 },
             }
         }
@@ -299,7 +416,7 @@ impl SimpleComponent for ViewInfo {
     fn init(
         (state, people_repo): Self::Init,
         _root: Self::Root,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let folder = adw::ActionRow::new();
         let file_name = adw::ActionRow::new();
@@ -316,6 +433,15 @@ impl SimpleComponent for ViewInfo {
         let exif_details = adw::PreferencesGroup::new();
         let exif_originally_created_at = adw::ActionRow::new();
         let exif_originally_modified_at = adw::ActionRow::new();
+        let exif_camera = adw::ActionRow::new();
+        let exif_lens = adw::ActionRow::new();
+        let exif_focal_length = adw::ActionRow::new();
+        let exif_aperture = adw::ActionRow::new();
+        let exif_exposure_time = adw::ActionRow::new();
+        let exif_iso = adw::ActionRow::new();
+        let exif_flash = adw::ActionRow::new();
+        let exif_white_balance = adw::ActionRow::new();
+        let exif_dimensions = adw::ActionRow::new();
 
         let video_details = adw::PreferencesGroup::new();
         let video_duration = adw::ActionRow::new();
@@ -325,9 +451,15 @@ impl SimpleComponent for ViewInfo {
         let video_audio_codec = adw::ActionRow::new();
         let video_file_size = adw::ActionRow::new();
         let video_originally_created_at = adw::ActionRow::new();
+        let video_transcode_error = adw::ActionRow::new();
 
         let faces_row = adw::ActionRow::new();
-        let face_thumbnails = FaceThumbnails::builder().launch(people_repo).detach();
+        let face_thumbnails =
+            FaceThumbnails::builder()
+                .launch(people_repo)
+                .forward(sender.output_sender(), |msg| match msg {
+                    FaceThumbnailsOutput::PersonConfirmed => ViewInfoOutput::PersonConfirmed,
+                });
 
         let model = ViewInfo {
             state,
@@ -348,6 +480,15 @@ impl SimpleComponent for ViewInfo {
             exif_details: exif_details.clone(),
             exif_originally_created_at: exif_originally_created_at.clone(),
             exif_originally_modified_at: exif_originally_modified_at.clone(),
+            exif_camera: exif_camera.clone(),
+            exif_lens: exif_lens.clone(),
+            exif_focal_length: exif_focal_length.clone(),
+            exif_aperture: exif_aperture.clone(),
+            exif_exposure_time: exif_exposure_time.clone(),
+            exif_iso: exif_iso.clone(),
+            exif_flash: exif_flash.clone(),
+            exif_white_balance: exif_white_balance.clone(),
+            exif_dimensions: exif_dimensions.clone(),
 
             video_details: video_details.clone(),
             video_file_size: video_file_size.clone(),
@@ -357,6 +498,7 @@ impl SimpleComponent for ViewInfo {
             video_codec: video_codec.clone(),
             video_audio_codec: video_audio_codec.clone(),
             video_dimensions: video_dimensions.clone(),
+            video_transcode_error: video_transcode_error.clone(),
 
             faces_row: faces_row.clone(),
             face_thumbnails,
@@ -546,18 +688,51 @@ impl ViewInfo {
                 .and_then(|x| x.fs_modified_at)
                 .map(|x| x.format("%Y-%m-%d %H:%M:%S %:z").to_string());
 
-            let has_exif_details = [
+            let has_exif_dates = [
                 Self::update_row(&self.exif_originally_created_at, fs_created_at),
                 Self::update_row(&self.exif_originally_modified_at, fs_modified_at),
             ]
             .into_iter()
             .any(|x| x);
 
-            self.exif_details.set_visible(has_exif_details);
+            self.exif_details.set_visible(has_exif_dates);
         } else {
             self.exif_details.set_visible(false);
         }
 
+        let exif = vis.exif.clone().unwrap_or_default();
+
+        let flash = exif.is_flash_fired.map(|is_fired| {
+            if is_fired {
+                fl!("infobar-flash", "fired")
+            } else {
+                fl!("infobar-flash", "not-fired")
+            }
+        });
+
+        let white_balance = exif.white_balance.map(|white_balance| match white_balance {
+            WhiteBalance::Auto => fl!("infobar-white-balance", "auto"),
+            WhiteBalance::Manual => fl!("infobar-white-balance", "manual"),
+        });
+
+        let has_camera_details = [
+            Self::update_row(&self.exif_camera, exif.camera()),
+            Self::update_row(&self.exif_lens, exif.lens_model.clone()),
+            Self::update_row(&self.exif_focal_length, exif.focal_length_label()),
+            Self::update_row(&self.exif_aperture, exif.aperture_label()),
+            Self::update_row(&self.exif_exposure_time, exif.exposure_time_label()),
+            Self::update_row(&self.exif_iso, exif.iso.map(|x| x.to_string())),
+            Self::update_row(&self.exif_flash, flash),
+            Self::update_row(&self.exif_white_balance, white_balance),
+            Self::update_row(&self.exif_dimensions, exif.dimensions_label()),
+        ]
+        .into_iter()
+        .any(|x| x);
+
+        if has_camera_details {
+            self.exif_details.set_visible(true);
+        }
+
         Ok(())
     }
 
@@ -603,6 +778,10 @@ impl ViewInfo {
             Self::update_row(&self.video_codec, metadata.video_codec),
             Self::update_row(&self.video_audio_codec, metadata.audio_codec),
             Self::update_row(&self.video_file_size, fs_file_size_bytes),
+            Self::update_row(
+                &self.video_transcode_error,
+                vis.video_transcode_error.clone(),
+            ),
         ]
         .into_iter()
         .any(|x| x);
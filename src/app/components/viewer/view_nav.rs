@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use relm4::actions::{RelmAction, RelmActionGroup};
+use relm4::adw::prelude::*;
 use relm4::binding::*;
 use relm4::gtk;
 use relm4::gtk::gdk;
@@ -11,7 +12,7 @@ use relm4::gtk::prelude::*;
 use relm4::prelude::*;
 use relm4::*;
 
-use super::view_info::{ViewInfo, ViewInfoInput};
+use super::view_info::{ViewInfo, ViewInfoInput, ViewInfoOutput};
 use super::view_one::{ViewOne, ViewOneInput, ViewOneOutput};
 use crate::app::components::albums::album_filter::AlbumFilter;
 use crate::app::components::albums::album_sort::AlbumSort;
@@ -21,17 +22,27 @@ use crate::app::SharedState;
 use crate::app::components::progress_monitor::ProgressMonitor;
 use crate::fl;
 
+use fotema_core::PictureId;
+use fotema_core::VideoId;
 use fotema_core::Visual;
 use fotema_core::VisualId;
 use fotema_core::people;
+use fotema_core::thumbnailify::Thumbnailer;
+use std::rc::Rc;
 use std::sync::Arc;
 
+use chrono::{NaiveDateTime, TimeDelta};
+use strum::{EnumIter, FromRepr, IntoEnumIterator};
+
 use tracing::{debug, error, info};
 
 // FIXME does the faces menu definition and action handling belong here?
 // Maybe it belongs in view_one.rs or in face_thumbnails.rs?
 relm4::new_action_group!(ViewNavActionGroup, "viewnav");
 
+/// Format of date and time in the adjust date and time dialog.
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
 // Restore all ignored faces.
 relm4::new_stateless_action!(
     RestoreIgnoredFacesAction,
@@ -46,6 +57,9 @@ relm4::new_stateless_action!(
     "ignore_unknown_faces"
 );
 
+// Correct the date and time of items taken with a mis-set camera clock.
+relm4::new_stateless_action!(AdjustTimeAction, ViewNavActionGroup, "adjust_time");
+
 #[derive(Debug)]
 pub enum ViewNavInput {
     /// View an item after applying an album filter.
@@ -72,6 +86,9 @@ pub enum ViewNavInput {
     /// Transcode all incompatible videos
     TranscodeAll,
 
+    /// Transcode a video that previously failed to transcode
+    RetryTranscode(VisualId),
+
     /// Go to the previous photo
     GoLeft,
 
@@ -90,13 +107,49 @@ pub enum ViewNavInput {
     /// Ignore all unknown faces for item
     IgnoreUnknownFaces,
 
+    /// Start adjust date and time flow.
+    AdjustTimeDialog,
+
+    /// Actually adjust date and time so the current item was taken at the given
+    /// local time, shifting the other items in scope by the same amount.
+    AdjustTime(NaiveDateTime, TimeShiftScope),
+
+    /// Undo date and time adjustments, restoring the capture time from the metadata.
+    RestoreTime(TimeShiftScope),
+
     // Sort
     Sort(AlbumSort),
 }
 
+/// Items that a date and time adjustment applies to, relative to the item being viewed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter, FromRepr)]
+#[repr(u32)]
+pub enum TimeShiftScope {
+    /// Only the item being viewed.
+    #[default]
+    Item,
+
+    /// Items in the same folder taken with the same camera.
+    Camera,
+
+    /// All items in the same folder.
+    Folder,
+}
+
 #[derive(Debug)]
 pub enum ViewNavOutput {
     TranscodeAll,
+
+    RetryTranscode(VisualId),
+
+    /// Shift capture time of pictures and videos.
+    ShiftTime(Vec<PictureId>, Vec<VideoId>, TimeDelta),
+
+    /// Remove capture time shifts of pictures and videos.
+    RemoveTimeShift(Vec<PictureId>, Vec<VideoId>),
+
+    /// A face has been confirmed as a person, so recognize faces again.
+    RecognizeFaces,
 }
 
 pub struct ViewNav {
@@ -160,6 +213,7 @@ impl SimpleAsyncComponent for ViewNav {
         Arc<Reducer<ProgressMonitor>>,
         Arc<adaptive::LayoutState>,
         people::Repository,
+        Rc<Thumbnailer>,
     );
     type Input = ViewNavInput;
     type Output = ViewNavOutput;
@@ -170,6 +224,11 @@ impl SimpleAsyncComponent for ViewNav {
                 &fl!("viewer-faces-menu", "restore-ignored") => RestoreIgnoredFacesAction,
                 &fl!("viewer-faces-menu", "ignore-unknown") => IgnoreUnknownFacesAction,
             }
+        },
+        viewer_menu: {
+            section! {
+                &fl!("viewer-menu", "adjust-time") => AdjustTimeAction,
+            }
         }
     }
 
@@ -184,6 +243,12 @@ impl SimpleAsyncComponent for ViewNav {
                         set_menu_model: Some(&viewnav_menu),
                     },
 
+                    gtk::MenuButton {
+                        set_icon_name: "view-more-symbolic",
+                        set_tooltip_text: Some(&fl!("viewer-menu", "tooltip")),
+                        set_menu_model: Some(&viewer_menu),
+                    },
+
                     gtk::Button {
                         set_icon_name: "info-outline-symbolic",
                         set_tooltip_text: Some(&fl!("viewer-info-tooltip")),
@@ -219,7 +284,7 @@ impl SimpleAsyncComponent for ViewNav {
     }
 
     async fn init(
-        (state, transcode_progress_monitor, layout_state, people_repo): Self::Init,
+        (state, transcode_progress_monitor, layout_state, people_repo, thumbnailer): Self::Init,
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
@@ -332,9 +397,10 @@ impl SimpleAsyncComponent for ViewNav {
 
         carousel_pages.push(
             ViewOne::builder()
-                .launch(transcode_progress_monitor.clone())
+                .launch((transcode_progress_monitor.clone(), thumbnailer.clone()))
                 .forward(sender.input_sender(), |msg| match msg {
                     ViewOneOutput::TranscodeAll => ViewNavInput::TranscodeAll,
+                    ViewOneOutput::RetryTranscode(id) => ViewNavInput::RetryTranscode(id),
                     ViewOneOutput::PhotoShown(id, info) => ViewNavInput::ShowPhotoInfo(id, info),
                     ViewOneOutput::VideoShown(id) => ViewNavInput::ShowVideoInfo(id),
                     ViewOneOutput::ErrorShown(id) => ViewNavInput::ShowError(id),
@@ -344,9 +410,10 @@ impl SimpleAsyncComponent for ViewNav {
 
         carousel_pages.push(
             ViewOne::builder()
-                .launch(transcode_progress_monitor.clone())
+                .launch((transcode_progress_monitor.clone(), thumbnailer.clone()))
                 .forward(sender.input_sender(), |msg| match msg {
                     ViewOneOutput::TranscodeAll => ViewNavInput::TranscodeAll,
+                    ViewOneOutput::RetryTranscode(id) => ViewNavInput::RetryTranscode(id),
                     ViewOneOutput::PhotoShown(id, info) => ViewNavInput::ShowPhotoInfo(id, info),
                     ViewOneOutput::VideoShown(id) => ViewNavInput::ShowVideoInfo(id),
                     ViewOneOutput::ErrorShown(id) => ViewNavInput::ShowError(id),
@@ -356,9 +423,10 @@ impl SimpleAsyncComponent for ViewNav {
 
         carousel_pages.push(
             ViewOne::builder()
-                .launch(transcode_progress_monitor.clone())
+                .launch((transcode_progress_monitor.clone(), thumbnailer.clone()))
                 .forward(sender.input_sender(), |msg| match msg {
                     ViewOneOutput::TranscodeAll => ViewNavInput::TranscodeAll,
+                    ViewOneOutput::RetryTranscode(id) => ViewNavInput::RetryTranscode(id),
                     ViewOneOutput::PhotoShown(id, info) => ViewNavInput::ShowPhotoInfo(id, info),
                     ViewOneOutput::VideoShown(id) => ViewNavInput::ShowVideoInfo(id),
                     ViewOneOutput::ErrorShown(id) => ViewNavInput::ShowError(id),
@@ -368,7 +436,9 @@ impl SimpleAsyncComponent for ViewNav {
 
         let view_info = ViewInfo::builder()
             .launch((state.clone(), people_repo.clone()))
-            .detach();
+            .forward(sender.output_sender(), |msg| match msg {
+                ViewInfoOutput::PersonConfirmed => ViewNavOutput::RecognizeFaces,
+            });
 
         layout_state.subscribe(sender.input_sender(), |layout| ViewNavInput::Adapt(*layout));
 
@@ -405,9 +475,17 @@ impl SimpleAsyncComponent for ViewNav {
             })
         };
 
+        let adjust_time_action = {
+            let sender = sender.clone();
+            RelmAction::<AdjustTimeAction>::new_stateless(move |_| {
+                sender.input(ViewNavInput::AdjustTimeDialog);
+            })
+        };
+
         let mut actions = RelmActionGroup::<ViewNavActionGroup>::new();
         actions.add_action(restore_action);
         actions.add_action(ignore_unknown_faces_action);
+        actions.add_action(adjust_time_action);
         actions.register_for_widget(&root);
 
         let keys = gtk::EventControllerKey::new();
@@ -683,6 +761,10 @@ impl SimpleAsyncComponent for ViewNav {
                 // ViewOne should send straight to transcoder.
                 let _ = sender.output(ViewNavOutput::TranscodeAll);
             }
+            ViewNavInput::RetryTranscode(visual_id) => {
+                info!("Retry transcode: {}", visual_id);
+                let _ = sender.output(ViewNavOutput::RetryTranscode(visual_id));
+            }
             ViewNavInput::GoLeft => {
                 if self.album_index.is_some_and(|index| index > 0) {
                     self.carousel.scroll_to(&self.carousel.nth_page(0), false);
@@ -750,6 +832,147 @@ impl SimpleAsyncComponent for ViewNav {
 
                 self.view_info.emit(ViewInfoInput::RefreshFaces);
             }
+            ViewNavInput::AdjustTimeDialog => {
+                let Some(visual) = self.album_index.and_then(|index| self.album.get(index)) else {
+                    return;
+                };
+
+                info!("Starting adjust time flow for {}", visual.visual_id);
+
+                let date_time = adw::EntryRow::builder()
+                    .title(fl!("viewer-adjust-time-dialog", "date-time"))
+                    .text(visual.local_ts().format(DATE_TIME_FORMAT).to_string())
+                    .build();
+
+                // Show how many items each scope covers, so it is clear what will change
+                // before any adjustment is applied.
+                let scope_labels: Vec<String> = TimeShiftScope::iter()
+                    .map(|scope| {
+                        let count = self.time_shift_batch(visual, scope).len();
+                        match scope {
+                            TimeShiftScope::Item => fl!("viewer-adjust-time-dialog", "scope-item"),
+                            TimeShiftScope::Camera => {
+                                fl!("viewer-adjust-time-dialog", "scope-camera", count = count)
+                            }
+                            TimeShiftScope::Folder => {
+                                fl!("viewer-adjust-time-dialog", "scope-folder", count = count)
+                            }
+                        }
+                    })
+                    .collect();
+                let scope_labels: Vec<&str> = scope_labels.iter().map(String::as_str).collect();
+
+                let scope = adw::ComboRow::builder()
+                    .title(fl!("viewer-adjust-time-dialog", "scope"))
+                    .model(&gtk::StringList::new(&scope_labels))
+                    .selected(TimeShiftScope::Item as u32)
+                    .build();
+
+                let rows = gtk::ListBox::builder()
+                    .selection_mode(gtk::SelectionMode::None)
+                    .css_classes(["boxed-list"])
+                    .build();
+                rows.append(&date_time);
+                rows.append(&scope);
+
+                let dialog = adw::AlertDialog::builder()
+                    .heading(fl!("viewer-adjust-time-dialog", "heading"))
+                    .body(fl!("viewer-adjust-time-dialog", "body"))
+                    .extra_child(&rows)
+                    .build();
+
+                dialog.add_response("cancel", &fl!("viewer-adjust-time-dialog", "cancel-button"));
+                dialog.set_default_response(Some("cancel"));
+                dialog.set_close_response("cancel");
+
+                dialog.add_response(
+                    "restore",
+                    &fl!("viewer-adjust-time-dialog", "restore-button"),
+                );
+                dialog.set_response_appearance("restore", adw::ResponseAppearance::Destructive);
+
+                dialog.add_response("adjust", &fl!("viewer-adjust-time-dialog", "adjust-button"));
+                dialog.set_response_appearance("adjust", adw::ResponseAppearance::Suggested);
+
+                {
+                    let dialog = dialog.clone();
+                    date_time.connect_changed(move |entry| {
+                        let is_valid = parse_date_time(&entry.text()).is_some();
+                        dialog.set_response_enabled("adjust", is_valid);
+                    });
+                }
+
+                {
+                    let date_time = date_time.clone();
+                    let scope = scope.clone();
+                    dialog.connect_response(None, move |_, response| {
+                        let scope = TimeShiftScope::from_repr(scope.selected()).unwrap_or_default();
+                        if response == "restore" {
+                            sender.input(ViewNavInput::RestoreTime(scope));
+                        } else if response == "adjust"
+                            && let Some(local) = parse_date_time(&date_time.text())
+                        {
+                            sender.input(ViewNavInput::AdjustTime(local, scope));
+                        }
+                    });
+                }
+
+                if let Some(root) = gtk::Widget::root(self.carousel.widget_ref()) {
+                    dialog.present(Some(&root));
+                } else {
+                    error!("Couldn't get root widget!");
+                }
+            }
+            ViewNavInput::AdjustTime(local, scope) => {
+                let Some(visual) = self.album_index.and_then(|index| self.album.get(index)) else {
+                    return;
+                };
+
+                // The reference item was taken at the given local time, so every
+                // item taken with the same camera is off by the same amount.
+                let shift = local - visual.local_ts();
+                if shift.is_zero() {
+                    return;
+                }
+
+                let visuals = self.time_shift_batch(visual, scope);
+
+                info!(
+                    "Shifting time of {} items in {:?} by {}",
+                    visuals.len(),
+                    visual.parent_path,
+                    shift
+                );
+
+                let picture_ids = visuals.iter().filter_map(|v| v.picture_id).collect();
+                let video_ids = visuals.iter().filter_map(|v| v.video_id).collect();
+
+                // Items will be re-ordered, so re-filter the album next time an item is viewed.
+                self.album_filter = AlbumFilter::None;
+
+                let _ = sender.output(ViewNavOutput::ShiftTime(picture_ids, video_ids, shift));
+            }
+            ViewNavInput::RestoreTime(scope) => {
+                let Some(visual) = self.album_index.and_then(|index| self.album.get(index)) else {
+                    return;
+                };
+
+                let visuals = self.time_shift_batch(visual, scope);
+
+                info!(
+                    "Restoring time of {} items in {:?}",
+                    visuals.len(),
+                    visual.parent_path
+                );
+
+                let picture_ids = visuals.iter().filter_map(|v| v.picture_id).collect();
+                let video_ids = visuals.iter().filter_map(|v| v.video_id).collect();
+
+                // Items will be re-ordered, so re-filter the album next time an item is viewed.
+                self.album_filter = AlbumFilter::None;
+
+                let _ = sender.output(ViewNavOutput::RemoveTimeShift(picture_ids, video_ids));
+            }
             ViewNavInput::Sort(album_sort) => {
                 self.album_sort = album_sort;
                 self.album_filter = AlbumFilter::None;
@@ -769,6 +992,14 @@ impl SimpleAsyncComponent for ViewNav {
     }
 }
 
+/// Parses a date and time as entered in the adjust date and time dialog.
+fn parse_date_time(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    NaiveDateTime::parse_from_str(text, DATE_TIME_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M"))
+        .ok()
+}
+
 impl ViewNav {
     fn is_left_button_sensitive(&self) -> bool {
         self.album_index.is_some_and(|index| index > 0)
@@ -780,4 +1011,28 @@ impl ViewNav {
                 .album_index
                 .is_some_and(|index| index != self.album.len() - 1)
     }
+
+    /// Items to shift along with the given item. A mis-set clock usually belongs to one
+    /// camera, so a folder of items from several cameras can be limited to items from
+    /// the same camera. Items with no recorded camera, such as most videos, only match
+    /// other items with no recorded camera.
+    fn time_shift_batch(&self, visual: &Arc<Visual>, scope: TimeShiftScope) -> Vec<Arc<Visual>> {
+        let camera = visual.exif.as_ref().and_then(|exif| exif.camera());
+
+        let in_scope = |v: &Visual| match scope {
+            TimeShiftScope::Item => v.visual_id == visual.visual_id,
+            TimeShiftScope::Camera => {
+                v.parent_path == visual.parent_path
+                    && v.exif.as_ref().and_then(|exif| exif.camera()) == camera
+            }
+            TimeShiftScope::Folder => v.parent_path == visual.parent_path,
+        };
+
+        self.state
+            .read()
+            .iter()
+            .filter(|v| in_scope(v))
+            .cloned()
+            .collect()
+    }
 }
@@ -6,21 +6,33 @@ use chrono::TimeDelta;
 use fotema_core::FlatpakPathBuf;
 use fotema_core::Visual;
 use fotema_core::VisualId;
+use fotema_core::photo::motion_photo;
+use fotema_core::photo::raw;
+use fotema_core::thumbnailify::Thumbnailer;
+use fotema_core::visual::export::{self, ExportFormat, ExportOptions};
 use fotema_core::visual::model::PictureOrientation;
+use fotema_core::visual::still::{self, StillFormat};
 
 use glycin;
 use relm4::adw::gdk;
+use relm4::adw::prelude::*;
 use relm4::gtk;
 use relm4::gtk::gio;
+use relm4::gtk::glib;
 use relm4::gtk::prelude::*;
 use relm4::prelude::*;
 use relm4::*;
 use strum::IntoEnumIterator;
 
+use crate::APP_ID;
 use crate::app::components::progress_monitor::ProgressMonitor;
 use crate::app::components::progress_panel::ProgressPanel;
+use crate::app::components::sprite_preview::SpritePreview;
 use crate::fl;
 
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use tracing::{Level, debug, event, info};
@@ -28,6 +40,9 @@ use tracing::{Level, debug, event, info};
 const TEN_SECS_IN_MICROS: i64 = 10_000_000;
 const FIFTEEN_SECS_IN_MICROS: i64 = 15_000_000;
 
+/// Longest edge of exported GIFs and WebPs, which are much bigger than MP4s.
+const EXPORT_ANIMATION_MAX_SIZE: u32 = 720;
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum Viewing {
     Photo,
@@ -79,6 +94,9 @@ pub enum ViewOneInput {
     // Transcode all incompatible videos
     TranscodeAll,
 
+    // Transcode the loaded video, which previously failed to transcode
+    RetryTranscode,
+
     MuteToggle,
 
     PlayToggle,
@@ -95,6 +113,27 @@ pub enum ViewOneInput {
 
     // Video has been "prepared", so duration should be available
     VideoPrepared,
+
+    // User has moved the seek bar to a fraction of the way through the video.
+    Seek(f64),
+
+    // Pointer is hovering over the seek bar at an x coordinate, or has left it.
+    SeekPreview(Option<f64>),
+
+    // Ask how to export the video of a live photo.
+    ExportDialog,
+
+    // Export the video of a live photo in the given format.
+    Export(ExportFormat),
+
+    // Save the current frame of the video as a photo.
+    SaveFrame,
+
+    // Export has finished, with the exported file if it succeeded.
+    Exported(Option<PathBuf>),
+
+    // Saving a frame has finished, with the saved photo if it succeeded.
+    FrameSaved(Option<PathBuf>),
 }
 
 #[derive(Debug)]
@@ -102,6 +141,9 @@ pub enum ViewOneOutput {
     /// User has clicked transcode button.
     TranscodeAll,
 
+    /// User has clicked button to retry a failed transcode.
+    RetryTranscode(VisualId),
+
     /// Successfully showing a photo.
     PhotoShown(VisualId, glycin::ImageDetails),
 
@@ -126,204 +168,314 @@ pub struct ViewOne {
 
     video: Option<gtk::MediaFile>,
 
+    /// Copy of the video embedded in a motion photo that is being played.
+    video_copy: Option<motion_photo::VideoCopy>,
+
     /// Info for loaded image
     image_info: Option<glycin::ImageDetails>,
 
     visual_id: Option<VisualId>,
 
+    /// Loaded item
+    visual: Option<Arc<Visual>>,
+
     /// Should the video skip backwards/forwards buttons be enabled.
     is_skipping_allowed: bool,
 
     /// Label text displaying video timestamp
     video_timestamp: String,
 
+    /// Position in video as a fraction of its duration.
+    seek_bar: gtk::Scale,
+
+    /// Shows the frame under the pointer when hovering over the seek bar.
+    seek_preview: gtk::Popover,
+    seek_preview_picture: gtk::Picture,
+
+    /// Frames for previewing seek positions. Only present for videos that have
+    /// had a sprite sheet generated.
+    sprite_preview: Option<SpritePreview>,
+
+    thumbnailer: Rc<Thumbnailer>,
+
     transcode_progress: Controller<ProgressPanel>,
+
+    /// Shows the outcome of exports and saved frames.
+    toast_overlay: adw::ToastOverlay,
 }
 
 #[relm4::component(pub async)]
 impl SimpleAsyncComponent for ViewOne {
-    type Init = Arc<Reducer<ProgressMonitor>>;
+    type Init = (Arc<Reducer<ProgressMonitor>>, Rc<Thumbnailer>);
     type Input = ViewOneInput;
     type Output = ViewOneOutput;
 
     view! {
         #[root]
-        gtk::Overlay {
+        adw::ToastOverlay {
             set_vexpand: true,
             set_hexpand: true,
 
-            // video_controls
-            add_overlay = &gtk::Box {
-                set_orientation: gtk::Orientation::Vertical,
-                set_spacing: 12,
-                set_halign: gtk::Align::Center,
-                set_valign: gtk::Align::End,
-
-                #[watch]
-                set_visible: model.viewing == Viewing::Video || model.viewing == Viewing::MotionPhoto,
-
-                gtk::Frame {
-                    set_halign: gtk::Align::Center,
-                    add_css_class: "osd",
-
-                    #[watch]
-                    set_visible: model.viewing == Viewing::Video,
-
-                    #[wrap(Some)]
-                    set_child = &gtk::Label {
-                        set_halign: gtk::Align::Center,
-                        add_css_class: "photo-grid-month-label",
+            #[wrap(Some)]
+            set_child = &gtk::Overlay {
+                set_vexpand: true,
+                set_hexpand: true,
 
-                        #[watch]
-                        set_text: &model.video_timestamp,
-                    },
-                },
-                gtk::Box {
+                // video_controls
+                add_overlay = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 12,
                     set_halign: gtk::Align::Center,
                     set_valign: gtk::Align::End,
-                    set_orientation: gtk::Orientation::Horizontal,
-                    set_margin_start: 18,
-                    set_margin_end: 18,
-                    set_margin_bottom: 18,
-                    set_spacing: 12,
 
                     #[watch]
                     set_visible: model.viewing == Viewing::Video || model.viewing == Viewing::MotionPhoto,
 
-                    gtk::Button {
-                        set_icon_name: "skip-backwards-10-symbolic",
-                        add_css_class: "circular",
+                    gtk::Frame {
+                        set_halign: gtk::Align::Center,
                         add_css_class: "osd",
-                        set_tooltip_text: Some(&fl!("viewer-skip-backwards-10-seconds", "tooltip")),
 
                         #[watch]
-                        set_visible: model.viewing == Viewing::Video && model.is_skipping_allowed,
+                        set_visible: model.viewing == Viewing::Video,
 
-                        #[watch]
-                        set_sensitive: model.playback == Playback::Playing
-                            && model.is_skipping_allowed,
+                        #[wrap(Some)]
+                        set_child = &gtk::Label {
+                            set_halign: gtk::Align::Center,
+                            add_css_class: "photo-grid-month-label",
 
-                        connect_clicked => ViewOneInput::SkipBackwards,
+                            #[watch]
+                            set_text: &model.video_timestamp,
+                        },
                     },
 
-                    gtk::Button {
+                    #[local_ref]
+                    seek_bar -> gtk::Scale {
+                        set_width_request: 360,
+                        set_draw_value: false,
+                        add_css_class: "osd",
+
                         #[watch]
-                        set_icon_name: model.play_button_icon_name(),
+                        set_visible: model.viewing == Viewing::Video,
+                    },
 
-                        add_css_class: "circular",
-                        add_css_class: "osd",
-                        set_tooltip_text: Some(&fl!("viewer-play", "tooltip")),
+                    gtk::Box {
+                        set_halign: gtk::Align::Center,
+                        set_valign: gtk::Align::End,
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_margin_start: 18,
+                        set_margin_end: 18,
+                        set_margin_bottom: 18,
+                        set_spacing: 12,
 
                         #[watch]
                         set_visible: model.viewing == Viewing::Video || model.viewing == Viewing::MotionPhoto,
 
-                        connect_clicked => ViewOneInput::PlayToggle,
-                    },
+                        gtk::Button {
+                            set_icon_name: "skip-backwards-10-symbolic",
+                            add_css_class: "circular",
+                            add_css_class: "osd",
+                            set_tooltip_text: Some(&fl!("viewer-skip-backwards-10-seconds", "tooltip")),
 
-                    gtk::Button {
-                        set_icon_name: "skip-forward-10-symbolic",
-                        add_css_class: "circular",
-                        add_css_class: "osd",
-                        set_tooltip_text: Some(&fl!("viewer-skip-forward-10-seconds", "tooltip")),
+                            #[watch]
+                            set_visible: model.viewing == Viewing::Video && model.is_skipping_allowed,
 
-                        #[watch]
-                        set_visible: model.viewing == Viewing::Video && model.is_skipping_allowed,
+                            #[watch]
+                            set_sensitive: model.playback == Playback::Playing
+                                && model.is_skipping_allowed,
 
-                        #[watch]
-                        set_sensitive: model.playback == Playback::Playing
-                            && model.is_skipping_allowed,
+                            connect_clicked => ViewOneInput::SkipBackwards,
+                        },
 
-                        connect_clicked => ViewOneInput::SkipForward,
-                    },
+                        gtk::Button {
+                            #[watch]
+                            set_icon_name: model.play_button_icon_name(),
 
-                    gtk::Button {
-                        #[watch]
-                        set_icon_name: model.mute_button_icon_name(),
+                            add_css_class: "circular",
+                            add_css_class: "osd",
+                            set_tooltip_text: Some(&fl!("viewer-play", "tooltip")),
 
-                        set_margin_start: 36,
-                        add_css_class: "circular",
-                        add_css_class: "osd",
-                        set_tooltip_text: Some(&fl!("viewer-mute", "tooltip")),
+                            #[watch]
+                            set_visible: model.viewing == Viewing::Video || model.viewing == Viewing::MotionPhoto,
 
-                        #[watch]
-                        set_visible: model.viewing == Viewing::Video || model.viewing == Viewing::MotionPhoto,
+                            connect_clicked => ViewOneInput::PlayToggle,
+                        },
 
-                        connect_clicked => ViewOneInput::MuteToggle,
-                    }
-                }
-            },
+                        gtk::Button {
+                            set_icon_name: "skip-forward-10-symbolic",
+                            add_css_class: "circular",
+                            add_css_class: "osd",
+                            set_tooltip_text: Some(&fl!("viewer-skip-forward-10-seconds", "tooltip")),
 
-            #[wrap(Some)]
-            set_child = &gtk::Box {
-                set_orientation: gtk::Orientation::Vertical,
+                            #[watch]
+                            set_visible: model.viewing == Viewing::Video && model.is_skipping_allowed,
 
-                gtk::Box {
-                    set_vexpand: true,
-                    set_halign: gtk::Align::Center,
+                            #[watch]
+                            set_sensitive: model.playback == Playback::Playing
+                                && model.is_skipping_allowed,
 
-                    #[watch]
-                    set_visible: model.viewing == Viewing::Photo || model.viewing == Viewing::MotionPhoto || model.viewing == Viewing::Video,
+                            connect_clicked => ViewOneInput::SkipForward,
+                        },
 
-                    #[local_ref]
-                    picture -> gtk::Picture {}
-                },
+                        gtk::Button {
+                            #[watch]
+                            set_icon_name: model.mute_button_icon_name(),
 
-                adw::StatusPage {
-                    set_valign: gtk::Align::Start,
-                    set_vexpand: true,
+                            set_margin_start: 36,
+                            add_css_class: "circular",
+                            add_css_class: "osd",
+                            set_tooltip_text: Some(&fl!("viewer-mute", "tooltip")),
 
-                    set_icon_name: Some("playback-error-symbolic"),
-                    set_description: Some(&fl!("viewer-convert-all-description")),
+                            #[watch]
+                            set_visible: model.viewing == Viewing::Video || model.viewing == Viewing::MotionPhoto,
 
-                    #[watch]
-                    set_visible: model.viewing == Viewing::Transcode,
+                            connect_clicked => ViewOneInput::MuteToggle,
+                        },
 
-                    #[wrap(Some)]
-                    set_child = &adw::Clamp {
-                        set_orientation: gtk::Orientation::Horizontal,
-                        set_maximum_size: 400,
+                        gtk::Button {
+                            set_icon_name: "document-save-as-symbolic",
+                            add_css_class: "circular",
+                            add_css_class: "osd",
+                            set_tooltip_text: Some(&fl!("viewer-export", "tooltip")),
 
-                        #[wrap(Some)]
-                        set_child = &gtk::Box {
-                            set_orientation: gtk::Orientation::Vertical,
-
-                            // FIXME hide while transcodes are in progress
-                            gtk::Button {
-                                set_label: &fl!("viewer-convert-all-button"),
-                                add_css_class: "suggested-action",
-                                add_css_class: "pill",
-                                connect_clicked => ViewOneInput::TranscodeAll,
-                            },
-
-                            model.transcode_progress.widget(),
+                            #[watch]
+                            set_visible: model.viewing == Viewing::MotionPhoto,
+
+                            connect_clicked => ViewOneInput::ExportDialog,
+                        },
+
+                        gtk::Button {
+                            set_icon_name: "camera-photo-symbolic",
+                            add_css_class: "circular",
+                            add_css_class: "osd",
+                            set_tooltip_text: Some(&fl!("viewer-save-frame", "tooltip")),
+
+                            #[watch]
+                            set_visible: model.viewing == Viewing::Video || model.viewing == Viewing::MotionPhoto,
+
+                            connect_clicked => ViewOneInput::SaveFrame,
                         }
                     }
                 },
 
-                adw::StatusPage {
-                    set_valign: gtk::Align::Start,
-                    set_vexpand: true,
+                #[wrap(Some)]
+                set_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
 
-                    #[watch]
-                    set_icon_name: model.broken_status_icon_name(),
+                    gtk::Box {
+                        set_vexpand: true,
+                        set_halign: gtk::Align::Center,
 
-                    #[watch]
-                    set_description: model.broken_status_description().as_ref().map(|x| x.as_str()),
+                        #[watch]
+                        set_visible: model.viewing == Viewing::Photo || model.viewing == Viewing::MotionPhoto || model.viewing == Viewing::Video,
 
-                    #[watch]
-                    set_visible: model.viewing == Viewing::Error,
+                        #[local_ref]
+                        picture -> gtk::Picture {}
+                    },
+
+                    adw::StatusPage {
+                        set_valign: gtk::Align::Start,
+                        set_vexpand: true,
+
+                        set_icon_name: Some("playback-error-symbolic"),
+
+                        #[watch]
+                        set_description: Some(&model.transcode_status_description()),
+
+                        #[watch]
+                        set_visible: model.viewing == Viewing::Transcode,
+
+                        #[wrap(Some)]
+                        set_child = &adw::Clamp {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_maximum_size: 400,
+
+                            #[wrap(Some)]
+                            set_child = &gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+
+                                // FIXME hide while transcodes are in progress
+                                gtk::Button {
+                                    set_label: &fl!("viewer-convert-all-button"),
+                                    add_css_class: "suggested-action",
+                                    add_css_class: "pill",
+                                    #[watch]
+                                    set_visible: !model.is_transcode_failed(),
+                                    connect_clicked => ViewOneInput::TranscodeAll,
+                                },
+
+                                gtk::Button {
+                                    set_label: &fl!("viewer-convert-retry-button"),
+                                    add_css_class: "pill",
+                                    #[watch]
+                                    set_visible: model.is_transcode_failed(),
+                                    connect_clicked => ViewOneInput::RetryTranscode,
+                                },
+
+                                model.transcode_progress.widget(),
+                            }
+                        }
+                    },
+
+                    adw::StatusPage {
+                        set_valign: gtk::Align::Start,
+                        set_vexpand: true,
+
+                        #[watch]
+                        set_icon_name: model.broken_status_icon_name(),
+
+                        #[watch]
+                        set_description: model.broken_status_description().as_ref().map(|x| x.as_str()),
+
+                        #[watch]
+                        set_visible: model.viewing == Viewing::Error,
+                    }
                 }
             }
         }
     }
 
     async fn init(
-        transcode_progress_monitor: Self::Init,
+        (transcode_progress_monitor, thumbnailer): Self::Init,
         root: Self::Root,
-        _sender: AsyncComponentSender<Self>,
+        sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
         let picture = gtk::Picture::new();
 
+        let seek_bar = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 1.0, 0.001);
+
+        {
+            // Only emitted for changes made by the user, not for changes made while playing.
+            let sender = sender.clone();
+            seek_bar.connect_change_value(move |_, _, value| {
+                sender.input(ViewOneInput::Seek(value));
+                glib::Propagation::Proceed
+            });
+        }
+
+        let motion = gtk::EventControllerMotion::new();
+        {
+            let sender = sender.clone();
+            motion.connect_motion(move |_, x, _| sender.input(ViewOneInput::SeekPreview(Some(x))));
+        }
+        {
+            let sender = sender.clone();
+            motion.connect_leave(move |_| sender.input(ViewOneInput::SeekPreview(None)));
+        }
+        seek_bar.add_controller(motion);
+
+        let seek_preview_picture = gtk::Picture::builder()
+            .content_fit(gtk::ContentFit::Contain)
+            .can_shrink(false)
+            .build();
+
+        let seek_preview = gtk::Popover::builder()
+            .position(gtk::PositionType::Top)
+            .autohide(false)
+            .can_focus(false)
+            .child(&seek_preview_picture)
+            .build();
+        seek_preview.set_parent(&seek_bar);
+
         let transcode_progress = ProgressPanel::builder()
             .launch(transcode_progress_monitor.clone())
             .detach();
@@ -336,11 +488,19 @@ impl SimpleAsyncComponent for ViewOne {
 
             picture: picture.clone(),
             video: None,
+            video_copy: None,
             image_info: None,
             visual_id: None,
+            visual: None,
             is_skipping_allowed: false,
             video_timestamp: "".into(),
+            seek_bar: seek_bar.clone(),
+            seek_preview,
+            seek_preview_picture,
+            sprite_preview: None,
+            thumbnailer,
             transcode_progress,
+            toast_overlay: root.clone(),
         };
 
         let widgets = view_output!();
@@ -361,6 +521,10 @@ impl SimpleAsyncComponent for ViewOne {
                 self.broken = Broken::None;
                 self.is_skipping_allowed = false;
                 self.visual_id = None;
+                self.visual = None;
+                self.sprite_preview = None;
+                self.seek_bar.set_value(0.0);
+                self.seek_preview.popdown();
 
                 if !visual_sandbox_path.exists() {
                     self.viewing = Viewing::Error;
@@ -370,9 +534,11 @@ impl SimpleAsyncComponent for ViewOne {
 
                 self.picture.set_paintable(None::<&gdk::Paintable>);
                 self.video = None;
+                self.video_copy = None;
                 self.image_info = None;
 
                 self.visual_id = Some(visual.visual_id.clone());
+                self.visual = Some(visual.clone());
 
                 // clear orientation transformation css classes
                 for orient in PictureOrientation::iter() {
@@ -390,6 +556,35 @@ impl SimpleAsyncComponent for ViewOne {
                         .unwrap_or(PictureOrientation::North);
                     self.picture.add_css_class(orientation.as_ref());
 
+                    // Glycin can't decode camera RAW files, so show the embedded preview JPEG.
+                    if visual.is_raw_only() {
+                        // Reading the preview can be slow for large RAW files, so don't block the viewer.
+                        let path = visual_sandbox_path.to_path_buf();
+                        let file_type = visual.raw_file_type;
+                        let preview = relm4::spawn_blocking(move || {
+                            let file_type =
+                                file_type.ok_or_else(|| anyhow::anyhow!("Unknown file type"))?;
+                            raw::preview(&path, file_type)
+                        })
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|preview| preview);
+
+                        let texture = preview.and_then(|preview| {
+                            Ok(gdk::Texture::from_bytes(&glib::Bytes::from_owned(preview))?)
+                        });
+
+                        match texture {
+                            Ok(texture) => self.picture.set_paintable(Some(&texture)),
+                            Err(e) => {
+                                event!(Level::ERROR, "Failed loading RAW preview: {:?}", e);
+                                self.viewing = Viewing::Error;
+                                self.broken = Broken::Failed;
+                            }
+                        }
+                        return;
+                    }
+
                     let file = gio::File::for_path(visual_sandbox_path);
 
                     let mut loader = glycin::Loader::new(file);
@@ -440,12 +635,45 @@ impl SimpleAsyncComponent for ViewOne {
                             .filter(|x| x.exists())
                             .or_else(|| visual.video_path.clone().map(|p| p.sandbox_path))
                             .filter(|x| x.exists())
-                            .or_else(|| visual.motion_photo_video_path.clone())
-                            .expect("must have video path");
+                            .or_else(|| visual.motion_photo_video_path.clone());
 
                         debug!("Video path is: {:?}", video_path);
 
-                        let video = gtk::MediaFile::for_filename(video_path);
+                        let video = if let Some(video_path) = video_path {
+                            gtk::MediaFile::for_filename(video_path)
+                        } else {
+                            // GTK can only play files, so copy the video out of the motion photo.
+                            let picture_path = visual.picture_path.clone();
+                            let range = visual.motion_photo_video_range.clone();
+                            let copy = relm4::spawn_blocking(move || {
+                                let (picture_path, range) = picture_path
+                                    .zip(range)
+                                    .ok_or_else(|| anyhow::anyhow!("Missing motion photo video"))?;
+                                let cache_dir = glib::user_cache_dir().join(APP_ID);
+                                motion_photo::copy_video(
+                                    &picture_path.sandbox_path,
+                                    range,
+                                    &cache_dir,
+                                )
+                            })
+                            .await
+                            .map_err(anyhow::Error::from)
+                            .and_then(|copy| copy);
+
+                            let copy = match copy {
+                                Ok(copy) => copy,
+                                Err(e) => {
+                                    event!(Level::ERROR, "Failed copying video: {:?}", e);
+                                    self.viewing = Viewing::Error;
+                                    self.broken = Broken::Failed;
+                                    return;
+                                }
+                            };
+
+                            let video = gtk::MediaFile::for_filename(copy.path());
+                            self.video_copy = Some(copy);
+                            video
+                        };
                         if visual.is_motion_photo() {
                             debug!("Is a motion photo");
                             self.viewing = Viewing::MotionPhoto;
@@ -458,6 +686,15 @@ impl SimpleAsyncComponent for ViewOne {
                         } else {
                             self.viewing = Viewing::Video;
 
+                            let thumbnailer = Thumbnailer::clone(&self.thumbnailer);
+                            let hash = visual.thumbnail_hash();
+                            self.sprite_preview = relm4::spawn_blocking(move || {
+                                SpritePreview::load(&thumbnailer, &hash)
+                            })
+                            .await
+                            .ok()
+                            .flatten();
+
                             self.playback = Playback::Paused;
                             video.set_loop(false);
 
@@ -521,6 +758,7 @@ impl SimpleAsyncComponent for ViewOne {
             }
             ViewOneInput::Hidden => {
                 info!("Hide");
+                self.seek_preview.popdown();
                 if let Some(video) = self.video.as_ref() {
                     debug!("Pausing video");
                     if video.is_ended() {
@@ -627,12 +865,178 @@ impl SimpleAsyncComponent for ViewOne {
                         video.duration(),
                     ));
                     self.video_timestamp = format!("{}/{}", current_ts, total_ts).into();
+
+                    if video.duration() > 0 {
+                        self.seek_bar
+                            .set_value(video.timestamp() as f64 / video.duration() as f64);
+                    }
+                }
+            }
+            ViewOneInput::Seek(fraction) => {
+                if let Some(ref video) = self.video {
+                    let ts = (fraction.clamp(0.0, 1.0) * video.duration() as f64) as i64;
+                    video.seek(ts);
+                }
+            }
+            ViewOneInput::SeekPreview(Some(x)) => {
+                let width = f64::from(self.seek_bar.width());
+                let texture = self
+                    .sprite_preview
+                    .as_ref()
+                    .filter(|_| width > 0.0)
+                    .and_then(|preview| {
+                        let index = preview.sheet().frame_at_fraction(x / width)?;
+                        preview.frame(index)
+                    });
+
+                if let Some(texture) = texture {
+                    self.seek_preview_picture.set_paintable(Some(&texture));
+                    self.seek_preview
+                        .set_pointing_to(Some(&gdk::Rectangle::new(x as i32, 0, 1, 1)));
+                    self.seek_preview.popup();
+                } else {
+                    self.seek_preview.popdown();
                 }
             }
+            ViewOneInput::SeekPreview(None) => {
+                self.seek_preview.popdown();
+            }
             ViewOneInput::TranscodeAll => {
                 event!(Level::INFO, "Transcode all");
                 let _ = sender.output(ViewOneOutput::TranscodeAll);
             }
+            ViewOneInput::RetryTranscode => {
+                event!(Level::INFO, "Retry transcode");
+                if let Some(visual_id) = self.visual_id.as_ref() {
+                    let _ = sender.output(ViewOneOutput::RetryTranscode(visual_id.clone()));
+                }
+            }
+            ViewOneInput::ExportDialog => {
+                let dialog = adw::AlertDialog::builder()
+                    .heading(fl!("viewer-export-dialog", "heading"))
+                    .body(fl!("viewer-export-dialog", "body"))
+                    .build();
+
+                dialog.add_response("cancel", &fl!("viewer-export-dialog", "cancel-button"));
+                dialog.set_default_response(Some("cancel"));
+                dialog.set_close_response("cancel");
+
+                dialog.add_response(
+                    ExportFormat::Mp4.as_ref(),
+                    &fl!("viewer-export-dialog", "mp4-button"),
+                );
+                dialog.add_response(
+                    ExportFormat::Gif.as_ref(),
+                    &fl!("viewer-export-dialog", "gif-button"),
+                );
+                dialog.add_response(
+                    ExportFormat::WebP.as_ref(),
+                    &fl!("viewer-export-dialog", "webp-button"),
+                );
+
+                dialog.connect_response(None, move |_, response| {
+                    if let Ok(format) = ExportFormat::from_str(response) {
+                        sender.input(ViewOneInput::Export(format));
+                    }
+                });
+
+                dialog.present(Some(&self.picture));
+            }
+            ViewOneInput::Export(format) => {
+                let Some(visual) = self.visual.clone() else {
+                    return;
+                };
+
+                let file_name = visual
+                    .path()
+                    .sandbox_path
+                    .file_stem()
+                    .map(|x| format!("{}.{}", x.to_string_lossy(), format.extension()))
+                    .unwrap_or_default();
+
+                let dialog = gtk::FileDialog::builder()
+                    .title(fl!("viewer-export-dialog", "heading"))
+                    .initial_name(file_name)
+                    .modal(true)
+                    .build();
+
+                let window = self.picture.root().and_downcast::<gtk::Window>();
+                let Some(path) = dialog
+                    .save_future(window.as_ref())
+                    .await
+                    .ok()
+                    .and_then(|file| file.path())
+                else {
+                    return;
+                };
+
+                let options = ExportOptions {
+                    format,
+                    max_size: (format != ExportFormat::Mp4).then_some(EXPORT_ANIMATION_MAX_SIZE),
+                    ..Default::default()
+                };
+
+                // Encoding a GIF can take a few seconds, so don't block the viewer.
+                relm4::spawn_blocking(move || {
+                    if let Err(e) = export::export(&visual, &options, &path) {
+                        event!(Level::ERROR, "Failed exporting to {:?}: {:?}", path, e);
+                        sender.input(ViewOneInput::Exported(None));
+                    } else {
+                        info!("Exported {} to {:?}", visual.visual_id, path);
+                        sender.input(ViewOneInput::Exported(Some(path)));
+                    }
+                });
+            }
+            ViewOneInput::Exported(path) => {
+                let message = if let Some(path) = path {
+                    fl!(
+                        "viewer-export-toast",
+                        "success",
+                        file_name = path.file_name().unwrap_or_default().to_string_lossy()
+                    )
+                } else {
+                    fl!("viewer-export-toast", "failure")
+                };
+                self.toast_overlay.add_toast(adw::Toast::new(&message));
+            }
+            ViewOneInput::SaveFrame => {
+                let (Some(visual), Some(video)) = (self.visual.clone(), self.video.as_ref()) else {
+                    return;
+                };
+
+                let position = TimeDelta::microseconds(video.timestamp());
+
+                // Saved next to the original, so the next scan adds it to the library.
+                relm4::spawn_blocking(move || {
+                    match still::extract(&visual, position, StillFormat::Jpeg) {
+                        Ok(path) => {
+                            info!("Saved frame of {} to {:?}", visual.visual_id, path);
+                            sender.input(ViewOneInput::FrameSaved(Some(path)));
+                        }
+                        Err(e) => {
+                            event!(
+                                Level::ERROR,
+                                "Failed saving frame of {}: {:?}",
+                                visual.visual_id,
+                                e
+                            );
+                            sender.input(ViewOneInput::FrameSaved(None));
+                        }
+                    }
+                });
+            }
+            ViewOneInput::FrameSaved(path) => {
+                let message = if let Some(path) = path {
+                    fl!(
+                        "viewer-save-frame-toast",
+                        "success",
+                        file_name = path.file_name().unwrap_or_default().to_string_lossy()
+                    )
+                } else {
+                    fl!("viewer-save-frame-toast", "failure")
+                };
+                self.toast_overlay.add_toast(adw::Toast::new(&message));
+            }
         }
     }
 }
@@ -673,4 +1077,19 @@ impl ViewOne {
             Broken::None => None::<String>,
         }
     }
+
+    /// Has the loaded video previously failed to transcode?
+    fn is_transcode_failed(&self) -> bool {
+        self.visual
+            .as_ref()
+            .is_some_and(|visual| visual.video_transcode_error.is_some())
+    }
+
+    fn transcode_status_description(&self) -> String {
+        if self.is_transcode_failed() {
+            fl!("viewer-convert-failed-description")
+        } else {
+            fl!("viewer-convert-all-description")
+        }
+    }
 }